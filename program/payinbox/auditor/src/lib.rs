@@ -0,0 +1,355 @@
+//! Reconciliation auditor for the payinbox program.
+//!
+//! Loads every `TransferAccount` and the escrow token accounts, then checks
+//! the invariants that tie them together:
+//!
+//! - an Active transfer's escrow exists and holds exactly `amount`
+//! - a Claimed, Cancelled or Expired transfer's escrow was closed, or
+//!   handed over to the refund owner when their token account was frozen
+//! - every escrow belongs to a transfer
+//!
+//! and lists the Active transfers past their expiry, which nobody has
+//! reclaimed yet. [`Snapshot::load`] reads the accounts from an RPC node;
+//! [`audit`] only looks at the snapshot.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::Discriminator;
+use anchor_spl::token::spl_token;
+use anyhow::{Context, Result};
+use payinbox::{TransferAccount, TransferStatus};
+use payinbox_client::accounts::decode_transfer;
+use payinbox_client::pda;
+use payinbox_client::rpc::{Memcmp, RpcClient};
+use serde::{Serialize, Serializer};
+
+/// The accounts an audit looks at.
+#[derive(Default)]
+pub struct Snapshot {
+    /// Cluster time expiries are judged against
+    pub now: i64,
+    pub transfers: BTreeMap<Pubkey, TransferAccount>,
+    /// Token accounts by address: the transfers' escrows that exist, and
+    /// the escrows the orphan scan found
+    pub token_accounts: BTreeMap<Pubkey, spl_token::state::Account>,
+}
+
+impl Snapshot {
+    /// Reads every transfer and its escrow.
+    ///
+    /// With `scan_orphans`, also reads every token account of the
+    /// transfers' mints and of `extra_mints`, keeping the payinbox escrows
+    /// among them, so that escrows without a transfer show up. Escrows are
+    /// recognized by their address, the escrow PDA of their token owner.
+    pub fn load(rpc: &RpcClient, scan_orphans: bool, extra_mints: &[Pubkey]) -> Result<Self> {
+        let now = rpc
+            .get_unix_timestamp()
+            .context("reading the cluster clock")?;
+        let transfers = rpc
+            .get_program_accounts(
+                &payinbox::ID,
+                &[Memcmp {
+                    offset: 0,
+                    bytes: TransferAccount::DISCRIMINATOR.to_vec(),
+                }],
+            )
+            .context("listing transfer accounts")?
+            .into_iter()
+            .map(|(address, data)| {
+                decode_transfer(&data)
+                    .map(|transfer| (address, transfer))
+                    .with_context(|| format!("decoding transfer {address}"))
+            })
+            .collect::<Result<BTreeMap<_, _>>>()?;
+
+        let escrows: Vec<Pubkey> = transfers
+            .values()
+            .map(|transfer| transfer.escrow_token_account)
+            .collect();
+        let mut token_accounts: BTreeMap<_, _> = escrows
+            .iter()
+            .zip(
+                rpc.get_multiple_accounts_data(&escrows)
+                    .context("reading escrows")?,
+            )
+            .filter_map(|(address, data)| Some((*address, unpack_token_account(&data?)?)))
+            .collect();
+
+        if scan_orphans {
+            let mints: BTreeSet<Pubkey> = transfers
+                .values()
+                .map(|transfer| transfer.token_mint)
+                .chain(extra_mints.iter().copied())
+                .collect();
+            for mint in mints {
+                let accounts = rpc
+                    .get_program_accounts(
+                        &spl_token::ID,
+                        &[Memcmp {
+                            offset: 0,
+                            bytes: mint.to_bytes().to_vec(),
+                        }],
+                    )
+                    .with_context(|| format!("listing token accounts of {mint}"))?;
+                token_accounts.extend(accounts.into_iter().filter_map(|(address, data)| {
+                    let account = unpack_token_account(&data)?;
+                    is_escrow(&address, &account).then_some((address, account))
+                }));
+            }
+        }
+
+        Ok(Self {
+            now,
+            transfers,
+            token_accounts,
+        })
+    }
+}
+
+fn unpack_token_account(data: &[u8]) -> Option<spl_token::state::Account> {
+    spl_token::state::Account::unpack(data).ok()
+}
+
+/// Whether `account` is a payinbox escrow: those live at the escrow PDA of
+/// the transfer that owns them.
+fn is_escrow(address: &Pubkey, account: &spl_token::state::Account) -> bool {
+    *address == pda::escrow_address(&account.owner)
+}
+
+/// One problem the audit found.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    /// An Active transfer's escrow holds a different amount.
+    EscrowBalanceMismatch {
+        #[serde(serialize_with = "display")]
+        transfer: Pubkey,
+        #[serde(serialize_with = "display")]
+        escrow: Pubkey,
+        amount: u64,
+        balance: u64,
+    },
+    /// An Active transfer's escrow doesn't exist.
+    EscrowMissing {
+        #[serde(serialize_with = "display")]
+        transfer: Pubkey,
+        #[serde(serialize_with = "display")]
+        escrow: Pubkey,
+        amount: u64,
+    },
+    /// A finalized transfer's escrow is still open and owned by the
+    /// transfer.
+    EscrowNotClosed {
+        #[serde(serialize_with = "display")]
+        transfer: Pubkey,
+        status: &'static str,
+        #[serde(serialize_with = "display")]
+        escrow: Pubkey,
+        balance: u64,
+    },
+    /// An escrow whose transfer doesn't exist.
+    OrphanedEscrow {
+        #[serde(serialize_with = "display")]
+        escrow: Pubkey,
+        #[serde(serialize_with = "display")]
+        transfer: Pubkey,
+        #[serde(serialize_with = "display")]
+        mint: Pubkey,
+        balance: u64,
+    },
+    /// An Active transfer past its expiry. Group transfers are refunded
+    /// per contributor instead of reclaimed.
+    ReclaimDue {
+        #[serde(serialize_with = "display")]
+        transfer: Pubkey,
+        expiry: i64,
+        overdue_seconds: i64,
+        group: bool,
+    },
+}
+
+impl Finding {
+    /// Whether the finding breaks an invariant. A due reclaim is only
+    /// pending work.
+    pub fn is_violation(&self) -> bool {
+        !matches!(self, Finding::ReclaimDue { .. })
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::EscrowBalanceMismatch {
+                transfer,
+                escrow,
+                amount,
+                balance,
+            } => write!(
+                f,
+                "transfer {transfer}: escrow {escrow} holds {balance}, expected {amount}"
+            ),
+            Finding::EscrowMissing {
+                transfer,
+                escrow,
+                amount,
+            } => write!(
+                f,
+                "transfer {transfer}: escrow {escrow} is missing, expected {amount}"
+            ),
+            Finding::EscrowNotClosed {
+                transfer,
+                status,
+                escrow,
+                balance,
+            } => write!(
+                f,
+                "transfer {transfer} is {status} but escrow {escrow} is open with {balance}"
+            ),
+            Finding::OrphanedEscrow {
+                escrow,
+                transfer,
+                mint,
+                balance,
+            } => write!(
+                f,
+                "escrow {escrow} of missing transfer {transfer} holds {balance} of {mint}"
+            ),
+            Finding::ReclaimDue {
+                transfer,
+                overdue_seconds,
+                group,
+                ..
+            } => write!(
+                f,
+                "transfer {transfer} expired {overdue_seconds}s ago and awaits {}",
+                if *group {
+                    "refund_contribution"
+                } else {
+                    "reclaim_expired"
+                }
+            ),
+        }
+    }
+}
+
+fn display<T: fmt::Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// Outcome of an [`audit`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    pub now: i64,
+    /// Number of transfers checked
+    pub transfers: usize,
+    /// Number of escrows found
+    pub escrows: usize,
+    pub violations: usize,
+    pub reclaims_due: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Audited {} transfers and {} escrows at unix time {}",
+            self.transfers, self.escrows, self.now
+        )?;
+        for finding in &self.findings {
+            let label = if finding.is_violation() {
+                "VIOLATION"
+            } else {
+                "RECLAIM"
+            };
+            writeln!(f, "{label:<9} {finding}")?;
+        }
+        write!(
+            f,
+            "{} violations, {} reclaims due",
+            self.violations, self.reclaims_due
+        )
+    }
+}
+
+/// Checks `snapshot` for the invariants in the module docs. Findings are
+/// ordered by transfer address, orphaned escrows last.
+pub fn audit(snapshot: &Snapshot) -> Report {
+    let mut findings = Vec::new();
+    for (address, transfer) in &snapshot.transfers {
+        let escrow = transfer.escrow_token_account;
+        // A handed-over escrow belongs to someone else now, as if closed
+        let balance = snapshot
+            .token_accounts
+            .get(&escrow)
+            .filter(|account| account.owner == *address)
+            .map(|account| account.amount);
+        match (transfer.status, balance) {
+            (TransferStatus::Active, None) => findings.push(Finding::EscrowMissing {
+                transfer: *address,
+                escrow,
+                amount: transfer.amount,
+            }),
+            (TransferStatus::Active, Some(balance)) if balance != transfer.amount => {
+                findings.push(Finding::EscrowBalanceMismatch {
+                    transfer: *address,
+                    escrow,
+                    amount: transfer.amount,
+                    balance,
+                })
+            }
+            (TransferStatus::Active, Some(_)) | (_, None) => {}
+            (status, Some(balance)) => findings.push(Finding::EscrowNotClosed {
+                transfer: *address,
+                status: status_name(status),
+                escrow,
+                balance,
+            }),
+        }
+        if transfer.status == TransferStatus::Active && snapshot.now >= transfer.expiry {
+            findings.push(Finding::ReclaimDue {
+                transfer: *address,
+                expiry: transfer.expiry,
+                overdue_seconds: snapshot.now - transfer.expiry,
+                group: transfer.is_group(),
+            });
+        }
+    }
+
+    let escrows: Vec<_> = snapshot
+        .token_accounts
+        .iter()
+        .filter(|(address, account)| is_escrow(address, account))
+        .collect();
+    for (address, account) in &escrows {
+        if !snapshot.transfers.contains_key(&account.owner) {
+            findings.push(Finding::OrphanedEscrow {
+                escrow: **address,
+                transfer: account.owner,
+                mint: account.mint,
+                balance: account.amount,
+            });
+        }
+    }
+
+    Report {
+        now: snapshot.now,
+        transfers: snapshot.transfers.len(),
+        escrows: escrows.len(),
+        violations: findings.iter().filter(|f| f.is_violation()).count(),
+        reclaims_due: findings.iter().filter(|f| !f.is_violation()).count(),
+        findings,
+    }
+}
+
+fn status_name(status: TransferStatus) -> &'static str {
+    match status {
+        TransferStatus::Active => "active",
+        TransferStatus::Claimed => "claimed",
+        TransferStatus::Cancelled => "cancelled",
+        TransferStatus::Expired => "expired",
+    }
+}
@@ -0,0 +1,64 @@
+//! `payinbox-auditor`: checks every payinbox transfer against its escrow
+//! and prints what's wrong. Exits with status 1 when an invariant is
+//! violated, so it can gate a daily check.
+
+use std::process::ExitCode;
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use clap::Parser;
+use payinbox_auditor::{audit, Snapshot};
+use payinbox_client::rpc::RpcClient;
+
+#[derive(Parser)]
+#[command(
+    name = "payinbox-auditor",
+    version,
+    about = "Reconcile payinbox transfers with their escrows"
+)]
+struct Cli {
+    /// RPC endpoint
+    #[arg(
+        long,
+        env = "PAYINBOX_RPC_URL",
+        default_value = "https://api.devnet.solana.com"
+    )]
+    url: String,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Skip listing token accounts to find escrows without a transfer
+    #[arg(long)]
+    no_orphan_scan: bool,
+
+    /// Also look for orphaned escrows of this mint (repeatable)
+    #[arg(long = "mint")]
+    mints: Vec<Pubkey>,
+
+    /// Judge expiries at this unix time instead of the cluster's
+    #[arg(long)]
+    now: Option<i64>,
+}
+
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    let rpc = RpcClient::new(&cli.url);
+    let mut snapshot = Snapshot::load(&rpc, !cli.no_orphan_scan, &cli.mints)?;
+    if let Some(now) = cli.now {
+        snapshot.now = now;
+    }
+
+    let report = audit(&snapshot);
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{report}");
+    }
+    Ok(if report.violations > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
@@ -0,0 +1,282 @@
+//! Audits of transfers run in the in-process runtime, with escrows
+//! tampered with where the program itself would never leave them.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::spl_token;
+use anchor_spl::token::spl_token::state::AccountState;
+use payinbox_auditor::{audit, Finding, Snapshot};
+use payinbox_client::accounts::decode_transfer;
+use payinbox_client::hash::claim_code_hash;
+use payinbox_client::instructions::{
+    CancelTransfer, ClaimTransfer, CreateTransfer, ReclaimExpired,
+};
+use payinbox_client::pda;
+use payinbox_harness::{Account, Svm};
+
+const AMOUNT: u64 = 1_000_000;
+const CLAIM_CODE: &str = "correct-horse-battery-staple";
+const EXPIRY_HOURS: i64 = 72;
+
+struct Setup {
+    svm: Svm,
+    mint: Pubkey,
+    sender: Pubkey,
+    recipient: Pubkey,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let mut svm = Svm::new();
+        let mint = svm.create_mint(&Pubkey::new_unique(), 6);
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        svm.airdrop(&sender, 10_000_000_000);
+        svm.airdrop(&recipient, 1_000_000_000);
+        svm.create_token_account(&sender, &mint, 10 * AMOUNT);
+        svm.create_token_account(&recipient, &mint, 0);
+        Self {
+            svm,
+            mint,
+            sender,
+            recipient,
+        }
+    }
+
+    fn create(&mut self, email: u8) -> Pubkey {
+        let create = CreateTransfer::new(
+            self.sender,
+            self.mint,
+            [email; 32],
+            claim_code_hash(CLAIM_CODE),
+            AMOUNT,
+            EXPIRY_HOURS,
+        );
+        self.svm.process_instruction(create.instruction()).unwrap();
+        create.transfer_address()
+    }
+
+    fn claim(&mut self, transfer: &Pubkey) {
+        let state = self.svm.anchor_account(transfer).unwrap();
+        let ix = ClaimTransfer::new(&state, self.recipient, CLAIM_CODE).instruction();
+        self.svm.process_instruction(ix).unwrap();
+    }
+
+    fn cancel(&mut self, transfer: &Pubkey) {
+        let state = self.svm.anchor_account(transfer).unwrap();
+        let ix = CancelTransfer::new(&state, self.sender).instruction();
+        self.svm.process_instruction(ix).unwrap();
+    }
+
+    fn reclaim(&mut self, transfer: &Pubkey) {
+        let state = self.svm.anchor_account(transfer).unwrap();
+        let ix = ReclaimExpired::new(&state, self.recipient).instruction();
+        self.svm.process_instruction(ix).unwrap();
+    }
+
+    /// Every transfer and token account of the runtime, as `Snapshot::load`
+    /// would see them with the orphan scan on.
+    fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot {
+            now: self.svm.clock().unix_timestamp,
+            ..Snapshot::default()
+        };
+        for (address, account) in self.svm.accounts() {
+            if account.owner == payinbox::ID {
+                if let Ok(transfer) = decode_transfer(&account.data) {
+                    snapshot.transfers.insert(address, transfer);
+                }
+            } else if account.owner == spl_token::ID {
+                if let Ok(token_account) = spl_token::state::Account::unpack(&account.data) {
+                    snapshot.token_accounts.insert(address, token_account);
+                }
+            }
+        }
+        snapshot
+    }
+}
+
+#[test]
+fn consistent_transfers_pass() {
+    let mut setup = Setup::new();
+    setup.create(1);
+    let claimed = setup.create(2);
+    let cancelled = setup.create(3);
+    setup.claim(&claimed);
+    setup.cancel(&cancelled);
+
+    let report = audit(&setup.snapshot());
+    assert_eq!(report.transfers, 3);
+    assert_eq!(report.escrows, 1);
+    assert_eq!(report.findings, vec![]);
+    assert_eq!(report.violations, 0);
+}
+
+#[test]
+fn flags_an_escrow_holding_another_amount() {
+    let mut setup = Setup::new();
+    let transfer = setup.create(1);
+    let escrow = pda::escrow_address(&transfer);
+    // Anyone can send tokens to the escrow.
+    setup
+        .svm
+        .create_token_account_at(escrow, &transfer, &setup.mint, AMOUNT + 5);
+
+    let report = audit(&setup.snapshot());
+    assert_eq!(
+        report.findings,
+        vec![Finding::EscrowBalanceMismatch {
+            transfer,
+            escrow,
+            amount: AMOUNT,
+            balance: AMOUNT + 5,
+        }]
+    );
+    assert_eq!(report.violations, 1);
+}
+
+#[test]
+fn flags_an_active_transfer_without_escrow() {
+    let mut setup = Setup::new();
+    let transfer = setup.create(1);
+    let escrow = pda::escrow_address(&transfer);
+    setup.svm.set_account(escrow, Account::default());
+
+    assert_eq!(
+        audit(&setup.snapshot()).findings,
+        vec![Finding::EscrowMissing {
+            transfer,
+            escrow,
+            amount: AMOUNT,
+        }]
+    );
+}
+
+#[test]
+fn flags_a_finalized_transfer_whose_escrow_is_open() {
+    let mut setup = Setup::new();
+    let transfer = setup.create(1);
+    setup.claim(&transfer);
+    let escrow = pda::escrow_address(&transfer);
+    setup
+        .svm
+        .create_token_account_at(escrow, &transfer, &setup.mint, 0);
+
+    assert_eq!(
+        audit(&setup.snapshot()).findings,
+        vec![Finding::EscrowNotClosed {
+            transfer,
+            status: "claimed",
+            escrow,
+            balance: 0,
+        }]
+    );
+}
+
+#[test]
+fn accepts_an_escrow_handed_over_on_reclaim() {
+    let mut setup = Setup::new();
+    let transfer = setup.create(1);
+    // A frozen refund account makes the reclaim hand the escrow over.
+    let refund_account = get_associated_token_address(&setup.sender, &setup.mint);
+    let mut state = setup.svm.token_account(&refund_account).unwrap();
+    state.state = AccountState::Frozen;
+    let mut account = setup.svm.account(&refund_account).unwrap();
+    state.pack_into_slice(&mut account.data);
+    setup.svm.set_account(refund_account, account);
+    let expiry = setup
+        .svm
+        .anchor_account::<payinbox::TransferAccount>(&transfer)
+        .unwrap()
+        .expiry;
+    setup.svm.set_unix_timestamp(expiry);
+    setup.reclaim(&transfer);
+
+    let escrow = pda::escrow_address(&transfer);
+    let snapshot = setup.snapshot();
+    assert_eq!(snapshot.token_accounts[&escrow].owner, setup.sender);
+    let report = audit(&snapshot);
+    assert_eq!(report.findings, vec![]);
+    assert_eq!(report.escrows, 0);
+}
+
+#[test]
+fn lists_expired_transfers_until_reclaimed() {
+    let mut setup = Setup::new();
+    let transfer = setup.create(1);
+    let expiry = setup
+        .svm
+        .anchor_account::<payinbox::TransferAccount>(&transfer)
+        .unwrap()
+        .expiry;
+
+    setup.svm.set_unix_timestamp(expiry - 1);
+    assert_eq!(audit(&setup.snapshot()).reclaims_due, 0);
+
+    setup.svm.set_unix_timestamp(expiry + 60);
+    let report = audit(&setup.snapshot());
+    assert_eq!(
+        report.findings,
+        vec![Finding::ReclaimDue {
+            transfer,
+            expiry,
+            overdue_seconds: 60,
+            group: false,
+        }]
+    );
+    assert_eq!(report.violations, 0);
+    assert_eq!(report.reclaims_due, 1);
+
+    setup.reclaim(&transfer);
+    assert_eq!(audit(&setup.snapshot()).findings, vec![]);
+}
+
+#[test]
+fn flags_escrows_without_a_transfer() {
+    let mut setup = Setup::new();
+    let missing = Pubkey::new_unique();
+    let escrow = pda::escrow_address(&missing);
+    setup
+        .svm
+        .create_token_account_at(escrow, &missing, &setup.mint, 7);
+    // A token account at any other address isn't an escrow.
+    setup
+        .svm
+        .create_token_account_at(Pubkey::new_unique(), &missing, &setup.mint, 7);
+
+    let report = audit(&setup.snapshot());
+    assert_eq!(report.escrows, 1);
+    assert_eq!(
+        report.findings,
+        vec![Finding::OrphanedEscrow {
+            escrow,
+            transfer: missing,
+            mint: setup.mint,
+            balance: 7,
+        }]
+    );
+}
+
+#[test]
+fn reports_as_json_and_text() {
+    let mut setup = Setup::new();
+    let transfer = setup.create(1);
+    let escrow = pda::escrow_address(&transfer);
+    setup
+        .svm
+        .create_token_account_at(escrow, &transfer, &setup.mint, 0);
+
+    let report = audit(&setup.snapshot());
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["violations"], 1);
+    assert_eq!(json["findings"][0]["kind"], "escrow_balance_mismatch");
+    assert_eq!(json["findings"][0]["transfer"], transfer.to_string());
+    assert_eq!(json["findings"][0]["balance"], 0);
+
+    let text = report.to_string();
+    assert!(text.contains(&format!(
+        "VIOLATION transfer {transfer}: escrow {escrow} holds 0, expected {AMOUNT}"
+    )));
+    assert!(text.ends_with("1 violations, 0 reclaims due"));
+}
@@ -0,0 +1,922 @@
+//! `payinbox-bench`: runs every program instruction in LiteSVM, records
+//! what it costs and compares the result with the checked-in baseline.
+//!
+//! Per instruction it records the compute units the transaction consumed,
+//! as metered by the SBF VM for the built program (run `anchor build`
+//! first), the number of CPIs, the instruction data size and the accounts
+//! (bytes and rent) created and closed. Instructions checked against a
+//! signature precompile are measured together with it. Per account type it
+//! records size and rent-exempt minimum.
+//!
+//! Exits with status 1 if any number went up; `--update` accepts the new
+//! numbers by rewriting the baseline.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token;
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use payinbox::{claim_commitment, passkey_challenge, TransferAccount, TransferOptions};
+use payinbox_client::hash::{claim_code_hash, commit_keypair};
+use payinbox_client::instructions::{
+    CancelTransfer, ClaimTransfer, CommitClaim, CreateTransfer, ReclaimExpired, RevealClaim,
+    SetClaimPasskey,
+};
+use payinbox_client::pda;
+use payinbox_harness::{dkim, ed25519_instruction, Account, Passkey, Svm};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+const AMOUNT: u64 = 1_000_000;
+const CLAIM_CODE: &str = "correct-horse-battery-staple";
+const EXPIRY_HOURS: i64 = 72;
+const CONTRIBUTION_HOURS: i64 = 24;
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+const MULTI_CODES: [&str; 3] = ["alpha", "beta", "gamma"];
+const SALT: [u8; 32] = [5; 32];
+
+#[derive(Parser)]
+#[command(
+    name = "payinbox-bench",
+    about = "Measure instruction costs against the baseline"
+)]
+struct Cli {
+    /// Baseline file
+    #[arg(long, default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/baseline.json"))]
+    baseline: PathBuf,
+
+    /// Write the results to the baseline instead of comparing
+    #[arg(long)]
+    update: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Report {
+    accounts: BTreeMap<String, AccountCost>,
+    instructions: BTreeMap<String, InstructionCost>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AccountCost {
+    size: u64,
+    rent_exempt_lamports: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InstructionCost {
+    /// Consumed by the whole transaction, precompile included
+    compute_units: u64,
+    cpis: u64,
+    data_bytes: u64,
+    accounts_created: u64,
+    bytes_created: u64,
+    /// Lamports moved into created accounts
+    rent_paid: u64,
+    /// Lamports of closed accounts
+    rent_returned: u64,
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ExitCode> {
+    let report = measure()?;
+    let current = serde_json::to_value(&report)?;
+
+    if cli.update {
+        fs::write(
+            &cli.baseline,
+            serde_json::to_string_pretty(&current)? + "\n",
+        )
+        .with_context(|| format!("writing {}", cli.baseline.display()))?;
+        println!("Baseline written to {}", cli.baseline.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let baseline: Value =
+        serde_json::from_str(&fs::read_to_string(&cli.baseline).with_context(|| {
+            format!(
+                "reading {} (create it with --update)",
+                cli.baseline.display()
+            )
+        })?)
+        .with_context(|| format!("parsing {}", cli.baseline.display()))?;
+
+    let (baseline, current) = (flatten(&baseline), flatten(&current));
+    let mut rose = false;
+    println!(
+        "{:<50} {:>12} {:>12} {:>8}",
+        "METRIC", "BASELINE", "CURRENT", "CHANGE"
+    );
+    for (metric, now) in &current {
+        let Some(&before) = baseline.get(metric) else {
+            println!("{metric:<50} {:>12} {now:>12} {:>8}", "-", "new");
+            continue;
+        };
+        if before == *now {
+            continue;
+        }
+        rose |= *now > before;
+        let change = if before == 0 {
+            "+inf%".to_string()
+        } else {
+            format!(
+                "{:+.1}%",
+                (*now as f64 - before as f64) * 100.0 / before as f64
+            )
+        };
+        println!("{metric:<50} {before:>12} {now:>12} {change:>8}");
+    }
+    for metric in baseline
+        .keys()
+        .filter(|metric| !current.contains_key(*metric))
+    {
+        println!(
+            "{metric:<50} {:>12} {:>12} {:>8}",
+            baseline[metric], "-", "removed"
+        );
+    }
+
+    if rose {
+        println!("\nCosts rose; rerun with --update to accept them.");
+        Ok(ExitCode::FAILURE)
+    } else {
+        println!("\nNo cost went up.");
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Numeric leaves of `value` keyed by their dotted path.
+fn flatten(value: &Value) -> BTreeMap<String, u64> {
+    fn walk(prefix: &str, value: &Value, out: &mut BTreeMap<String, u64>) {
+        match value {
+            Value::Object(fields) => {
+                for (key, value) in fields {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{prefix}.{key}")
+                    };
+                    walk(&path, value, out);
+                }
+            }
+            Value::Number(number) => {
+                if let Some(number) = number.as_u64() {
+                    out.insert(prefix.to_string(), number);
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut out = BTreeMap::new();
+    walk("", value, &mut out);
+    out
+}
+
+/// A sender (also the config admin), a recipient, a cranker and a second
+/// contributor holding tokens of one mint, and the report being filled.
+struct Bench {
+    svm: Svm,
+    mint: Pubkey,
+    sender: Pubkey,
+    recipient: Pubkey,
+    cranker: Pubkey,
+    contributor: Pubkey,
+    report: Report,
+}
+
+impl Bench {
+    fn new() -> Self {
+        let mut svm = Svm::new();
+        let mint = svm.create_mint(&Pubkey::new_unique(), 6);
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let cranker = Pubkey::new_unique();
+        let contributor = Pubkey::new_unique();
+        for wallet in [sender, recipient, cranker, contributor] {
+            svm.airdrop(&wallet, 10 * LAMPORTS_PER_SOL);
+        }
+        svm.create_token_account(&sender, &mint, 100 * AMOUNT);
+        svm.create_token_account(&recipient, &mint, 0);
+        svm.create_token_account(&cranker, &mint, 0);
+        svm.create_token_account(&contributor, &mint, 10 * AMOUNT);
+        Self {
+            svm,
+            mint,
+            sender,
+            recipient,
+            cranker,
+            contributor,
+            report: Report {
+                accounts: BTreeMap::new(),
+                instructions: BTreeMap::new(),
+            },
+        }
+    }
+
+    fn create_ix(&self, email_hash: [u8; 32], options: TransferOptions) -> CreateTransfer {
+        CreateTransfer::new(
+            self.sender,
+            self.mint,
+            email_hash,
+            claim_code_hash(CLAIM_CODE),
+            AMOUNT,
+            EXPIRY_HOURS,
+        )
+        .options(options)
+    }
+
+    /// Creates a transfer without measuring it.
+    fn create(&mut self, email_hash: [u8; 32], options: TransferOptions) -> Result<Pubkey> {
+        let create = self.create_ix(email_hash, options);
+        self.run("create_transfer", create.instruction())?;
+        Ok(create.transfer_address())
+    }
+
+    fn transfer(&self, address: &Pubkey) -> Result<TransferAccount> {
+        self.svm
+            .anchor_account(address)
+            .ok_or_else(|| anyhow!("transfer {address} not found"))
+    }
+
+    /// Runs `ix` without measuring it.
+    fn run(&mut self, name: &str, ix: Instruction) -> Result<()> {
+        self.svm
+            .process_instruction(ix)
+            .map_err(|err| anyhow!("{name} failed: {err}"))
+    }
+
+    /// Runs `ix` and records what it cost under `name`.
+    fn measure(&mut self, name: &str, ix: Instruction) -> Result<()> {
+        self.measure_after(name, None, ix)
+    }
+
+    /// Runs `precompile` (if any) and `ix` in one transaction and records
+    /// what it cost under `name`.
+    fn measure_after(
+        &mut self,
+        name: &str,
+        precompile: Option<Instruction>,
+        ix: Instruction,
+    ) -> Result<()> {
+        let before: HashMap<Pubkey, Account> = self.svm.accounts().collect();
+        let data_bytes = ix.data.len() as u64;
+        let instructions: Vec<Instruction> = precompile.into_iter().chain([ix]).collect();
+        self.svm
+            .process_transaction(&instructions)
+            .map_err(|err| anyhow!("{name} failed: {err}"))?;
+
+        let created: Vec<Account> = self
+            .svm
+            .accounts()
+            .filter(|(address, _)| !before.contains_key(address))
+            .map(|(_, account)| account)
+            .collect();
+        let rent_returned = before
+            .iter()
+            .filter(|(address, _)| self.svm.account(address).is_none())
+            .map(|(_, account)| account.lamports)
+            .sum();
+        let cpis = self
+            .svm
+            .logs()
+            .iter()
+            .filter(|line| line.contains(" invoke [") && !line.ends_with(" invoke [1]"))
+            .count() as u64;
+        let cost = InstructionCost {
+            compute_units: self.svm.compute_units(),
+            cpis,
+            data_bytes,
+            accounts_created: created.len() as u64,
+            bytes_created: created
+                .iter()
+                .map(|account| account.data.len() as u64)
+                .sum(),
+            rent_paid: created.iter().map(|account| account.lamports).sum(),
+            rent_returned,
+        };
+        self.report.instructions.insert(name.to_string(), cost);
+        Ok(())
+    }
+
+    /// Records size and rent of the account at `address` under `name`.
+    fn record_account(&mut self, name: &str, address: &Pubkey) -> Result<()> {
+        let size = self
+            .svm
+            .account(address)
+            .ok_or_else(|| anyhow!("account {address} not found"))?
+            .data
+            .len();
+        let cost = AccountCost {
+            size: size as u64,
+            rent_exempt_lamports: self.svm.rent().minimum_balance(size),
+        };
+        self.report.accounts.insert(name.to_string(), cost);
+        Ok(())
+    }
+
+    fn token_account(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address(owner, &self.mint)
+    }
+}
+
+/// A payinbox instruction the client has no builder for.
+fn instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: payinbox::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn measure() -> Result<Report> {
+    let mut bench = Bench::new();
+
+    // Transfers created now, settled once they've expired
+    let reclaimed = bench.create([4; 32], TransferOptions::default())?;
+    let bountied = lifecycle(&mut bench)?;
+    let group_refunded = group(&mut bench)?;
+    multi_code(&mut bench)?;
+    let verifier = config(&mut bench)?;
+    inbox(&mut bench, &verifier)?;
+    registry(&mut bench, &verifier)?;
+    attestation(&mut bench, &verifier)?;
+    dkim_proof(&mut bench)?;
+    passkey(&mut bench)?;
+    commit_reveal(&mut bench)?;
+
+    bench.svm.advance_clock(EXPIRY_HOURS * 3600);
+    let ix = ReclaimExpired::new(&bench.transfer(&reclaimed)?, bench.cranker).instruction();
+    bench.measure("reclaim_expired", ix)?;
+
+    let ix = ReclaimExpired::new(&bench.transfer(&bountied)?, bench.cranker)
+        .cranker_token_account(bench.token_account(&bench.cranker))
+        .instruction();
+    bench.measure("reclaim_expired_with_bounty", ix)?;
+
+    let ix = instruction(
+        payinbox::accounts::RefundContribution {
+            transfer: group_refunded,
+            contribution: pda::contribution_address(&group_refunded, &bench.contributor),
+            contributor: bench.contributor,
+            contributor_token_account: bench.token_account(&bench.contributor),
+            escrow_token_account: pda::escrow_address(&group_refunded),
+            organizer: bench.sender,
+            token_program: token::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::RefundContribution {},
+    );
+    bench.measure("refund_contribution", ix)?;
+
+    Ok(bench.report)
+}
+
+/// create, claim, cancel and set_cancel_authority; returns a transfer with
+/// a crank bounty, to be reclaimed once expired.
+fn lifecycle(bench: &mut Bench) -> Result<Pubkey> {
+    let create = bench.create_ix([1; 32], TransferOptions::default());
+    bench.measure("create_transfer", create.instruction())?;
+    let claimed = create.transfer_address();
+
+    let bounty = TransferOptions {
+        crank_bounty_lamports: 5_000_000,
+        crank_bounty_bps: 100,
+        ..TransferOptions::default()
+    };
+    let create = bench.create_ix([2; 32], bounty);
+    bench.measure("create_transfer_with_bounty", create.instruction())?;
+    let bountied = create.transfer_address();
+
+    bench.record_account("transfer", &claimed)?;
+    bench.record_account("escrow", &pda::escrow_address(&claimed))?;
+
+    let ix =
+        ClaimTransfer::new(&bench.transfer(&claimed)?, bench.recipient, CLAIM_CODE).instruction();
+    bench.measure("claim_transfer", ix)?;
+
+    let cancelled = bench.create([3; 32], TransferOptions::default())?;
+    let ix = instruction(
+        payinbox::accounts::SetCancelAuthority {
+            transfer: cancelled,
+            sender: bench.sender,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::SetCancelAuthority {
+            cancel_authority: Some(bench.cranker),
+        },
+    );
+    bench.measure("set_cancel_authority", ix)?;
+
+    let ix = CancelTransfer::new(&bench.transfer(&cancelled)?, bench.sender).instruction();
+    bench.measure("cancel_transfer", ix)?;
+
+    Ok(bountied)
+}
+
+/// create_multi_code_transfer, approve_claim and claim_approved.
+fn multi_code(bench: &mut Bench) -> Result<()> {
+    let transfer = pda::transfer_address(&bench.sender, &[5; 32]);
+    let approvals = pda::approvals_address(&transfer);
+    let ix = instruction(
+        payinbox::accounts::CreateMultiCodeTransfer {
+            transfer,
+            approvals,
+            sender: bench.sender,
+            sender_token_account: bench.token_account(&bench.sender),
+            token_mint: bench.mint,
+            escrow_token_account: pda::escrow_address(&transfer),
+            token_program: token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::CreateMultiCodeTransfer {
+            email_hash: [5; 32],
+            code_keys: MULTI_CODES
+                .iter()
+                .map(|code| commit_keypair(code).pubkey())
+                .collect(),
+            threshold: 2,
+            amount: AMOUNT,
+            expiry_hours: EXPIRY_HOURS,
+            options: TransferOptions::default(),
+        },
+    );
+    bench.measure("create_multi_code_transfer", ix)?;
+    bench.record_account("approvals", &approvals)?;
+
+    for (index, code) in MULTI_CODES[..2].iter().enumerate() {
+        let ix = instruction(
+            payinbox::accounts::ApproveClaim {
+                transfer,
+                approvals,
+                approver: commit_keypair(code).pubkey(),
+                event_authority: pda::event_authority_address(),
+                program: payinbox::ID,
+            },
+            payinbox::instruction::ApproveClaim {
+                recipient: bench.recipient,
+            },
+        );
+        if index == 0 {
+            bench.measure("approve_claim", ix)?;
+        } else {
+            bench.run("approve_claim", ix)?;
+        }
+    }
+
+    let ix = instruction(
+        payinbox::accounts::ClaimApproved {
+            transfer,
+            approvals,
+            recipient: bench.recipient,
+            recipient_token_account: bench.token_account(&bench.recipient),
+            escrow_token_account: pda::escrow_address(&transfer),
+            sender: bench.sender,
+            token_program: token::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::ClaimApproved {},
+    );
+    bench.measure("claim_approved", ix)
+}
+
+/// create_group_transfer, contribute and close_contribution; returns a
+/// group transfer with a contribution, to be refunded once expired.
+fn group(bench: &mut Bench) -> Result<Pubkey> {
+    let create = |bench: &Bench, email_hash: [u8; 32]| {
+        let transfer = pda::transfer_address(&bench.sender, &email_hash);
+        let ix = instruction(
+            payinbox::accounts::CreateGroupTransfer {
+                transfer,
+                contribution: pda::contribution_address(&transfer, &bench.sender),
+                organizer: bench.sender,
+                organizer_token_account: bench.token_account(&bench.sender),
+                token_mint: bench.mint,
+                escrow_token_account: pda::escrow_address(&transfer),
+                token_program: token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+                event_authority: pda::event_authority_address(),
+                program: payinbox::ID,
+            },
+            payinbox::instruction::CreateGroupTransfer {
+                email_hash,
+                claim_code_hash: claim_code_hash(CLAIM_CODE),
+                amount: AMOUNT,
+                expiry_hours: EXPIRY_HOURS,
+                contribution_hours: CONTRIBUTION_HOURS,
+            },
+        );
+        (transfer, ix)
+    };
+    let contribute = |bench: &Bench, transfer: Pubkey| {
+        instruction(
+            payinbox::accounts::Contribute {
+                transfer,
+                contribution: pda::contribution_address(&transfer, &bench.contributor),
+                contributor: bench.contributor,
+                contributor_token_account: bench.token_account(&bench.contributor),
+                escrow_token_account: pda::escrow_address(&transfer),
+                token_program: token::ID,
+                system_program: system_program::ID,
+                event_authority: pda::event_authority_address(),
+                program: payinbox::ID,
+            },
+            payinbox::instruction::Contribute { amount: AMOUNT },
+        )
+    };
+
+    let (claimed, ix) = create(bench, [6; 32]);
+    bench.measure("create_group_transfer", ix)?;
+    let ix = contribute(bench, claimed);
+    bench.measure("contribute", ix)?;
+    let contribution = pda::contribution_address(&claimed, &bench.contributor);
+    bench.record_account("contribution", &contribution)?;
+
+    let ix =
+        ClaimTransfer::new(&bench.transfer(&claimed)?, bench.recipient, CLAIM_CODE).instruction();
+    bench.run("claim_transfer", ix)?;
+    let ix = instruction(
+        payinbox::accounts::CloseContribution {
+            transfer: claimed,
+            contribution,
+            contributor: bench.contributor,
+        },
+        payinbox::instruction::CloseContribution {},
+    );
+    bench.measure("close_contribution", ix)?;
+
+    let (refunded, ix) = create(bench, [7; 32]);
+    bench.run("create_group_transfer", ix)?;
+    let ix = contribute(bench, refunded);
+    bench.run("contribute", ix)?;
+    Ok(refunded)
+}
+
+/// initialize_config, add_verifier and remove_verifier; returns the
+/// verifier left registered.
+fn config(bench: &mut Bench) -> Result<Keypair> {
+    let config = pda::config_address();
+    let ix = instruction(
+        payinbox::accounts::InitializeConfig {
+            config,
+            admin: bench.sender,
+            system_program: system_program::ID,
+        },
+        payinbox::instruction::InitializeConfig {},
+    );
+    bench.measure("initialize_config", ix)?;
+
+    let admin = bench.sender;
+    let update = || payinbox::accounts::UpdateConfig {
+        config,
+        admin,
+        event_authority: pda::event_authority_address(),
+        program: payinbox::ID,
+    };
+    let verifier = Keypair::new();
+    let ix = instruction(
+        update(),
+        payinbox::instruction::AddVerifier {
+            verifier: verifier.pubkey(),
+        },
+    );
+    bench.measure("add_verifier", ix)?;
+
+    let removed = Pubkey::new_unique();
+    let ix = instruction(
+        update(),
+        payinbox::instruction::AddVerifier { verifier: removed },
+    );
+    bench.run("add_verifier", ix)?;
+    let ix = instruction(
+        update(),
+        payinbox::instruction::RemoveVerifier { verifier: removed },
+    );
+    bench.measure("remove_verifier", ix)?;
+
+    bench.record_account("config", &config)?;
+    Ok(verifier)
+}
+
+/// open_inbox, bind_inbox, sweep_inbox and set_inbox_passkey.
+fn inbox(bench: &mut Bench, verifier: &Keypair) -> Result<()> {
+    const EMAIL_HASH: [u8; 32] = [8; 32];
+    let inbox = pda::inbox_address(&EMAIL_HASH);
+    let ix = instruction(
+        payinbox::accounts::OpenInbox {
+            inbox,
+            payer: bench.sender,
+            system_program: system_program::ID,
+        },
+        payinbox::instruction::OpenInbox {
+            email_hash: EMAIL_HASH,
+        },
+    );
+    bench.measure("open_inbox", ix)?;
+
+    let create = bench
+        .create_ix(EMAIL_HASH, TransferOptions::default())
+        .with_inbox();
+    bench.run("create_transfer", create.instruction())?;
+    let transfer = create.transfer_address();
+
+    let expires_at = bench.svm.clock().unix_timestamp + 3600;
+    let message = payinbox::bind_attestation_message(&EMAIL_HASH, &bench.recipient, expires_at);
+    let ix = instruction(
+        payinbox::accounts::BindInbox {
+            inbox,
+            config: pda::config_address(),
+            attestation_record: pda::attestation_record_address(&message),
+            wallet: bench.recipient,
+            instructions: sysvar::instructions::ID,
+            system_program: system_program::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::BindInbox { expires_at },
+    );
+    bench.measure_after(
+        "bind_inbox",
+        Some(ed25519_instruction(verifier, &message)),
+        ix,
+    )?;
+    bench.record_account("inbox", &inbox)?;
+    bench.record_account(
+        "attestation_record",
+        &pda::attestation_record_address(&message),
+    )?;
+
+    let mut accounts = payinbox::accounts::SweepInbox {
+        inbox,
+        owner: bench.recipient,
+        token_program: token::ID,
+        event_authority: pda::event_authority_address(),
+        program: payinbox::ID,
+    }
+    .to_account_metas(None);
+    accounts.extend([
+        AccountMeta::new(transfer, false),
+        AccountMeta::new(pda::escrow_address(&transfer), false),
+        AccountMeta::new(bench.token_account(&bench.recipient), false),
+        AccountMeta::new(bench.sender, false),
+    ]);
+    let ix = Instruction {
+        program_id: payinbox::ID,
+        accounts,
+        data: payinbox::instruction::SweepInbox {}.data(),
+    };
+    bench.measure("sweep_inbox", ix)?;
+
+    let ix = instruction(
+        payinbox::accounts::SetInboxPasskey {
+            inbox,
+            owner: bench.recipient,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::SetInboxPasskey {
+            passkey: Some(Passkey::new().public_key()),
+        },
+    );
+    bench.measure("set_inbox_passkey", ix)
+}
+
+/// register_wallet and send_to_email, paying the registered wallet
+/// directly and falling back to an escrow.
+fn registry(bench: &mut Bench, verifier: &Keypair) -> Result<()> {
+    const REGISTERED: [u8; 32] = [9; 32];
+    const UNREGISTERED: [u8; 32] = [10; 32];
+    let expires_at = bench.svm.clock().unix_timestamp + 3600;
+    let message = payinbox::register_attestation_message(&REGISTERED, &bench.recipient, expires_at);
+    let ix = instruction(
+        payinbox::accounts::RegisterWallet {
+            registry: pda::registry_address(&REGISTERED),
+            config: pda::config_address(),
+            attestation_record: pda::attestation_record_address(&message),
+            wallet: bench.recipient,
+            instructions: sysvar::instructions::ID,
+            system_program: system_program::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::RegisterWallet {
+            email_hash: REGISTERED,
+            expires_at,
+        },
+    );
+    bench.measure_after(
+        "register_wallet",
+        Some(ed25519_instruction(verifier, &message)),
+        ix,
+    )?;
+    bench.record_account("registry", &pda::registry_address(&REGISTERED))?;
+
+    let send = |bench: &Bench, email_hash: [u8; 32], direct: bool| {
+        let transfer = pda::transfer_address(&bench.sender, &email_hash);
+        instruction(
+            payinbox::accounts::SendToEmail {
+                registry: pda::registry_address(&email_hash),
+                sender: bench.sender,
+                sender_token_account: bench.token_account(&bench.sender),
+                token_mint: bench.mint,
+                recipient_token_account: direct.then(|| bench.token_account(&bench.recipient)),
+                transfer: (!direct).then_some(transfer),
+                escrow_token_account: (!direct).then(|| pda::escrow_address(&transfer)),
+                token_program: token::ID,
+                system_program: system_program::ID,
+                inbox: None,
+                event_authority: pda::event_authority_address(),
+                program: payinbox::ID,
+            },
+            payinbox::instruction::SendToEmail {
+                email_hash,
+                claim_code_hash: claim_code_hash(CLAIM_CODE),
+                amount: AMOUNT,
+                expiry_hours: EXPIRY_HOURS,
+                options: TransferOptions::default(),
+            },
+        )
+    };
+    let ix = send(bench, REGISTERED, true);
+    bench.measure("send_to_email", ix)?;
+    let ix = send(bench, UNREGISTERED, false);
+    bench.measure("send_to_email_escrow", ix)
+}
+
+/// claim_with_attestation, paid for by the cranker as relayer.
+fn attestation(bench: &mut Bench, verifier: &Keypair) -> Result<()> {
+    let transfer = bench.create([11; 32], TransferOptions::default())?;
+    let state = bench.transfer(&transfer)?;
+    let expires_at = bench.svm.clock().unix_timestamp + 600;
+    let message = payinbox::claim_attestation_message(
+        &state.email_hash,
+        &transfer,
+        &bench.recipient,
+        expires_at,
+    );
+    let ix = instruction(
+        payinbox::accounts::ClaimWithAttestation {
+            transfer,
+            config: pda::config_address(),
+            attestation_record: pda::attestation_record_address(&message),
+            destination: bench.recipient,
+            destination_token_account: bench.token_account(&bench.recipient),
+            escrow_token_account: pda::escrow_address(&transfer),
+            sender: bench.sender,
+            payer: bench.cranker,
+            instructions: sysvar::instructions::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+            inbox: None,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::ClaimWithAttestation { expires_at },
+    );
+    bench.measure_after(
+        "claim_with_attestation",
+        Some(ed25519_instruction(verifier, &message)),
+        ix,
+    )
+}
+
+/// The DKIM verifying key and key list, and claim_with_dkim_proof with
+/// the harness's fixed proof.
+fn dkim_proof(bench: &mut Bench) -> Result<()> {
+    let config = pda::config_address();
+    let dkim_verifier = pda::dkim_verifier_address();
+    let dkim_domain = pda::dkim_domain_address(&dkim::DOMAIN_HASH);
+    let ix = instruction(
+        payinbox::accounts::SetDkimVerifyingKey {
+            config,
+            dkim_verifier,
+            admin: bench.sender,
+            system_program: system_program::ID,
+        },
+        dkim::verifying_key(),
+    );
+    bench.measure("set_dkim_verifying_key", ix)?;
+    let ix = instruction(
+        payinbox::accounts::SetDkimVerifyingKeyIc {
+            config,
+            dkim_verifier,
+            admin: bench.sender,
+        },
+        payinbox::instruction::SetDkimVerifyingKeyIc { ic: dkim::ic() },
+    );
+    bench.measure("set_dkim_verifying_key_ic", ix)?;
+    bench.record_account("dkim_verifier", &dkim_verifier)?;
+
+    let admin = bench.sender;
+    let update_keys = || payinbox::accounts::UpdateDkimKeys {
+        config,
+        dkim_domain,
+        admin,
+        system_program: system_program::ID,
+        event_authority: pda::event_authority_address(),
+        program: payinbox::ID,
+    };
+    let ix = instruction(
+        update_keys(),
+        payinbox::instruction::AddDkimKey {
+            domain_hash: dkim::DOMAIN_HASH,
+            key_hash: dkim::KEY_HASH,
+        },
+    );
+    bench.measure("add_dkim_key", ix)?;
+    bench.record_account("dkim_domain", &dkim_domain)?;
+
+    let transfer = bench.create(dkim::EMAIL_HASH, TransferOptions::default())?;
+    let destination = dkim::destination();
+    let destination_token_account = bench.svm.create_token_account(&destination, &bench.mint, 0);
+    let ix = instruction(
+        payinbox::accounts::ClaimWithDkimProof {
+            transfer,
+            dkim_verifier,
+            dkim_domain,
+            destination,
+            destination_token_account,
+            escrow_token_account: pda::escrow_address(&transfer),
+            sender: bench.sender,
+            token_program: token::ID,
+            inbox: None,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::ClaimWithDkimProof {
+            proof: dkim::proof(),
+            domain_hash: dkim::DOMAIN_HASH,
+            dkim_key_hash: dkim::KEY_HASH,
+        },
+    );
+    bench.measure("claim_with_dkim_proof", ix)?;
+
+    let ix = instruction(
+        update_keys(),
+        payinbox::instruction::RemoveDkimKey {
+            domain_hash: dkim::DOMAIN_HASH,
+            key_hash: dkim::KEY_HASH,
+        },
+    );
+    bench.measure("remove_dkim_key", ix)
+}
+
+/// set_claim_passkey, and claim_transfer with a WebAuthn assertion.
+fn passkey(bench: &mut Bench) -> Result<()> {
+    let transfer = bench.create([12; 32], TransferOptions::default())?;
+    let passkey = Passkey::new();
+    let ix =
+        SetClaimPasskey::new(&bench.transfer(&transfer)?, Some(passkey.public_key())).instruction();
+    bench.measure("set_claim_passkey", ix)?;
+
+    let (assertion, client_data) = passkey.assert(&passkey_challenge(&transfer, &bench.recipient));
+    let ix =
+        ClaimTransfer::new(&bench.transfer(&transfer)?, bench.recipient, client_data).instruction();
+    bench.measure_after("claim_transfer_with_passkey", Some(assertion), ix)
+}
+
+/// commit_claim and reveal_claim.
+fn commit_reveal(bench: &mut Bench) -> Result<()> {
+    let committer = commit_keypair(CLAIM_CODE).pubkey();
+    let options = TransferOptions {
+        commit_key: Some(committer),
+        ..TransferOptions::default()
+    };
+    let transfer = bench.create([13; 32], options)?;
+    let commitment = claim_commitment(CLAIM_CODE.as_bytes(), &bench.recipient, &SALT);
+    let ix = CommitClaim::new(&bench.transfer(&transfer)?, committer, commitment).instruction();
+    bench.measure("commit_claim", ix)?;
+
+    let ix = RevealClaim::new(
+        &bench.transfer(&transfer)?,
+        bench.recipient,
+        CLAIM_CODE,
+        SALT,
+    )
+    .instruction();
+    bench.measure("reveal_claim", ix)
+}
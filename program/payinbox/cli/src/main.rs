@@ -0,0 +1,480 @@
+//! `payinbox` command-line tool: send, claim, cancel, reclaim and inspect
+//! transfers against any RPC endpoint.
+
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_spl::associated_token::{
+    get_associated_token_address,
+    spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+};
+use anchor_spl::token;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use payinbox::{TransferAccount, TransferOptions};
+use payinbox_client::accounts::{fetch_transfer, fetch_transfers};
+use payinbox_client::hash::{claim_code_hash, commit_keypair, email_hash};
+use payinbox_client::instructions::{
+    CancelTransfer, ClaimTransfer, CommitClaim, CreateTransfer, ReclaimExpired, RevealClaim,
+};
+use payinbox_client::rpc::RpcClient;
+use payinbox_client::{pda, ClientError};
+use rand::RngCore;
+use solana_keypair::{read_keypair_file, Keypair};
+use solana_signer::Signer;
+
+#[derive(Parser)]
+#[command(name = "payinbox", version, about = "Send and claim tokens by email")]
+struct Cli {
+    /// RPC endpoint
+    #[arg(
+        long,
+        global = true,
+        env = "PAYINBOX_RPC_URL",
+        default_value = "https://api.devnet.solana.com"
+    )]
+    url: String,
+
+    /// Keypair file that signs and pays for transactions
+    #[arg(
+        long,
+        global = true,
+        env = "PAYINBOX_KEYPAIR",
+        default_value = "~/.config/solana/id.json"
+    )]
+    keypair: String,
+
+    /// Salt mixed into email hashes; must match the agent service
+    #[arg(
+        long,
+        global = true,
+        env = "PAYINBOX_EMAIL_SALT",
+        default_value = "",
+        hide_env_values = true
+    )]
+    email_salt: String,
+
+    /// Simulate the transaction instead of submitting it
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a transfer to an email address
+    Send(SendArgs),
+    /// Claim a transfer with its claim code
+    Claim {
+        /// Transfer address
+        transfer: Pubkey,
+        /// Claim code from the email
+        #[arg(long)]
+        code: String,
+    },
+    /// Cancel an active transfer (sender or cancel authority)
+    Cancel {
+        /// Transfer address
+        transfer: Pubkey,
+    },
+    /// Return an expired transfer's funds and collect its crank bounty
+    Reclaim {
+        /// Transfer address
+        transfer: Pubkey,
+    },
+    /// Print a transfer's state
+    Show {
+        /// Transfer address
+        transfer: Pubkey,
+    },
+    /// List transfers
+    List {
+        /// Only transfers from this sender (default: the keypair)
+        #[arg(long, conflicts_with = "all")]
+        sender: Option<Pubkey>,
+        /// Transfers from every sender
+        #[arg(long)]
+        all: bool,
+        /// Only transfers to this email
+        #[arg(long, conflicts_with = "email_hash")]
+        email: Option<String>,
+        /// Only transfers to this email hash (64 hex characters)
+        #[arg(long, value_parser = parse_hash)]
+        email_hash: Option<[u8; 32]>,
+    },
+}
+
+#[derive(Args)]
+struct SendArgs {
+    #[command(flatten)]
+    recipient: RecipientArgs,
+    /// Token mint
+    #[arg(long)]
+    mint: Pubkey,
+    /// Amount in the mint's base units
+    #[arg(long)]
+    amount: u64,
+    /// Hours until the transfer expires (1-168)
+    #[arg(long, default_value_t = 72)]
+    expiry_hours: i64,
+    /// Claim code (default: 32 random bytes, hex encoded)
+    #[arg(long)]
+    code: Option<String>,
+    /// Disable cancel_transfer for this transfer
+    #[arg(long)]
+    irrevocable: bool,
+    /// Owner of the account refunds go to (default: the sender)
+    #[arg(long)]
+    refund_to: Option<Pubkey>,
+    /// Owner paid on expiry instead of the refund owner
+    #[arg(long)]
+    expiry_beneficiary: Option<Pubkey>,
+    /// Lamports paid to whoever reclaims the expired transfer
+    #[arg(long, default_value_t = 0)]
+    crank_bounty_lamports: u64,
+    /// Share of the tokens (basis points) paid to the reclaimer
+    #[arg(long, default_value_t = 0)]
+    crank_bounty_bps: u16,
+    /// Wallet that may cancel besides the sender
+    #[arg(long)]
+    cancel_authority: Option<Pubkey>,
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+struct RecipientArgs {
+    /// Recipient email, hashed with the email salt
+    #[arg(long)]
+    email: Option<String>,
+    /// Recipient email hash (64 hex characters)
+    #[arg(long, value_parser = parse_hash)]
+    email_hash: Option<[u8; 32]>,
+}
+
+/// The given email hash, or the hash of the given email.
+fn recipient_hash(email: Option<&String>, hash: Option<&[u8; 32]>, salt: &str) -> Option<[u8; 32]> {
+    hash.copied()
+        .or_else(|| email.map(|email| email_hash(email, salt)))
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(err) = run(cli) {
+        eprintln!("error: {}", describe(&err));
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let rpc = RpcClient::new(&cli.url);
+    match &cli.command {
+        Command::Send(args) => send(&cli, &rpc, args),
+        Command::Claim { transfer, code } => {
+            let signer = load_keypair(&cli.keypair)?;
+            let state = fetch_transfer(&rpc, transfer)?;
+            let recipient = signer.pubkey();
+            let create_account = create_associated_token_account_idempotent(
+                &recipient,
+                &recipient,
+                &state.token_mint,
+                &token::ID,
+            );
+            if state.commit_key.is_none() {
+                let claim = ClaimTransfer::new(&state, recipient, code.as_str()).instruction();
+                execute(&cli, &rpc, &signer, &[], &[create_account, claim])?;
+                return report(&cli, &rpc, transfer);
+            }
+
+            // Commit to the destination before revealing the code, so the
+            // code can't be lifted from the claim and raced to another wallet
+            let commit_key = commit_keypair(code);
+            let salt: [u8; 32] = rand::random();
+            let commitment = payinbox::claim_commitment(code.as_bytes(), &recipient, &salt);
+            let commit = CommitClaim::new(&state, commit_key.pubkey(), commitment).instruction();
+            let reveal = RevealClaim::new(&state, recipient, code.as_str(), salt).instruction();
+            if cli.dry_run {
+                // The reveal needs the commit, so simulate both together
+                let instructions = [commit, create_account, reveal];
+                execute(&cli, &rpc, &signer, &[&commit_key], &instructions)?;
+            } else {
+                execute(&cli, &rpc, &signer, &[&commit_key], &[commit])?;
+                execute(&cli, &rpc, &signer, &[], &[create_account, reveal])?;
+            }
+            report(&cli, &rpc, transfer)
+        }
+        Command::Cancel { transfer } => {
+            let signer = load_keypair(&cli.keypair)?;
+            let state = fetch_transfer(&rpc, transfer)?;
+            let instruction = CancelTransfer::new(&state, signer.pubkey()).instruction();
+            execute(&cli, &rpc, &signer, &[], &[instruction])?;
+            report(&cli, &rpc, transfer)
+        }
+        Command::Reclaim { transfer } => {
+            let signer = load_keypair(&cli.keypair)?;
+            let state = fetch_transfer(&rpc, transfer)?;
+            let mut builder = ReclaimExpired::new(&state, signer.pubkey());
+            let mut instructions = Vec::new();
+            if state.crank_bounty_bps > 0 {
+                let cranker = signer.pubkey();
+                instructions.push(create_associated_token_account_idempotent(
+                    &cranker,
+                    &cranker,
+                    &state.token_mint,
+                    &token::ID,
+                ));
+                builder = builder.cranker_token_account(get_associated_token_address(
+                    &cranker,
+                    &state.token_mint,
+                ));
+            }
+            instructions.push(builder.instruction());
+            execute(&cli, &rpc, &signer, &[], &instructions)?;
+            report(&cli, &rpc, transfer)
+        }
+        Command::Show { transfer } => {
+            let state = fetch_transfer(&rpc, transfer)?;
+            print_transfer(transfer, &state);
+            Ok(())
+        }
+        Command::List {
+            sender,
+            all,
+            email,
+            email_hash,
+        } => {
+            let sender = match (sender, all) {
+                (Some(sender), _) => Some(*sender),
+                (None, true) => None,
+                (None, false) => Some(load_keypair(&cli.keypair)?.pubkey()),
+            };
+            let email_hash = recipient_hash(email.as_ref(), email_hash.as_ref(), &cli.email_salt);
+            let mut transfers = fetch_transfers(&rpc, sender.as_ref(), email_hash.as_ref())?;
+            transfers.sort_by_key(|(_, transfer)| transfer.created_at);
+            for (address, transfer) in &transfers {
+                println!(
+                    "{address}  {:<9}  {:>20}  {}  expires {}",
+                    format!("{:?}", transfer.status),
+                    transfer.amount,
+                    transfer.token_mint,
+                    transfer.expiry
+                );
+            }
+            eprintln!("{} transfer(s)", transfers.len());
+            Ok(())
+        }
+    }
+}
+
+fn send(cli: &Cli, rpc: &RpcClient, args: &SendArgs) -> Result<()> {
+    let signer = load_keypair(&cli.keypair)?;
+    let recipient = &args.recipient;
+    let email_hash = recipient_hash(
+        recipient.email.as_ref(),
+        recipient.email_hash.as_ref(),
+        &cli.email_salt,
+    )
+    .expect("clap requires --email or --email-hash");
+    let code = args.code.clone().unwrap_or_else(random_claim_code);
+
+    let mut builder = CreateTransfer::new(
+        signer.pubkey(),
+        args.mint,
+        email_hash,
+        claim_code_hash(&code),
+        args.amount,
+        args.expiry_hours,
+    )
+    .options(TransferOptions {
+        irrevocable: args.irrevocable,
+        refund_to: args.refund_to,
+        expiry_beneficiary: args.expiry_beneficiary,
+        crank_bounty_lamports: args.crank_bounty_lamports,
+        crank_bounty_bps: args.crank_bounty_bps,
+        cancel_authority: args.cancel_authority,
+        commit_key: Some(commit_keypair(&code).pubkey()),
+    });
+    // Count the transfer in the recipient's inbox if they opened one
+    if rpc
+        .get_account_data(&pda::inbox_address(&email_hash))?
+        .is_some()
+    {
+        builder = builder.with_inbox();
+    }
+
+    let transfer = builder.transfer_address();
+    execute(cli, rpc, &signer, &[], &[builder.instruction()])?;
+    if args.code.is_none() {
+        println!("claim code: {code}");
+    }
+    report(cli, rpc, &transfer)
+}
+
+/// Signs `instructions` with `signer` as fee payer, and with `signers`,
+/// then simulates or submits and waits for confirmation.
+fn execute(
+    cli: &Cli,
+    rpc: &RpcClient,
+    signer: &Keypair,
+    signers: &[&Keypair],
+    instructions: &[Instruction],
+) -> Result<()> {
+    if cli.dry_run {
+        let transaction = rpc.sign_transaction(instructions, signer, signers)?;
+        let simulation = rpc.simulate_transaction(&bincode::serialize(&transaction)?)?;
+        for log in &simulation.logs {
+            println!("  {log}");
+        }
+        if let Some(units) = simulation.units_consumed {
+            println!("compute units: {units}");
+        }
+        return match simulation.error_message() {
+            None => Ok(()),
+            Some(error) => bail!("simulation failed: {error}"),
+        };
+    }
+
+    let sent = rpc.send_and_confirm(instructions, signer, signers)?;
+    println!("signature: {}", sent.signature);
+    Ok(())
+}
+
+/// Prints the transfer PDA and, unless simulating, its status.
+fn report(cli: &Cli, rpc: &RpcClient, transfer: &Pubkey) -> Result<()> {
+    println!("transfer: {transfer}");
+    if cli.dry_run {
+        println!("status: unchanged (dry run)");
+    } else {
+        println!("status: {:?}", fetch_transfer(rpc, transfer)?.status);
+    }
+    Ok(())
+}
+
+fn print_transfer(address: &Pubkey, transfer: &TransferAccount) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+    let optional = |key: Option<Pubkey>| key.map_or_else(|| "-".to_string(), |key| key.to_string());
+
+    println!("transfer:           {address}");
+    println!("status:             {:?}", transfer.status);
+    println!("claim mode:         {:?}", transfer.claim_mode);
+    println!("sender:             {}", transfer.sender);
+    println!("email hash:         {}", hex(&transfer.email_hash));
+    println!("amount:             {}", transfer.amount);
+    println!("mint:               {}", transfer.token_mint);
+    println!("escrow:             {}", transfer.escrow_token_account);
+    println!("created at:         {}", transfer.created_at);
+    println!(
+        "expiry:             {}{}",
+        transfer.expiry,
+        if now >= transfer.expiry {
+            " (expired)"
+        } else {
+            ""
+        }
+    );
+    println!("irrevocable:        {}", transfer.irrevocable);
+    println!("refund to:          {}", transfer.refund_to);
+    println!(
+        "expiry beneficiary: {}",
+        optional(transfer.expiry_beneficiary)
+    );
+    println!(
+        "cancel authority:   {}",
+        optional(transfer.cancel_authority)
+    );
+    println!(
+        "crank bounty:       {} lamports + {} bps",
+        transfer.crank_bounty_lamports, transfer.crank_bounty_bps
+    );
+    println!("in inbox:           {}", transfer.in_inbox);
+    println!(
+        "passkey:            {}",
+        transfer
+            .claim_passkey
+            .map_or("-".to_string(), |key| hex(&key))
+    );
+    if transfer.is_reserved(now) {
+        println!("reserved until:     {}", transfer.reservation_expiry);
+    }
+}
+
+fn load_keypair(path: &str) -> Result<Keypair> {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var("HOME").context("HOME is not set")?).join(rest),
+        None => PathBuf::from(path),
+    };
+    read_keypair_file(&path).map_err(|e| anyhow!("reading keypair {}: {e}", path.display()))
+}
+
+fn random_claim_code() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex(&bytes)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn parse_hash(value: &str) -> Result<[u8; 32], String> {
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    if value.len() != 64 {
+        return Err("expected 64 hex characters".into());
+    }
+    let mut hash = [0u8; 32];
+    for (byte, chunk) in hash.iter_mut().zip(value.as_bytes().chunks(2)) {
+        let chunk = std::str::from_utf8(chunk).map_err(|e| e.to_string())?;
+        *byte = u8::from_str_radix(chunk, 16).map_err(|e| e.to_string())?;
+    }
+    Ok(hash)
+}
+
+/// Names the program error behind a failed RPC call, if there is one.
+fn describe(err: &anyhow::Error) -> String {
+    match err.downcast_ref::<ClientError>() {
+        Some(ClientError::Rpc {
+            program_error: Some(error),
+            ..
+        }) => format!("{} ({}): {error}", error.name(), u32::from(*error)),
+        _ => format!("{err:#}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn send_requires_one_recipient() {
+        let mint = Pubkey::new_unique().to_string();
+        let hash = "00".repeat(32);
+        let send = |recipient: &[&str]| {
+            let base = ["payinbox", "send", "--mint", &mint, "--amount", "5"];
+            Cli::try_parse_from([&base[..], recipient].concat())
+        };
+        assert!(send(&[]).is_err());
+        assert!(send(&["--email", "a@b.c"]).is_ok());
+        assert!(send(&["--email-hash", &hash]).is_ok());
+        assert!(send(&["--email", "a@b.c", "--email-hash", &hash]).is_err());
+    }
+
+    #[test]
+    fn parses_hex_hashes() {
+        let hash = parse_hash(&format!("0x{}", "ab".repeat(32))).unwrap();
+        assert_eq!(hash, [0xab; 32]);
+        assert!(parse_hash("abcd").is_err());
+        assert!(parse_hash(&"zz".repeat(32)).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+//! Fetching and decoding program accounts.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::{AccountDeserialize, Discriminator};
+use payinbox::{TransferAccount, TransferStatus};
+
+use crate::error::ClientError;
+use crate::rpc::{Memcmp, RpcClient};
+
+/// Offset of `TransferAccount::sender` (after the discriminator).
+pub const TRANSFER_SENDER_OFFSET: usize = 8;
+/// Offset of `TransferAccount::email_hash`.
+pub const TRANSFER_EMAIL_HASH_OFFSET: usize = 40;
+/// Offset of `TransferAccount::status`.
+pub const TRANSFER_STATUS_OFFSET: usize = 192;
+
+/// Decodes raw account data (including the discriminator) as a
+/// `TransferAccount`.
+pub fn decode_transfer(data: &[u8]) -> Result<TransferAccount, ClientError> {
+    TransferAccount::try_deserialize(&mut &data[..]).map_err(|e| ClientError::Decode(e.to_string()))
+}
+
+/// Fetches and decodes the transfer at `address`.
+pub fn fetch_transfer(rpc: &RpcClient, address: &Pubkey) -> Result<TransferAccount, ClientError> {
+    let data = rpc
+        .get_account_data(address)?
+        .ok_or_else(|| ClientError::AccountNotFound(address.to_string()))?;
+    decode_transfer(&data)
+}
+
+/// Fetches every transfer, optionally only those of `sender` and/or to
+/// `email_hash`.
+pub fn fetch_transfers(
+    rpc: &RpcClient,
+    sender: Option<&Pubkey>,
+    email_hash: Option<&[u8; 32]>,
+) -> Result<Vec<(Pubkey, TransferAccount)>, ClientError> {
+    let mut filters = vec![Memcmp {
+        offset: 0,
+        bytes: TransferAccount::DISCRIMINATOR.to_vec(),
+    }];
+    if let Some(sender) = sender {
+        filters.push(Memcmp {
+            offset: TRANSFER_SENDER_OFFSET,
+            bytes: sender.to_bytes().to_vec(),
+        });
+    }
+    if let Some(email_hash) = email_hash {
+        filters.push(Memcmp {
+            offset: TRANSFER_EMAIL_HASH_OFFSET,
+            bytes: email_hash.to_vec(),
+        });
+    }
+    rpc.get_program_accounts(&payinbox::ID, &filters)?
+        .into_iter()
+        .map(|(address, data)| Ok((address, decode_transfer(&data)?)))
+        .collect()
+}
+
+/// Fetches every transfer still Active.
+pub fn fetch_active_transfers(
+    rpc: &RpcClient,
+) -> Result<Vec<(Pubkey, TransferAccount)>, ClientError> {
+    let filters = [
+        Memcmp {
+            offset: 0,
+            bytes: TransferAccount::DISCRIMINATOR.to_vec(),
+        },
+        Memcmp {
+            offset: TRANSFER_STATUS_OFFSET,
+            bytes: vec![TransferStatus::Active as u8],
+        },
+    ];
+    rpc.get_program_accounts(&payinbox::ID, &filters)?
+        .into_iter()
+        .map(|(address, data)| Ok((address, decode_transfer(&data)?)))
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use anchor_lang::AccountSerialize;
+    use payinbox::ClaimMode;
+
+    pub(crate) fn sample_transfer() -> TransferAccount {
+        let sender = Pubkey::new_unique();
+        TransferAccount {
+            sender,
+            email_hash: [7; 32],
+            claim_code_hash: [9; 32],
+            amount: 1_000_000,
+            token_mint: Pubkey::new_unique(),
+            escrow_token_account: Pubkey::new_unique(),
+            created_at: 1_700_000_000,
+            expiry: 1_700_259_200,
+            status: TransferStatus::Active,
+            bump: 255,
+            escrow_bump: 254,
+            claim_mode: ClaimMode::SingleCode,
+            contribution_deadline: 0,
+            in_inbox: false,
+            claim_passkey: None,
+            claim_commitment: [0; 32],
+            reservation_expiry: 0,
+            reservation_count: 0,
+            irrevocable: false,
+            refund_to: sender,
+            expiry_beneficiary: None,
+            crank_bounty_lamports: 0,
+            crank_bounty_bps: 0,
+            cancel_authority: None,
+            commit_key: None,
+        }
+    }
+
+    #[test]
+    fn decodes_serialized_transfer() {
+        let transfer = sample_transfer();
+        let mut data = Vec::new();
+        transfer.try_serialize(&mut data).unwrap();
+
+        let decoded = decode_transfer(&data).unwrap();
+        assert_eq!(decoded.sender, transfer.sender);
+        assert_eq!(decoded.amount, transfer.amount);
+        assert_eq!(decoded.refund_to, transfer.refund_to);
+        assert!(decoded.status == TransferStatus::Active);
+    }
+
+    #[test]
+    fn filter_offsets_match_layout() {
+        let transfer = sample_transfer();
+        let mut data = Vec::new();
+        transfer.try_serialize(&mut data).unwrap();
+
+        assert_eq!(
+            &data[TRANSFER_SENDER_OFFSET..TRANSFER_SENDER_OFFSET + 32],
+            transfer.sender.as_ref()
+        );
+        assert_eq!(
+            &data[TRANSFER_EMAIL_HASH_OFFSET..TRANSFER_EMAIL_HASH_OFFSET + 32],
+            &transfer.email_hash
+        );
+
+        let mut cancelled = transfer;
+        cancelled.status = TransferStatus::Cancelled;
+        let mut data = Vec::new();
+        cancelled.try_serialize(&mut data).unwrap();
+        assert_eq!(
+            data[TRANSFER_STATUS_OFFSET],
+            TransferStatus::Cancelled as u8
+        );
+    }
+
+    #[test]
+    fn rejects_other_account_types() {
+        let mut data = payinbox::Inbox::DISCRIMINATOR.to_vec();
+        data.resize(8 + TransferAccount::LEN, 0);
+        assert!(matches!(
+            decode_transfer(&data),
+            Err(ClientError::Decode(_))
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+//! Client errors and mapping of program error numbers back to `ErrorCode`.
+
+use anchor_lang::error::ERROR_CODE_OFFSET;
+use payinbox::ErrorCode;
+use serde_json::Value;
+use thiserror::Error;
+
+/// Errors returned by the client.
+#[derive(Debug, Error)]
+pub enum ClientError {
+    /// The RPC node could not be reached or returned a non-JSON response
+    #[error("transport error: {0}")]
+    Transport(String),
+    /// The RPC node answered with a JSON-RPC error
+    #[error("rpc error {code}: {message}")]
+    Rpc {
+        code: i64,
+        message: String,
+        /// `ErrorCode` of the failed instruction, if the program raised one
+        program_error: Option<ErrorCode>,
+    },
+    /// No account exists at the address
+    #[error("account {0} not found")]
+    AccountNotFound(String),
+    /// Account or response data could not be decoded
+    #[error("decode error: {0}")]
+    Decode(String),
+}
+
+/// Every `ErrorCode` variant in declaration order, so that
+/// `ERROR_CODES[n]` is the variant with number `6000 + n`.
+pub const ERROR_CODES: [ErrorCode; 52] = [
+    ErrorCode::InvalidClaimCode,
+    ErrorCode::ClaimCodeTooLong,
+    ErrorCode::TransferExpired,
+    ErrorCode::AlreadyClaimed,
+    ErrorCode::AlreadyRefunded,
+    ErrorCode::NotExpired,
+    ErrorCode::Unauthorized,
+    ErrorCode::InvalidAmount,
+    ErrorCode::InvalidExpiry,
+    ErrorCode::InvalidTokenAccount,
+    ErrorCode::InvalidTokenMint,
+    ErrorCode::InsufficientFunds,
+    ErrorCode::InvalidEscrowAccount,
+    ErrorCode::InvalidSender,
+    ErrorCode::Overflow,
+    ErrorCode::InvalidTransferState,
+    ErrorCode::InvalidClaimMode,
+    ErrorCode::InvalidClaimCodeSet,
+    ErrorCode::InvalidThreshold,
+    ErrorCode::InsufficientApprovals,
+    ErrorCode::ApprovalRecipientMismatch,
+    ErrorCode::InvalidContributionDeadline,
+    ErrorCode::NotGroupTransfer,
+    ErrorCode::ContributionsClosed,
+    ErrorCode::GroupTransferRefund,
+    ErrorCode::NotConfigAdmin,
+    ErrorCode::VerifierListFull,
+    ErrorCode::UnknownVerifier,
+    ErrorCode::InvalidAttestation,
+    ErrorCode::AttestationExpired,
+    ErrorCode::InboxFull,
+    ErrorCode::InboxRequired,
+    ErrorCode::NotInboxOwner,
+    ErrorCode::InvalidSweepAccounts,
+    ErrorCode::RecipientAccountRequired,
+    ErrorCode::EscrowAccountsRequired,
+    ErrorCode::InvalidProof,
+    ErrorCode::DkimVerifierNotReady,
+    ErrorCode::DkimKeyListFull,
+    ErrorCode::UnknownDkimKey,
+    ErrorCode::InvalidPasskey,
+    ErrorCode::InvalidPasskeySignature,
+    ErrorCode::ClaimReserved,
+    ErrorCode::NoActiveReservation,
+    ErrorCode::InvalidCommitment,
+    ErrorCode::ReservationLimitReached,
+    ErrorCode::IrrevocableTransfer,
+    ErrorCode::InvalidCrankBounty,
+    ErrorCode::InvalidCommitter,
+    ErrorCode::ApprovalsRequired,
+    ErrorCode::InboxMintNotTracked,
+    ErrorCode::ClientDataTooLong,
+];
+
+/// Maps a custom program error number (e.g. 6000) to its `ErrorCode`.
+pub fn program_error(code: u32) -> Option<ErrorCode> {
+    let index = code.checked_sub(ERROR_CODE_OFFSET)?;
+    ERROR_CODES.get(index as usize).copied()
+}
+
+/// Finds the `ErrorCode` in a runtime message such as
+/// `"... failed: custom program error: 0x1770"`.
+pub fn parse_program_error(message: &str) -> Option<ErrorCode> {
+    let (_, hex) = message.split_once("custom program error: 0x")?;
+    let end = hex
+        .find(|c: char| !c.is_ascii_hexdigit())
+        .unwrap_or(hex.len());
+    program_error(u32::from_str_radix(&hex[..end], 16).ok()?)
+}
+
+/// Finds the `ErrorCode` in an RPC transaction error such as
+/// `{"InstructionError": [0, {"Custom": 6000}]}`.
+pub fn transaction_error(err: &Value) -> Option<ErrorCode> {
+    let code = err
+        .get("InstructionError")?
+        .get(1)?
+        .get("Custom")?
+        .as_u64()?;
+    program_error(u32::try_from(code).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn error_codes_follow_declaration_order() {
+        for (index, error) in ERROR_CODES.iter().enumerate() {
+            assert_eq!(u32::from(*error), ERROR_CODE_OFFSET + index as u32);
+        }
+    }
+
+    #[test]
+    fn maps_numbers_to_variants() {
+        assert_eq!(program_error(6000).unwrap().name(), "InvalidClaimCode");
+        assert_eq!(program_error(6051).unwrap().name(), "ClientDataTooLong");
+        assert!(program_error(5999).is_none());
+        assert!(program_error(6052).is_none());
+    }
+
+    #[test]
+    fn parses_runtime_messages() {
+        let message = "Error processing Instruction 0: custom program error: 0x1771";
+        assert_eq!(
+            parse_program_error(message).unwrap().name(),
+            "ClaimCodeTooLong"
+        );
+        assert!(parse_program_error("insufficient funds for rent").is_none());
+    }
+
+    #[test]
+    fn parses_transaction_errors() {
+        let err = json!({ "InstructionError": [1, { "Custom": 6002 }] });
+        assert_eq!(transaction_error(&err).unwrap().name(), "TransferExpired");
+        assert!(transaction_error(&json!("AccountNotFound")).is_none());
+    }
+}
@@ -0,0 +1,177 @@
+//! Decoding of the transfer lifecycle events.
+//!
+//! The program emits them with `emit_cpi!`: each event is the data of an
+//! instruction the program invokes on itself, signed by its event
+//! authority, so it shows up among the transaction's inner instructions
+//! and can't be truncated away like log lines.
+
+use anchor_lang::event::EVENT_IX_TAG_LE;
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use payinbox::{
+    GroupTransferRefunded, TransferCancelled, TransferClaimed, TransferCreated, TransferReclaimed,
+    TRANSFER_EVENT_VERSION,
+};
+
+use crate::pda;
+use crate::rpc::InnerInstruction;
+
+/// The transfer a lifecycle event is about, and when it happened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EventHeader {
+    pub transfer: Pubkey,
+    pub sender: Pubkey,
+    pub email_hash: [u8; 32],
+    pub token_mint: Pubkey,
+    pub created_at: i64,
+    pub expiry: i64,
+    /// Unix timestamp of the event
+    pub timestamp: i64,
+}
+
+/// A lifecycle event of one transfer.
+pub enum TransferEvent {
+    Created(TransferCreated),
+    Claimed(TransferClaimed),
+    Cancelled(TransferCancelled),
+    Reclaimed(TransferReclaimed),
+    /// The last refund of an expired group transfer
+    GroupRefunded(GroupTransferRefunded),
+}
+
+impl TransferEvent {
+    /// The fields every lifecycle event carries.
+    pub fn header(&self) -> EventHeader {
+        macro_rules! header {
+            ($event:expr) => {
+                EventHeader {
+                    transfer: $event.transfer,
+                    sender: $event.sender,
+                    email_hash: $event.email_hash,
+                    token_mint: $event.token_mint,
+                    created_at: $event.created_at,
+                    expiry: $event.expiry,
+                    timestamp: $event.timestamp,
+                }
+            };
+        }
+        match self {
+            TransferEvent::Created(event) => header!(event),
+            TransferEvent::Claimed(event) => header!(event),
+            TransferEvent::Cancelled(event) => header!(event),
+            TransferEvent::Reclaimed(event) => header!(event),
+            TransferEvent::GroupRefunded(event) => header!(event),
+        }
+    }
+
+    /// Decodes event data (discriminator included). `None` for other
+    /// events of the program, other schema versions and malformed data.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        fn body<E: Discriminator>(data: &[u8]) -> Option<&[u8]> {
+            data.strip_prefix(E::DISCRIMINATOR)
+        }
+        fn parse<E: AnchorDeserialize>(data: &[u8]) -> Option<E> {
+            // The version leads every layout; anything else may not parse.
+            if data.first() != Some(&TRANSFER_EVENT_VERSION) {
+                return None;
+            }
+            E::try_from_slice(data).ok()
+        }
+
+        if let Some(data) = body::<TransferCreated>(data) {
+            parse(data).map(TransferEvent::Created)
+        } else if let Some(data) = body::<TransferClaimed>(data) {
+            parse(data).map(TransferEvent::Claimed)
+        } else if let Some(data) = body::<TransferCancelled>(data) {
+            parse(data).map(TransferEvent::Cancelled)
+        } else if let Some(data) = body::<TransferReclaimed>(data) {
+            parse(data).map(TransferEvent::Reclaimed)
+        } else if let Some(data) = body::<GroupTransferRefunded>(data) {
+            parse(data).map(TransferEvent::GroupRefunded)
+        } else {
+            None
+        }
+    }
+}
+
+/// Transfer events among the inner instructions of a successful
+/// transaction, in order.
+///
+/// Only self-invocations of the payinbox program through its event
+/// authority count: the program rejects that instruction from anyone else,
+/// so other programs can't forge events.
+pub fn parse_inner_instructions(instructions: &[InnerInstruction]) -> Vec<TransferEvent> {
+    let authority = pda::event_authority_address();
+    instructions
+        .iter()
+        .filter(|ix| ix.program_id == payinbox::ID && ix.accounts.first() == Some(&authority))
+        .filter_map(|ix| ix.data.strip_prefix(EVENT_IX_TAG_LE))
+        .filter_map(TransferEvent::decode)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Event;
+
+    fn claimed() -> TransferClaimed {
+        TransferClaimed {
+            version: TRANSFER_EVENT_VERSION,
+            transfer: Pubkey::new_unique(),
+            sender: Pubkey::new_unique(),
+            email_hash: [7; 32],
+            token_mint: Pubkey::new_unique(),
+            recipient: Pubkey::new_unique(),
+            amount: 42,
+            created_at: 1_700_000_000,
+            expiry: 1_700_003_600,
+            timestamp: 1_700_000_600,
+        }
+    }
+
+    fn event_ix(program_id: Pubkey, authority: Pubkey, event: &impl Event) -> InnerInstruction {
+        InnerInstruction {
+            program_id,
+            accounts: vec![authority],
+            data: [EVENT_IX_TAG_LE, &event.data()].concat(),
+        }
+    }
+
+    #[test]
+    fn decodes_events_of_the_program() {
+        let event = claimed();
+        let events = parse_inner_instructions(&[event_ix(
+            payinbox::ID,
+            pda::event_authority_address(),
+            &event,
+        )]);
+
+        assert_eq!(events.len(), 1);
+        let TransferEvent::Claimed(decoded) = &events[0] else {
+            panic!("expected TransferClaimed");
+        };
+        assert_eq!(decoded.transfer, event.transfer);
+        assert_eq!(decoded.email_hash, event.email_hash);
+        assert_eq!(decoded.amount, 42);
+        assert_eq!(events[0].header().timestamp, event.timestamp);
+        assert_eq!(events[0].header().token_mint, event.token_mint);
+    }
+
+    #[test]
+    fn ignores_other_programs_and_authorities() {
+        let event = claimed();
+        let instructions = [
+            event_ix(Pubkey::new_unique(), pda::event_authority_address(), &event),
+            event_ix(payinbox::ID, Pubkey::new_unique(), &event),
+        ];
+        assert!(parse_inner_instructions(&instructions).is_empty());
+    }
+
+    #[test]
+    fn skips_unknown_schema_versions() {
+        let mut event = claimed();
+        event.version = TRANSFER_EVENT_VERSION + 1;
+        assert!(TransferEvent::decode(&event.data()).is_none());
+    }
+}
@@ -0,0 +1,70 @@
+//! Hashes of the recipient email and claim code, as stored on the
+//! transfer, and the commit key derived from the code.
+
+use payinbox::COMMIT_KEY_DOMAIN;
+use solana_keccak_hasher as keccak;
+use solana_keypair::Keypair;
+use solana_sha256_hasher as sha256;
+
+/// keccak(claim_code), the hash `claim_transfer` checks the code against.
+pub fn claim_code_hash(claim_code: &str) -> [u8; 32] {
+    keccak::hash(claim_code.as_bytes()).to_bytes()
+}
+
+/// The keypair that signs `commit_claim` for transfers locked by
+/// `claim_code`. Its public key goes in `TransferOptions::commit_key`, or
+/// in the `code_keys` of an M-of-N transfer, where it signs `approve_claim`.
+pub fn commit_keypair(claim_code: &str) -> Keypair {
+    let seed = keccak::hashv(&[COMMIT_KEY_DOMAIN, claim_code.as_bytes()]);
+    Keypair::new_from_array(seed.to_bytes())
+}
+
+/// SHA256(normalized email + salt), matching the agent service so that
+/// transfers land in the same inbox whichever side created them.
+pub fn email_hash(email: &str, salt: &str) -> [u8; 32] {
+    let normalized = email.trim().to_lowercase();
+    sha256::hashv(&[normalized.as_bytes(), salt.as_bytes()]).to_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_signer::Signer;
+
+    #[test]
+    fn claim_code_hash_is_keccak256() {
+        // keccak256("") from the Ethereum yellow paper
+        assert_eq!(
+            claim_code_hash(""),
+            [
+                0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+                0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+                0x5d, 0x85, 0xa4, 0x70
+            ]
+        );
+    }
+
+    #[test]
+    fn commit_keypair_is_derived_from_the_code() {
+        assert_eq!(
+            commit_keypair("code").pubkey(),
+            commit_keypair("code").pubkey()
+        );
+        assert_ne!(
+            commit_keypair("code").pubkey(),
+            commit_keypair("other").pubkey()
+        );
+    }
+
+    #[test]
+    fn email_hash_normalizes_address() {
+        assert_eq!(
+            email_hash(" Alice@Example.com ", "salt"),
+            email_hash("alice@example.com", "salt")
+        );
+        assert_ne!(
+            email_hash("alice@example.com", "salt"),
+            email_hash("alice@example.com", "pepper")
+        );
+    }
+}
@@ -0,0 +1,573 @@
+//! Typed builders for the transfer lifecycle instructions.
+//!
+//! Token accounts default to the owner's associated token account and the
+//! inbox is passed whenever the transfer is tracked by one. Each builder
+//! ends with `instruction()`.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::{self, get_associated_token_address};
+use anchor_spl::token;
+use payinbox::{ClaimMode, TransferAccount, TransferOptions, ID};
+
+use crate::pda;
+
+/// Builds `create_transfer`.
+#[derive(Clone, Debug)]
+pub struct CreateTransfer {
+    sender: Pubkey,
+    token_mint: Pubkey,
+    email_hash: [u8; 32],
+    claim_code_hash: [u8; 32],
+    amount: u64,
+    expiry_hours: i64,
+    options: TransferOptions,
+    sender_token_account: Option<Pubkey>,
+    with_inbox: bool,
+}
+
+impl CreateTransfer {
+    pub fn new(
+        sender: Pubkey,
+        token_mint: Pubkey,
+        email_hash: [u8; 32],
+        claim_code_hash: [u8; 32],
+        amount: u64,
+        expiry_hours: i64,
+    ) -> Self {
+        Self {
+            sender,
+            token_mint,
+            email_hash,
+            claim_code_hash,
+            amount,
+            expiry_hours,
+            options: TransferOptions::default(),
+            sender_token_account: None,
+            with_inbox: false,
+        }
+    }
+
+    /// Sets irrevocability, refund destinations, crank bounty and cancel
+    /// authority.
+    pub fn options(mut self, options: TransferOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Funds the transfer from `account` instead of the sender's ATA.
+    pub fn sender_token_account(mut self, account: Pubkey) -> Self {
+        self.sender_token_account = Some(account);
+        self
+    }
+
+    /// Passes the recipient's inbox; it must already be open.
+    pub fn with_inbox(mut self) -> Self {
+        self.with_inbox = true;
+        self
+    }
+
+    /// Address of the transfer this instruction creates.
+    pub fn transfer_address(&self) -> Pubkey {
+        pda::transfer_address(&self.sender, &self.email_hash)
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let transfer = self.transfer_address();
+        let accounts = payinbox::accounts::CreateTransfer {
+            transfer,
+            sender: self.sender,
+            sender_token_account: self
+                .sender_token_account
+                .unwrap_or_else(|| get_associated_token_address(&self.sender, &self.token_mint)),
+            token_mint: self.token_mint,
+            escrow_token_account: pda::escrow_address(&transfer),
+            token_program: token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            inbox: self
+                .with_inbox
+                .then(|| pda::inbox_address(&self.email_hash)),
+            event_authority: pda::event_authority_address(),
+            program: ID,
+        };
+        let data = payinbox::instruction::CreateTransfer {
+            email_hash: self.email_hash,
+            claim_code_hash: self.claim_code_hash,
+            amount: self.amount,
+            expiry_hours: self.expiry_hours,
+            options: self.options.clone(),
+        };
+        Instruction {
+            program_id: ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        }
+    }
+}
+
+/// Builds `claim_transfer`.
+#[derive(Clone, Debug)]
+pub struct ClaimTransfer {
+    transfer: Pubkey,
+    sender: Pubkey,
+    token_mint: Pubkey,
+    email_hash: [u8; 32],
+    in_inbox: bool,
+    recipient: Pubkey,
+    claim_code: String,
+    recipient_token_account: Option<Pubkey>,
+    passkey: bool,
+}
+
+impl ClaimTransfer {
+    /// `claim_code` is the code itself, or the WebAuthn clientDataJSON when
+    /// the transfer is claimed with a passkey.
+    pub fn new(
+        transfer: &TransferAccount,
+        recipient: Pubkey,
+        claim_code: impl Into<String>,
+    ) -> Self {
+        Self {
+            transfer: pda::transfer_address(&transfer.sender, &transfer.email_hash),
+            sender: transfer.sender,
+            token_mint: transfer.token_mint,
+            email_hash: transfer.email_hash,
+            in_inbox: transfer.in_inbox,
+            recipient,
+            claim_code: claim_code.into(),
+            recipient_token_account: None,
+            passkey: transfer.claim_passkey.is_some(),
+        }
+    }
+
+    /// Pays out to `account` instead of the recipient's ATA.
+    pub fn recipient_token_account(mut self, account: Pubkey) -> Self {
+        self.recipient_token_account = Some(account);
+        self
+    }
+
+    /// Passes the instructions sysvar for a passkey claim. Set
+    /// automatically for transfer passkeys; needed by hand when only the
+    /// inbox has one.
+    pub fn with_passkey(mut self) -> Self {
+        self.passkey = true;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let accounts = payinbox::accounts::ClaimTransfer {
+            transfer: self.transfer,
+            recipient: self.recipient,
+            recipient_token_account: self
+                .recipient_token_account
+                .unwrap_or_else(|| get_associated_token_address(&self.recipient, &self.token_mint)),
+            escrow_token_account: pda::escrow_address(&self.transfer),
+            sender: self.sender,
+            token_program: token::ID,
+            inbox: self.in_inbox.then(|| pda::inbox_address(&self.email_hash)),
+            instructions: self.passkey.then_some(sysvar::instructions::ID),
+            event_authority: pda::event_authority_address(),
+            program: ID,
+        };
+        let data = payinbox::instruction::ClaimTransfer {
+            claim_code: self.claim_code.clone(),
+        };
+        Instruction {
+            program_id: ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        }
+    }
+}
+
+/// Builds `set_claim_passkey`.
+#[derive(Clone, Debug)]
+pub struct SetClaimPasskey {
+    transfer: Pubkey,
+    sender: Pubkey,
+    passkey: Option<[u8; 33]>,
+}
+
+impl SetClaimPasskey {
+    /// `passkey` is a compressed secp256r1 key, or `None` to go back to
+    /// the claim code.
+    pub fn new(transfer: &TransferAccount, passkey: Option<[u8; 33]>) -> Self {
+        Self {
+            transfer: pda::transfer_address(&transfer.sender, &transfer.email_hash),
+            sender: transfer.sender,
+            passkey,
+        }
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let accounts = payinbox::accounts::SetClaimPasskey {
+            transfer: self.transfer,
+            sender: self.sender,
+            event_authority: pda::event_authority_address(),
+            program: ID,
+        };
+        let data = payinbox::instruction::SetClaimPasskey {
+            passkey: self.passkey,
+        };
+        Instruction {
+            program_id: ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        }
+    }
+}
+
+/// Builds `commit_claim`, the first step of a claim by commitment.
+#[derive(Clone, Debug)]
+pub struct CommitClaim {
+    transfer: Pubkey,
+    email_hash: [u8; 32],
+    in_inbox: bool,
+    committer: Pubkey,
+    commitment: [u8; 32],
+}
+
+impl CommitClaim {
+    /// `committer` is the transfer's commit key,
+    /// `hash::commit_keypair(claim_code)`, and signs. `commitment` is
+    /// `payinbox::claim_commitment(claim_code, destination, salt)`.
+    pub fn new(transfer: &TransferAccount, committer: Pubkey, commitment: [u8; 32]) -> Self {
+        Self {
+            transfer: pda::transfer_address(&transfer.sender, &transfer.email_hash),
+            email_hash: transfer.email_hash,
+            in_inbox: transfer.in_inbox,
+            committer,
+            commitment,
+        }
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let accounts = payinbox::accounts::CommitClaim {
+            transfer: self.transfer,
+            committer: self.committer,
+            inbox: self.in_inbox.then(|| pda::inbox_address(&self.email_hash)),
+            event_authority: pda::event_authority_address(),
+            program: ID,
+        };
+        let data = payinbox::instruction::CommitClaim {
+            commitment: self.commitment,
+        };
+        Instruction {
+            program_id: ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        }
+    }
+}
+
+/// Builds `reveal_claim`, which pays out a claim reserved by `CommitClaim`.
+#[derive(Clone, Debug)]
+pub struct RevealClaim {
+    transfer: Pubkey,
+    sender: Pubkey,
+    token_mint: Pubkey,
+    email_hash: [u8; 32],
+    in_inbox: bool,
+    destination: Pubkey,
+    claim_code: String,
+    salt: [u8; 32],
+    destination_token_account: Option<Pubkey>,
+}
+
+impl RevealClaim {
+    /// `destination` and `salt` must be the ones committed to. Needs no
+    /// signer, so anyone can submit it.
+    pub fn new(
+        transfer: &TransferAccount,
+        destination: Pubkey,
+        claim_code: impl Into<String>,
+        salt: [u8; 32],
+    ) -> Self {
+        Self {
+            transfer: pda::transfer_address(&transfer.sender, &transfer.email_hash),
+            sender: transfer.sender,
+            token_mint: transfer.token_mint,
+            email_hash: transfer.email_hash,
+            in_inbox: transfer.in_inbox,
+            destination,
+            claim_code: claim_code.into(),
+            salt,
+            destination_token_account: None,
+        }
+    }
+
+    /// Pays out to `account` instead of the destination's ATA.
+    pub fn destination_token_account(mut self, account: Pubkey) -> Self {
+        self.destination_token_account = Some(account);
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let accounts = payinbox::accounts::RevealClaim {
+            transfer: self.transfer,
+            destination: self.destination,
+            destination_token_account: self.destination_token_account.unwrap_or_else(|| {
+                get_associated_token_address(&self.destination, &self.token_mint)
+            }),
+            escrow_token_account: pda::escrow_address(&self.transfer),
+            sender: self.sender,
+            token_program: token::ID,
+            inbox: self.in_inbox.then(|| pda::inbox_address(&self.email_hash)),
+            event_authority: pda::event_authority_address(),
+            program: ID,
+        };
+        let data = payinbox::instruction::RevealClaim {
+            claim_code: self.claim_code.clone(),
+            salt: self.salt,
+        };
+        Instruction {
+            program_id: ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        }
+    }
+}
+
+/// Builds `cancel_transfer`. The approvals of an M-of-N transfer are
+/// passed to be closed.
+#[derive(Clone, Debug)]
+pub struct CancelTransfer {
+    transfer: Pubkey,
+    sender: Pubkey,
+    token_mint: Pubkey,
+    email_hash: [u8; 32],
+    in_inbox: bool,
+    refund_to: Pubkey,
+    multi_code: bool,
+    authority: Pubkey,
+    refund_token_account: Option<Pubkey>,
+}
+
+impl CancelTransfer {
+    /// `authority` is the sender or the transfer's cancel authority.
+    pub fn new(transfer: &TransferAccount, authority: Pubkey) -> Self {
+        Self {
+            transfer: pda::transfer_address(&transfer.sender, &transfer.email_hash),
+            sender: transfer.sender,
+            token_mint: transfer.token_mint,
+            email_hash: transfer.email_hash,
+            in_inbox: transfer.in_inbox,
+            refund_to: transfer.refund_to,
+            multi_code: transfer.claim_mode == ClaimMode::MultiCode,
+            authority,
+            refund_token_account: None,
+        }
+    }
+
+    /// Refunds to `account` instead of the `refund_to` owner's ATA.
+    pub fn refund_token_account(mut self, account: Pubkey) -> Self {
+        self.refund_token_account = Some(account);
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let accounts = payinbox::accounts::CancelTransfer {
+            transfer: self.transfer,
+            authority: self.authority,
+            sender: self.sender,
+            refund_token_account: self
+                .refund_token_account
+                .unwrap_or_else(|| get_associated_token_address(&self.refund_to, &self.token_mint)),
+            escrow_token_account: pda::escrow_address(&self.transfer),
+            token_program: token::ID,
+            inbox: self.in_inbox.then(|| pda::inbox_address(&self.email_hash)),
+            approvals: self
+                .multi_code
+                .then(|| pda::approvals_address(&self.transfer)),
+            event_authority: pda::event_authority_address(),
+            program: ID,
+        };
+        Instruction {
+            program_id: ID,
+            accounts: accounts.to_account_metas(None),
+            data: payinbox::instruction::CancelTransfer {}.data(),
+        }
+    }
+}
+
+/// Builds `reclaim_expired`. The approvals of an M-of-N transfer are
+/// passed to be closed.
+#[derive(Clone, Debug)]
+pub struct ReclaimExpired {
+    transfer: Pubkey,
+    sender: Pubkey,
+    token_mint: Pubkey,
+    email_hash: [u8; 32],
+    in_inbox: bool,
+    multi_code: bool,
+    refund_owner: Pubkey,
+    cranker: Pubkey,
+    refund_token_account: Option<Pubkey>,
+    cranker_token_account: Option<Pubkey>,
+}
+
+impl ReclaimExpired {
+    /// `cranker` signs, collects the crank bounty and pays for a missing
+    /// refund account.
+    pub fn new(transfer: &TransferAccount, cranker: Pubkey) -> Self {
+        Self {
+            transfer: pda::transfer_address(&transfer.sender, &transfer.email_hash),
+            sender: transfer.sender,
+            token_mint: transfer.token_mint,
+            email_hash: transfer.email_hash,
+            in_inbox: transfer.in_inbox,
+            multi_code: transfer.claim_mode == ClaimMode::MultiCode,
+            refund_owner: transfer.expiry_recipient(),
+            cranker,
+            refund_token_account: None,
+            cranker_token_account: None,
+        }
+    }
+
+    /// Refunds to `account` instead of the expiry recipient's ATA.
+    pub fn refund_token_account(mut self, account: Pubkey) -> Self {
+        self.refund_token_account = Some(account);
+        self
+    }
+
+    /// Collects the token bounty into `account`; without it only the
+    /// lamport bounty is paid.
+    pub fn cranker_token_account(mut self, account: Pubkey) -> Self {
+        self.cranker_token_account = Some(account);
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let accounts = payinbox::accounts::ReclaimExpired {
+            transfer: self.transfer,
+            refund_token_account: self.refund_token_account.unwrap_or_else(|| {
+                get_associated_token_address(&self.refund_owner, &self.token_mint)
+            }),
+            refund_owner: self.refund_owner,
+            token_mint: self.token_mint,
+            escrow_token_account: pda::escrow_address(&self.transfer),
+            original_sender: self.sender,
+            cranker: self.cranker,
+            cranker_token_account: self.cranker_token_account,
+            token_program: token::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+            inbox: self.in_inbox.then(|| pda::inbox_address(&self.email_hash)),
+            approvals: self
+                .multi_code
+                .then(|| pda::approvals_address(&self.transfer)),
+            event_authority: pda::event_authority_address(),
+            program: ID,
+        };
+        Instruction {
+            program_id: ID,
+            accounts: accounts.to_account_metas(None),
+            data: payinbox::instruction::ReclaimExpired {}.data(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::tests::sample_transfer;
+    use anchor_lang::Discriminator;
+
+    #[test]
+    fn create_transfer_derives_accounts() {
+        let sender = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let builder = CreateTransfer::new(sender, mint, [1; 32], [2; 32], 500, 72);
+        let ix = builder.instruction();
+
+        let transfer = pda::transfer_address(&sender, &[1; 32]);
+        assert_eq!(ix.program_id, ID);
+        assert_eq!(ix.accounts[0].pubkey, transfer);
+        assert!(ix.accounts[1].is_signer);
+        assert_eq!(
+            ix.accounts[2].pubkey,
+            get_associated_token_address(&sender, &mint)
+        );
+        assert_eq!(ix.accounts[4].pubkey, pda::escrow_address(&transfer));
+        // Optional inbox left out: Anchor passes the program ID instead
+        assert_eq!(ix.accounts[8].pubkey, ID);
+        assert!(ix
+            .data
+            .starts_with(payinbox::instruction::CreateTransfer::DISCRIMINATOR));
+    }
+
+    #[test]
+    fn claim_passes_inbox_and_sysvar_when_needed() {
+        let mut transfer = sample_transfer();
+        let recipient = Pubkey::new_unique();
+        let ix = ClaimTransfer::new(&transfer, recipient, "code").instruction();
+        assert_eq!(ix.accounts[6].pubkey, ID);
+        assert_eq!(ix.accounts[7].pubkey, ID);
+
+        transfer.in_inbox = true;
+        transfer.claim_passkey = Some([2; 33]);
+        let ix = ClaimTransfer::new(&transfer, recipient, "{}").instruction();
+        assert_eq!(
+            ix.accounts[6].pubkey,
+            pda::inbox_address(&transfer.email_hash)
+        );
+        assert_eq!(ix.accounts[7].pubkey, sysvar::instructions::ID);
+    }
+
+    #[test]
+    fn reveal_needs_no_signer() {
+        let transfer = sample_transfer();
+        let destination = Pubkey::new_unique();
+        let ix = RevealClaim::new(&transfer, destination, "code", [3; 32]).instruction();
+
+        assert!(ix.accounts.iter().all(|meta| !meta.is_signer));
+        assert_eq!(ix.accounts[1].pubkey, destination);
+        assert_eq!(
+            ix.accounts[2].pubkey,
+            get_associated_token_address(&destination, &transfer.token_mint)
+        );
+        let committer = Pubkey::new_unique();
+        let ix = CommitClaim::new(&transfer, committer, [4; 32]).instruction();
+        assert_eq!(ix.accounts[1].pubkey, committer);
+        assert!(ix.accounts[1].is_signer);
+    }
+
+    #[test]
+    fn cancel_refunds_to_refund_owner() {
+        let mut transfer = sample_transfer();
+        transfer.refund_to = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let ix = CancelTransfer::new(&transfer, authority).instruction();
+
+        assert_eq!(ix.accounts[1].pubkey, authority);
+        assert!(ix.accounts[1].is_signer);
+        assert_eq!(ix.accounts[2].pubkey, transfer.sender);
+        assert_eq!(
+            ix.accounts[3].pubkey,
+            get_associated_token_address(&transfer.refund_to, &transfer.token_mint)
+        );
+    }
+
+    #[test]
+    fn reclaim_pays_expiry_recipient() {
+        let mut transfer = sample_transfer();
+        let beneficiary = Pubkey::new_unique();
+        transfer.expiry_beneficiary = Some(beneficiary);
+        let cranker = Pubkey::new_unique();
+        let cranker_account = Pubkey::new_unique();
+        let ix = ReclaimExpired::new(&transfer, cranker)
+            .cranker_token_account(cranker_account)
+            .instruction();
+
+        assert_eq!(
+            ix.accounts[1].pubkey,
+            get_associated_token_address(&beneficiary, &transfer.token_mint)
+        );
+        assert_eq!(ix.accounts[2].pubkey, beneficiary);
+        assert_eq!(ix.accounts[6].pubkey, cranker);
+        assert_eq!(ix.accounts[7].pubkey, cranker_account);
+    }
+}
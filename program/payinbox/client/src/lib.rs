@@ -0,0 +1,21 @@
+//! Off-chain client for the payinbox program.
+//!
+//! - [`pda`]: program-derived addresses for every account the program owns
+//! - [`instructions`]: typed builders for create, claim, passkeys,
+//!   commit/reveal, cancel and reclaim
+//! - [`accounts`]: fetching and decoding `TransferAccount`
+//! - [`events`]: decoding lifecycle events from inner instructions
+//! - [`hash`]: email and claim code hashes
+//! - [`error`]: client errors and mapping program error numbers to `ErrorCode`
+//! - [`rpc`]: minimal blocking JSON-RPC client
+
+pub mod accounts;
+pub mod error;
+pub mod events;
+pub mod hash;
+pub mod instructions;
+pub mod pda;
+pub mod rpc;
+
+pub use error::{program_error, ClientError};
+pub use payinbox::{self, ID as PROGRAM_ID};
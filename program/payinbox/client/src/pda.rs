@@ -0,0 +1,67 @@
+//! Program-derived addresses, mirroring the seeds in the program's account
+//! contexts.
+
+use anchor_lang::prelude::Pubkey;
+use payinbox::ID;
+
+/// Transfer state account. Seeds: ["transfer", sender, email_hash]
+pub fn transfer_address(sender: &Pubkey, email_hash: &[u8; 32]) -> Pubkey {
+    find(&[b"transfer", sender.as_ref(), email_hash.as_ref()])
+}
+
+/// Escrow token account of a transfer. Seeds: ["escrow", transfer]
+pub fn escrow_address(transfer: &Pubkey) -> Pubkey {
+    find(&[b"escrow", transfer.as_ref()])
+}
+
+/// Approvals account of an M-of-N transfer. Seeds: ["approvals", transfer]
+pub fn approvals_address(transfer: &Pubkey) -> Pubkey {
+    find(&[b"approvals", transfer.as_ref()])
+}
+
+/// One wallet's contribution to a group transfer.
+/// Seeds: ["contribution", transfer, contributor]
+pub fn contribution_address(transfer: &Pubkey, contributor: &Pubkey) -> Pubkey {
+    find(&[b"contribution", transfer.as_ref(), contributor.as_ref()])
+}
+
+/// Recipient inbox. Seeds: ["inbox", email_hash]
+pub fn inbox_address(email_hash: &[u8; 32]) -> Pubkey {
+    find(&[b"inbox", email_hash.as_ref()])
+}
+
+/// Wallet registry entry. Seeds: ["registry", email_hash]
+pub fn registry_address(email_hash: &[u8; 32]) -> Pubkey {
+    find(&[b"registry", email_hash.as_ref()])
+}
+
+/// Program config. Seeds: ["config"]
+pub fn config_address() -> Pubkey {
+    find(&[b"config"])
+}
+
+/// Marks a verifier attestation as used.
+/// Seeds: ["attestation", keccak(message)]
+pub fn attestation_record_address(message: &[u8]) -> Pubkey {
+    find(&[b"attestation", &payinbox::attestation_record_seed(message)])
+}
+
+/// Groth16 verifying key for DKIM proofs. Seeds: ["dkim_verifier"]
+pub fn dkim_verifier_address() -> Pubkey {
+    find(&[b"dkim_verifier"])
+}
+
+/// Registered DKIM keys of a domain. Seeds: ["dkim", domain_hash]
+pub fn dkim_domain_address(domain_hash: &[u8; 32]) -> Pubkey {
+    find(&[b"dkim", domain_hash.as_ref()])
+}
+
+/// Signer of the program's event self-CPIs (`emit_cpi!`).
+/// Seeds: ["__event_authority"]
+pub fn event_authority_address() -> Pubkey {
+    find(&[b"__event_authority"])
+}
+
+fn find(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &ID).0
+}
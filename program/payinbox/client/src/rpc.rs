@@ -0,0 +1,558 @@
+//! Minimal blocking JSON-RPC client for the calls the client needs.
+
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::Duration;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use solana_hash::Hash;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+
+use crate::error::{parse_program_error, transaction_error, ClientError};
+
+/// How long `send_and_confirm` waits for a transaction to confirm, in
+/// one-second polls.
+pub const CONFIRM_ATTEMPTS: u32 = 30;
+
+/// Result of `simulateTransaction`.
+#[derive(Debug)]
+pub struct Simulation {
+    /// Transaction error, if the simulation failed
+    pub err: Option<Value>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// Lamports after the transaction, fee included, of the accounts asked
+    /// for by `simulate_transaction_with_balances`; `None` once closed
+    pub balances: Vec<Option<u64>>,
+}
+
+impl Simulation {
+    /// `ErrorCode` raised by the program, if that is why it failed.
+    pub fn program_error(&self) -> Option<payinbox::ErrorCode> {
+        self.err.as_ref().and_then(transaction_error)
+    }
+
+    /// Why the simulation failed, naming the program error if there is one.
+    pub fn error_message(&self) -> Option<String> {
+        let err = self.err.as_ref()?;
+        Some(match self.program_error() {
+            Some(error) => format!("{} ({}): {error}", error.name(), u32::from(error)),
+            None => err.to_string(),
+        })
+    }
+}
+
+/// A transaction landed by `send_and_confirm`.
+#[derive(Debug)]
+pub struct Sent {
+    pub signature: String,
+    /// Its simulation, with the payer's balance after it
+    pub simulation: Simulation,
+}
+
+/// One entry of `getSignaturesForAddress`.
+#[derive(Clone, Debug)]
+pub struct SignatureInfo {
+    pub signature: String,
+    pub slot: u64,
+    /// Whether the transaction failed
+    pub failed: bool,
+}
+
+/// A cross-program invocation made by a transaction, with its account
+/// indexes resolved to addresses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InnerInstruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<Pubkey>,
+    pub data: Vec<u8>,
+}
+
+/// The parts of `getTransaction` an indexer needs.
+#[derive(Clone, Debug)]
+pub struct TransactionRecord {
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    /// Whether the transaction failed
+    pub failed: bool,
+    pub logs: Vec<String>,
+    /// Inner instructions of all top-level instructions, in order
+    pub inner_instructions: Vec<InnerInstruction>,
+}
+
+impl TransactionRecord {
+    /// Reads a `getTransaction` result in `json` encoding.
+    pub fn from_rpc(result: &Value) -> Result<Self, ClientError> {
+        let meta = &result["meta"];
+        // Indexes run over the message's keys, then the addresses loaded
+        // from lookup tables (writable first).
+        let keys: Vec<Pubkey> = result["transaction"]["message"]["accountKeys"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .chain(
+                meta["loadedAddresses"]["writable"]
+                    .as_array()
+                    .into_iter()
+                    .flatten(),
+            )
+            .chain(
+                meta["loadedAddresses"]["readonly"]
+                    .as_array()
+                    .into_iter()
+                    .flatten(),
+            )
+            .map(|key| {
+                key.as_str()
+                    .and_then(|key| key.parse().ok())
+                    .ok_or_else(|| ClientError::Decode("invalid account key".into()))
+            })
+            .collect::<Result<_, _>>()?;
+        let key = |index: &Value| {
+            index
+                .as_u64()
+                .and_then(|index| keys.get(index as usize))
+                .copied()
+                .ok_or_else(|| ClientError::Decode("account index out of range".into()))
+        };
+
+        let mut inner_instructions = Vec::new();
+        for group in meta["innerInstructions"].as_array().into_iter().flatten() {
+            for ix in group["instructions"].as_array().into_iter().flatten() {
+                let data = ix["data"]
+                    .as_str()
+                    .and_then(|data| bs58::decode(data).into_vec().ok())
+                    .ok_or_else(|| ClientError::Decode("invalid instruction data".into()))?;
+                inner_instructions.push(InnerInstruction {
+                    program_id: key(&ix["programIdIndex"])?,
+                    accounts: ix["accounts"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .map(key)
+                        .collect::<Result<_, _>>()?,
+                    data,
+                });
+            }
+        }
+
+        Ok(Self {
+            slot: result["slot"]
+                .as_u64()
+                .ok_or_else(|| ClientError::Decode("missing slot".into()))?,
+            block_time: result["blockTime"].as_i64(),
+            failed: !meta["err"].is_null(),
+            logs: meta["logMessages"]
+                .as_array()
+                .map(|logs| {
+                    logs.iter()
+                        .filter_map(|log| log.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            inner_instructions,
+        })
+    }
+}
+
+/// Filter for `getProgramAccounts`: `bytes` must appear at `offset`.
+#[derive(Clone, Debug)]
+pub struct Memcmp {
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+/// Blocking JSON-RPC client for a Solana node.
+pub struct RpcClient {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl RpcClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Sends one JSON-RPC request and returns its `result`.
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, ClientError> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let mut response: Value = self
+            .agent
+            .post(&self.url)
+            .send_json(request)
+            .map_err(|e| ClientError::Transport(e.to_string()))?
+            .into_json()
+            .map_err(|e| ClientError::Transport(e.to_string()))?;
+
+        if let Some(error) = response.get("error") {
+            let message = error["message"].as_str().unwrap_or_default().to_string();
+            let program_error = error["data"]
+                .get("err")
+                .and_then(transaction_error)
+                .or_else(|| parse_program_error(&message));
+            return Err(ClientError::Rpc {
+                code: error["code"].as_i64().unwrap_or_default(),
+                message,
+                program_error,
+            });
+        }
+        Ok(response["result"].take())
+    }
+
+    /// Returns the data of the account at `address`, or `None` if it
+    /// doesn't exist.
+    pub fn get_account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, ClientError> {
+        let result = self.call(
+            "getAccountInfo",
+            json!([address.to_string(), { "encoding": "base64" }]),
+        )?;
+        let value = &result["value"];
+        if value.is_null() {
+            return Ok(None);
+        }
+        value["data"][0]
+            .as_str()
+            .ok_or_else(|| ClientError::Decode("missing account data".into()))
+            .and_then(|data| {
+                STANDARD
+                    .decode(data)
+                    .map_err(|e| ClientError::Decode(e.to_string()))
+            })
+            .map(Some)
+    }
+
+    /// Returns the data of each account at `addresses`, in order, `None`
+    /// for those that don't exist. Large lists are fetched in chunks.
+    pub fn get_multiple_accounts_data(
+        &self,
+        addresses: &[Pubkey],
+    ) -> Result<Vec<Option<Vec<u8>>>, ClientError> {
+        // The node's limit per request
+        const CHUNK: usize = 100;
+        let mut accounts = Vec::with_capacity(addresses.len());
+        for chunk in addresses.chunks(CHUNK) {
+            let keys: Vec<String> = chunk.iter().map(ToString::to_string).collect();
+            let result = self.call(
+                "getMultipleAccounts",
+                json!([keys, { "encoding": "base64" }]),
+            )?;
+            let values = result["value"]
+                .as_array()
+                .filter(|values| values.len() == chunk.len())
+                .ok_or_else(|| ClientError::Decode("expected one entry per account".into()))?;
+            for value in values {
+                if value.is_null() {
+                    accounts.push(None);
+                    continue;
+                }
+                let data = value["data"][0]
+                    .as_str()
+                    .ok_or_else(|| ClientError::Decode("missing account data".into()))?;
+                accounts.push(Some(
+                    STANDARD
+                        .decode(data)
+                        .map_err(|e| ClientError::Decode(e.to_string()))?,
+                ));
+            }
+        }
+        Ok(accounts)
+    }
+
+    /// Returns the lamports held by `address` (0 if it doesn't exist).
+    pub fn get_balance(&self, address: &Pubkey) -> Result<u64, ClientError> {
+        let result = self.call(
+            "getBalance",
+            json!([address.to_string(), { "commitment": "confirmed" }]),
+        )?;
+        result["value"]
+            .as_u64()
+            .ok_or_else(|| ClientError::Decode("missing balance".into()))
+    }
+
+    /// Returns the cluster's `Clock::unix_timestamp`, the time the program
+    /// checks expiries against.
+    pub fn get_unix_timestamp(&self) -> Result<i64, ClientError> {
+        // slot, epoch_start_timestamp, epoch and leader_schedule_epoch
+        // precede it
+        const OFFSET: usize = 32;
+        let data = self
+            .get_account_data(&anchor_lang::solana_program::sysvar::clock::ID)?
+            .ok_or_else(|| ClientError::AccountNotFound("clock sysvar".into()))?;
+        data.get(OFFSET..OFFSET + 8)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(i64::from_le_bytes)
+            .ok_or_else(|| ClientError::Decode("clock sysvar too short".into()))
+    }
+
+    /// Returns the latest blockhash (base58).
+    pub fn get_latest_blockhash(&self) -> Result<String, ClientError> {
+        let result = self.call("getLatestBlockhash", json!([{ "commitment": "confirmed" }]))?;
+        result["value"]["blockhash"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| ClientError::Decode("missing blockhash".into()))
+    }
+
+    /// Returns the fee the cluster would charge for a serialized message.
+    pub fn get_fee_for_message(&self, message: &[u8]) -> Result<u64, ClientError> {
+        let result = self.call(
+            "getFeeForMessage",
+            json!([STANDARD.encode(message), { "commitment": "confirmed" }]),
+        )?;
+        result["value"]
+            .as_u64()
+            .ok_or_else(|| ClientError::Decode("missing fee (expired blockhash?)".into()))
+    }
+
+    /// Submits a serialized, signed transaction and returns its signature.
+    pub fn send_transaction(&self, transaction: &[u8]) -> Result<String, ClientError> {
+        let result = self.call(
+            "sendTransaction",
+            json!([STANDARD.encode(transaction), { "encoding": "base64" }]),
+        )?;
+        result
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| ClientError::Decode("missing signature".into()))
+    }
+
+    /// Simulates a serialized transaction without submitting it.
+    pub fn simulate_transaction(&self, transaction: &[u8]) -> Result<Simulation, ClientError> {
+        self.simulate_transaction_with_balances(transaction, &[])
+    }
+
+    /// Simulates a serialized transaction, also returning the lamports
+    /// `addresses` would hold after it.
+    pub fn simulate_transaction_with_balances(
+        &self,
+        transaction: &[u8],
+        addresses: &[Pubkey],
+    ) -> Result<Simulation, ClientError> {
+        let mut config =
+            json!({ "encoding": "base64", "commitment": "confirmed", "sigVerify": true });
+        if !addresses.is_empty() {
+            let keys: Vec<String> = addresses.iter().map(ToString::to_string).collect();
+            config["accounts"] = json!({ "addresses": keys, "encoding": "base64" });
+        }
+        let result = self.call(
+            "simulateTransaction",
+            json!([STANDARD.encode(transaction), config]),
+        )?;
+        let value = &result["value"];
+        let err = Some(value["err"].clone()).filter(|err| !err.is_null());
+        let balances = match value["accounts"].as_array() {
+            Some(accounts) if accounts.len() == addresses.len() => accounts
+                .iter()
+                .map(|account| account["lamports"].as_u64())
+                .collect(),
+            // A failed simulation returns no accounts
+            _ if err.is_some() || addresses.is_empty() => Vec::new(),
+            _ => {
+                return Err(ClientError::Decode(
+                    "expected one simulated account per address".into(),
+                ))
+            }
+        };
+        Ok(Simulation {
+            err,
+            logs: value["logs"]
+                .as_array()
+                .map(|logs| {
+                    logs.iter()
+                        .filter_map(|log| log.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            units_consumed: value["unitsConsumed"].as_u64(),
+            balances,
+        })
+    }
+
+    /// Signs `instructions` as one transaction over the latest blockhash,
+    /// paid for by `payer` and also signed by `signers`.
+    pub fn sign_transaction(
+        &self,
+        instructions: &[Instruction],
+        payer: &Keypair,
+        signers: &[&Keypair],
+    ) -> Result<Transaction, ClientError> {
+        let blockhash = Hash::from_str(&self.get_latest_blockhash()?)
+            .map_err(|e| ClientError::Decode(format!("invalid blockhash: {e}")))?;
+        let mut keypairs = vec![payer];
+        keypairs.extend_from_slice(signers);
+        Ok(Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &keypairs,
+            blockhash,
+        ))
+    }
+
+    /// Signs `instructions` as one transaction (see `sign_transaction`),
+    /// simulates it and, unless that fails, sends it and waits for it to
+    /// confirm. A transaction that would fail costs no fee.
+    pub fn send_and_confirm(
+        &self,
+        instructions: &[Instruction],
+        payer: &Keypair,
+        signers: &[&Keypair],
+    ) -> Result<Sent, ClientError> {
+        let transaction = self.sign_transaction(instructions, payer, signers)?;
+        let wire = bincode::serialize(&transaction)
+            .map_err(|e| ClientError::Decode(format!("unserializable transaction: {e}")))?;
+
+        let simulation = self.simulate_transaction_with_balances(&wire, &[payer.pubkey()])?;
+        if let Some(message) = simulation.error_message() {
+            return Err(ClientError::Rpc {
+                code: 0,
+                message: format!("simulation failed: {message}"),
+                program_error: simulation.program_error(),
+            });
+        }
+
+        let signature = self.send_transaction(&wire)?;
+        for _ in 0..CONFIRM_ATTEMPTS {
+            if let Some("confirmed" | "finalized") =
+                self.get_signature_status(&signature)?.as_deref()
+            {
+                return Ok(Sent {
+                    signature,
+                    simulation,
+                });
+            }
+            sleep(Duration::from_secs(1));
+        }
+        Err(ClientError::Rpc {
+            code: 0,
+            message: format!("transaction {signature} not confirmed after {CONFIRM_ATTEMPTS}s"),
+            program_error: None,
+        })
+    }
+
+    /// Returns the confirmation status of `signature` (`None` while
+    /// unknown), failing with the transaction error if it failed.
+    pub fn get_signature_status(&self, signature: &str) -> Result<Option<String>, ClientError> {
+        let result = self.call("getSignatureStatuses", json!([[signature]]))?;
+        let status = &result["value"][0];
+        if status.is_null() {
+            return Ok(None);
+        }
+        if !status["err"].is_null() {
+            return Err(ClientError::Rpc {
+                code: 0,
+                message: format!("transaction {signature} failed: {}", status["err"]),
+                program_error: transaction_error(&status["err"]),
+            });
+        }
+        Ok(status["confirmationStatus"].as_str().map(str::to_string))
+    }
+
+    /// Returns up to `limit` signatures involving `address`, newest first,
+    /// older than `before` and newer than `until` when given.
+    pub fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<&str>,
+        until: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<SignatureInfo>, ClientError> {
+        let mut config = json!({ "limit": limit, "commitment": "confirmed" });
+        if let Some(before) = before {
+            config["before"] = json!(before);
+        }
+        if let Some(until) = until {
+            config["until"] = json!(until);
+        }
+        let result = self.call(
+            "getSignaturesForAddress",
+            json!([address.to_string(), config]),
+        )?;
+        result
+            .as_array()
+            .ok_or_else(|| ClientError::Decode("expected a signature list".into()))?
+            .iter()
+            .map(|entry| {
+                Ok(SignatureInfo {
+                    signature: entry["signature"]
+                        .as_str()
+                        .ok_or_else(|| ClientError::Decode("missing signature".into()))?
+                        .to_string(),
+                    slot: entry["slot"].as_u64().unwrap_or_default(),
+                    failed: !entry["err"].is_null(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the confirmed transaction `signature`, or `None` if the node
+    /// doesn't know it.
+    pub fn get_transaction(
+        &self,
+        signature: &str,
+    ) -> Result<Option<TransactionRecord>, ClientError> {
+        let result = self.call(
+            "getTransaction",
+            json!([signature, {
+                "encoding": "json",
+                "commitment": "confirmed",
+                "maxSupportedTransactionVersion": 0,
+            }]),
+        )?;
+        if result.is_null() {
+            return Ok(None);
+        }
+        TransactionRecord::from_rpc(&result).map(Some)
+    }
+
+    /// Returns every account of `program` matching all `filters`.
+    pub fn get_program_accounts(
+        &self,
+        program: &Pubkey,
+        filters: &[Memcmp],
+    ) -> Result<Vec<(Pubkey, Vec<u8>)>, ClientError> {
+        let filters: Vec<Value> = filters
+            .iter()
+            .map(|filter| {
+                json!({ "memcmp": {
+                    "offset": filter.offset,
+                    "bytes": STANDARD.encode(&filter.bytes),
+                    "encoding": "base64",
+                } })
+            })
+            .collect();
+        let result = self.call(
+            "getProgramAccounts",
+            json!([program.to_string(), { "encoding": "base64", "filters": filters }]),
+        )?;
+        result
+            .as_array()
+            .ok_or_else(|| ClientError::Decode("expected an account list".into()))?
+            .iter()
+            .map(|entry| {
+                let address = entry["pubkey"]
+                    .as_str()
+                    .and_then(|key| key.parse().ok())
+                    .ok_or_else(|| ClientError::Decode("invalid account address".into()))?;
+                let data = entry["account"]["data"][0]
+                    .as_str()
+                    .ok_or_else(|| ClientError::Decode("missing account data".into()))?;
+                let data = STANDARD
+                    .decode(data)
+                    .map_err(|e| ClientError::Decode(e.to_string()))?;
+                Ok((address, data))
+            })
+            .collect()
+    }
+}
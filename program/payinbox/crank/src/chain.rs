@@ -0,0 +1,60 @@
+//! Where the crank reads transfers and lands reclaims.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use anyhow::Result;
+use payinbox::TransferAccount;
+use payinbox_client::accounts::fetch_active_transfers;
+use payinbox_client::rpc::RpcClient;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+/// A cluster the crank works against.
+pub trait Chain {
+    /// Signs and pays for reclaims, and collects their bounties.
+    fn cranker(&self) -> Pubkey;
+
+    /// The cluster's unix time, which expiries are checked against.
+    fn now(&self) -> Result<i64>;
+
+    /// Every Active transfer.
+    fn active_transfers(&self) -> Result<Vec<(Pubkey, TransferAccount)>>;
+
+    /// Lands `instructions` as one transaction. Fails if it failed or
+    /// didn't confirm.
+    fn submit(&self, instructions: &[Instruction]) -> Result<()>;
+}
+
+/// An RPC node, with the cranker's keypair.
+pub struct RpcChain {
+    rpc: RpcClient,
+    cranker: Keypair,
+}
+
+impl RpcChain {
+    pub fn new(rpc: RpcClient, cranker: Keypair) -> Self {
+        Self { rpc, cranker }
+    }
+}
+
+impl Chain for RpcChain {
+    fn cranker(&self) -> Pubkey {
+        self.cranker.pubkey()
+    }
+
+    fn now(&self) -> Result<i64> {
+        Ok(self.rpc.get_unix_timestamp()?)
+    }
+
+    fn active_transfers(&self) -> Result<Vec<(Pubkey, TransferAccount)>> {
+        Ok(fetch_active_transfers(&self.rpc)?)
+    }
+
+    /// Simulates first, so a reclaim that would fail (someone else got to
+    /// it, a clock skew) costs no fee.
+    fn submit(&self, instructions: &[Instruction]) -> Result<()> {
+        self.rpc
+            .send_and_confirm(instructions, &self.cranker, &[])?;
+        Ok(())
+    }
+}
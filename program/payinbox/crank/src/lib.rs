@@ -0,0 +1,264 @@
+//! Reclaim crank for the payinbox program.
+//!
+//! Expired transfers only go back to their senders when someone calls
+//! `reclaim_expired`. [`Crank::pass`] finds the Active transfers past their
+//! expiry and reclaims them, several per transaction; a transfer whose
+//! reclaim fails is retried on later passes with exponential backoff.
+//!
+//! - [`chain`]: where transfers are read and reclaims land, an RPC node
+//!   outside of tests
+//! - [`metrics`]: counters of the crank's progress, in Prometheus text
+//!   format
+
+pub mod chain;
+pub mod metrics;
+
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_spl::associated_token::{
+    get_associated_token_address,
+    spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+};
+use anchor_spl::token;
+use anyhow::Result;
+use payinbox::{TransferAccount, TransferStatus};
+use payinbox_client::instructions::ReclaimExpired;
+use solana_transaction::Transaction;
+
+use chain::Chain;
+use metrics::Metrics;
+
+/// Largest serialized transaction a validator accepts
+/// (`solana_packet::PACKET_DATA_SIZE`).
+const MAX_TRANSACTION_SIZE: u64 = 1232;
+
+/// How a [`Crank`] batches and retries.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Most reclaims per transaction
+    pub batch_size: usize,
+    /// Wait before retrying a failed reclaim; doubles with every failure
+    pub retry_delay: Duration,
+    /// Longest wait between retries
+    pub max_retry_delay: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            batch_size: 3,
+            retry_delay: Duration::from_secs(30),
+            max_retry_delay: Duration::from_secs(3600),
+        }
+    }
+}
+
+/// Outcome of one [`Crank::pass`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PassStats {
+    /// Expired transfers found, including deferred ones
+    pub due: usize,
+    /// Due transfers skipped because their retry delay hasn't passed
+    pub deferred: usize,
+    /// Transactions submitted
+    pub transactions: usize,
+    pub reclaimed: Vec<Pubkey>,
+    /// Transfers whose reclaim failed, with the reason
+    pub failed: Vec<(Pubkey, String)>,
+}
+
+/// Failures of one transfer so far.
+struct Retry {
+    failures: u32,
+    not_before: Instant,
+}
+
+/// Reclaims expired transfers, remembering failures between passes.
+pub struct Crank {
+    config: Config,
+    retries: HashMap<Pubkey, Retry>,
+    metrics: Arc<Metrics>,
+}
+
+impl Crank {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            retries: HashMap::new(),
+            metrics: Arc::default(),
+        }
+    }
+
+    /// The crank's counters, shared with whoever serves them.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Reclaims every due transfer once. `at` is the wall-clock time retry
+    /// delays are measured against.
+    ///
+    /// Fails only if the transfers can't be listed; failed reclaims are
+    /// reported in the stats and retried by a later pass.
+    pub fn pass(&mut self, chain: &impl Chain, at: Instant) -> Result<PassStats> {
+        let (now, transfers) = chain
+            .now()
+            .and_then(|now| Ok((now, chain.active_transfers()?)))
+            .inspect_err(|_| {
+                self.metrics.scan_errors.fetch_add(1, Ordering::Relaxed);
+            })?;
+
+        // Transfers no longer Active were finalized by someone, maybe us.
+        self.retries
+            .retain(|address, _| transfers.iter().any(|(active, _)| active == address));
+
+        let mut due: Vec<_> = transfers
+            .into_iter()
+            .filter(|(_, transfer)| is_due(transfer, now))
+            .collect();
+        due.sort_by_key(|(address, transfer)| (transfer.expiry, *address));
+        let mut stats = PassStats {
+            due: due.len(),
+            ..PassStats::default()
+        };
+        due.retain(|(address, _)| {
+            self.retries
+                .get(address)
+                .is_none_or(|retry| at >= retry.not_before)
+        });
+        stats.deferred = stats.due - due.len();
+
+        for batch in batches(&due, &chain.cranker(), self.config.batch_size) {
+            match self.submit(chain, batch, &mut stats) {
+                Ok(()) => {}
+                // One bad transfer fails the whole transaction; reclaiming
+                // one at a time finds it and lets the others through.
+                Err(_) if batch.len() > 1 => {
+                    for single in batch.chunks(1) {
+                        if let Err(err) = self.submit(chain, single, &mut stats) {
+                            self.fail(&single[0].0, err, at, &mut stats);
+                        }
+                    }
+                }
+                Err(err) => self.fail(&batch[0].0, err, at, &mut stats),
+            }
+        }
+
+        let metrics = &self.metrics;
+        metrics.passes.fetch_add(1, Ordering::Relaxed);
+        metrics.due.store(stats.due as u64, Ordering::Relaxed);
+        metrics
+            .deferred
+            .store(stats.deferred as u64, Ordering::Relaxed);
+        metrics.last_pass_time.store(now, Ordering::Relaxed);
+        Ok(stats)
+    }
+
+    fn submit(
+        &mut self,
+        chain: &impl Chain,
+        batch: &[(Pubkey, TransferAccount)],
+        stats: &mut PassStats,
+    ) -> Result<()> {
+        let cranker = chain.cranker();
+        let instructions: Vec<_> = batch
+            .iter()
+            .flat_map(|(_, transfer)| reclaim_instructions(transfer, &cranker))
+            .collect();
+        stats.transactions += 1;
+        self.metrics.transactions.fetch_add(1, Ordering::Relaxed);
+        if let Err(err) = chain.submit(&instructions) {
+            self.metrics
+                .transaction_failures
+                .fetch_add(1, Ordering::Relaxed);
+            return Err(err);
+        }
+
+        for (address, transfer) in batch {
+            self.retries.remove(address);
+            stats.reclaimed.push(*address);
+            self.metrics.reclaims.fetch_add(1, Ordering::Relaxed);
+            self.metrics
+                .bounty_lamports
+                .fetch_add(transfer.crank_bounty_lamports, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, address: &Pubkey, err: anyhow::Error, at: Instant, stats: &mut PassStats) {
+        let retry = self.retries.entry(*address).or_insert(Retry {
+            failures: 0,
+            not_before: at,
+        });
+        retry.failures += 1;
+        let delay = self
+            .config
+            .retry_delay
+            .saturating_mul(2u32.saturating_pow(retry.failures - 1))
+            .min(self.config.max_retry_delay);
+        retry.not_before = at + delay;
+        stats.failed.push((*address, format!("{err:#}")));
+        self.metrics
+            .reclaim_failures
+            .fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Whether `transfer` can be reclaimed at cluster time `now`. Group
+/// transfers are refunded per contributor instead.
+pub fn is_due(transfer: &TransferAccount, now: i64) -> bool {
+    transfer.status == TransferStatus::Active && !transfer.is_group() && now >= transfer.expiry
+}
+
+/// The instructions reclaiming `transfer` with `cranker` signing. When the
+/// transfer pays a token bounty, the cranker's associated token account is
+/// created if needed to receive it.
+pub fn reclaim_instructions(transfer: &TransferAccount, cranker: &Pubkey) -> Vec<Instruction> {
+    let mut builder = ReclaimExpired::new(transfer, *cranker);
+    let mut instructions = Vec::new();
+    if transfer.crank_bounty_bps > 0 {
+        instructions.push(create_associated_token_account_idempotent(
+            cranker,
+            cranker,
+            &transfer.token_mint,
+            &token::ID,
+        ));
+        builder = builder
+            .cranker_token_account(get_associated_token_address(cranker, &transfer.token_mint));
+    }
+    instructions.push(builder.instruction());
+    instructions
+}
+
+/// Splits `due` into runs of at most `batch_size` transfers whose reclaims
+/// fit in one transaction.
+fn batches<'a>(
+    due: &'a [(Pubkey, TransferAccount)],
+    cranker: &Pubkey,
+    batch_size: usize,
+) -> Vec<&'a [(Pubkey, TransferAccount)]> {
+    let fits = |batch: &[(Pubkey, TransferAccount)]| {
+        let instructions: Vec<_> = batch
+            .iter()
+            .flat_map(|(_, transfer)| reclaim_instructions(transfer, cranker))
+            .collect();
+        let transaction = Transaction::new_with_payer(&instructions, Some(cranker));
+        bincode::serialized_size(&transaction).is_ok_and(|size| size <= MAX_TRANSACTION_SIZE)
+    };
+
+    let mut batches = Vec::new();
+    let mut start = 0;
+    while start < due.len() {
+        let mut end = start + 1;
+        while end < due.len() && end - start < batch_size && fits(&due[start..=end]) {
+            end += 1;
+        }
+        batches.push(&due[start..end]);
+        start = end;
+    }
+    batches
+}
@@ -0,0 +1,116 @@
+//! `payinbox-crank`: reclaims expired payinbox transfers as they come due.
+
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use payinbox_client::rpc::RpcClient;
+use payinbox_crank::chain::{Chain, RpcChain};
+use payinbox_crank::{metrics, Config, Crank, PassStats};
+use solana_keypair::read_keypair_file;
+
+#[derive(Parser)]
+#[command(
+    name = "payinbox-crank",
+    version,
+    about = "Reclaim expired payinbox transfers"
+)]
+struct Cli {
+    /// RPC endpoint
+    #[arg(
+        long,
+        env = "PAYINBOX_RPC_URL",
+        default_value = "https://api.devnet.solana.com"
+    )]
+    url: String,
+
+    /// Keypair file that signs and pays for reclaims and collects bounties
+    #[arg(
+        long,
+        env = "PAYINBOX_KEYPAIR",
+        default_value = "~/.config/solana/id.json"
+    )]
+    keypair: String,
+
+    /// Seconds between passes
+    #[arg(long, default_value_t = 60)]
+    interval: u64,
+
+    /// Most reclaims per transaction
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u64).range(1..))]
+    batch_size: u64,
+
+    /// Seconds before retrying a failed reclaim; doubles with every failure
+    #[arg(long, default_value_t = 30)]
+    retry_delay: u64,
+
+    /// Longest wait between retries, in seconds
+    #[arg(long, default_value_t = 3600)]
+    max_retry_delay: u64,
+
+    /// Serve Prometheus metrics at this address, e.g. 127.0.0.1:9464
+    #[arg(long, env = "PAYINBOX_CRANK_METRICS_ADDR")]
+    metrics_addr: Option<String>,
+
+    /// Make one pass and exit
+    #[arg(long)]
+    once: bool,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let chain = RpcChain::new(RpcClient::new(&cli.url), load_keypair(&cli.keypair)?);
+    let mut crank = Crank::new(Config {
+        batch_size: cli.batch_size as usize,
+        retry_delay: Duration::from_secs(cli.retry_delay),
+        max_retry_delay: Duration::from_secs(cli.max_retry_delay),
+    });
+    if let Some(addr) = &cli.metrics_addr {
+        metrics::serve(addr, crank.metrics())
+            .with_context(|| format!("serving metrics at {addr}"))?;
+    }
+    println!("cranker: {}", chain.cranker());
+
+    loop {
+        match crank.pass(&chain, Instant::now()) {
+            Ok(stats) => report(&stats),
+            // A failed scan (node unreachable, rate limited) is retried by
+            // the next pass.
+            Err(err) if !cli.once => eprintln!("error: {err:#}"),
+            Err(err) => return Err(err),
+        }
+        if cli.once {
+            return Ok(());
+        }
+        sleep(Duration::from_secs(cli.interval));
+    }
+}
+
+fn report(stats: &PassStats) {
+    for address in &stats.reclaimed {
+        println!("reclaimed {address}");
+    }
+    for (address, reason) in &stats.failed {
+        eprintln!("reclaim of {address} failed: {reason}");
+    }
+    if stats.due > 0 {
+        println!(
+            "{} due, {} reclaimed in {} transactions, {} failed, {} deferred",
+            stats.due,
+            stats.reclaimed.len(),
+            stats.transactions,
+            stats.failed.len(),
+            stats.deferred
+        );
+    }
+}
+
+fn load_keypair(path: &str) -> Result<solana_keypair::Keypair> {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var("HOME").context("HOME is not set")?).join(rest),
+        None => PathBuf::from(path),
+    };
+    read_keypair_file(&path).map_err(|e| anyhow!("reading keypair {}: {e}", path.display()))
+}
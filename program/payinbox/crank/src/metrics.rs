@@ -0,0 +1,113 @@
+//! Progress counters of the crank, served over HTTP for Prometheus.
+
+use std::fmt::Write;
+use std::io;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use tiny_http::{Header, Response, Server};
+
+/// Counters since the crank started, and gauges of the last pass.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub passes: AtomicU64,
+    /// Passes that couldn't list the transfers
+    pub scan_errors: AtomicU64,
+    pub transactions: AtomicU64,
+    pub transaction_failures: AtomicU64,
+    /// Transfers reclaimed
+    pub reclaims: AtomicU64,
+    /// Transfers whose reclaim failed, counted once per attempt
+    pub reclaim_failures: AtomicU64,
+    /// Lamport bounties collected by the reclaims
+    pub bounty_lamports: AtomicU64,
+    /// Expired transfers found by the last pass
+    pub due: AtomicU64,
+    /// Of those, the ones waiting out a retry delay
+    pub deferred: AtomicU64,
+    /// Cluster time of the last pass
+    pub last_pass_time: AtomicI64,
+}
+
+impl Metrics {
+    /// The metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let counters = [
+            ("passes_total", "Passes over the transfers", &self.passes),
+            (
+                "scan_errors_total",
+                "Passes that failed to list the transfers",
+                &self.scan_errors,
+            ),
+            (
+                "transactions_total",
+                "Reclaim transactions submitted",
+                &self.transactions,
+            ),
+            (
+                "transaction_failures_total",
+                "Reclaim transactions that failed",
+                &self.transaction_failures,
+            ),
+            ("reclaims_total", "Transfers reclaimed", &self.reclaims),
+            (
+                "reclaim_failures_total",
+                "Failed reclaim attempts",
+                &self.reclaim_failures,
+            ),
+            (
+                "bounty_lamports_total",
+                "Lamport bounties collected",
+                &self.bounty_lamports,
+            ),
+        ];
+        let gauges = [
+            (
+                "transfers_due",
+                "Expired transfers found by the last pass",
+                self.due.load(Ordering::Relaxed) as i64,
+            ),
+            (
+                "transfers_deferred",
+                "Due transfers waiting out a retry delay",
+                self.deferred.load(Ordering::Relaxed) as i64,
+            ),
+            (
+                "last_pass_timestamp_seconds",
+                "Cluster time of the last pass",
+                self.last_pass_time.load(Ordering::Relaxed),
+            ),
+        ];
+
+        let mut out = String::new();
+        for (name, help, value) in counters {
+            let value = value.load(Ordering::Relaxed);
+            let _ = writeln!(out, "# HELP payinbox_crank_{name} {help}");
+            let _ = writeln!(out, "# TYPE payinbox_crank_{name} counter");
+            let _ = writeln!(out, "payinbox_crank_{name} {value}");
+        }
+        for (name, help, value) in gauges {
+            let _ = writeln!(out, "# HELP payinbox_crank_{name} {help}");
+            let _ = writeln!(out, "# TYPE payinbox_crank_{name} gauge");
+            let _ = writeln!(out, "payinbox_crank_{name} {value}");
+        }
+        out
+    }
+}
+
+/// Serves `metrics` at `addr` (any path) from a background thread.
+pub fn serve(addr: &str, metrics: Arc<Metrics>) -> io::Result<()> {
+    let server = Server::http(addr).map_err(io::Error::other)?;
+    let content_type = Header::from_bytes("Content-Type", "text/plain; version=0.0.4")
+        .expect("static header is valid");
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response =
+                Response::from_string(metrics.render()).with_header(content_type.clone());
+            // The scraper hanging up early is its problem.
+            let _ = request.respond(response);
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,257 @@
+//! Crank passes over transfers in the in-process runtime.
+
+use std::cell::RefCell;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_spl::associated_token::get_associated_token_address;
+use anyhow::{anyhow, Result};
+use payinbox::{TransferAccount, TransferOptions, TransferStatus};
+use payinbox_client::accounts::decode_transfer;
+use payinbox_client::hash::claim_code_hash;
+use payinbox_client::instructions::CreateTransfer;
+use payinbox_client::pda;
+use payinbox_crank::chain::Chain;
+use payinbox_crank::{Config, Crank, PassStats};
+use payinbox_harness::Svm;
+
+const AMOUNT: u64 = 1_000_000;
+const EXPIRY_HOURS: i64 = 72;
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// The runtime as a cluster, with transactions signed by `cranker`.
+struct Local {
+    svm: RefCell<Svm>,
+    mint: Pubkey,
+    sender: Pubkey,
+    cranker: Pubkey,
+}
+
+impl Chain for Local {
+    fn cranker(&self) -> Pubkey {
+        self.cranker
+    }
+
+    fn now(&self) -> Result<i64> {
+        Ok(self.svm.borrow().clock().unix_timestamp)
+    }
+
+    fn active_transfers(&self) -> Result<Vec<(Pubkey, TransferAccount)>> {
+        Ok(self
+            .svm
+            .borrow()
+            .accounts()
+            .filter(|(_, account)| account.owner == payinbox::ID)
+            .filter_map(|(address, account)| Some((address, decode_transfer(&account.data).ok()?)))
+            .filter(|(_, transfer)| transfer.status == TransferStatus::Active)
+            .collect())
+    }
+
+    fn submit(&self, instructions: &[Instruction]) -> Result<()> {
+        self.svm
+            .borrow_mut()
+            .process_transaction(instructions)
+            .map_err(|e| anyhow!("transaction failed: {e}"))
+    }
+}
+
+impl Local {
+    fn new() -> Self {
+        let mut svm = Svm::new();
+        let mint = svm.create_mint(&Pubkey::new_unique(), 6);
+        let sender = Pubkey::new_unique();
+        let cranker = Pubkey::new_unique();
+        svm.airdrop(&sender, 10_000_000_000);
+        svm.airdrop(&cranker, 1_000_000_000);
+        svm.create_token_account(&sender, &mint, 100 * AMOUNT);
+        Self {
+            svm: RefCell::new(svm),
+            mint,
+            sender,
+            cranker,
+        }
+    }
+
+    fn create(&self, email: u8, options: TransferOptions) -> Pubkey {
+        let create = CreateTransfer::new(
+            self.sender,
+            self.mint,
+            [email; 32],
+            claim_code_hash("correct-horse-battery-staple"),
+            AMOUNT,
+            EXPIRY_HOURS,
+        )
+        .options(options);
+        self.svm
+            .borrow_mut()
+            .process_instruction(create.instruction())
+            .unwrap();
+        create.transfer_address()
+    }
+
+    fn advance_clock(&self, seconds: i64) {
+        self.svm.borrow_mut().advance_clock(seconds);
+    }
+
+    fn status(&self, transfer: &Pubkey) -> TransferStatus {
+        self.svm
+            .borrow()
+            .anchor_account::<TransferAccount>(transfer)
+            .unwrap()
+            .status
+    }
+
+    /// Leaves the escrow short, so reclaiming the transfer fails.
+    fn drain_escrow(&self, transfer: &Pubkey) {
+        self.svm.borrow_mut().create_token_account_at(
+            pda::escrow_address(transfer),
+            transfer,
+            &self.mint,
+            AMOUNT - 1,
+        );
+    }
+
+    fn restore_escrow(&self, transfer: &Pubkey) {
+        self.svm.borrow_mut().create_token_account_at(
+            pda::escrow_address(transfer),
+            transfer,
+            &self.mint,
+            AMOUNT,
+        );
+    }
+}
+
+fn crank() -> Crank {
+    Crank::new(Config {
+        batch_size: 3,
+        retry_delay: RETRY_DELAY,
+        max_retry_delay: RETRY_DELAY * 4,
+    })
+}
+
+#[test]
+fn reclaims_expired_transfers_in_batches() {
+    let chain = Local::new();
+    let expiring: Vec<_> = (1..=4)
+        .map(|email| chain.create(email, TransferOptions::default()))
+        .collect();
+    chain.advance_clock(3600);
+    let later = chain.create(5, TransferOptions::default());
+    chain.advance_clock(EXPIRY_HOURS * 3600 - 3600);
+
+    let mut crank = crank();
+    let stats = crank.pass(&chain, Instant::now()).unwrap();
+    assert_eq!(stats.due, 4);
+    assert_eq!(stats.transactions, 2);
+    assert_eq!(stats.failed, vec![]);
+    let mut expected = expiring.clone();
+    expected.sort();
+    assert_eq!(stats.reclaimed, expected);
+    for transfer in &expiring {
+        assert_eq!(chain.status(transfer), TransferStatus::Expired);
+    }
+    assert_eq!(chain.status(&later), TransferStatus::Active);
+    let sender_ata = get_associated_token_address(&chain.sender, &chain.mint);
+    assert_eq!(
+        chain.svm.borrow().token_balance(&sender_ata),
+        100 * AMOUNT - AMOUNT
+    );
+
+    assert_eq!(
+        crank.pass(&chain, Instant::now()).unwrap(),
+        PassStats::default()
+    );
+}
+
+#[test]
+fn collects_crank_bounties() {
+    let chain = Local::new();
+    let transfer = chain.create(
+        1,
+        TransferOptions {
+            crank_bounty_lamports: 5_000_000,
+            crank_bounty_bps: 100,
+            ..TransferOptions::default()
+        },
+    );
+    chain.advance_clock(EXPIRY_HOURS * 3600);
+    let lamports = chain.svm.borrow().lamports(&chain.cranker);
+
+    let mut crank = crank();
+    let stats = crank.pass(&chain, Instant::now()).unwrap();
+    assert_eq!(stats.reclaimed, vec![transfer]);
+    let svm = chain.svm.borrow();
+    let cranker_ata = get_associated_token_address(&chain.cranker, &chain.mint);
+    assert_eq!(svm.token_balance(&cranker_ata), AMOUNT / 100);
+    // Less the rent of the cranker's new token account
+    assert_eq!(
+        svm.lamports(&chain.cranker),
+        lamports + 5_000_000 - svm.rent().minimum_balance(165)
+    );
+    assert_eq!(
+        crank.metrics().bounty_lamports.load(Ordering::Relaxed),
+        5_000_000
+    );
+}
+
+#[test]
+fn isolates_failing_reclaims_and_backs_off() {
+    let chain = Local::new();
+    let mut transfers: Vec<_> = (1..=3)
+        .map(|email| chain.create(email, TransferOptions::default()))
+        .collect();
+    // Transfers expiring together are reclaimed in address order.
+    transfers.sort();
+    let broken = transfers[1];
+    chain.drain_escrow(&broken);
+    chain.advance_clock(EXPIRY_HOURS * 3600);
+
+    let mut crank = crank();
+    let start = Instant::now();
+    let stats = crank.pass(&chain, start).unwrap();
+    // The batch of three, then each alone.
+    assert_eq!(stats.transactions, 4);
+    assert_eq!(stats.reclaimed, vec![transfers[0], transfers[2]]);
+    assert_eq!(stats.failed.len(), 1);
+    assert_eq!(stats.failed[0].0, broken);
+
+    // Waits out the retry delay, which doubles after the second failure.
+    let stats = crank.pass(&chain, start + RETRY_DELAY / 2).unwrap();
+    assert_eq!((stats.due, stats.deferred, stats.transactions), (1, 1, 0));
+    let retried = start + RETRY_DELAY;
+    assert_eq!(crank.pass(&chain, retried).unwrap().failed.len(), 1);
+    let stats = crank.pass(&chain, retried + RETRY_DELAY).unwrap();
+    assert_eq!(stats.deferred, 1);
+
+    chain.restore_escrow(&broken);
+    let stats = crank.pass(&chain, retried + RETRY_DELAY * 2).unwrap();
+    assert_eq!(stats.reclaimed, vec![broken]);
+    assert_eq!(chain.status(&broken), TransferStatus::Expired);
+
+    let metrics = crank.metrics();
+    assert_eq!(metrics.reclaims.load(Ordering::Relaxed), 3);
+    assert_eq!(metrics.reclaim_failures.load(Ordering::Relaxed), 2);
+    assert_eq!(metrics.transactions.load(Ordering::Relaxed), 6);
+    assert_eq!(metrics.transaction_failures.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn renders_prometheus_metrics() {
+    let chain = Local::new();
+    chain.create(1, TransferOptions::default());
+    chain.advance_clock(EXPIRY_HOURS * 3600);
+    let mut crank = crank();
+    crank.pass(&chain, Instant::now()).unwrap();
+
+    let text = crank.metrics().render();
+    assert!(text.contains(
+        "# TYPE payinbox_crank_reclaims_total counter\npayinbox_crank_reclaims_total 1\n"
+    ));
+    assert!(text.contains("payinbox_crank_transfers_due 1\n"));
+    assert!(text.contains(&format!(
+        "payinbox_crank_last_pass_timestamp_seconds {}\n",
+        chain.now().unwrap()
+    )));
+}
@@ -0,0 +1,86 @@
+//! A crank pass against `solana-test-validator`, through RPC.
+//!
+//! Needs `solana-test-validator` on the PATH and the program built with
+//! `anchor build`; run with `cargo test -p payinbox-crank -- --ignored`.
+
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_spl::token::spl_token;
+use payinbox::{TransferAccount, TransferOptions, TransferStatus};
+use payinbox_client::accounts::fetch_transfer;
+use payinbox_client::hash::claim_code_hash;
+use payinbox_client::instructions::CreateTransfer;
+use payinbox_client::pda;
+use payinbox_crank::chain::RpcChain;
+use payinbox_crank::{Config, Crank};
+use payinbox_harness::{Account, Svm, TestValidator};
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+const AMOUNT: u64 = 1_000_000;
+
+#[test]
+#[ignore = "needs solana-test-validator and target/deploy/payinbox.so"]
+fn reclaims_through_rpc() {
+    // A transfer created four days ago, past its 72 hour expiry now.
+    let mut svm = Svm::new();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    svm.set_unix_timestamp(now - 4 * 24 * 3600);
+    let mint = svm.create_mint(&Pubkey::new_unique(), 6);
+    let sender = Pubkey::new_unique();
+    svm.airdrop(&sender, 1_000_000_000);
+    let sender_ata = svm.create_token_account(&sender, &mint, AMOUNT);
+    let create = CreateTransfer::new(
+        sender,
+        mint,
+        [1; 32],
+        claim_code_hash("correct-horse-battery-staple"),
+        AMOUNT,
+        72,
+    )
+    .options(TransferOptions {
+        crank_bounty_lamports: 1_000_000,
+        ..TransferOptions::default()
+    });
+    svm.process_instruction(create.instruction()).unwrap();
+    let transfer = create.transfer_address();
+    let escrow = pda::escrow_address(&transfer);
+
+    let cranker = Keypair::new();
+    let mut accounts: Vec<_> = [transfer, escrow, mint, sender, sender_ata]
+        .iter()
+        .map(|address| (*address, svm.account(address).unwrap()))
+        .collect();
+    accounts.push((
+        cranker.pubkey(),
+        Account {
+            lamports: 10_000_000_000,
+            ..Account::default()
+        },
+    ));
+
+    let validator = TestValidator::start(&accounts).expect("starting solana-test-validator");
+    let rpc = validator.rpc();
+    let chain = RpcChain::new(validator.rpc(), cranker.insecure_clone());
+    let mut crank = Crank::new(Config::default());
+    let stats = crank.pass(&chain, Instant::now()).unwrap();
+    assert_eq!(stats.failed, vec![]);
+    assert_eq!(stats.reclaimed, vec![transfer]);
+
+    let state: TransferAccount = fetch_transfer(&rpc, &transfer).unwrap();
+    assert_eq!(state.status, TransferStatus::Expired);
+    assert_eq!(rpc.get_account_data(&escrow).unwrap(), None);
+    let refund = rpc.get_account_data(&sender_ata).unwrap().unwrap();
+    assert_eq!(
+        spl_token::state::Account::unpack(&refund).unwrap().amount,
+        AMOUNT
+    );
+
+    // Nothing left to do.
+    assert_eq!(crank.pass(&chain, Instant::now()).unwrap().due, 0);
+}
@@ -0,0 +1,11 @@
+//! honggfuzz target: `cargo hfuzz run transfer_lifecycle` from `fuzz/`.
+
+use honggfuzz::fuzz;
+
+fn main() {
+    loop {
+        fuzz!(|data: &[u8]| {
+            payinbox_fuzz::run(data);
+        });
+    }
+}
@@ -0,0 +1,414 @@
+//! Fuzzing of the transfer lifecycle on the in-process runtime.
+//!
+//! An input [decodes](decode) to a sequence of [`Op`]s: creates, claims, cancels and
+//! reclaims by any of a few wallets, optionally with some of their accounts
+//! swapped for other known accounts, and clock warps. [`run`] applies them
+//! to a fresh [`World`] and after every op, whether the program accepted it
+//! or not, checks that
+//!
+//! - every token of the mint is still held by some token account, and the
+//!   escrow of each active transfer holds exactly its amount;
+//! - a transfer that was claimed, cancelled or reclaimed keeps that status;
+//! - a finalized transfer no longer controls an escrow.
+//!
+//! Lamport conservation is asserted by the runtime itself.
+
+use std::collections::HashMap;
+
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token;
+use arbitrary::{Arbitrary, Unstructured};
+use payinbox::{TransferAccount, TransferOptions, TransferStatus};
+use payinbox_client::hash::claim_code_hash;
+use payinbox_client::instructions::{
+    CancelTransfer, ClaimTransfer, CreateTransfer, ReclaimExpired,
+};
+use payinbox_client::pda;
+use payinbox_harness::Svm;
+
+/// Tokens each wallet starts with.
+pub const BALANCE: u64 = 1_000_000;
+
+const LAMPORTS: u64 = 100_000_000_000;
+
+/// One of the wallets taking part.
+#[derive(Arbitrary, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Actor {
+    Alice,
+    Bob,
+    Carol,
+    Mallory,
+}
+
+impl Actor {
+    const ALL: [Actor; 4] = [Actor::Alice, Actor::Bob, Actor::Carol, Actor::Mallory];
+}
+
+/// Recipient email of a transfer. With the sender it fixes the address.
+#[derive(Arbitrary, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Email {
+    First,
+    Second,
+}
+
+impl Email {
+    const ALL: [Email; 2] = [Email::First, Email::Second];
+
+    fn hash(self) -> [u8; 32] {
+        [self as u8 + 1; 32]
+    }
+
+    fn claim_code(self) -> String {
+        format!("claim-code-{}", self as u8)
+    }
+}
+
+/// A transfer, created or not.
+#[derive(Arbitrary, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransferRef {
+    pub sender: Actor,
+    pub email: Email,
+}
+
+impl TransferRef {
+    fn all() -> impl Iterator<Item = TransferRef> {
+        Actor::ALL.into_iter().flat_map(|sender| {
+            Email::ALL
+                .into_iter()
+                .map(move |email| TransferRef { sender, email })
+        })
+    }
+}
+
+/// Claim code submitted with a claim.
+#[derive(Arbitrary, Clone, Copy, Debug)]
+pub enum Code {
+    Right,
+    /// The code of the sender's other transfer
+    Other,
+    Empty,
+    /// One byte over the program's limit
+    TooLong,
+}
+
+/// An account that can stand in for the one an instruction expects.
+#[derive(Arbitrary, Clone, Copy, Debug)]
+pub enum Target {
+    Wallet(Actor),
+    TokenAccount(Actor),
+    OtherMintAccount(Actor),
+    Transfer(TransferRef),
+    Escrow(TransferRef),
+    Mint,
+    OtherMint,
+    TokenProgram,
+    SystemProgram,
+    /// A wallet nobody funded
+    Stranger,
+}
+
+/// Replaces account `index` (modulo the number of accounts) with `target`.
+#[derive(Arbitrary, Clone, Copy, Debug)]
+pub struct Substitution {
+    pub index: u8,
+    pub target: Target,
+}
+
+/// Options of a created transfer, mapped onto `TransferOptions`.
+#[derive(Arbitrary, Clone, Copy, Debug)]
+pub struct Options {
+    pub irrevocable: bool,
+    pub refund_to: Option<Actor>,
+    pub expiry_beneficiary: Option<Actor>,
+    pub cancel_authority: Option<Actor>,
+    /// Taken modulo 600, so both valid and oversized bounties show up
+    pub crank_bounty_bps: u16,
+    pub crank_bounty_lamports: u32,
+}
+
+#[derive(Arbitrary, Clone, Debug)]
+pub enum Op {
+    Create {
+        transfer: TransferRef,
+        /// Taken modulo twice the sender's starting balance
+        amount: u32,
+        expiry_hours: u8,
+        options: Options,
+        substitutions: Vec<Substitution>,
+    },
+    Claim {
+        transfer: TransferRef,
+        recipient: Actor,
+        code: Code,
+        substitutions: Vec<Substitution>,
+    },
+    Cancel {
+        transfer: TransferRef,
+        authority: Actor,
+        substitutions: Vec<Substitution>,
+    },
+    Reclaim {
+        transfer: TransferRef,
+        cranker: Actor,
+        token_bounty: bool,
+        substitutions: Vec<Substitution>,
+    },
+    Warp {
+        seconds: u16,
+    },
+}
+
+/// The ledger plus what the invariants need to remember between ops.
+pub struct World {
+    pub svm: Svm,
+    mint: Pubkey,
+    other_mint: Pubkey,
+    wallets: [Pubkey; 4],
+    stranger: Pubkey,
+    /// Terminal status of every transfer that reached one
+    finalized: HashMap<Pubkey, TransferStatus>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl World {
+    /// Funds every wallet with SOL and `BALANCE` tokens of the mint, plus
+    /// an empty account of another mint.
+    pub fn new() -> Self {
+        let mut svm = Svm::new();
+        let mint = svm.create_mint(&Pubkey::new_unique(), 6);
+        let other_mint = svm.create_mint(&Pubkey::new_unique(), 6);
+        let wallets = Actor::ALL.map(|_| Pubkey::new_unique());
+        for wallet in &wallets {
+            svm.airdrop(wallet, LAMPORTS);
+            svm.create_token_account(wallet, &mint, BALANCE);
+            svm.create_token_account(wallet, &other_mint, 0);
+        }
+        Self {
+            svm,
+            mint,
+            other_mint,
+            wallets,
+            stranger: Pubkey::new_unique(),
+            finalized: HashMap::new(),
+        }
+    }
+
+    pub fn wallet(&self, actor: Actor) -> Pubkey {
+        self.wallets[actor as usize]
+    }
+
+    pub fn transfer_address(&self, transfer: TransferRef) -> Pubkey {
+        pda::transfer_address(&self.wallet(transfer.sender), &transfer.email.hash())
+    }
+
+    pub fn transfer(&self, transfer: TransferRef) -> Option<TransferAccount> {
+        self.svm.anchor_account(&self.transfer_address(transfer))
+    }
+
+    /// Submits `op`. Returns `None` if nothing was submitted: a warp, or an
+    /// op on a transfer that doesn't exist.
+    pub fn apply(&mut self, op: &Op) -> Option<Result<(), ProgramError>> {
+        let (ix, substitutions) = match op {
+            Op::Create {
+                transfer,
+                amount,
+                expiry_hours,
+                options,
+                substitutions,
+            } => {
+                let ix = CreateTransfer::new(
+                    self.wallet(transfer.sender),
+                    self.mint,
+                    transfer.email.hash(),
+                    claim_code_hash(&transfer.email.claim_code()),
+                    u64::from(*amount) % (2 * BALANCE),
+                    i64::from(*expiry_hours),
+                )
+                .options(self.options(options))
+                .instruction();
+                (ix, substitutions)
+            }
+            Op::Claim {
+                transfer,
+                recipient,
+                code,
+                substitutions,
+            } => {
+                let code = match code {
+                    Code::Right => transfer.email.claim_code(),
+                    Code::Other => match transfer.email {
+                        Email::First => Email::Second.claim_code(),
+                        Email::Second => Email::First.claim_code(),
+                    },
+                    Code::Empty => String::new(),
+                    Code::TooLong => "x".repeat(257),
+                };
+                let ix =
+                    ClaimTransfer::new(&self.transfer(*transfer)?, self.wallet(*recipient), code)
+                        .instruction();
+                (ix, substitutions)
+            }
+            Op::Cancel {
+                transfer,
+                authority,
+                substitutions,
+            } => {
+                let ix = CancelTransfer::new(&self.transfer(*transfer)?, self.wallet(*authority))
+                    .instruction();
+                (ix, substitutions)
+            }
+            Op::Reclaim {
+                transfer,
+                cranker,
+                token_bounty,
+                substitutions,
+            } => {
+                let cranker = self.wallet(*cranker);
+                let mut reclaim = ReclaimExpired::new(&self.transfer(*transfer)?, cranker);
+                if *token_bounty {
+                    reclaim = reclaim
+                        .cranker_token_account(get_associated_token_address(&cranker, &self.mint));
+                }
+                (reclaim.instruction(), substitutions)
+            }
+            Op::Warp { seconds } => {
+                self.svm.advance_clock(i64::from(*seconds));
+                return None;
+            }
+        };
+        let ix = self.substitute(ix, substitutions);
+        Some(self.svm.process_instruction(ix))
+    }
+
+    fn options(&self, options: &Options) -> TransferOptions {
+        TransferOptions {
+            irrevocable: options.irrevocable,
+            refund_to: options.refund_to.map(|actor| self.wallet(actor)),
+            expiry_beneficiary: options.expiry_beneficiary.map(|actor| self.wallet(actor)),
+            crank_bounty_lamports: u64::from(options.crank_bounty_lamports),
+            crank_bounty_bps: options.crank_bounty_bps % 600,
+            cancel_authority: options.cancel_authority.map(|actor| self.wallet(actor)),
+            commit_key: None,
+        }
+    }
+
+    /// Applies `substitutions` to `ix`. A substituted signer stays a signer
+    /// only if its replacement is a wallet: nobody can sign for a PDA.
+    fn substitute(&self, mut ix: Instruction, substitutions: &[Substitution]) -> Instruction {
+        for substitution in substitutions {
+            let index = usize::from(substitution.index) % ix.accounts.len();
+            let (address, can_sign) = self.resolve(substitution.target);
+            let meta = &mut ix.accounts[index];
+            meta.pubkey = address;
+            meta.is_signer &= can_sign;
+        }
+        ix
+    }
+
+    fn resolve(&self, target: Target) -> (Pubkey, bool) {
+        match target {
+            Target::Wallet(actor) => (self.wallet(actor), true),
+            Target::TokenAccount(actor) => (
+                get_associated_token_address(&self.wallet(actor), &self.mint),
+                false,
+            ),
+            Target::OtherMintAccount(actor) => (
+                get_associated_token_address(&self.wallet(actor), &self.other_mint),
+                false,
+            ),
+            Target::Transfer(transfer) => (self.transfer_address(transfer), false),
+            Target::Escrow(transfer) => {
+                (pda::escrow_address(&self.transfer_address(transfer)), false)
+            }
+            Target::Mint => (self.mint, false),
+            Target::OtherMint => (self.other_mint, false),
+            Target::TokenProgram => (token::ID, false),
+            Target::SystemProgram => (system_program::ID, false),
+            Target::Stranger => (self.stranger, true),
+        }
+    }
+
+    /// Panics if an invariant is broken.
+    pub fn check(&mut self) {
+        let held: u64 = self
+            .svm
+            .accounts()
+            .filter_map(|(address, _)| self.svm.token_account(&address))
+            .filter(|account| account.mint == self.mint)
+            .map(|account| account.amount)
+            .sum();
+        assert_eq!(
+            held,
+            BALANCE * Actor::ALL.len() as u64,
+            "tokens were minted or burned"
+        );
+
+        for transfer in TransferRef::all() {
+            let address = self.transfer_address(transfer);
+            let Some(state) = self.transfer(transfer) else {
+                assert!(
+                    !self.finalized.contains_key(&address),
+                    "{transfer:?} disappeared"
+                );
+                continue;
+            };
+            let escrow = self.svm.token_account(&state.escrow_token_account);
+            if let Some(previous) = self.finalized.get(&address) {
+                assert_eq!(
+                    state.status, *previous,
+                    "{transfer:?} left its terminal status"
+                );
+            }
+            match state.status {
+                TransferStatus::Active => {
+                    let escrow = escrow.unwrap_or_else(|| panic!("{transfer:?} lost its escrow"));
+                    assert_eq!(escrow.owner, address, "{transfer:?} lost its escrow");
+                    assert_eq!(escrow.mint, state.token_mint);
+                    assert_eq!(
+                        escrow.amount, state.amount,
+                        "escrow of {transfer:?} doesn't hold its amount"
+                    );
+                }
+                status => {
+                    assert!(
+                        escrow.is_none_or(|escrow| escrow.owner != address),
+                        "{transfer:?} is {status:?} but still controls its escrow"
+                    );
+                    self.finalized.insert(address, status);
+                }
+            }
+        }
+    }
+}
+
+/// Decodes fuzzer input into ops, one after the other until it runs out.
+pub fn decode(data: &[u8]) -> Vec<Op> {
+    let mut input = Unstructured::new(data);
+    let mut ops = Vec::new();
+    while !input.is_empty() {
+        match Op::arbitrary(&mut input) {
+            Ok(op) => ops.push(op),
+            Err(_) => break,
+        }
+    }
+    ops
+}
+
+/// Applies the ops in `data` to a fresh world, checking the invariants
+/// after each.
+pub fn run(data: &[u8]) {
+    let mut world = World::new();
+    world.check();
+    for op in decode(data) {
+        let _ = world.apply(&op);
+        world.check();
+    }
+}
@@ -0,0 +1,436 @@
+//! In-process runtime for testing the payinbox program.
+//!
+//! [`Svm`] keeps a set of accounts and runs transactions against them in
+//! LiteSVM: the SBF build of the program (`target/deploy/payinbox.so`, see
+//! [`program_path`]) executes in the same VM, with the same compute
+//! metering, SPL Token and associated token programs, precompiles and
+//! sysvars as on chain. The clock can be set to any timestamp and events,
+//! logs, inner instructions and compute units of the last transaction are
+//! kept for assertions.
+//!
+//! [`ed25519_instruction`] signs attestations for the instructions that
+//! check them, and a [`Passkey`] signs WebAuthn assertions for passkey
+//! claims. [`dkim`] holds a verifying key and proof for DKIM claims.
+//!
+//! [`TestValidator`] starts a `solana-test-validator` with accounts from an
+//! `Svm`, for tests that have to go through a real cluster.
+
+pub mod dkim;
+mod precompiles;
+mod validator;
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anchor_lang::event::EVENT_IX_TAG_LE;
+use anchor_lang::prelude::{AccountMeta, Clock, ProgramError, Pubkey, Rent};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_pack::{IsInitialized, Pack};
+use anchor_lang::{system_program, AccountDeserialize, Event};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::{self, spl_token};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use litesvm::LiteSVM;
+use solana_message::compiled_instruction::CompiledInstruction;
+use solana_message::Message;
+use solana_signature::Signature;
+use solana_transaction::Transaction;
+use solana_transaction_error::TransactionError;
+
+pub use precompiles::{authenticator_data, ed25519_instruction, Passkey};
+pub use validator::TestValidator;
+
+/// Unix timestamp the clock starts at.
+pub const GENESIS_TIMESTAMP: i64 = 1_700_000_000;
+
+/// Lamports of the wallet that pays the transaction fees.
+const FEE_PAYER_LAMPORTS: u64 = 1_000_000_000_000_000;
+
+/// `target/deploy/payinbox.so`, as `anchor build` leaves it.
+pub fn program_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../target/deploy/payinbox.so")
+}
+
+/// The program binary, read once per process.
+fn program() -> &'static [u8] {
+    static PROGRAM: OnceLock<Vec<u8>> = OnceLock::new();
+    PROGRAM.get_or_init(|| {
+        let path = program_path();
+        std::fs::read(&path).unwrap_or_else(|error| {
+            panic!(
+                "{} can't be read ({error}); run `anchor build` first",
+                path.display()
+            )
+        })
+    })
+}
+
+/// An account as stored between transactions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Account {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+impl From<solana_account::Account> for Account {
+    fn from(account: solana_account::Account) -> Self {
+        Self {
+            lamports: account.lamports,
+            data: account.data,
+            owner: account.owner,
+            executable: account.executable,
+        }
+    }
+}
+
+impl From<Account> for solana_account::Account {
+    fn from(account: Account) -> Self {
+        Self {
+            lamports: account.lamports,
+            data: account.data,
+            owner: account.owner,
+            executable: account.executable,
+            rent_epoch: u64::MAX,
+        }
+    }
+}
+
+/// Accounts, sysvars and the output of the last transaction.
+///
+/// Cloning forks the ledger, e.g. to try competing transactions against
+/// the same state.
+#[derive(Clone)]
+pub struct Svm {
+    svm: LiteSVM,
+    /// Pays the fees, so the wallets under test only see what the
+    /// instructions move
+    fee_payer: Pubkey,
+    clock: Clock,
+    rent: Rent,
+    logs: Vec<String>,
+    events: Vec<Vec<u8>>,
+    inner_instructions: Vec<Instruction>,
+    compute_units: u64,
+}
+
+impl Default for Svm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Svm {
+    /// Creates a runtime with the payinbox program deployed next to the
+    /// SPL programs, builtins and precompiles.
+    ///
+    /// Panics if the program hasn't been built.
+    pub fn new() -> Self {
+        // Signatures aren't checked: instructions name their signers in
+        // their account metas, as the tests need no keypairs. Identical
+        // transactions may be sent again.
+        let mut svm = LiteSVM::new()
+            .with_sigverify(false)
+            .with_transaction_history(0);
+        svm.add_program(payinbox::ID, program())
+            .expect("the payinbox program loads");
+        let mut svm = Self {
+            svm,
+            fee_payer: Pubkey::new_unique(),
+            clock: Clock {
+                slot: 1,
+                epoch_start_timestamp: GENESIS_TIMESTAMP,
+                epoch: 0,
+                leader_schedule_epoch: 1,
+                unix_timestamp: GENESIS_TIMESTAMP,
+            },
+            rent: Rent::default(),
+            logs: Vec::new(),
+            events: Vec::new(),
+            inner_instructions: Vec::new(),
+            compute_units: 0,
+        };
+        svm.svm.set_sysvar(&svm.rent);
+        svm.svm.set_sysvar(&svm.clock);
+        let fee_payer = svm.fee_payer;
+        svm.airdrop(&fee_payer, FEE_PAYER_LAMPORTS);
+        svm
+    }
+
+    /// The account at `address`, if it holds lamports.
+    pub fn account(&self, address: &Pubkey) -> Option<Account> {
+        self.svm
+            .get_account(address)
+            .filter(|account| account.lamports > 0)
+            .map(Account::from)
+    }
+
+    /// Stores `account`; one without lamports is removed.
+    pub fn set_account(&mut self, address: Pubkey, account: Account) {
+        self.svm
+            .set_account(address, account.into())
+            .expect("account is stored");
+    }
+
+    /// All accounts but the programs, in no particular order.
+    pub fn accounts(&self) -> impl Iterator<Item = (Pubkey, Account)> + '_ {
+        self.svm
+            .accounts_db()
+            .inner
+            .keys()
+            .filter_map(|address| Some((*address, self.account(address)?)))
+            .filter(|(_, account)| !account.executable)
+    }
+
+    /// Lamports of `address` (0 if it doesn't exist).
+    pub fn lamports(&self, address: &Pubkey) -> u64 {
+        self.svm.get_balance(address).unwrap_or(0)
+    }
+
+    /// Credits `lamports` to a (system-owned) wallet.
+    pub fn airdrop(&mut self, address: &Pubkey, lamports: u64) {
+        let mut account = self.account(address).unwrap_or(Account {
+            owner: system_program::ID,
+            ..Account::default()
+        });
+        account.lamports += lamports;
+        self.set_account(*address, account);
+    }
+
+    /// Decodes the Anchor account at `address`, if it exists and is a `T`.
+    pub fn anchor_account<T: AccountDeserialize>(&self, address: &Pubkey) -> Option<T> {
+        let account = self.account(address)?;
+        T::try_deserialize(&mut account.data.as_slice()).ok()
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub fn rent(&self) -> &Rent {
+        &self.rent
+    }
+
+    /// Replaces the clock, e.g. to move the slot or epoch as well.
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+        self.svm.set_sysvar(&self.clock);
+    }
+
+    /// Sets the clock to `unix_timestamp`, advancing the slot by one.
+    pub fn set_unix_timestamp(&mut self, unix_timestamp: i64) {
+        let mut clock = self.clock.clone();
+        clock.unix_timestamp = unix_timestamp;
+        clock.slot += 1;
+        self.set_clock(clock);
+    }
+
+    /// Moves the clock forward by `seconds`.
+    pub fn advance_clock(&mut self, seconds: i64) {
+        self.set_unix_timestamp(self.clock.unix_timestamp + seconds);
+    }
+
+    /// Runs a single instruction as its own transaction.
+    pub fn process_instruction(&mut self, instruction: Instruction) -> Result<(), ProgramError> {
+        self.process_transaction(&[instruction])
+    }
+
+    /// Runs `instructions` atomically: if one fails, no account changes.
+    /// Signer flags are taken from the account metas as given.
+    ///
+    /// Failures other than an instruction's `ProgramError` (a transaction
+    /// the runtime rejects outright, or an instruction error programs
+    /// can't return themselves) come back as `InvalidArgument`; the logs
+    /// tell which.
+    pub fn process_transaction(
+        &mut self,
+        instructions: &[Instruction],
+    ) -> Result<(), ProgramError> {
+        let message = Message::new_with_blockhash(
+            instructions,
+            Some(&self.fee_payer),
+            &self.svm.latest_blockhash(),
+        );
+        let transaction = Transaction {
+            signatures: vec![Signature::default(); message.header.num_required_signatures.into()],
+            message: message.clone(),
+        };
+
+        let (result, meta) = match self.svm.send_transaction(transaction) {
+            Ok(meta) => (Ok(()), meta),
+            Err(failed) => (Err(program_error(failed.err)), failed.meta),
+        };
+        self.logs = meta.logs;
+        self.compute_units = meta.compute_units_consumed;
+        self.inner_instructions = meta
+            .inner_instructions
+            .into_iter()
+            .flatten()
+            .map(|inner| decompile(&message, &inner.instruction))
+            .collect();
+        self.events = match result {
+            Ok(()) => events(&self.logs, &self.inner_instructions),
+            Err(_) => Vec::new(),
+        };
+        result
+    }
+
+    /// Log lines of the last transaction.
+    pub fn logs(&self) -> &[String] {
+        &self.logs
+    }
+
+    /// Cross-program invocations of the last transaction, in the order
+    /// they started (what RPC nodes report as inner instructions).
+    pub fn inner_instructions(&self) -> &[Instruction] {
+        &self.inner_instructions
+    }
+
+    /// Compute units the last transaction consumed, as metered by the SBF
+    /// VM: the programs' own instructions, syscalls, CPIs and the programs
+    /// they invoke.
+    pub fn compute_units(&self) -> u64 {
+        self.compute_units
+    }
+
+    /// Events of type `E` emitted by the last successful transaction, with
+    /// `emit!` or `emit_cpi!`.
+    pub fn events<E: Event>(&self) -> Vec<E> {
+        self.events
+            .iter()
+            .filter_map(|data| data.strip_prefix(E::DISCRIMINATOR))
+            .map(|mut data| E::deserialize(&mut data).expect("malformed event"))
+            .collect()
+    }
+
+    /// Creates an SPL token mint.
+    pub fn create_mint(&mut self, authority: &Pubkey, decimals: u8) -> Pubkey {
+        let mint = Pubkey::new_unique();
+        let state = spl_token::state::Mint {
+            mint_authority: Some(*authority).into(),
+            supply: 0,
+            decimals,
+            is_initialized: true,
+            freeze_authority: Some(*authority).into(),
+        };
+        self.set_packed(mint, state);
+        mint
+    }
+
+    /// Creates `owner`'s associated token account holding `amount` newly
+    /// minted tokens.
+    pub fn create_token_account(&mut self, owner: &Pubkey, mint: &Pubkey, amount: u64) -> Pubkey {
+        let address = get_associated_token_address(owner, mint);
+        self.create_token_account_at(address, owner, mint, amount);
+        address
+    }
+
+    /// Like [`Svm::create_token_account`], at an arbitrary address.
+    pub fn create_token_account_at(
+        &mut self,
+        address: Pubkey,
+        owner: &Pubkey,
+        mint: &Pubkey,
+        amount: u64,
+    ) {
+        let mut mint_state = self
+            .token_state::<spl_token::state::Mint>(mint)
+            .expect("mint exists");
+        mint_state.supply += amount;
+        self.set_packed(*mint, mint_state);
+        let state = spl_token::state::Account {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        self.set_packed(address, state);
+    }
+
+    /// Decodes the SPL token account at `address`.
+    pub fn token_account(&self, address: &Pubkey) -> Option<spl_token::state::Account> {
+        self.token_state(address)
+    }
+
+    /// Token balance of `address` (0 if it doesn't exist).
+    pub fn token_balance(&self, address: &Pubkey) -> u64 {
+        self.token_account(address)
+            .map_or(0, |account| account.amount)
+    }
+
+    fn token_state<T: Pack + IsInitialized>(&self, address: &Pubkey) -> Option<T> {
+        let account = self.account(address)?;
+        (account.owner == token::ID)
+            .then(|| T::unpack(&account.data).ok())
+            .flatten()
+    }
+
+    fn set_packed<T: Pack>(&mut self, address: Pubkey, state: T) {
+        let mut data = vec![0; T::LEN];
+        T::pack(state, &mut data).expect("state packs");
+        self.set_account(
+            address,
+            Account {
+                lamports: self.rent.minimum_balance(T::LEN),
+                data,
+                owner: token::ID,
+                executable: false,
+            },
+        );
+    }
+}
+
+/// The `ProgramError` a failed transaction maps to.
+fn program_error(error: TransactionError) -> ProgramError {
+    match error {
+        TransactionError::InstructionError(_, error) => {
+            ProgramError::try_from(error).unwrap_or(ProgramError::InvalidArgument)
+        }
+        TransactionError::InsufficientFundsForRent { .. } => ProgramError::InsufficientFunds,
+        _ => ProgramError::InvalidArgument,
+    }
+}
+
+/// Resolves the account indexes of an inner instruction of `message`.
+fn decompile(message: &Message, instruction: &CompiledInstruction) -> Instruction {
+    let keys = &message.account_keys;
+    Instruction {
+        program_id: keys[usize::from(instruction.program_id_index)],
+        accounts: instruction
+            .accounts
+            .iter()
+            .map(|&index| {
+                let index = usize::from(index);
+                AccountMeta {
+                    pubkey: keys[index],
+                    is_signer: message.is_signer(index),
+                    is_writable: message.is_maybe_writable(index, None),
+                }
+            })
+            .collect(),
+        data: instruction.data.clone(),
+    }
+}
+
+/// Anchor events in a transaction's output: `emit!` logs them as
+/// `Program data:` lines, `emit_cpi!` as the data of the program's
+/// invocations of itself.
+fn events(logs: &[String], inner_instructions: &[Instruction]) -> Vec<Vec<u8>> {
+    let logged = logs
+        .iter()
+        .filter_map(|line| line.strip_prefix("Program data: "))
+        .filter_map(|line| {
+            line.split(' ')
+                .map(|field| STANDARD.decode(field).ok())
+                .collect::<Option<Vec<_>>>()
+        })
+        .map(|fields| fields.concat());
+    let invoked = inner_instructions
+        .iter()
+        .filter(|ix| ix.program_id == payinbox::ID)
+        .filter_map(|ix| ix.data.strip_prefix(EVENT_IX_TAG_LE))
+        .map(<[u8]>::to_vec);
+    logged.chain(invoked).collect()
+}
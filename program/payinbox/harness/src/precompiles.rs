@@ -0,0 +1,100 @@
+//! Signature precompile instructions, signed the way a verifier or a
+//! WebAuthn authenticator would sign them.
+//!
+//! Both go right before the program instruction that checks them, in the
+//! same transaction; the runtime verifies the signature and the program
+//! reads key and message back through the instructions sysvar.
+
+use anchor_lang::solana_program::instruction::Instruction;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use openssl::bn::BigNumContext;
+use openssl::ec::{EcGroup, EcKey, PointConversionForm};
+use openssl::nid::Nid;
+use openssl::pkey::Private;
+use openssl::sha::sha256;
+use solana_keypair::Keypair;
+use solana_secp256r1_program::{new_secp256r1_instruction_with_signature, sign_message};
+use solana_signer::Signer;
+
+/// Relying party the test passkeys are registered with.
+const RELYING_PARTY: &str = "payinbox.app";
+
+/// Authenticator data flags: user present and user verified.
+const USER_PRESENT_AND_VERIFIED: u8 = 0x01 | 0x04;
+
+/// Ed25519 program instruction in which `signer` signs `message`, e.g. a
+/// verifier attesting a `bind_attestation_message`.
+pub fn ed25519_instruction(signer: &Keypair, message: &[u8]) -> Instruction {
+    let signature = signer.sign_message(message);
+    solana_ed25519_program::new_ed25519_instruction_with_signature(
+        message,
+        signature.as_array(),
+        &signer.pubkey().to_bytes(),
+    )
+}
+
+/// A secp256r1 key standing in for a WebAuthn authenticator.
+pub struct Passkey {
+    key: EcKey<Private>,
+}
+
+impl Default for Passkey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Passkey {
+    /// Generates a new key.
+    pub fn new() -> Self {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).expect("P-256 is supported");
+        Self {
+            key: EcKey::generate(&group).expect("key generates"),
+        }
+    }
+
+    /// The compressed public key, as the program stores passkeys.
+    pub fn public_key(&self) -> [u8; 33] {
+        let mut context = BigNumContext::new().expect("context");
+        self.key
+            .public_key()
+            .to_bytes(
+                self.key.group(),
+                PointConversionForm::COMPRESSED,
+                &mut context,
+            )
+            .expect("point encodes")
+            .try_into()
+            .expect("compressed points are 33 bytes")
+    }
+
+    /// Asserts `challenge` like a browser's `navigator.credentials.get`.
+    ///
+    /// Returns the secp256r1 instruction over `authenticatorData ||
+    /// SHA256(clientDataJSON)` and the client data JSON, which the program
+    /// takes in place of the claim code.
+    pub fn assert(&self, challenge: &[u8; 32]) -> (Instruction, String) {
+        let client_data = format!(
+            r#"{{"type":"webauthn.get","challenge":"{}","origin":"https://{RELYING_PARTY}","crossOrigin":false}}"#,
+            URL_SAFE_NO_PAD.encode(challenge)
+        );
+        let mut message = authenticator_data(USER_PRESENT_AND_VERIFIED);
+        message.extend_from_slice(&sha256(client_data.as_bytes()));
+        (self.sign(&message), client_data)
+    }
+
+    /// The secp256r1 instruction in which this key signs `message`.
+    pub fn sign(&self, message: &[u8]) -> Instruction {
+        let der = self.key.private_key_to_der().expect("key encodes");
+        let signature = sign_message(message, &der).expect("message signs");
+        new_secp256r1_instruction_with_signature(message, &signature, &self.public_key())
+    }
+}
+
+/// `rpIdHash || flags || signCount` for the test relying party.
+pub fn authenticator_data(flags: u8) -> Vec<u8> {
+    let mut data = sha256(RELYING_PARTY.as_bytes()).to_vec();
+    data.push(flags);
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data
+}
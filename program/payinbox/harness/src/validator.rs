@@ -0,0 +1,145 @@
+//! A `solana-test-validator` for tests that need a real cluster.
+//!
+//! The validator runs the BPF build of the program and starts with
+//! accounts taken from an [`Svm`](crate::Svm), so state the cluster can't
+//! reach quickly (a transfer created days ago) can be set up in LiteSVM and
+//! then exercised over RPC.
+
+use std::fs;
+use std::io;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use anchor_lang::prelude::Pubkey;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use payinbox_client::rpc::RpcClient;
+use serde_json::json;
+
+use crate::Account;
+
+/// How long the validator may take to become healthy.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A running `solana-test-validator`, killed on drop.
+pub struct TestValidator {
+    process: Child,
+    ledger: PathBuf,
+    url: String,
+}
+
+impl TestValidator {
+    /// The program the validator runs, the same build as [`Svm`](crate::Svm)'s
+    /// (see [`crate::program_path`]).
+    pub fn program_path() -> PathBuf {
+        crate::program_path()
+    }
+
+    /// Starts a validator with the program at [`TestValidator::program_path`]
+    /// and `accounts` preloaded, and waits until it answers RPC.
+    pub fn start(accounts: &[(Pubkey, Account)]) -> io::Result<Self> {
+        let program = Self::program_path();
+        if !program.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is missing; run `anchor build`", program.display()),
+            ));
+        }
+
+        let rpc_port = free_port_pair()?;
+        let faucet_port = free_port_pair()?;
+        let ledger = std::env::temp_dir().join(format!("payinbox-validator-{rpc_port}"));
+        let accounts_dir = ledger.with_extension("accounts");
+        fs::create_dir_all(&accounts_dir)?;
+
+        let mut command = Command::new("solana-test-validator");
+        command
+            .arg("--ledger")
+            .arg(&ledger)
+            .args(["--reset", "--quiet", "--bind-address", "127.0.0.1"])
+            .args(["--rpc-port", &rpc_port.to_string()])
+            .args(["--faucet-port", &faucet_port.to_string()])
+            .arg("--bpf-program")
+            .arg(payinbox::ID.to_string())
+            .arg(&program);
+        for (address, account) in accounts {
+            let path = accounts_dir.join(format!("{address}.json"));
+            fs::write(&path, account_json(address, account))?;
+            command.arg("--account").arg(address.to_string()).arg(&path);
+        }
+        let process = command
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let validator = Self {
+            process,
+            ledger,
+            url: format!("http://127.0.0.1:{rpc_port}"),
+        };
+        validator.wait_until_healthy()?;
+        Ok(validator)
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn rpc(&self) -> RpcClient {
+        RpcClient::new(&self.url)
+    }
+
+    fn wait_until_healthy(&self) -> io::Result<()> {
+        let rpc = self.rpc();
+        let started = Instant::now();
+        while started.elapsed() < STARTUP_TIMEOUT {
+            // Blockhashes are served once the first slot is done.
+            if rpc.call("getHealth", json!([])).is_ok() && rpc.get_latest_blockhash().is_ok() {
+                return Ok(());
+            }
+            sleep(Duration::from_millis(250));
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("validator at {} didn't become healthy", self.url),
+        ))
+    }
+}
+
+impl Drop for TestValidator {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_dir_all(&self.ledger);
+        let _ = fs::remove_dir_all(self.ledger.with_extension("accounts"));
+    }
+}
+
+/// A port that is free, and whose successor is too: the validator serves
+/// websockets (and the faucet) one port above.
+fn free_port_pair() -> io::Result<u16> {
+    loop {
+        let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        if port < u16::MAX && TcpListener::bind(("127.0.0.1", port + 1)).is_ok() {
+            return Ok(port);
+        }
+    }
+}
+
+/// The `--account` file format (that of `solana account --output json`).
+fn account_json(address: &Pubkey, account: &Account) -> String {
+    json!({
+        "pubkey": address.to_string(),
+        "account": {
+            "lamports": account.lamports,
+            "data": [STANDARD.encode(&account.data), "base64"],
+            "owner": account.owner.to_string(),
+            "executable": account.executable,
+            "rentEpoch": u64::MAX,
+            "space": account.data.len(),
+        },
+    })
+    .to_string()
+}
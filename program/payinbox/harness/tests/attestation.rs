@@ -0,0 +1,344 @@
+//! Claims with a verifier attestation: reading the Ed25519 instruction in
+//! front of `claim_with_attestation`, using each attestation once, and
+//! who may register verifiers.
+
+mod common;
+
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token;
+use payinbox::{claim_commitment, AttestationRecord, ErrorCode, TransferClaimed, TransferStatus};
+use payinbox_client::hash::commit_keypair;
+use payinbox_client::instructions::CommitClaim;
+use payinbox_client::pda;
+use payinbox_harness::ed25519_instruction;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+use common::*;
+
+/// Offset of the first signature's offsets in Ed25519 instruction data.
+const OFFSETS_START: usize = 2;
+/// Size of one signature's offsets.
+const OFFSETS_LEN: usize = 14;
+
+/// A default transfer, a registered verifier and a relayer paying for the
+/// claim.
+struct Attested {
+    setup: Setup,
+    transfer: Pubkey,
+    verifier: Keypair,
+    relayer: Pubkey,
+    expires_at: i64,
+}
+
+impl Attested {
+    fn new() -> Self {
+        Self::with(Setup::new(), Default::default())
+    }
+
+    fn with(mut setup: Setup, options: payinbox::TransferOptions) -> Self {
+        let transfer = setup.create_with(options);
+        let verifier = setup.add_verifier();
+        let relayer = Pubkey::new_unique();
+        setup.svm.airdrop(&relayer, LAMPORTS_PER_SOL);
+        let expires_at = setup.svm.clock().unix_timestamp + 600;
+        Self {
+            setup,
+            transfer,
+            verifier,
+            relayer,
+            expires_at,
+        }
+    }
+
+    /// What the verifier signs to let `destination` claim.
+    fn message(&self, destination: &Pubkey) -> Vec<u8> {
+        payinbox::claim_attestation_message(
+            &EMAIL_HASH,
+            &self.transfer,
+            destination,
+            self.expires_at,
+        )
+    }
+
+    /// `claim_with_attestation` into `destination`'s token account.
+    fn claim_ix(&self, destination: Pubkey) -> Instruction {
+        let state = self.setup.transfer(&self.transfer);
+        instruction(
+            payinbox::accounts::ClaimWithAttestation {
+                transfer: self.transfer,
+                config: pda::config_address(),
+                attestation_record: pda::attestation_record_address(&self.message(&destination)),
+                destination,
+                destination_token_account: get_associated_token_address(
+                    &destination,
+                    &state.token_mint,
+                ),
+                escrow_token_account: pda::escrow_address(&self.transfer),
+                sender: state.sender,
+                payer: self.relayer,
+                instructions: sysvar::instructions::ID,
+                token_program: token::ID,
+                system_program: system_program::ID,
+                inbox: None,
+                event_authority: pda::event_authority_address(),
+                program: payinbox::ID,
+            },
+            payinbox::instruction::ClaimWithAttestation {
+                expires_at: self.expires_at,
+            },
+        )
+    }
+
+    /// Runs `attestation` followed by the claim into the recipient.
+    fn claim_after(&mut self, attestation: Instruction) -> Result<(), ProgramError> {
+        let claim = self.claim_ix(self.setup.recipient);
+        self.setup.svm.process_transaction(&[attestation, claim])
+    }
+
+    /// Claims into the recipient with a proper attestation.
+    fn claim(&mut self) -> Result<(), ProgramError> {
+        let attestation = ed25519_instruction(&self.verifier, &self.message(&self.setup.recipient));
+        self.claim_after(attestation)
+    }
+}
+
+/// Points every offset of `attestation` at instruction `index` instead of
+/// the instruction itself.
+fn with_instruction_index(mut attestation: Instruction, index: u16) -> Instruction {
+    for field in [2, 6, 12] {
+        let at = OFFSETS_START + field;
+        attestation.data[at..at + 2].copy_from_slice(&index.to_le_bytes());
+    }
+    attestation
+}
+
+/// One Ed25519 instruction carrying both signatures of `first` and
+/// `second`, each with its data in the instruction itself.
+fn with_two_signatures(first: &Instruction, second: &Instruction) -> Instruction {
+    let body = |ix: &Instruction| ix.data[OFFSETS_START + OFFSETS_LEN..].to_vec();
+    let shift = |ix: &Instruction, by: usize| {
+        let mut offsets = ix.data[OFFSETS_START..OFFSETS_START + OFFSETS_LEN].to_vec();
+        for field in [0, 4, 8] {
+            let value = u16::from_le_bytes([offsets[field], offsets[field + 1]]) as usize + by;
+            offsets[field..field + 2].copy_from_slice(&(value as u16).to_le_bytes());
+        }
+        offsets
+    };
+    let first_body = body(first);
+    let mut data = vec![2, 0];
+    data.extend(shift(first, OFFSETS_LEN));
+    data.extend(shift(second, OFFSETS_LEN + first_body.len()));
+    data.extend(first_body);
+    data.extend(body(second));
+    Instruction {
+        program_id: first.program_id,
+        accounts: Vec::new(),
+        data,
+    }
+}
+
+#[test]
+fn attested_claim_pays_the_destination() {
+    let mut attested = Attested::new();
+    attested.claim().unwrap();
+
+    let setup = &attested.setup;
+    assert_eq!(
+        setup.transfer(&attested.transfer).status,
+        TransferStatus::Claimed
+    );
+    let recipient_account = get_associated_token_address(&setup.recipient, &setup.mint);
+    assert_eq!(setup.svm.token_balance(&recipient_account), AMOUNT);
+    let claimed = setup.svm.events::<TransferClaimed>();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].recipient, setup.recipient);
+
+    // The relayer paid for the record; the recipient paid nothing
+    let record: AttestationRecord = setup
+        .svm
+        .anchor_account(&pda::attestation_record_address(
+            &attested.message(&setup.recipient),
+        ))
+        .unwrap();
+    assert_eq!(record.expires_at, attested.expires_at);
+    assert_eq!(setup.svm.lamports(&setup.recipient), LAMPORTS_PER_SOL);
+}
+
+#[test]
+fn attestation_is_used_once() {
+    let mut attested = Attested::new();
+    attested.claim().unwrap();
+
+    // Put the transfer and its escrow back as they were: only the
+    // attestation record is left to stop the replay
+    let setup = &mut attested.setup;
+    let mut state = setup.transfer(&attested.transfer);
+    state.status = TransferStatus::Active;
+    write_account(&mut setup.svm, &attested.transfer, &state);
+    let mint = setup.mint;
+    setup.svm.create_token_account_at(
+        pda::escrow_address(&attested.transfer),
+        &attested.transfer,
+        &mint,
+        AMOUNT,
+    );
+
+    // The record already exists, so creating it again fails in the
+    // system program (`AccountAlreadyInUse`)
+    assert_eq!(attested.claim(), Err(ProgramError::Custom(0)));
+}
+
+#[test]
+fn attestation_names_the_destination() {
+    let mut attested = Attested::new();
+    let thief = Pubkey::new_unique();
+    attested
+        .setup
+        .svm
+        .create_token_account(&thief, &attested.setup.mint, 0);
+
+    // Signed for the recipient, claimed into the thief's account
+    let attestation = ed25519_instruction(
+        &attested.verifier,
+        &attested.message(&attested.setup.recipient),
+    );
+    let claim = attested.claim_ix(thief);
+    assert_error(
+        attested
+            .setup
+            .svm
+            .process_transaction(&[attestation, claim]),
+        ErrorCode::InvalidAttestation,
+    );
+    attested.claim().unwrap();
+}
+
+#[test]
+fn attestation_must_come_from_a_registered_verifier() {
+    let mut attested = Attested::new();
+    let attestation = ed25519_instruction(
+        &Keypair::new(),
+        &attested.message(&attested.setup.recipient),
+    );
+    assert_error(
+        attested.claim_after(attestation),
+        ErrorCode::UnknownVerifier,
+    );
+}
+
+#[test]
+fn expired_attestation_is_rejected() {
+    let mut attested = Attested::new();
+    attested.setup.svm.set_unix_timestamp(attested.expires_at);
+    assert_error(attested.claim(), ErrorCode::AttestationExpired);
+}
+
+#[test]
+fn attestation_must_directly_precede_the_claim() {
+    let mut attested = Attested::new();
+    let claim = attested.claim_ix(attested.setup.recipient);
+    assert_error(
+        attested.setup.svm.process_instruction(claim.clone()),
+        ErrorCode::InvalidAttestation,
+    );
+
+    let attestation = ed25519_instruction(
+        &attested.verifier,
+        &attested.message(&attested.setup.recipient),
+    );
+    assert_error(
+        attested
+            .setup
+            .svm
+            .process_transaction(&[claim, attestation]),
+        ErrorCode::InvalidAttestation,
+    );
+}
+
+#[test]
+fn attestation_data_must_live_in_the_ed25519_instruction() {
+    let mut attested = Attested::new();
+    let attestation = ed25519_instruction(
+        &attested.verifier,
+        &attested.message(&attested.setup.recipient),
+    );
+
+    // Valid for the precompile (instruction 0 is the Ed25519 instruction
+    // itself), but the program only trusts offsets into its own data
+    let indexed = with_instruction_index(attestation, 0);
+    assert_error(attested.claim_after(indexed), ErrorCode::InvalidAttestation);
+}
+
+#[test]
+fn attestation_must_carry_exactly_one_signature() {
+    let mut attested = Attested::new();
+    let message = attested.message(&attested.setup.recipient);
+    let attestation = ed25519_instruction(&attested.verifier, &message);
+    let other = ed25519_instruction(&Keypair::new(), b"unrelated");
+
+    let both = with_two_signatures(&attestation, &other);
+    assert_error(attested.claim_after(both), ErrorCode::InvalidAttestation);
+    attested.claim_after(attestation).unwrap();
+}
+
+/// `add_verifier` of a fresh key, signed by `admin`.
+fn add_verifier(setup: &mut Setup, admin: Pubkey) -> Result<(), ProgramError> {
+    let ix = instruction(
+        payinbox::accounts::UpdateConfig {
+            config: pda::config_address(),
+            admin,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::AddVerifier {
+            verifier: Pubkey::new_unique(),
+        },
+    );
+    setup.svm.process_instruction(ix)
+}
+
+#[test]
+fn only_the_admin_registers_verifiers() {
+    let mut setup = Setup::new();
+    setup.initialize_config();
+    let recipient = setup.recipient;
+    assert_error(
+        add_verifier(&mut setup, recipient),
+        ErrorCode::NotConfigAdmin,
+    );
+    let sender = setup.sender;
+    add_verifier(&mut setup, sender).unwrap();
+}
+
+#[test]
+fn verifier_list_is_capped() {
+    let mut setup = Setup::new();
+    for _ in 0..4 {
+        setup.add_verifier();
+    }
+    let sender = setup.sender;
+    assert_error(
+        add_verifier(&mut setup, sender),
+        ErrorCode::VerifierListFull,
+    );
+}
+
+#[test]
+fn reserved_transfer_cannot_be_claimed_by_attestation() {
+    let mut attested = Attested::with(Setup::new(), commit_options());
+    let setup = &mut attested.setup;
+    let commitment = claim_commitment(CLAIM_CODE.as_bytes(), &setup.recipient, &[5; 32]);
+    let commit = CommitClaim::new(
+        &setup.transfer(&attested.transfer),
+        commit_keypair(CLAIM_CODE).pubkey(),
+        commitment,
+    );
+    setup.svm.process_instruction(commit.instruction()).unwrap();
+
+    assert_error(attested.claim(), ErrorCode::ClaimReserved);
+}
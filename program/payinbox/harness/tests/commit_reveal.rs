@@ -0,0 +1,166 @@
+//! Claims by commitment: `commit_claim` reserves, `reveal_claim` pays out.
+
+mod common;
+
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_spl::associated_token::get_associated_token_address;
+use payinbox::{claim_commitment, ClaimCommitted, ErrorCode, Inbox, TransferStatus};
+use payinbox_client::hash::commit_keypair;
+use payinbox_client::instructions::{CommitClaim, RevealClaim, SetClaimPasskey};
+use solana_signer::Signer;
+
+use common::*;
+
+const SALT: [u8; 32] = [5; 32];
+/// `CLAIM_RESERVATION_SECONDS`
+const RESERVATION_SECONDS: i64 = 300;
+
+fn commit_ix(setup: &Setup, transfer: &Pubkey, committer: Pubkey) -> Instruction {
+    let commitment = claim_commitment(CLAIM_CODE.as_bytes(), &setup.recipient, &SALT);
+    CommitClaim::new(&setup.transfer(transfer), committer, commitment).instruction()
+}
+
+fn commit(setup: &mut Setup, transfer: &Pubkey) -> Result<(), ProgramError> {
+    let ix = commit_ix(setup, transfer, commit_keypair(CLAIM_CODE).pubkey());
+    setup.svm.process_instruction(ix)
+}
+
+/// Reveals the committed claim into the recipient's token account.
+fn reveal(setup: &mut Setup, transfer: &Pubkey) -> Result<(), ProgramError> {
+    let ix = RevealClaim::new(&setup.transfer(transfer), setup.recipient, CLAIM_CODE, SALT)
+        .instruction();
+    setup.svm.process_instruction(ix)
+}
+
+#[test]
+fn commit_then_reveal_pays_destination() {
+    let mut setup = Setup::new();
+    let transfer = setup.create_with(commit_options());
+    commit(&mut setup, &transfer).unwrap();
+
+    let committed = setup.svm.events::<ClaimCommitted>();
+    assert_eq!(committed.len(), 1);
+    assert_eq!(committed[0].committer, commit_keypair(CLAIM_CODE).pubkey());
+    let state = setup.transfer(&transfer);
+    assert_eq!(state.reservation_count, 1);
+    assert_eq!(
+        state.reservation_expiry,
+        setup.svm.clock().unix_timestamp + RESERVATION_SECONDS
+    );
+
+    reveal(&mut setup, &transfer).unwrap();
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Claimed);
+    let recipient_account = get_associated_token_address(&setup.recipient, &setup.mint);
+    assert_eq!(setup.svm.token_balance(&recipient_account), AMOUNT);
+}
+
+#[test]
+fn commit_must_be_signed_by_the_commit_key() {
+    let mut setup = Setup::new();
+    let transfer = setup.create_with(commit_options());
+    for committer in [
+        Pubkey::new_unique(),
+        setup.sender,
+        commit_keypair("wrong").pubkey(),
+    ] {
+        let ix = commit_ix(&setup, &transfer, committer);
+        assert_error(
+            setup.svm.process_instruction(ix),
+            ErrorCode::InvalidCommitter,
+        );
+    }
+    assert_eq!(setup.transfer(&transfer).reservation_count, 0);
+
+    // Without a commit key nobody can commit
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    assert_error(commit(&mut setup, &transfer), ErrorCode::InvalidCommitter);
+}
+
+#[test]
+fn reservation_blocks_claim_and_cancel_until_it_lapses() {
+    let mut setup = Setup::new();
+    let transfer = setup.create_with(commit_options());
+    commit(&mut setup, &transfer).unwrap();
+
+    assert_error(setup.claim(&transfer, CLAIM_CODE), ErrorCode::ClaimReserved);
+    assert_error(
+        setup.cancel(&transfer, setup.sender),
+        ErrorCode::ClaimReserved,
+    );
+    assert_error(commit(&mut setup, &transfer), ErrorCode::ClaimReserved);
+
+    setup.svm.advance_clock(RESERVATION_SECONDS);
+    assert_error(
+        reveal(&mut setup, &transfer),
+        ErrorCode::NoActiveReservation,
+    );
+    setup.cancel(&transfer, setup.sender).unwrap();
+}
+
+#[test]
+fn reservations_per_transfer_are_limited() {
+    let mut setup = Setup::new();
+    let transfer = setup.create_with(commit_options());
+    for _ in 0..3 {
+        commit(&mut setup, &transfer).unwrap();
+        setup.svm.advance_clock(RESERVATION_SECONDS);
+    }
+    assert_error(
+        commit(&mut setup, &transfer),
+        ErrorCode::ReservationLimitReached,
+    );
+    setup.claim(&transfer, CLAIM_CODE).unwrap();
+}
+
+#[test]
+fn reveal_rejects_other_destination() {
+    let mut setup = Setup::new();
+    let transfer = setup.create_with(commit_options());
+    commit(&mut setup, &transfer).unwrap();
+
+    let thief = Pubkey::new_unique();
+    let thief_account = setup.svm.create_token_account(&thief, &setup.mint, 0);
+    let ix = RevealClaim::new(&setup.transfer(&transfer), thief, CLAIM_CODE, SALT)
+        .destination_token_account(thief_account)
+        .instruction();
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::InvalidCommitment,
+    );
+    reveal(&mut setup, &transfer).unwrap();
+}
+
+#[test]
+fn passkey_is_frozen_while_reserved() {
+    let mut setup = Setup::new();
+    let transfer = setup.create_with(commit_options());
+    commit(&mut setup, &transfer).unwrap();
+
+    let set_passkey = SetClaimPasskey::new(&setup.transfer(&transfer), Some([2; 33])).instruction();
+    assert_error(
+        setup.svm.process_instruction(set_passkey),
+        ErrorCode::ClaimReserved,
+    );
+    reveal(&mut setup, &transfer).unwrap();
+}
+
+#[test]
+fn commit_honors_the_inbox_passkey() {
+    let mut setup = Setup::new();
+    let inbox = setup.open_inbox();
+    let create = setup.create_ix().options(commit_options()).with_inbox();
+    setup.svm.process_instruction(create.instruction()).unwrap();
+    let transfer = create.transfer_address();
+
+    // The inbox can't be left out to skip its passkey
+    let mut ix = commit_ix(&setup, &transfer, commit_keypair(CLAIM_CODE).pubkey());
+    ix.accounts[2].pubkey = payinbox::ID;
+    assert_error(setup.svm.process_instruction(ix), ErrorCode::InboxRequired);
+
+    let mut state: Inbox = setup.svm.anchor_account(&inbox).unwrap();
+    state.passkey = Some([3; 33]);
+    write_account(&mut setup.svm, &inbox, &state);
+    assert_error(commit(&mut setup, &transfer), ErrorCode::InvalidClaimMode);
+}
@@ -0,0 +1,245 @@
+//! Shared fixture: a funded sender, a recipient with an empty token
+//! account, and a mint.
+
+#![allow(dead_code)]
+
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, AccountSerialize, InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token::state::AccountState;
+use payinbox::{ErrorCode, TransferAccount, TransferOptions};
+use payinbox_client::hash::{claim_code_hash, commit_keypair};
+use payinbox_client::instructions::{
+    CancelTransfer, ClaimTransfer, CreateTransfer, ReclaimExpired,
+};
+use payinbox_client::pda;
+use payinbox_harness::{ed25519_instruction, Svm};
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+pub const AMOUNT: u64 = 1_000_000;
+pub const SENDER_BALANCE: u64 = 10 * AMOUNT;
+pub const CLAIM_CODE: &str = "correct-horse-battery-staple";
+pub const EXPIRY_HOURS: i64 = 72;
+pub const EMAIL_HASH: [u8; 32] = [7; 32];
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+#[derive(Clone)]
+pub struct Setup {
+    pub svm: Svm,
+    pub mint: Pubkey,
+    pub sender: Pubkey,
+    pub recipient: Pubkey,
+}
+
+impl Setup {
+    pub fn new() -> Self {
+        let mut svm = Svm::new();
+        let mint = svm.create_mint(&Pubkey::new_unique(), 6);
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        svm.airdrop(&sender, 10 * LAMPORTS_PER_SOL);
+        svm.airdrop(&recipient, LAMPORTS_PER_SOL);
+        svm.create_token_account(&sender, &mint, SENDER_BALANCE);
+        svm.create_token_account(&recipient, &mint, 0);
+        Self {
+            svm,
+            mint,
+            sender,
+            recipient,
+        }
+    }
+
+    /// `create_transfer` of `AMOUNT` to `EMAIL_HASH`, locked by `CLAIM_CODE`.
+    pub fn create_ix(&self) -> CreateTransfer {
+        self.create_ix_for(AMOUNT, EXPIRY_HOURS)
+    }
+
+    pub fn create_ix_for(&self, amount: u64, expiry_hours: i64) -> CreateTransfer {
+        CreateTransfer::new(
+            self.sender,
+            self.mint,
+            EMAIL_HASH,
+            claim_code_hash(CLAIM_CODE),
+            amount,
+            expiry_hours,
+        )
+    }
+
+    /// Creates the default transfer and returns its address.
+    pub fn create(&mut self) -> Pubkey {
+        self.create_with(TransferOptions::default())
+    }
+
+    pub fn create_with(&mut self, options: TransferOptions) -> Pubkey {
+        let create = self.create_ix().options(options);
+        self.svm
+            .process_instruction(create.instruction())
+            .expect("create_transfer");
+        create.transfer_address()
+    }
+
+    pub fn transfer(&self, address: &Pubkey) -> TransferAccount {
+        self.svm
+            .anchor_account(address)
+            .expect("transfer account exists")
+    }
+
+    pub fn claim(&mut self, transfer: &Pubkey, code: &str) -> Result<(), ProgramError> {
+        let ix = ClaimTransfer::new(&self.transfer(transfer), self.recipient, code).instruction();
+        self.svm.process_instruction(ix)
+    }
+
+    pub fn cancel(&mut self, transfer: &Pubkey, authority: Pubkey) -> Result<(), ProgramError> {
+        let ix = CancelTransfer::new(&self.transfer(transfer), authority).instruction();
+        self.svm.process_instruction(ix)
+    }
+
+    pub fn reclaim(&mut self, transfer: &Pubkey, cranker: Pubkey) -> Result<(), ProgramError> {
+        let ix = ReclaimExpired::new(&self.transfer(transfer), cranker).instruction();
+        self.svm.process_instruction(ix)
+    }
+
+    /// Unix timestamp at which the transfer expires.
+    pub fn expiry(&self, transfer: &Pubkey) -> i64 {
+        self.transfer(transfer).expiry
+    }
+
+    /// Opens the inbox for `EMAIL_HASH`, paid for by the sender.
+    pub fn open_inbox(&mut self) -> Pubkey {
+        let inbox = pda::inbox_address(&EMAIL_HASH);
+        let ix = instruction(
+            payinbox::accounts::OpenInbox {
+                inbox,
+                payer: self.sender,
+                system_program: system_program::ID,
+            },
+            payinbox::instruction::OpenInbox {
+                email_hash: EMAIL_HASH,
+            },
+        );
+        self.svm.process_instruction(ix).expect("open_inbox");
+        inbox
+    }
+
+    /// Creates the program config with the sender as admin, unless it
+    /// exists already.
+    pub fn initialize_config(&mut self) -> Pubkey {
+        let config = pda::config_address();
+        if self.svm.account(&config).is_none() {
+            let ix = instruction(
+                payinbox::accounts::InitializeConfig {
+                    config,
+                    admin: self.sender,
+                    system_program: system_program::ID,
+                },
+                payinbox::instruction::InitializeConfig {},
+            );
+            self.svm.process_instruction(ix).expect("initialize_config");
+        }
+        config
+    }
+
+    /// Registers a new verifier, creating the config (with the sender as
+    /// admin) on first use.
+    pub fn add_verifier(&mut self) -> Keypair {
+        let config = self.initialize_config();
+        let verifier = Keypair::new();
+        let ix = instruction(
+            payinbox::accounts::UpdateConfig {
+                config,
+                admin: self.sender,
+                event_authority: pda::event_authority_address(),
+                program: payinbox::ID,
+            },
+            payinbox::instruction::AddVerifier {
+                verifier: verifier.pubkey(),
+            },
+        );
+        self.svm.process_instruction(ix).expect("add_verifier");
+        verifier
+    }
+
+    /// Binds the inbox for `EMAIL_HASH` to `wallet` with an attestation
+    /// by `verifier`, valid for an hour.
+    pub fn bind_inbox(&mut self, verifier: &Keypair, wallet: Pubkey) -> Result<(), ProgramError> {
+        let expires_at = self.svm.clock().unix_timestamp + 3600;
+        let message = payinbox::bind_attestation_message(&EMAIL_HASH, &wallet, expires_at);
+        let ix = instruction(
+            payinbox::accounts::BindInbox {
+                inbox: pda::inbox_address(&EMAIL_HASH),
+                config: pda::config_address(),
+                attestation_record: pda::attestation_record_address(&message),
+                wallet,
+                instructions: sysvar::instructions::ID,
+                system_program: system_program::ID,
+                event_authority: pda::event_authority_address(),
+                program: payinbox::ID,
+            },
+            payinbox::instruction::BindInbox { expires_at },
+        );
+        self.svm
+            .process_transaction(&[ed25519_instruction(verifier, &message), ix])
+    }
+}
+
+/// Options that let `CLAIM_CODE` be claimed by commitment.
+pub fn commit_options() -> TransferOptions {
+    TransferOptions {
+        commit_key: Some(commit_keypair(CLAIM_CODE).pubkey()),
+        ..TransferOptions::default()
+    }
+}
+
+/// A payinbox instruction the client has no builder for.
+pub fn instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: payinbox::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// Overwrites the Anchor account at `address` with `state`, e.g. to set up
+/// what only an attestation could otherwise produce.
+pub fn write_account<T: AccountSerialize>(svm: &mut Svm, address: &Pubkey, state: &T) {
+    let mut account = svm.account(address).expect("account exists");
+    let mut data = Vec::new();
+    state.try_serialize(&mut data).expect("account serializes");
+    account.data[..data.len()].copy_from_slice(&data);
+    svm.set_account(*address, account);
+}
+
+/// Asserts that `result` failed with the program's `error`.
+#[track_caller]
+pub fn assert_error(result: Result<(), ProgramError>, error: ErrorCode) {
+    assert_eq!(
+        result,
+        Err(ProgramError::Custom(u32::from(error))),
+        "expected {}",
+        error.name()
+    );
+}
+
+/// Asserts that `result` failed with Anchor's own `error` (account
+/// validation before any program check runs).
+#[track_caller]
+pub fn assert_anchor_error(result: Result<(), ProgramError>, error: anchor_lang::error::ErrorCode) {
+    assert_eq!(
+        result,
+        Err(ProgramError::Custom(u32::from(error))),
+        "expected {}",
+        error.name()
+    );
+}
+
+/// Freezes the token account at `address`.
+pub fn freeze(svm: &mut Svm, address: &Pubkey) {
+    let mut state = svm.token_account(address).expect("token account exists");
+    state.state = AccountState::Frozen;
+    let mut account = svm.account(address).unwrap();
+    state.pack_into_slice(&mut account.data);
+    svm.set_account(*address, account);
+}
@@ -0,0 +1,303 @@
+//! Claims with a zk-DKIM proof: the verifying key, the registered DKIM
+//! keys of a domain, and the public inputs binding a proof to one email
+//! and one destination.
+
+mod common;
+
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token;
+use payinbox::{claim_commitment, ErrorCode, TransferClaimed, TransferStatus};
+use payinbox_client::hash::{claim_code_hash, commit_keypair};
+use payinbox_client::instructions::{CommitClaim, CreateTransfer};
+use payinbox_client::pda;
+use payinbox_harness::dkim::{self as fixture, destination, DOMAIN_HASH, KEY_HASH};
+use solana_signer::Signer;
+
+use common::*;
+
+/// The fixture's verifying key and DKIM key registered, the proven email
+/// owed a transfer, and the proof's destination holding a token account.
+struct Dkim {
+    setup: Setup,
+    transfer: Pubkey,
+}
+
+impl Dkim {
+    fn new() -> Self {
+        Self::with(Default::default())
+    }
+
+    fn with(options: payinbox::TransferOptions) -> Self {
+        let mut setup = Setup::new();
+        set_verifying_key(&mut setup);
+        set_ic(&mut setup, fixture::ic()).unwrap();
+        update_key(&mut setup, KEY_HASH, true).unwrap();
+        let transfer = create_for(&mut setup, fixture::EMAIL_HASH, options);
+        let mint = setup.mint;
+        setup.svm.create_token_account(&destination(), &mint, 0);
+        Self { setup, transfer }
+    }
+
+    /// `claim_with_dkim_proof` of the fixture's proof into `destination`.
+    fn claim_ix(
+        &self,
+        destination: Pubkey,
+        domain_hash: [u8; 32],
+        key_hash: [u8; 32],
+    ) -> Instruction {
+        claim_ix(
+            &self.setup,
+            &self.transfer,
+            destination,
+            domain_hash,
+            key_hash,
+        )
+    }
+
+    fn claim(&mut self) -> Result<(), ProgramError> {
+        let ix = self.claim_ix(destination(), DOMAIN_HASH, KEY_HASH);
+        self.setup.svm.process_instruction(ix)
+    }
+}
+
+/// Creates the default transfer, but to `email_hash`.
+fn create_for(
+    setup: &mut Setup,
+    email_hash: [u8; 32],
+    options: payinbox::TransferOptions,
+) -> Pubkey {
+    let create = CreateTransfer::new(
+        setup.sender,
+        setup.mint,
+        email_hash,
+        claim_code_hash(CLAIM_CODE),
+        AMOUNT,
+        EXPIRY_HOURS,
+    )
+    .options(options);
+    setup
+        .svm
+        .process_instruction(create.instruction())
+        .expect("create_transfer");
+    create.transfer_address()
+}
+
+/// Writes the fixture's fixed curve points, clearing the IC points.
+fn set_verifying_key(setup: &mut Setup) {
+    let config = setup.initialize_config();
+    let ix = instruction(
+        payinbox::accounts::SetDkimVerifyingKey {
+            config,
+            dkim_verifier: pda::dkim_verifier_address(),
+            admin: setup.sender,
+            system_program: system_program::ID,
+        },
+        fixture::verifying_key(),
+    );
+    setup
+        .svm
+        .process_instruction(ix)
+        .expect("set_dkim_verifying_key");
+}
+
+fn set_ic(setup: &mut Setup, ic: Vec<[u8; 64]>) -> Result<(), ProgramError> {
+    let ix = instruction(
+        payinbox::accounts::SetDkimVerifyingKeyIc {
+            config: pda::config_address(),
+            dkim_verifier: pda::dkim_verifier_address(),
+            admin: setup.sender,
+        },
+        payinbox::instruction::SetDkimVerifyingKeyIc { ic },
+    );
+    setup.svm.process_instruction(ix)
+}
+
+/// Adds (or removes) `key_hash` as a DKIM key of `DOMAIN_HASH`.
+fn update_key(setup: &mut Setup, key_hash: [u8; 32], add: bool) -> Result<(), ProgramError> {
+    let accounts = payinbox::accounts::UpdateDkimKeys {
+        config: pda::config_address(),
+        dkim_domain: pda::dkim_domain_address(&DOMAIN_HASH),
+        admin: setup.sender,
+        system_program: system_program::ID,
+        event_authority: pda::event_authority_address(),
+        program: payinbox::ID,
+    };
+    let ix = if add {
+        instruction(
+            accounts,
+            payinbox::instruction::AddDkimKey {
+                domain_hash: DOMAIN_HASH,
+                key_hash,
+            },
+        )
+    } else {
+        instruction(
+            accounts,
+            payinbox::instruction::RemoveDkimKey {
+                domain_hash: DOMAIN_HASH,
+                key_hash,
+            },
+        )
+    };
+    setup.svm.process_instruction(ix)
+}
+
+fn claim_ix(
+    setup: &Setup,
+    transfer: &Pubkey,
+    destination: Pubkey,
+    domain_hash: [u8; 32],
+    key_hash: [u8; 32],
+) -> Instruction {
+    let state = setup.transfer(transfer);
+    instruction(
+        payinbox::accounts::ClaimWithDkimProof {
+            transfer: *transfer,
+            dkim_verifier: pda::dkim_verifier_address(),
+            dkim_domain: pda::dkim_domain_address(&domain_hash),
+            destination,
+            destination_token_account: get_associated_token_address(
+                &destination,
+                &state.token_mint,
+            ),
+            escrow_token_account: pda::escrow_address(transfer),
+            sender: state.sender,
+            token_program: token::ID,
+            inbox: None,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::ClaimWithDkimProof {
+            proof: fixture::proof(),
+            domain_hash,
+            dkim_key_hash: key_hash,
+        },
+    )
+}
+
+#[test]
+fn proof_pays_the_destination() {
+    let mut dkim = Dkim::new();
+    dkim.claim().unwrap();
+
+    let setup = &dkim.setup;
+    assert_eq!(
+        setup.transfer(&dkim.transfer).status,
+        TransferStatus::Claimed
+    );
+    let destination_account = get_associated_token_address(&destination(), &setup.mint);
+    assert_eq!(setup.svm.token_balance(&destination_account), AMOUNT);
+    assert!(setup
+        .svm
+        .account(&pda::escrow_address(&dkim.transfer))
+        .is_none());
+    let claimed = setup.svm.events::<TransferClaimed>();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].recipient, destination());
+}
+
+#[test]
+fn proof_is_bound_to_its_destination() {
+    let mut dkim = Dkim::new();
+    let recipient = dkim.setup.recipient;
+    let ix = dkim.claim_ix(recipient, DOMAIN_HASH, KEY_HASH);
+    assert_error(
+        dkim.setup.svm.process_instruction(ix),
+        ErrorCode::InvalidProof,
+    );
+    dkim.claim().unwrap();
+}
+
+#[test]
+fn proof_is_bound_to_its_email() {
+    let mut dkim = Dkim::new();
+    // Same sender, same destination, but owed to another email
+    let other = create_for(&mut dkim.setup, EMAIL_HASH, Default::default());
+    let ix = claim_ix(&dkim.setup, &other, destination(), DOMAIN_HASH, KEY_HASH);
+    assert_error(
+        dkim.setup.svm.process_instruction(ix),
+        ErrorCode::InvalidProof,
+    );
+    assert_eq!(dkim.setup.transfer(&other).status, TransferStatus::Active);
+}
+
+#[test]
+fn tampered_proof_is_rejected() {
+    let mut dkim = Dkim::new();
+    let mut ix = dkim.claim_ix(destination(), DOMAIN_HASH, KEY_HASH);
+    // Proof A starts right after the discriminator
+    ix.data[8 + 63] ^= 1;
+    assert!(dkim.setup.svm.process_instruction(ix).is_err());
+    dkim.claim().unwrap();
+}
+
+#[test]
+fn dkim_key_must_be_registered_for_the_domain() {
+    let mut dkim = Dkim::new();
+    let ix = dkim.claim_ix(destination(), DOMAIN_HASH, [0x44; 32]);
+    assert_error(
+        dkim.setup.svm.process_instruction(ix),
+        ErrorCode::UnknownDkimKey,
+    );
+
+    // A key the proof was made with, since revoked
+    update_key(&mut dkim.setup, KEY_HASH, false).unwrap();
+    assert_error(dkim.claim(), ErrorCode::UnknownDkimKey);
+}
+
+#[test]
+fn domain_holds_a_limited_number_of_keys() {
+    let mut dkim = Dkim::new();
+    for byte in 1..=3 {
+        update_key(&mut dkim.setup, [byte; 32], true).unwrap();
+    }
+    // Adding a registered key again is a no-op
+    update_key(&mut dkim.setup, KEY_HASH, true).unwrap();
+    assert_error(
+        update_key(&mut dkim.setup, [4; 32], true),
+        ErrorCode::DkimKeyListFull,
+    );
+    dkim.claim().unwrap();
+}
+
+#[test]
+fn unknown_domain_is_rejected() {
+    let mut dkim = Dkim::new();
+    let ix = dkim.claim_ix(destination(), [0x44; 32], KEY_HASH);
+    assert_anchor_error(
+        dkim.setup.svm.process_instruction(ix),
+        anchor_lang::error::ErrorCode::AccountNotInitialized,
+    );
+}
+
+#[test]
+fn verifying_key_needs_every_ic_point() {
+    let mut dkim = Dkim::new();
+    let mut ic = fixture::ic();
+    ic.pop();
+    assert_error(set_ic(&mut dkim.setup, ic), ErrorCode::DkimVerifierNotReady);
+
+    // Rewriting the fixed points clears the IC points until they're set again
+    set_verifying_key(&mut dkim.setup);
+    assert_error(dkim.claim(), ErrorCode::DkimVerifierNotReady);
+    set_ic(&mut dkim.setup, fixture::ic()).unwrap();
+    dkim.claim().unwrap();
+}
+
+#[test]
+fn reserved_transfer_cannot_be_claimed_by_proof() {
+    let mut dkim = Dkim::with(commit_options());
+    let setup = &mut dkim.setup;
+    let commitment = claim_commitment(CLAIM_CODE.as_bytes(), &setup.recipient, &[5; 32]);
+    let commit = CommitClaim::new(
+        &setup.transfer(&dkim.transfer),
+        commit_keypair(CLAIM_CODE).pubkey(),
+        commitment,
+    );
+    setup.svm.process_instruction(commit.instruction()).unwrap();
+
+    assert_error(dkim.claim(), ErrorCode::ClaimReserved);
+}
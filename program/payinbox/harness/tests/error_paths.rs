@@ -0,0 +1,404 @@
+//! Every `ErrorCode` that create, claim, cancel and reclaim can raise, and
+//! who may change the cancel authority.
+
+mod common;
+
+use anchor_lang::error::ErrorCode as AnchorErrorCode;
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_spl::associated_token::get_associated_token_address;
+use payinbox::{ErrorCode, TransferOptions};
+use payinbox_client::instructions::{ClaimTransfer, ReclaimExpired};
+use payinbox_client::pda;
+
+use common::*;
+
+/// Replaces account `index` of `ix` with `account`.
+fn with_account(mut ix: Instruction, index: usize, account: Pubkey) -> Instruction {
+    ix.accounts[index].pubkey = account;
+    ix
+}
+
+// ============================================================================
+// create_transfer
+// ============================================================================
+
+#[test]
+fn create_rejects_zero_amount() {
+    let mut setup = Setup::new();
+    let ix = setup.create_ix_for(0, EXPIRY_HOURS).instruction();
+    assert_error(setup.svm.process_instruction(ix), ErrorCode::InvalidAmount);
+}
+
+#[test]
+fn create_rejects_expiry_out_of_range() {
+    let mut setup = Setup::new();
+    for hours in [0, 169, -1, i64::MAX] {
+        let ix = setup.create_ix_for(AMOUNT, hours).instruction();
+        assert_error(setup.svm.process_instruction(ix), ErrorCode::InvalidExpiry);
+    }
+}
+
+#[test]
+fn create_rejects_amount_above_balance() {
+    let mut setup = Setup::new();
+    let ix = setup
+        .create_ix_for(SENDER_BALANCE + 1, EXPIRY_HOURS)
+        .instruction();
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::InsufficientFunds,
+    );
+}
+
+#[test]
+fn create_rejects_foreign_token_account() {
+    let mut setup = Setup::new();
+    let stranger = Pubkey::new_unique();
+    let account = setup
+        .svm
+        .create_token_account(&stranger, &setup.mint, SENDER_BALANCE);
+    let ix = setup
+        .create_ix()
+        .sender_token_account(account)
+        .instruction();
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::InvalidTokenAccount,
+    );
+}
+
+#[test]
+fn create_rejects_token_account_of_other_mint() {
+    let mut setup = Setup::new();
+    let other_mint = setup.svm.create_mint(&Pubkey::new_unique(), 6);
+    let account = setup
+        .svm
+        .create_token_account(&setup.sender, &other_mint, SENDER_BALANCE);
+    let ix = setup
+        .create_ix()
+        .sender_token_account(account)
+        .instruction();
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::InvalidTokenMint,
+    );
+}
+
+#[test]
+fn create_rejects_crank_bounty_above_maximum() {
+    let mut setup = Setup::new();
+    let ix = setup
+        .create_ix()
+        .options(TransferOptions {
+            crank_bounty_bps: 501,
+            ..TransferOptions::default()
+        })
+        .instruction();
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::InvalidCrankBounty,
+    );
+}
+
+#[test]
+fn create_rejects_expiry_past_the_end_of_time() {
+    let mut setup = Setup::new();
+    setup.svm.set_unix_timestamp(i64::MAX - 60);
+    let ix = setup.create_ix().instruction();
+    assert_error(setup.svm.process_instruction(ix), ErrorCode::Overflow);
+}
+
+// ============================================================================
+// claim_transfer
+// ============================================================================
+
+#[test]
+fn claim_rejects_wrong_code() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    assert_error(
+        setup.claim(&transfer, "incorrect-horse"),
+        ErrorCode::InvalidClaimCode,
+    );
+}
+
+#[test]
+fn claim_rejects_oversized_code() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    assert_error(
+        setup.claim(&transfer, &"x".repeat(257)),
+        ErrorCode::ClaimCodeTooLong,
+    );
+}
+
+#[test]
+fn claim_rejects_expired_transfer() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    assert_error(
+        setup.claim(&transfer, CLAIM_CODE),
+        ErrorCode::TransferExpired,
+    );
+}
+
+#[test]
+fn claim_rejects_second_claim() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    setup.claim(&transfer, CLAIM_CODE).unwrap();
+    // The escrow was closed by the first claim, so account validation
+    // fails before the transfer's status is looked at.
+    assert_anchor_error(
+        setup.claim(&transfer, CLAIM_CODE),
+        AnchorErrorCode::AccountNotInitialized,
+    );
+    let recipient_ata = get_associated_token_address(&setup.recipient, &setup.mint);
+    assert_eq!(setup.svm.token_balance(&recipient_ata), AMOUNT);
+}
+
+#[test]
+fn claim_rejects_reclaimed_transfer() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    // A frozen refund account makes reclaim hand over the escrow instead
+    // of closing it, so the claim gets as far as the status check.
+    let sender_ata = get_associated_token_address(&setup.sender, &setup.mint);
+    freeze(&mut setup.svm, &sender_ata);
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    setup.reclaim(&transfer, setup.recipient).unwrap();
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer) - 1);
+    assert_error(
+        setup.claim(&transfer, CLAIM_CODE),
+        ErrorCode::InvalidTransferState,
+    );
+}
+
+#[test]
+fn claim_rejects_token_account_of_other_mint() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let other_mint = setup.svm.create_mint(&Pubkey::new_unique(), 6);
+    let account = setup
+        .svm
+        .create_token_account(&setup.recipient, &other_mint, 0);
+    let ix = ClaimTransfer::new(&setup.transfer(&transfer), setup.recipient, CLAIM_CODE)
+        .recipient_token_account(account)
+        .instruction();
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::InvalidTokenMint,
+    );
+}
+
+#[test]
+fn claim_rejects_token_account_of_other_owner() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let account = setup
+        .svm
+        .create_token_account(&Pubkey::new_unique(), &setup.mint, 0);
+    let ix = ClaimTransfer::new(&setup.transfer(&transfer), setup.recipient, CLAIM_CODE)
+        .recipient_token_account(account)
+        .instruction();
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::InvalidTokenAccount,
+    );
+}
+
+#[test]
+fn claim_rejects_wrong_sender() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let ix =
+        ClaimTransfer::new(&setup.transfer(&transfer), setup.recipient, CLAIM_CODE).instruction();
+    // transfer, recipient, recipient_token_account, escrow, sender, ...
+    let ix = with_account(ix, 4, Pubkey::new_unique());
+    assert_error(setup.svm.process_instruction(ix), ErrorCode::InvalidSender);
+}
+
+// ============================================================================
+// cancel_transfer
+// ============================================================================
+
+#[test]
+fn cancel_rejects_stranger() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    assert_error(
+        setup.cancel(&transfer, Pubkey::new_unique()),
+        ErrorCode::Unauthorized,
+    );
+}
+
+#[test]
+fn cancel_rejects_claimed_transfer() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    setup.claim(&transfer, CLAIM_CODE).unwrap();
+    assert_anchor_error(
+        setup.cancel(&transfer, setup.sender),
+        AnchorErrorCode::AccountNotInitialized,
+    );
+}
+
+#[test]
+fn cancel_rejects_irrevocable_transfer() {
+    let mut setup = Setup::new();
+    let transfer = setup.create_with(TransferOptions {
+        irrevocable: true,
+        ..TransferOptions::default()
+    });
+    assert_error(
+        setup.cancel(&transfer, setup.sender),
+        ErrorCode::IrrevocableTransfer,
+    );
+}
+
+// ============================================================================
+// reclaim_expired
+// ============================================================================
+
+#[test]
+fn reclaim_rejects_unexpired_transfer() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer) - 1);
+    assert_error(
+        setup.reclaim(&transfer, setup.recipient),
+        ErrorCode::NotExpired,
+    );
+}
+
+#[test]
+fn reclaim_rejects_cancelled_transfer() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    setup.cancel(&transfer, setup.sender).unwrap();
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    assert_anchor_error(
+        setup.reclaim(&transfer, setup.recipient),
+        AnchorErrorCode::AccountNotInitialized,
+    );
+}
+
+#[test]
+fn reclaim_rejects_wrong_refund_owner() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    let ix = ReclaimExpired::new(&setup.transfer(&transfer), setup.recipient).instruction();
+    // transfer, refund_token_account, refund_owner, ...
+    let ix = with_account(ix, 2, setup.recipient);
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::InvalidTokenAccount,
+    );
+}
+
+#[test]
+fn reclaim_rejects_wrong_mint() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    let other_mint = setup.svm.create_mint(&Pubkey::new_unique(), 6);
+    let ix = ReclaimExpired::new(&setup.transfer(&transfer), setup.recipient).instruction();
+    // transfer, refund_token_account, refund_owner, token_mint, ...
+    let ix = with_account(ix, 3, other_mint);
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::InvalidTokenMint,
+    );
+}
+
+#[test]
+fn reclaim_rejects_wrong_original_sender() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    let ix = ReclaimExpired::new(&setup.transfer(&transfer), setup.recipient).instruction();
+    // ..., token_mint, escrow_token_account, original_sender, ...
+    let ix = with_account(ix, 5, Pubkey::new_unique());
+    assert_error(setup.svm.process_instruction(ix), ErrorCode::InvalidSender);
+}
+
+#[test]
+fn reclaim_rejects_escrow_of_other_transfer() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let other = setup.svm.create_token_account(&transfer, &setup.mint, 0);
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    let ix = ReclaimExpired::new(&setup.transfer(&transfer), setup.recipient).instruction();
+    let ix = with_account(ix, 4, other);
+    // The escrow's seeds pin its address before `InvalidEscrowAccount` is
+    // checked.
+    assert_anchor_error(
+        setup.svm.process_instruction(ix),
+        AnchorErrorCode::ConstraintSeeds,
+    );
+}
+
+#[test]
+fn reclaim_rejects_escrow_the_transfer_does_not_record() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    // The escrow PDA is right, but the transfer points somewhere else
+    let mut state = setup.transfer(&transfer);
+    state.escrow_token_account = Pubkey::new_unique();
+    write_account(&mut setup.svm, &transfer, &state);
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    let ix = ReclaimExpired::new(&state, setup.recipient).instruction();
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::InvalidEscrowAccount,
+    );
+}
+
+// ============================================================================
+// set_cancel_authority
+// ============================================================================
+
+fn set_cancel_authority(
+    setup: &mut Setup,
+    transfer: &Pubkey,
+    signer: Pubkey,
+    cancel_authority: Option<Pubkey>,
+) -> Result<(), ProgramError> {
+    let ix = instruction(
+        payinbox::accounts::SetCancelAuthority {
+            transfer: *transfer,
+            sender: signer,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::SetCancelAuthority { cancel_authority },
+    );
+    setup.svm.process_instruction(ix)
+}
+
+#[test]
+fn set_cancel_authority_rejects_everyone_but_the_sender() {
+    let mut setup = Setup::new();
+    let authority = Pubkey::new_unique();
+    let transfer = setup.create_with(TransferOptions {
+        cancel_authority: Some(authority),
+        ..TransferOptions::default()
+    });
+
+    // The cancel authority may cancel, but not hand that power on
+    for signer in [Pubkey::new_unique(), authority, setup.recipient] {
+        assert_error(
+            set_cancel_authority(&mut setup, &transfer, signer, Some(signer)),
+            ErrorCode::Unauthorized,
+        );
+    }
+    assert_eq!(setup.transfer(&transfer).cancel_authority, Some(authority));
+
+    let sender = setup.sender;
+    set_cancel_authority(&mut setup, &transfer, sender, None).unwrap();
+    assert_eq!(setup.transfer(&transfer).cancel_authority, None);
+    assert_error(setup.cancel(&transfer, authority), ErrorCode::Unauthorized);
+}
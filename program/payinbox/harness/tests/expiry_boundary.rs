@@ -0,0 +1,112 @@
+//! Claim and reclaim around `transfer.expiry`: claim is allowed strictly
+//! before it and reclaim from it on, so at every instant exactly one of
+//! them succeeds.
+
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use payinbox::ErrorCode;
+
+use common::*;
+
+const MIN_EXPIRY_HOURS: i64 = 1;
+const MAX_EXPIRY_HOURS: i64 = 168;
+
+/// Creates a transfer expiring `hours` from now and returns it with its
+/// expiry timestamp.
+fn create_expiring_in(setup: &mut Setup, hours: i64) -> (Pubkey, i64) {
+    let create = setup.create_ix_for(AMOUNT, hours);
+    setup.svm.process_instruction(create.instruction()).unwrap();
+    let transfer = create.transfer_address();
+    (transfer, setup.expiry(&transfer))
+}
+
+#[test]
+fn expiry_is_creation_time_plus_hours() {
+    for hours in [MIN_EXPIRY_HOURS, EXPIRY_HOURS, MAX_EXPIRY_HOURS] {
+        let mut setup = Setup::new();
+        let now = setup.svm.clock().unix_timestamp;
+        let (_, expiry) = create_expiring_in(&mut setup, hours);
+        assert_eq!(expiry, now + hours * 3600);
+    }
+}
+
+#[test]
+fn claim_succeeds_one_second_before_expiry() {
+    let mut setup = Setup::new();
+    let (transfer, expiry) = create_expiring_in(&mut setup, MIN_EXPIRY_HOURS);
+    setup.svm.set_unix_timestamp(expiry - 1);
+    setup.claim(&transfer, CLAIM_CODE).unwrap();
+}
+
+#[test]
+fn claim_fails_at_expiry() {
+    let mut setup = Setup::new();
+    let (transfer, expiry) = create_expiring_in(&mut setup, MIN_EXPIRY_HOURS);
+    setup.svm.set_unix_timestamp(expiry);
+    assert_error(
+        setup.claim(&transfer, CLAIM_CODE),
+        ErrorCode::TransferExpired,
+    );
+}
+
+#[test]
+fn reclaim_fails_one_second_before_expiry() {
+    let mut setup = Setup::new();
+    let (transfer, expiry) = create_expiring_in(&mut setup, MIN_EXPIRY_HOURS);
+    setup.svm.set_unix_timestamp(expiry - 1);
+    assert_error(
+        setup.reclaim(&transfer, setup.recipient),
+        ErrorCode::NotExpired,
+    );
+}
+
+#[test]
+fn reclaim_succeeds_at_expiry() {
+    let mut setup = Setup::new();
+    let (transfer, expiry) = create_expiring_in(&mut setup, MIN_EXPIRY_HOURS);
+    setup.svm.set_unix_timestamp(expiry);
+    setup.reclaim(&transfer, setup.recipient).unwrap();
+}
+
+#[test]
+fn claim_and_reclaim_partition_time() {
+    for hours in [MIN_EXPIRY_HOURS, MAX_EXPIRY_HOURS] {
+        let mut setup = Setup::new();
+        let created_at = setup.svm.clock().unix_timestamp;
+        let (transfer, expiry) = create_expiring_in(&mut setup, hours);
+
+        let instants = [created_at, created_at + 1, expiry - 2, expiry - 1]
+            .into_iter()
+            .chain([expiry, expiry + 1, expiry + 3600, i64::MAX]);
+        for now in instants {
+            let mut claim = setup.clone();
+            claim.svm.set_unix_timestamp(now);
+            let mut reclaim = claim.clone();
+            let claimed = claim.claim(&transfer, CLAIM_CODE);
+            let reclaimed = reclaim.reclaim(&transfer, setup.recipient);
+
+            if now < expiry {
+                assert_eq!(claimed, Ok(()), "claim at expiry{:+}", now - expiry);
+                assert_error(reclaimed, ErrorCode::NotExpired);
+            } else {
+                assert_error(claimed, ErrorCode::TransferExpired);
+                assert_eq!(reclaimed, Ok(()), "reclaim at expiry{:+}", now - expiry);
+            }
+        }
+    }
+}
+
+#[test]
+fn create_accepts_only_one_to_168_hours() {
+    for (hours, accepted) in [(0, false), (1, true), (168, true), (169, false)] {
+        let mut setup = Setup::new();
+        let ix = setup.create_ix_for(AMOUNT, hours).instruction();
+        let result = setup.svm.process_instruction(ix);
+        if accepted {
+            assert_eq!(result, Ok(()), "{hours} hours");
+        } else {
+            assert_error(result, ErrorCode::InvalidExpiry);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Group transfers: contributions before the deadline, one claim for the
+//! total, or a pro-rata refund per contributor once expired.
+
+mod common;
+
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::{self, spl_token};
+use payinbox::{ContributionRefunded, ErrorCode, GroupTransferRefunded, TransferStatus};
+use payinbox_client::hash::claim_code_hash;
+use payinbox_client::pda;
+
+use common::*;
+
+const CONTRIBUTION_HOURS: i64 = 24;
+
+/// A group transfer of `AMOUNT` from the sender, plus a second contributor
+/// with tokens to spare.
+struct Group {
+    setup: Setup,
+    transfer: Pubkey,
+    contributor: Pubkey,
+}
+
+/// `create_group_transfer` of `AMOUNT` from the sender to `EMAIL_HASH`.
+fn create_ix(setup: &Setup, contribution_hours: i64) -> Instruction {
+    let transfer = pda::transfer_address(&setup.sender, &EMAIL_HASH);
+    instruction(
+        payinbox::accounts::CreateGroupTransfer {
+            transfer,
+            contribution: pda::contribution_address(&transfer, &setup.sender),
+            organizer: setup.sender,
+            organizer_token_account: get_associated_token_address(&setup.sender, &setup.mint),
+            token_mint: setup.mint,
+            escrow_token_account: pda::escrow_address(&transfer),
+            token_program: token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::CreateGroupTransfer {
+            email_hash: EMAIL_HASH,
+            claim_code_hash: claim_code_hash(CLAIM_CODE),
+            amount: AMOUNT,
+            expiry_hours: EXPIRY_HOURS,
+            contribution_hours,
+        },
+    )
+}
+
+/// `contribute` of `amount` from `contributor`'s token account.
+fn contribute_ix(
+    setup: &Setup,
+    transfer: &Pubkey,
+    contributor: Pubkey,
+    amount: u64,
+) -> Instruction {
+    instruction(
+        payinbox::accounts::Contribute {
+            transfer: *transfer,
+            contribution: pda::contribution_address(transfer, &contributor),
+            contributor,
+            contributor_token_account: get_associated_token_address(&contributor, &setup.mint),
+            escrow_token_account: pda::escrow_address(transfer),
+            token_program: token::ID,
+            system_program: system_program::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::Contribute { amount },
+    )
+}
+
+impl Group {
+    fn new() -> Self {
+        let mut setup = Setup::new();
+        let transfer = pda::transfer_address(&setup.sender, &EMAIL_HASH);
+        let ix = create_ix(&setup, CONTRIBUTION_HOURS);
+        setup
+            .svm
+            .process_instruction(ix)
+            .expect("create_group_transfer");
+
+        let contributor = Pubkey::new_unique();
+        setup.svm.airdrop(&contributor, LAMPORTS_PER_SOL);
+        setup
+            .svm
+            .create_token_account(&contributor, &setup.mint, SENDER_BALANCE);
+        Self {
+            setup,
+            transfer,
+            contributor,
+        }
+    }
+
+    fn contribute(&mut self, amount: u64) -> Result<(), ProgramError> {
+        let ix = contribute_ix(&self.setup, &self.transfer, self.contributor, amount);
+        self.setup.svm.process_instruction(ix)
+    }
+
+    fn refund(&mut self, contributor: Pubkey) -> Result<(), ProgramError> {
+        let ix = instruction(
+            payinbox::accounts::RefundContribution {
+                transfer: self.transfer,
+                contribution: pda::contribution_address(&self.transfer, &contributor),
+                contributor,
+                contributor_token_account: self.token_account(&contributor),
+                escrow_token_account: pda::escrow_address(&self.transfer),
+                organizer: self.setup.sender,
+                token_program: token::ID,
+                event_authority: pda::event_authority_address(),
+                program: payinbox::ID,
+            },
+            payinbox::instruction::RefundContribution {},
+        );
+        self.setup.svm.process_instruction(ix)
+    }
+
+    fn token_account(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address(owner, &self.setup.mint)
+    }
+
+    fn balance(&self, owner: &Pubkey) -> u64 {
+        self.setup.svm.token_balance(&self.token_account(owner))
+    }
+
+    fn expire(&mut self) {
+        let expiry = self.setup.expiry(&self.transfer);
+        self.setup.svm.set_unix_timestamp(expiry);
+    }
+}
+
+#[test]
+fn contributions_add_up_and_are_claimed_together() {
+    let mut group = Group::new();
+    group.contribute(AMOUNT).unwrap();
+    group.contribute(2 * AMOUNT).unwrap();
+    assert_eq!(group.setup.transfer(&group.transfer).amount, 4 * AMOUNT);
+
+    group.setup.claim(&group.transfer, CLAIM_CODE).unwrap();
+    assert_eq!(group.balance(&group.setup.recipient), 4 * AMOUNT);
+
+    // Claimed: contributors only get their PDA rent back
+    let contribution = pda::contribution_address(&group.transfer, &group.contributor);
+    let ix = instruction(
+        payinbox::accounts::CloseContribution {
+            transfer: group.transfer,
+            contribution,
+            contributor: group.contributor,
+        },
+        payinbox::instruction::CloseContribution {},
+    );
+    group.setup.svm.process_instruction(ix).unwrap();
+    assert!(group.setup.svm.account(&contribution).is_none());
+}
+
+#[test]
+fn contribution_deadline_must_fall_within_expiry() {
+    let mut setup = Setup::new();
+    for contribution_hours in [0, EXPIRY_HOURS + 1] {
+        let ix = create_ix(&setup, contribution_hours);
+        assert_error(
+            setup.svm.process_instruction(ix),
+            ErrorCode::InvalidContributionDeadline,
+        );
+    }
+    let ix = create_ix(&setup, EXPIRY_HOURS);
+    setup.svm.process_instruction(ix).unwrap();
+}
+
+#[test]
+fn only_group_transfers_take_contributions() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let ix = contribute_ix(&setup, &transfer, setup.sender, AMOUNT);
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::NotGroupTransfer,
+    );
+    assert_eq!(setup.transfer(&transfer).amount, AMOUNT);
+}
+
+#[test]
+fn contributions_close_at_the_deadline() {
+    let mut group = Group::new();
+    group.setup.svm.advance_clock(CONTRIBUTION_HOURS * 3600);
+    assert_error(group.contribute(AMOUNT), ErrorCode::ContributionsClosed);
+}
+
+#[test]
+fn refunds_wait_for_expiry() {
+    let mut group = Group::new();
+    group.contribute(AMOUNT).unwrap();
+    let contributor = group.contributor;
+    assert_error(group.refund(contributor), ErrorCode::NotExpired);
+}
+
+#[test]
+fn refunds_share_the_escrow_pro_rata() {
+    let mut group = Group::new();
+    let (organizer, contributor) = (group.setup.sender, group.contributor);
+    group.contribute(3 * AMOUNT).unwrap();
+    // Tokens sent straight to the escrow are shared like the contributions
+    let stray = spl_token::instruction::transfer(
+        &token::ID,
+        &group.token_account(&organizer),
+        &pda::escrow_address(&group.transfer),
+        &organizer,
+        &[],
+        AMOUNT / 2,
+    )
+    .unwrap();
+    group.setup.svm.process_instruction(stray).unwrap();
+    group.expire();
+
+    let contributor_before = group.balance(&contributor);
+    group.refund(contributor).unwrap();
+    let share = 4 * AMOUNT + AMOUNT / 2;
+    assert_eq!(
+        group.balance(&contributor),
+        contributor_before + share * 3 / 4
+    );
+    let refunded = group.setup.svm.events::<ContributionRefunded>();
+    assert_eq!(refunded[0].amount, share * 3 / 4);
+    let state = group.setup.transfer(&group.transfer);
+    assert_eq!(state.amount, AMOUNT);
+    assert_eq!(state.status, TransferStatus::Active);
+    assert!(group.setup.svm.events::<GroupTransferRefunded>().is_empty());
+    assert!(group
+        .setup
+        .svm
+        .account(&pda::contribution_address(&group.transfer, &contributor))
+        .is_none());
+}
+
+#[test]
+fn last_refund_empties_escrow_and_expires_transfer() {
+    let mut group = Group::new();
+    let (organizer, contributor) = (group.setup.sender, group.contributor);
+    group.contribute(AMOUNT).unwrap();
+    group.expire();
+    group.refund(contributor).unwrap();
+
+    let escrow = pda::escrow_address(&group.transfer);
+    let escrow_rent = group.setup.svm.lamports(&escrow);
+    let organizer_tokens = group.balance(&organizer);
+    let organizer_lamports = group.setup.svm.lamports(&organizer);
+    let contribution_rent = group
+        .setup
+        .svm
+        .lamports(&pda::contribution_address(&group.transfer, &organizer));
+
+    group.refund(organizer).unwrap();
+    assert_eq!(group.balance(&organizer), organizer_tokens + AMOUNT);
+    assert!(group.setup.svm.account(&escrow).is_none());
+    assert_eq!(
+        group.setup.svm.lamports(&organizer),
+        organizer_lamports + escrow_rent + contribution_rent
+    );
+    let state = group.setup.transfer(&group.transfer);
+    assert_eq!(state.status, TransferStatus::Expired);
+    assert_eq!(state.amount, 0);
+    let ended = group.setup.svm.events::<GroupTransferRefunded>();
+    assert_eq!(ended.len(), 1);
+    assert_eq!(ended[0].transfer, group.transfer);
+    assert_eq!(ended[0].contributor, organizer);
+    assert_eq!(ended[0].amount, AMOUNT);
+}
+
+#[test]
+fn refund_requires_an_active_transfer() {
+    let mut group = Group::new();
+    group.contribute(AMOUNT).unwrap();
+    group.expire();
+    // Every way out closes the escrow, so mark the transfer by hand
+    let mut state = group.setup.transfer(&group.transfer);
+    state.status = TransferStatus::Expired;
+    write_account(&mut group.setup.svm, &group.transfer, &state);
+    let contributor = group.contributor;
+    assert_error(group.refund(contributor), ErrorCode::InvalidTransferState);
+}
+
+#[test]
+fn group_transfers_cannot_be_cancelled_or_reclaimed() {
+    let mut group = Group::new();
+    let sender = group.setup.sender;
+    assert_error(
+        group.setup.cancel(&group.transfer, sender),
+        ErrorCode::GroupTransferRefund,
+    );
+    group.expire();
+    let cranker = Pubkey::new_unique();
+    group.setup.svm.airdrop(&cranker, LAMPORTS_PER_SOL);
+    assert_error(
+        group.setup.reclaim(&group.transfer, cranker),
+        ErrorCode::GroupTransferRefund,
+    );
+    assert_eq!(
+        group.setup.transfer(&group.transfer).status,
+        TransferStatus::Active
+    );
+}
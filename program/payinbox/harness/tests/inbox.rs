@@ -0,0 +1,333 @@
+//! Inboxes: opening one, binding a wallet with a verifier attestation and
+//! sweeping the transfers it tracks.
+
+mod common;
+
+use anchor_lang::prelude::{AccountMeta, ProgramError, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token;
+use payinbox::{
+    claim_commitment, ErrorCode, Inbox, InboxBound, InboxSwept, MintTotal, TransferStatus,
+};
+use payinbox_client::hash::commit_keypair;
+use payinbox_client::instructions::{CommitClaim, SetClaimPasskey};
+use payinbox_client::pda;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+use common::*;
+
+/// A well-formed compressed secp256r1 key; nothing signs with it here.
+const PASSKEY: [u8; 33] = [2; 33];
+
+/// Creates the default transfer, tracked by the (open) inbox.
+fn create_in_inbox(setup: &mut Setup, options: payinbox::TransferOptions) -> Pubkey {
+    let create = setup.create_ix().options(options).with_inbox();
+    setup
+        .svm
+        .process_instruction(create.instruction())
+        .expect("create_transfer");
+    create.transfer_address()
+}
+
+/// `sweep_inbox` of `transfers` into `owner`'s token account.
+fn sweep_ix(setup: &Setup, owner: Pubkey, transfers: &[Pubkey]) -> Instruction {
+    let mut accounts = payinbox::accounts::SweepInbox {
+        inbox: pda::inbox_address(&EMAIL_HASH),
+        owner,
+        token_program: token::ID,
+        event_authority: pda::event_authority_address(),
+        program: payinbox::ID,
+    }
+    .to_account_metas(None);
+    for transfer in transfers {
+        let state = setup.transfer(transfer);
+        accounts.extend([
+            AccountMeta::new(*transfer, false),
+            AccountMeta::new(pda::escrow_address(transfer), false),
+            AccountMeta::new(
+                get_associated_token_address(&owner, &state.token_mint),
+                false,
+            ),
+            AccountMeta::new(state.sender, false),
+        ]);
+    }
+    Instruction {
+        program_id: payinbox::ID,
+        accounts,
+        data: payinbox::instruction::SweepInbox {}.data(),
+    }
+}
+
+fn sweep(setup: &mut Setup, transfers: &[Pubkey]) -> Result<(), ProgramError> {
+    let ix = sweep_ix(setup, setup.recipient, transfers);
+    setup.svm.process_instruction(ix)
+}
+
+/// An inbox bound to the recipient, tracking the default transfer.
+fn bound_inbox(options: payinbox::TransferOptions) -> (Setup, Pubkey) {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    let transfer = create_in_inbox(&mut setup, options);
+    let verifier = setup.add_verifier();
+    let recipient = setup.recipient;
+    setup.bind_inbox(&verifier, recipient).unwrap();
+    (setup, transfer)
+}
+
+fn inbox(setup: &Setup) -> Inbox {
+    setup
+        .svm
+        .anchor_account(&pda::inbox_address(&EMAIL_HASH))
+        .expect("inbox exists")
+}
+
+// ============================================================================
+// open_inbox
+// ============================================================================
+
+#[test]
+fn open_inbox_starts_unbound_and_empty() {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+
+    let inbox = inbox(&setup);
+    assert_eq!(inbox.email_hash, EMAIL_HASH);
+    assert!(!inbox.is_bound());
+    assert_eq!(inbox.pending_count, 0);
+    assert!(inbox.totals.is_empty());
+    assert_eq!(inbox.passkey, None);
+}
+
+#[test]
+fn inbox_counts_pending_transfers_until_settled() {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    let transfer = create_in_inbox(&mut setup, Default::default());
+
+    let state = inbox(&setup);
+    assert_eq!(state.pending_count, 1);
+    assert_eq!(
+        state.totals,
+        vec![MintTotal {
+            mint: setup.mint,
+            amount: AMOUNT
+        }]
+    );
+    assert!(setup.transfer(&transfer).in_inbox);
+
+    setup.claim(&transfer, CLAIM_CODE).unwrap();
+    let state = inbox(&setup);
+    assert_eq!(state.pending_count, 0);
+    assert!(state.totals.is_empty());
+}
+
+#[test]
+fn settling_a_mint_the_inbox_does_not_track_fails() {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    let transfer = create_in_inbox(&mut setup, Default::default());
+
+    let mut state = inbox(&setup);
+    state.totals.clear();
+    write_account(&mut setup.svm, &pda::inbox_address(&EMAIL_HASH), &state);
+    assert_error(
+        setup.claim(&transfer, CLAIM_CODE),
+        ErrorCode::InboxMintNotTracked,
+    );
+}
+
+#[test]
+fn inbox_tracks_a_limited_number_of_mints() {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    let mut state = inbox(&setup);
+    state.totals = (0..8)
+        .map(|_| MintTotal {
+            mint: Pubkey::new_unique(),
+            amount: AMOUNT,
+        })
+        .collect();
+    write_account(&mut setup.svm, &pda::inbox_address(&EMAIL_HASH), &state);
+
+    let create = setup.create_ix().with_inbox();
+    assert_error(
+        setup.svm.process_instruction(create.instruction()),
+        ErrorCode::InboxFull,
+    );
+}
+
+// ============================================================================
+// bind_inbox
+// ============================================================================
+
+#[test]
+fn bind_sets_the_attested_wallet() {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    let verifier = setup.add_verifier();
+    let recipient = setup.recipient;
+    setup.bind_inbox(&verifier, recipient).unwrap();
+
+    assert_eq!(inbox(&setup).owner, recipient);
+    let bound = setup.svm.events::<InboxBound>();
+    assert_eq!(bound.len(), 1);
+    assert_eq!(bound[0].owner, recipient);
+    assert_eq!(bound[0].verifier, verifier.pubkey());
+}
+
+#[test]
+fn bind_needs_a_registered_verifier() {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    setup.add_verifier();
+    let recipient = setup.recipient;
+    assert_error(
+        setup.bind_inbox(&Keypair::new(), recipient),
+        ErrorCode::UnknownVerifier,
+    );
+    assert!(!inbox(&setup).is_bound());
+}
+
+#[test]
+fn bind_needs_the_attestation_instruction() {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    setup.add_verifier();
+    let recipient = setup.recipient;
+
+    // A bind without the Ed25519 instruction in front
+    let expires_at = setup.svm.clock().unix_timestamp + 3600;
+    let message = payinbox::bind_attestation_message(&EMAIL_HASH, &recipient, expires_at);
+    let ix = instruction(
+        payinbox::accounts::BindInbox {
+            inbox: pda::inbox_address(&EMAIL_HASH),
+            config: pda::config_address(),
+            attestation_record: pda::attestation_record_address(&message),
+            wallet: recipient,
+            instructions: anchor_lang::solana_program::sysvar::instructions::ID,
+            system_program: anchor_lang::system_program::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::BindInbox { expires_at },
+    );
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::InvalidAttestation,
+    );
+}
+
+#[test]
+fn attestation_binds_only_once() {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    let verifier = setup.add_verifier();
+    let recipient = setup.recipient;
+    setup.bind_inbox(&verifier, recipient).unwrap();
+
+    // Rebinding to someone else, then replaying the first attestation
+    let other = Pubkey::new_unique();
+    setup.svm.airdrop(&other, LAMPORTS_PER_SOL);
+    setup.bind_inbox(&verifier, other).unwrap();
+    assert!(setup.bind_inbox(&verifier, recipient).is_err());
+    assert_eq!(inbox(&setup).owner, other);
+}
+
+// ============================================================================
+// sweep_inbox
+// ============================================================================
+
+#[test]
+fn sweep_claims_every_listed_transfer() {
+    let (mut setup, transfer) = bound_inbox(Default::default());
+    sweep(&mut setup, &[transfer]).unwrap();
+
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Claimed);
+    let recipient_account = get_associated_token_address(&setup.recipient, &setup.mint);
+    assert_eq!(setup.svm.token_balance(&recipient_account), AMOUNT);
+    assert!(setup.svm.account(&pda::escrow_address(&transfer)).is_none());
+    assert_eq!(inbox(&setup).pending_count, 0);
+    let swept = setup.svm.events::<InboxSwept>();
+    assert_eq!(swept.len(), 1);
+    assert_eq!(swept[0].transfers, 1);
+}
+
+#[test]
+fn sweep_is_for_the_bound_wallet_only() {
+    let (mut setup, transfer) = bound_inbox(Default::default());
+    let ix = sweep_ix(&setup, setup.sender, &[transfer]);
+    assert_error(setup.svm.process_instruction(ix), ErrorCode::NotInboxOwner);
+
+    // An unbound inbox has no owner to sweep it
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    let transfer = create_in_inbox(&mut setup, Default::default());
+    assert_error(sweep(&mut setup, &[transfer]), ErrorCode::NotInboxOwner);
+}
+
+#[test]
+fn sweep_rejects_transfers_outside_the_inbox() {
+    let (mut setup, _) = bound_inbox(Default::default());
+    assert_error(sweep(&mut setup, &[]), ErrorCode::InvalidSweepAccounts);
+
+    let mut other = setup.clone();
+    other.sender = Pubkey::new_unique();
+    other.svm.airdrop(&other.sender, 10 * LAMPORTS_PER_SOL);
+    other
+        .svm
+        .create_token_account(&other.sender, &other.mint, SENDER_BALANCE);
+    let untracked = other.create();
+    assert_error(
+        sweep(&mut other, &[untracked]),
+        ErrorCode::InvalidSweepAccounts,
+    );
+}
+
+#[test]
+fn sweep_rejects_expired_transfers() {
+    let (mut setup, transfer) = bound_inbox(Default::default());
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    assert_error(sweep(&mut setup, &[transfer]), ErrorCode::TransferExpired);
+}
+
+#[test]
+fn sweep_rejects_reserved_transfers() {
+    let (mut setup, transfer) = bound_inbox(commit_options());
+    let commitment = claim_commitment(CLAIM_CODE.as_bytes(), &setup.recipient, &[5; 32]);
+    let commit = CommitClaim::new(
+        &setup.transfer(&transfer),
+        commit_keypair(CLAIM_CODE).pubkey(),
+        commitment,
+    );
+    setup.svm.process_instruction(commit.instruction()).unwrap();
+
+    assert_error(sweep(&mut setup, &[transfer]), ErrorCode::ClaimReserved);
+}
+
+#[test]
+fn sweep_rejects_transfers_behind_a_passkey() {
+    let (mut setup, transfer) = bound_inbox(Default::default());
+    let ix = SetClaimPasskey::new(&setup.transfer(&transfer), Some(PASSKEY)).instruction();
+    setup.svm.process_instruction(ix).unwrap();
+    assert_error(sweep(&mut setup, &[transfer]), ErrorCode::InvalidClaimMode);
+
+    // The inbox's own passkey covers every transfer it tracks
+    let (mut setup, transfer) = bound_inbox(Default::default());
+    let ix = instruction(
+        payinbox::accounts::SetInboxPasskey {
+            inbox: pda::inbox_address(&EMAIL_HASH),
+            owner: setup.recipient,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::SetInboxPasskey {
+            passkey: Some(PASSKEY),
+        },
+    );
+    setup.svm.process_instruction(ix).unwrap();
+    assert_error(sweep(&mut setup, &[transfer]), ErrorCode::InvalidClaimMode);
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Active);
+}
@@ -0,0 +1,334 @@
+//! Happy paths of create, claim, cancel and reclaim: balances, state and
+//! events.
+
+mod common;
+
+use anchor_lang::event::EVENT_IX_TAG_LE;
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::Event;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::spl_token;
+use payinbox::{
+    TransferCancelled, TransferClaimed, TransferCreated, TransferOptions, TransferReclaimed,
+    TransferStatus, TRANSFER_EVENT_VERSION,
+};
+use payinbox_client::pda;
+
+use common::*;
+
+#[test]
+fn create_locks_tokens_in_escrow() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let escrow = pda::escrow_address(&transfer);
+
+    let sender_ata = get_associated_token_address(&setup.sender, &setup.mint);
+    assert_eq!(
+        setup.svm.token_balance(&sender_ata),
+        SENDER_BALANCE - AMOUNT
+    );
+    assert_eq!(setup.svm.token_balance(&escrow), AMOUNT);
+    assert_eq!(setup.svm.token_account(&escrow).unwrap().owner, transfer);
+
+    let state = setup.transfer(&transfer);
+    assert_eq!(state.sender, setup.sender);
+    assert_eq!(state.email_hash, EMAIL_HASH);
+    assert_eq!(state.amount, AMOUNT);
+    assert_eq!(state.token_mint, setup.mint);
+    assert_eq!(state.escrow_token_account, escrow);
+    assert_eq!(state.status, TransferStatus::Active);
+    assert_eq!(state.created_at, setup.svm.clock().unix_timestamp);
+    assert_eq!(state.expiry, state.created_at + EXPIRY_HOURS * 3600);
+
+    let events = setup.svm.events::<TransferCreated>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].transfer, transfer);
+    assert_eq!(events[0].sender, setup.sender);
+    assert_eq!(events[0].token_mint, setup.mint);
+    assert_eq!(events[0].amount, AMOUNT);
+    assert_eq!(events[0].expiry, state.expiry);
+    assert!(!events[0].irrevocable);
+    assert_eq!(events[0].version, TRANSFER_EVENT_VERSION);
+    assert_eq!(events[0].email_hash, EMAIL_HASH);
+    assert_eq!(events[0].created_at, state.created_at);
+    assert_eq!(events[0].timestamp, state.created_at);
+}
+
+#[test]
+fn claim_pays_recipient_and_returns_escrow_rent() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let escrow = pda::escrow_address(&transfer);
+    let escrow_rent = setup.svm.lamports(&escrow);
+    let sender_lamports = setup.svm.lamports(&setup.sender);
+
+    setup.claim(&transfer, CLAIM_CODE).unwrap();
+
+    let recipient_ata = get_associated_token_address(&setup.recipient, &setup.mint);
+    assert_eq!(setup.svm.token_balance(&recipient_ata), AMOUNT);
+    assert!(setup.svm.account(&escrow).is_none());
+    assert_eq!(
+        setup.svm.lamports(&setup.sender),
+        sender_lamports + escrow_rent
+    );
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Claimed);
+
+    let events = setup.svm.events::<TransferClaimed>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].transfer, transfer);
+    assert_eq!(events[0].recipient, setup.recipient);
+    assert_eq!(events[0].amount, AMOUNT);
+    assert_eq!(events[0].sender, setup.sender);
+    assert_eq!(events[0].email_hash, EMAIL_HASH);
+    assert_eq!(events[0].token_mint, setup.mint);
+    assert_eq!(events[0].expiry, setup.expiry(&transfer));
+    assert_eq!(events[0].timestamp, setup.svm.clock().unix_timestamp);
+}
+
+#[test]
+fn cancel_refunds_sender() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+
+    setup.cancel(&transfer, setup.sender).unwrap();
+
+    let sender_ata = get_associated_token_address(&setup.sender, &setup.mint);
+    assert_eq!(setup.svm.token_balance(&sender_ata), SENDER_BALANCE);
+    assert!(setup.svm.account(&pda::escrow_address(&transfer)).is_none());
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Cancelled);
+
+    let events = setup.svm.events::<TransferCancelled>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].transfer, transfer);
+    assert_eq!(events[0].sender, setup.sender);
+    assert_eq!(events[0].cancelled_by, setup.sender);
+    assert_eq!(events[0].refund_to, setup.sender);
+    assert_eq!(events[0].amount, AMOUNT);
+}
+
+#[test]
+fn cancel_authority_refunds_to_refund_owner() {
+    let mut setup = Setup::new();
+    let authority = Pubkey::new_unique();
+    let treasury = Pubkey::new_unique();
+    let treasury_ata = setup.svm.create_token_account(&treasury, &setup.mint, 0);
+    let transfer = setup.create_with(TransferOptions {
+        refund_to: Some(treasury),
+        cancel_authority: Some(authority),
+        ..TransferOptions::default()
+    });
+
+    setup.cancel(&transfer, authority).unwrap();
+
+    assert_eq!(setup.svm.token_balance(&treasury_ata), AMOUNT);
+    let events = setup.svm.events::<TransferCancelled>();
+    assert_eq!(events[0].cancelled_by, authority);
+    assert_eq!(events[0].refund_to, treasury);
+}
+
+#[test]
+fn reclaim_after_expiry_refunds_sender() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let cranker = Pubkey::new_unique();
+    setup.svm.airdrop(&cranker, 1_000_000_000);
+
+    let expiry = setup.expiry(&transfer);
+    setup.svm.set_unix_timestamp(expiry);
+    setup.reclaim(&transfer, cranker).unwrap();
+
+    let sender_ata = get_associated_token_address(&setup.sender, &setup.mint);
+    assert_eq!(setup.svm.token_balance(&sender_ata), SENDER_BALANCE);
+    assert!(setup.svm.account(&pda::escrow_address(&transfer)).is_none());
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Expired);
+
+    let events = setup.svm.events::<TransferReclaimed>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].transfer, transfer);
+    assert_eq!(events[0].sender, setup.sender);
+    assert_eq!(events[0].recipient, setup.sender);
+    assert_eq!(events[0].amount, AMOUNT);
+    assert_eq!(events[0].cranker, cranker);
+    assert_eq!(events[0].bounty_lamports, 0);
+    assert_eq!(events[0].bounty_tokens, 0);
+    assert_eq!(events[0].created_at, expiry - EXPIRY_HOURS * 3600);
+    assert_eq!(events[0].timestamp, expiry);
+}
+
+#[test]
+fn events_are_self_invocations_signed_by_the_event_authority() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    setup.claim(&transfer, CLAIM_CODE).unwrap();
+
+    let event_ix = setup
+        .svm
+        .inner_instructions()
+        .iter()
+        .find(|ix| ix.program_id == payinbox::ID)
+        .expect("event self-invocation");
+    assert!(event_ix.data.starts_with(EVENT_IX_TAG_LE));
+    assert_eq!(event_ix.accounts.len(), 1);
+    assert_eq!(event_ix.accounts[0].pubkey, pda::event_authority_address());
+    assert!(event_ix.accounts[0].is_signer);
+}
+
+#[test]
+fn event_invocations_from_outside_are_rejected() {
+    let mut setup = Setup::new();
+    let forged = TransferClaimed {
+        version: TRANSFER_EVENT_VERSION,
+        transfer: Pubkey::new_unique(),
+        sender: setup.sender,
+        email_hash: EMAIL_HASH,
+        token_mint: setup.mint,
+        recipient: setup.recipient,
+        amount: AMOUNT,
+        created_at: 0,
+        expiry: 0,
+        timestamp: 0,
+    };
+    let ix = Instruction {
+        program_id: payinbox::ID,
+        accounts: vec![AccountMeta::new_readonly(
+            pda::event_authority_address(),
+            false,
+        )],
+        data: [EVENT_IX_TAG_LE, &forged.data()].concat(),
+    };
+
+    assert!(setup.svm.process_instruction(ix).is_err());
+}
+
+#[test]
+fn reclaim_pays_crank_bounty() {
+    let mut setup = Setup::new();
+    let bounty_lamports = 5_000_000;
+    let transfer = setup.create_with(TransferOptions {
+        crank_bounty_lamports: bounty_lamports,
+        crank_bounty_bps: 100,
+        ..TransferOptions::default()
+    });
+    let cranker = Pubkey::new_unique();
+    setup.svm.airdrop(&cranker, 1_000_000_000);
+    let cranker_ata = setup.svm.create_token_account(&cranker, &setup.mint, 0);
+
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    let ix =
+        payinbox_client::instructions::ReclaimExpired::new(&setup.transfer(&transfer), cranker)
+            .cranker_token_account(cranker_ata)
+            .instruction();
+    setup.svm.process_instruction(ix).unwrap();
+
+    let bounty_tokens = AMOUNT / 100;
+    assert_eq!(setup.svm.token_balance(&cranker_ata), bounty_tokens);
+    assert_eq!(
+        setup.svm.lamports(&cranker),
+        1_000_000_000 + bounty_lamports
+    );
+    let sender_ata = get_associated_token_address(&setup.sender, &setup.mint);
+    assert_eq!(
+        setup.svm.token_balance(&sender_ata),
+        SENDER_BALANCE - bounty_tokens
+    );
+
+    let events = setup.svm.events::<TransferReclaimed>();
+    assert_eq!(events[0].amount, AMOUNT - bounty_tokens);
+    assert_eq!(events[0].bounty_lamports, bounty_lamports);
+    assert_eq!(events[0].bounty_tokens, bounty_tokens);
+}
+
+#[test]
+fn reclaim_creates_closed_refund_account() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let sender_ata = get_associated_token_address(&setup.sender, &setup.mint);
+    // The sender empties and closes their token account while waiting.
+    let drain = get_associated_token_address(&Pubkey::new_unique(), &setup.mint);
+    setup
+        .svm
+        .create_token_account_at(drain, &Pubkey::new_unique(), &setup.mint, 0);
+    let ixs = [
+        spl_token::instruction::transfer(
+            &spl_token::ID,
+            &sender_ata,
+            &drain,
+            &setup.sender,
+            &[],
+            SENDER_BALANCE - AMOUNT,
+        )
+        .unwrap(),
+        spl_token::instruction::close_account(
+            &spl_token::ID,
+            &sender_ata,
+            &setup.sender,
+            &setup.sender,
+            &[],
+        )
+        .unwrap(),
+    ];
+    setup.svm.process_transaction(&ixs).unwrap();
+    assert!(setup.svm.account(&sender_ata).is_none());
+
+    let cranker = Pubkey::new_unique();
+    setup.svm.airdrop(&cranker, 1_000_000_000);
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    setup.reclaim(&transfer, cranker).unwrap();
+
+    let refund = setup.svm.token_account(&sender_ata).unwrap();
+    assert_eq!(refund.owner, setup.sender);
+    assert_eq!(refund.amount, AMOUNT);
+    // The escrow's rent reimburses the cranker for the new account.
+    assert_eq!(setup.svm.lamports(&cranker), 1_000_000_000);
+}
+
+#[test]
+fn reclaim_hands_escrow_to_frozen_refund_owner() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let sender_ata = get_associated_token_address(&setup.sender, &setup.mint);
+    // The mint's freeze authority freezes the sender's token account.
+    freeze(&mut setup.svm, &sender_ata);
+
+    let cranker = Pubkey::new_unique();
+    setup.svm.airdrop(&cranker, 1_000_000_000);
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+    setup.reclaim(&transfer, cranker).unwrap();
+
+    // The escrow stays open, now owned by the sender instead of the transfer.
+    let escrow = setup
+        .svm
+        .token_account(&pda::escrow_address(&transfer))
+        .unwrap();
+    assert_eq!(escrow.owner, setup.sender);
+    assert_eq!(escrow.amount, AMOUNT);
+    assert_eq!(
+        setup.svm.token_balance(&sender_ata),
+        SENDER_BALANCE - AMOUNT
+    );
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Expired);
+
+    let events = setup.svm.events::<TransferReclaimed>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].transfer, transfer);
+    assert_eq!(events[0].recipient, setup.sender);
+    assert_eq!(events[0].amount, AMOUNT);
+}
+
+#[test]
+fn failed_transaction_leaves_accounts_untouched() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let before = setup.transfer(&transfer);
+
+    assert!(setup.claim(&transfer, "wrong").is_err());
+
+    let after = setup.transfer(&transfer);
+    assert_eq!(after.status, before.status);
+    assert_eq!(
+        setup.svm.token_balance(&pda::escrow_address(&transfer)),
+        AMOUNT
+    );
+    assert!(setup.svm.events::<TransferClaimed>().is_empty());
+}
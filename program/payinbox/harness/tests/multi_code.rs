@@ -0,0 +1,319 @@
+//! M-of-N transfers: codes approved with `approve_claim`, paid out with
+//! `claim_approved`, approvals closed whichever way the transfer ends.
+
+mod common;
+
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token;
+use payinbox::{ClaimApprovals, ErrorCode, TransferOptions, TransferStatus};
+use payinbox_client::hash::commit_keypair;
+use payinbox_client::pda;
+use solana_signer::Signer;
+
+use common::*;
+
+const CODES: [&str; 3] = ["alpha", "beta", "gamma"];
+const THRESHOLD: u8 = 2;
+
+/// `create_multi_code_transfer` of `AMOUNT` needing `threshold` of `codes`.
+fn create_ix(setup: &Setup, codes: &[&str], threshold: u8) -> Instruction {
+    let transfer = pda::transfer_address(&setup.sender, &EMAIL_HASH);
+    instruction(
+        payinbox::accounts::CreateMultiCodeTransfer {
+            transfer,
+            approvals: pda::approvals_address(&transfer),
+            sender: setup.sender,
+            sender_token_account: get_associated_token_address(&setup.sender, &setup.mint),
+            token_mint: setup.mint,
+            escrow_token_account: pda::escrow_address(&transfer),
+            token_program: token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::CreateMultiCodeTransfer {
+            email_hash: EMAIL_HASH,
+            code_keys: codes
+                .iter()
+                .map(|code| commit_keypair(code).pubkey())
+                .collect(),
+            threshold,
+            amount: AMOUNT,
+            expiry_hours: EXPIRY_HOURS,
+            options: TransferOptions::default(),
+        },
+    )
+}
+
+/// Creates a 2-of-3 transfer of `AMOUNT` and returns its address.
+fn create(setup: &mut Setup) -> Pubkey {
+    let ix = create_ix(setup, &CODES, THRESHOLD);
+    setup
+        .svm
+        .process_instruction(ix)
+        .expect("create_multi_code_transfer");
+    pda::transfer_address(&setup.sender, &EMAIL_HASH)
+}
+
+/// `approve_claim` for `recipient`, signed by `approver`.
+fn approve_as(
+    setup: &mut Setup,
+    transfer: &Pubkey,
+    approver: Pubkey,
+    recipient: Pubkey,
+) -> Result<(), ProgramError> {
+    let ix = instruction(
+        payinbox::accounts::ApproveClaim {
+            transfer: *transfer,
+            approvals: pda::approvals_address(transfer),
+            approver,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::ApproveClaim { recipient },
+    );
+    setup.svm.process_instruction(ix)
+}
+
+/// `approve_claim` for `recipient`, signed by the key of `code`.
+fn approve(
+    setup: &mut Setup,
+    transfer: &Pubkey,
+    code: &str,
+    recipient: Pubkey,
+) -> Result<(), ProgramError> {
+    approve_as(setup, transfer, commit_keypair(code).pubkey(), recipient)
+}
+
+fn claim(setup: &mut Setup, transfer: &Pubkey, recipient: Pubkey) -> Result<(), ProgramError> {
+    let ix = instruction(
+        payinbox::accounts::ClaimApproved {
+            transfer: *transfer,
+            approvals: pda::approvals_address(transfer),
+            recipient,
+            recipient_token_account: get_associated_token_address(&recipient, &setup.mint),
+            escrow_token_account: pda::escrow_address(transfer),
+            sender: setup.sender,
+            token_program: token::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::ClaimApproved {},
+    );
+    setup.svm.process_instruction(ix)
+}
+
+fn approvals(setup: &Setup, transfer: &Pubkey) -> Option<ClaimApprovals> {
+    setup.svm.anchor_account(&pda::approvals_address(transfer))
+}
+
+#[test]
+fn two_of_three_codes_release_to_recipient() {
+    let mut setup = Setup::new();
+    let transfer = create(&mut setup);
+    let recipient = setup.recipient;
+    let approvals_rent = setup.svm.lamports(&pda::approvals_address(&transfer));
+    let escrow_rent = setup.svm.lamports(&pda::escrow_address(&transfer));
+    let sender_lamports = setup.svm.lamports(&setup.sender);
+
+    approve(&mut setup, &transfer, "alpha", recipient).unwrap();
+    assert_error(
+        claim(&mut setup, &transfer, recipient),
+        ErrorCode::InsufficientApprovals,
+    );
+    // The same code twice still counts once
+    approve(&mut setup, &transfer, "alpha", recipient).unwrap();
+    assert_eq!(
+        approvals(&setup, &transfer)
+            .unwrap()
+            .approval_count(&recipient),
+        1
+    );
+    approve(&mut setup, &transfer, "gamma", recipient).unwrap();
+
+    claim(&mut setup, &transfer, recipient).unwrap();
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Claimed);
+    assert_eq!(
+        setup
+            .svm
+            .token_balance(&get_associated_token_address(&recipient, &setup.mint)),
+        AMOUNT
+    );
+    assert!(approvals(&setup, &transfer).is_none());
+    assert_eq!(
+        setup.svm.lamports(&setup.sender),
+        sender_lamports + approvals_rent + escrow_rent
+    );
+}
+
+#[test]
+fn create_needs_one_to_eight_distinct_codes() {
+    let mut setup = Setup::new();
+    let nine = ["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+    for codes in [&[][..], &nine[..], &["alpha", "beta", "alpha"][..]] {
+        let ix = create_ix(&setup, codes, 1);
+        assert_error(
+            setup.svm.process_instruction(ix),
+            ErrorCode::InvalidClaimCodeSet,
+        );
+    }
+    let ix = create_ix(&setup, &nine[..8], 8);
+    setup.svm.process_instruction(ix).unwrap();
+}
+
+#[test]
+fn create_needs_a_reachable_threshold() {
+    let mut setup = Setup::new();
+    for threshold in [0, CODES.len() as u8 + 1] {
+        let ix = create_ix(&setup, &CODES, threshold);
+        assert_error(
+            setup.svm.process_instruction(ix),
+            ErrorCode::InvalidThreshold,
+        );
+    }
+}
+
+#[test]
+fn approve_rejects_unknown_code() {
+    let mut setup = Setup::new();
+    let transfer = create(&mut setup);
+    let recipient = setup.recipient;
+    assert_error(
+        approve(&mut setup, &transfer, "delta", recipient),
+        ErrorCode::InvalidClaimCode,
+    );
+}
+
+#[test]
+fn approvals_are_not_replayable() {
+    let mut setup = Setup::new();
+    let transfer = create(&mut setup);
+    let recipient = setup.recipient;
+    let thief = Pubkey::new_unique();
+    setup.svm.create_token_account(&thief, &setup.mint, 0);
+
+    // Everything a third party sees of the first approval: the code key
+    // and the recipient, never the code itself
+    approve(&mut setup, &transfer, "alpha", recipient).unwrap();
+    let ix = instruction(
+        payinbox::accounts::ApproveClaim {
+            transfer,
+            approvals: pda::approvals_address(&transfer),
+            approver: commit_keypair("alpha").pubkey(),
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::ApproveClaim { recipient },
+    );
+    assert!(!ix
+        .data
+        .windows("alpha".len())
+        .any(|window| window == b"alpha"));
+
+    // Signing with its own key for its own wallet gets the thief nowhere
+    assert_error(
+        approve_as(&mut setup, &transfer, thief, thief),
+        ErrorCode::InvalidClaimCode,
+    );
+    assert_error(
+        claim(&mut setup, &transfer, thief),
+        ErrorCode::InsufficientApprovals,
+    );
+    let state = approvals(&setup, &transfer).unwrap();
+    assert_eq!(state.approval_count(&recipient), 1);
+    assert_eq!(state.approval_count(&thief), 0);
+
+    approve(&mut setup, &transfer, "beta", recipient).unwrap();
+    claim(&mut setup, &transfer, recipient).unwrap();
+}
+
+#[test]
+fn approving_another_wallet_keeps_other_approvals() {
+    let mut setup = Setup::new();
+    let transfer = create(&mut setup);
+    let recipient = setup.recipient;
+    let wrong = Pubkey::new_unique();
+
+    approve(&mut setup, &transfer, "alpha", recipient).unwrap();
+    approve(&mut setup, &transfer, "beta", wrong).unwrap();
+    let state = approvals(&setup, &transfer).unwrap();
+    assert_eq!(state.approval_count(&recipient), 1);
+    assert_eq!(state.approval_count(&wrong), 1);
+    assert_error(
+        claim(&mut setup, &transfer, recipient),
+        ErrorCode::InsufficientApprovals,
+    );
+
+    // Moving a code's approval only moves that code's
+    approve(&mut setup, &transfer, "beta", recipient).unwrap();
+    let state = approvals(&setup, &transfer).unwrap();
+    assert_eq!(state.approval_count(&recipient), 2);
+    assert_eq!(state.approval_count(&wrong), 0);
+    claim(&mut setup, &transfer, recipient).unwrap();
+}
+
+#[test]
+fn wallet_is_fixed_once_threshold_is_met() {
+    let mut setup = Setup::new();
+    let transfer = create(&mut setup);
+    let recipient = setup.recipient;
+    let other = Pubkey::new_unique();
+    setup.svm.create_token_account(&other, &setup.mint, 0);
+
+    approve(&mut setup, &transfer, "alpha", recipient).unwrap();
+    approve(&mut setup, &transfer, "beta", recipient).unwrap();
+    assert_error(
+        approve(&mut setup, &transfer, "gamma", other),
+        ErrorCode::ApprovalRecipientMismatch,
+    );
+    assert_error(
+        claim(&mut setup, &transfer, other),
+        ErrorCode::ApprovalRecipientMismatch,
+    );
+    claim(&mut setup, &transfer, recipient).unwrap();
+}
+
+#[test]
+fn cancel_closes_approvals() {
+    let mut setup = Setup::new();
+    let transfer = create(&mut setup);
+    let recipient = setup.recipient;
+    approve(&mut setup, &transfer, "alpha", recipient).unwrap();
+
+    // Leaving the approvals out would leak their rent
+    let mut ix = payinbox_client::instructions::CancelTransfer::new(
+        &setup.transfer(&transfer),
+        setup.sender,
+    )
+    .instruction();
+    let approvals_index = ix.accounts.len() - 3;
+    assert_eq!(
+        ix.accounts[approvals_index].pubkey,
+        pda::approvals_address(&transfer)
+    );
+    ix.accounts[approvals_index].pubkey = payinbox::ID;
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::ApprovalsRequired,
+    );
+
+    setup.cancel(&transfer, setup.sender).unwrap();
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Cancelled);
+    assert!(approvals(&setup, &transfer).is_none());
+}
+
+#[test]
+fn reclaim_closes_approvals() {
+    let mut setup = Setup::new();
+    let transfer = create(&mut setup);
+    setup.svm.set_unix_timestamp(setup.expiry(&transfer));
+
+    setup.reclaim(&transfer, Pubkey::new_unique()).unwrap();
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Expired);
+    assert!(approvals(&setup, &transfer).is_none());
+}
@@ -0,0 +1,331 @@
+//! Setting and clearing the passkey that replaces a transfer's claim code,
+//! and claiming with a WebAuthn assertion signed by it.
+
+mod common;
+
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_spl::associated_token::get_associated_token_address;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey, EcPoint};
+use openssl::ecdsa::EcdsaSig;
+use openssl::nid::Nid;
+use openssl::sha::sha256;
+use payinbox::{passkey_challenge, ErrorCode, TransferOptions, TransferStatus};
+use payinbox_client::instructions::{ClaimTransfer, SetClaimPasskey};
+use payinbox_client::pda;
+use payinbox_harness::{authenticator_data, Passkey};
+
+use common::*;
+
+/// A well-formed compressed secp256r1 key; nothing signs with it here.
+const PASSKEY: [u8; 33] = [2; 33];
+
+#[test]
+fn sender_sets_and_clears_passkey() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+
+    let ix = SetClaimPasskey::new(&setup.transfer(&transfer), Some(PASSKEY)).instruction();
+    setup.svm.process_instruction(ix).unwrap();
+    assert_eq!(setup.transfer(&transfer).claim_passkey, Some(PASSKEY));
+
+    let ix = SetClaimPasskey::new(&setup.transfer(&transfer), None).instruction();
+    setup.svm.process_instruction(ix).unwrap();
+    setup.claim(&transfer, CLAIM_CODE).unwrap();
+}
+
+#[test]
+fn irrevocable_transfer_keeps_its_claim_code() {
+    let mut setup = Setup::new();
+    let transfer = setup.create_with(TransferOptions {
+        irrevocable: true,
+        ..TransferOptions::default()
+    });
+
+    let ix = SetClaimPasskey::new(&setup.transfer(&transfer), Some(PASSKEY)).instruction();
+    assert_error(
+        setup.svm.process_instruction(ix),
+        ErrorCode::IrrevocableTransfer,
+    );
+    assert_eq!(setup.transfer(&transfer).claim_passkey, None);
+    setup.claim(&transfer, CLAIM_CODE).unwrap();
+}
+
+#[test]
+fn passkey_must_be_a_compressed_point() {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let mut uncompressed = PASSKEY;
+    uncompressed[0] = 0x04;
+
+    let ix = SetClaimPasskey::new(&setup.transfer(&transfer), Some(uncompressed)).instruction();
+    assert_error(setup.svm.process_instruction(ix), ErrorCode::InvalidPasskey);
+    assert_eq!(setup.transfer(&transfer).claim_passkey, None);
+}
+
+/// A transfer locked by a fresh passkey instead of its claim code.
+fn passkey_transfer() -> (Setup, Pubkey, Passkey) {
+    let mut setup = Setup::new();
+    let transfer = setup.create();
+    let passkey = Passkey::new();
+    let ix =
+        SetClaimPasskey::new(&setup.transfer(&transfer), Some(passkey.public_key())).instruction();
+    setup.svm.process_instruction(ix).unwrap();
+    (setup, transfer, passkey)
+}
+
+/// Runs `assertion` followed by `claim_transfer` into `recipient`, with
+/// `client_data` in place of the claim code.
+fn claim_with(
+    setup: &mut Setup,
+    transfer: &Pubkey,
+    recipient: Pubkey,
+    assertion: Instruction,
+    client_data: &str,
+) -> Result<(), ProgramError> {
+    let claim = ClaimTransfer::new(&setup.transfer(transfer), recipient, client_data).instruction();
+    setup.svm.process_transaction(&[assertion, claim])
+}
+
+/// The client data JSON a browser would produce for `challenge`, with
+/// `kind` as its type.
+fn client_data(kind: &str, challenge: &[u8; 32]) -> String {
+    format!(
+        r#"{{"type":"{kind}","challenge":"{}","origin":"https://payinbox.app"}}"#,
+        URL_SAFE_NO_PAD.encode(challenge)
+    )
+}
+
+#[test]
+fn assertion_signature_is_valid_p256() {
+    let passkey = Passkey::new();
+    let (assertion, client_data) = passkey.assert(&[9; 32]);
+
+    // One signature, everything inside the instruction itself
+    let data = &assertion.data;
+    assert_eq!(data[0], 1);
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]) as usize;
+    for index in [4, 8, 14] {
+        assert_eq!(read_u16(index), u16::MAX as usize);
+    }
+    let signature = &data[read_u16(2)..read_u16(2) + 64];
+    let public_key = &data[read_u16(6)..read_u16(6) + 33];
+    let message = &data[read_u16(10)..read_u16(10) + read_u16(12)];
+    assert_eq!(public_key, passkey.public_key());
+
+    // authenticatorData || SHA256(clientDataJSON), signed over its SHA256
+    let (authenticator, client_data_hash) = message.split_at(message.len() - 32);
+    assert_eq!(authenticator, authenticator_data(0x05));
+    assert_eq!(client_data_hash, sha256(client_data.as_bytes()));
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let mut context = BigNumContext::new().unwrap();
+    let point = EcPoint::from_bytes(&group, public_key, &mut context).unwrap();
+    let key = EcKey::from_public_key(&group, &point).unwrap();
+    let signature = EcdsaSig::from_private_components(
+        BigNum::from_slice(&signature[..32]).unwrap(),
+        BigNum::from_slice(&signature[32..]).unwrap(),
+    )
+    .unwrap();
+    assert!(signature.verify(&sha256(message), &key).unwrap());
+}
+
+#[test]
+fn passkey_assertion_claims_the_transfer() {
+    let (mut setup, transfer, passkey) = passkey_transfer();
+    let recipient = setup.recipient;
+    let (assertion, client_data) = passkey.assert(&passkey_challenge(&transfer, &recipient));
+    claim_with(&mut setup, &transfer, recipient, assertion, &client_data).unwrap();
+
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Claimed);
+    let recipient_account = get_associated_token_address(&recipient, &setup.mint);
+    assert_eq!(setup.svm.token_balance(&recipient_account), AMOUNT);
+}
+
+#[test]
+fn claim_code_no_longer_claims_a_passkey_transfer() {
+    let (mut setup, transfer, _) = passkey_transfer();
+    assert_error(
+        setup.claim(&transfer, CLAIM_CODE),
+        ErrorCode::InvalidPasskeySignature,
+    );
+}
+
+#[test]
+fn assertion_is_bound_to_transfer_and_destination() {
+    let (mut setup, transfer, passkey) = passkey_transfer();
+    let recipient = setup.recipient;
+
+    // Signed for the recipient, claimed into someone else's account
+    let other = Pubkey::new_unique();
+    setup
+        .svm
+        .create_token_account(&other, &setup.mint.clone(), 0);
+    let (assertion, client_data) = passkey.assert(&passkey_challenge(&transfer, &recipient));
+    assert_error(
+        claim_with(&mut setup, &transfer, other, assertion, &client_data),
+        ErrorCode::InvalidPasskeySignature,
+    );
+
+    // Signed for another transfer
+    let challenge = passkey_challenge(&Pubkey::new_unique(), &recipient);
+    let (assertion, client_data) = passkey.assert(&challenge);
+    assert_error(
+        claim_with(&mut setup, &transfer, recipient, assertion, &client_data),
+        ErrorCode::InvalidPasskeySignature,
+    );
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Active);
+}
+
+#[test]
+fn assertion_must_come_from_the_passkey() {
+    let (mut setup, transfer, _) = passkey_transfer();
+    let recipient = setup.recipient;
+    let (assertion, client_data) = Passkey::new().assert(&passkey_challenge(&transfer, &recipient));
+    assert_error(
+        claim_with(&mut setup, &transfer, recipient, assertion, &client_data),
+        ErrorCode::InvalidPasskeySignature,
+    );
+}
+
+#[test]
+fn assertion_needs_a_present_and_verified_user() {
+    let (mut setup, transfer, passkey) = passkey_transfer();
+    let recipient = setup.recipient;
+    let client_data = client_data("webauthn.get", &passkey_challenge(&transfer, &recipient));
+
+    // User present only: no PIN or biometric check
+    let mut message = authenticator_data(0x01);
+    message.extend_from_slice(&sha256(client_data.as_bytes()));
+    assert_error(
+        claim_with(
+            &mut setup,
+            &transfer,
+            recipient,
+            passkey.sign(&message),
+            &client_data,
+        ),
+        ErrorCode::InvalidPasskeySignature,
+    );
+
+    let mut message = authenticator_data(0x05);
+    message.extend_from_slice(&sha256(client_data.as_bytes()));
+    claim_with(
+        &mut setup,
+        &transfer,
+        recipient,
+        passkey.sign(&message),
+        &client_data,
+    )
+    .unwrap();
+}
+
+#[test]
+fn client_data_must_be_the_signed_get_assertion() {
+    let (mut setup, transfer, passkey) = passkey_transfer();
+    let recipient = setup.recipient;
+    let challenge = passkey_challenge(&transfer, &recipient);
+
+    // Client data other than what was signed
+    let (assertion, _) = passkey.assert(&challenge);
+    assert_error(
+        claim_with(
+            &mut setup,
+            &transfer,
+            recipient,
+            assertion,
+            &client_data("webauthn.get", &challenge),
+        ),
+        ErrorCode::InvalidPasskeySignature,
+    );
+
+    // A signed registration rather than an assertion
+    let created = client_data("webauthn.create", &challenge);
+    let mut message = authenticator_data(0x05);
+    message.extend_from_slice(&sha256(created.as_bytes()));
+    assert_error(
+        claim_with(
+            &mut setup,
+            &transfer,
+            recipient,
+            passkey.sign(&message),
+            &created,
+        ),
+        ErrorCode::InvalidPasskeySignature,
+    );
+}
+
+#[test]
+fn long_client_data_claims_the_transfer() {
+    let (mut setup, transfer, passkey) = passkey_transfer();
+    let recipient = setup.recipient;
+
+    // Longer than any claim code, as when a browser adds extra fields
+    let short = client_data("webauthn.get", &passkey_challenge(&transfer, &recipient));
+    let padded = format!(
+        r#"{},"other_keys_can_be_added_here":"{}"}}"#,
+        &short[..short.len() - 1],
+        "x".repeat(200)
+    );
+    assert!(padded.len() > 300);
+    let mut message = authenticator_data(0x05);
+    message.extend_from_slice(&sha256(padded.as_bytes()));
+    claim_with(
+        &mut setup,
+        &transfer,
+        recipient,
+        passkey.sign(&message),
+        &padded,
+    )
+    .unwrap();
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Claimed);
+}
+
+#[test]
+fn oversized_client_data_is_rejected() {
+    let (mut setup, transfer, _) = passkey_transfer();
+    let recipient = setup.recipient;
+    let claim =
+        ClaimTransfer::new(&setup.transfer(&transfer), recipient, "x".repeat(1025)).instruction();
+    assert_error(
+        setup.svm.process_instruction(claim),
+        ErrorCode::ClientDataTooLong,
+    );
+}
+
+#[test]
+fn inbox_passkey_claims_its_transfers() {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    let create = setup.create_ix().with_inbox();
+    setup
+        .svm
+        .process_instruction(create.instruction())
+        .expect("create_transfer");
+    let transfer = create.transfer_address();
+    let verifier = setup.add_verifier();
+    let recipient = setup.recipient;
+    setup.bind_inbox(&verifier, recipient).unwrap();
+
+    let passkey = Passkey::new();
+    let ix = instruction(
+        payinbox::accounts::SetInboxPasskey {
+            inbox: pda::inbox_address(&EMAIL_HASH),
+            owner: recipient,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::SetInboxPasskey {
+            passkey: Some(passkey.public_key()),
+        },
+    );
+    setup.svm.process_instruction(ix).unwrap();
+
+    let (assertion, client_data) = passkey.assert(&passkey_challenge(&transfer, &recipient));
+    let claim = ClaimTransfer::new(&setup.transfer(&transfer), recipient, client_data)
+        .with_passkey()
+        .instruction();
+    setup.svm.process_transaction(&[assertion, claim]).unwrap();
+    assert_eq!(setup.transfer(&transfer).status, TransferStatus::Claimed);
+}
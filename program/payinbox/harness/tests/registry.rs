@@ -0,0 +1,305 @@
+//! Registered wallets: `register_wallet` with a verifier attestation, and
+//! `send_to_email` paying a registered wallet directly or falling back to
+//! an escrow.
+
+mod common;
+
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token;
+use payinbox::{
+    DirectPaymentSent, ErrorCode, RegistryEntry, TransferCreated, TransferOptions, TransferStatus,
+    WalletRegistered,
+};
+use payinbox_client::hash::claim_code_hash;
+use payinbox_client::pda;
+use payinbox_harness::ed25519_instruction;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+use common::*;
+
+/// `register_wallet` of `wallet` for `EMAIL_HASH`; the attestation goes
+/// in front of it.
+fn register_ix(wallet: Pubkey, expires_at: i64) -> Instruction {
+    let record_message = payinbox::register_attestation_message(&EMAIL_HASH, &wallet, expires_at);
+    instruction(
+        payinbox::accounts::RegisterWallet {
+            registry: pda::registry_address(&EMAIL_HASH),
+            config: pda::config_address(),
+            attestation_record: pda::attestation_record_address(&record_message),
+            wallet,
+            instructions: sysvar::instructions::ID,
+            system_program: system_program::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::RegisterWallet {
+            email_hash: EMAIL_HASH,
+            expires_at,
+        },
+    )
+}
+
+/// Registers the recipient's wallet for `EMAIL_HASH`.
+fn register(setup: &mut Setup, verifier: &Keypair) -> Result<(), ProgramError> {
+    let expires_at = setup.svm.clock().unix_timestamp + 3600;
+    let message = payinbox::register_attestation_message(&EMAIL_HASH, &setup.recipient, expires_at);
+    let ix = register_ix(setup.recipient, expires_at);
+    setup
+        .svm
+        .process_transaction(&[ed25519_instruction(verifier, &message), ix])
+}
+
+/// Which of `send_to_email`'s optional accounts to pass.
+#[derive(Clone, Copy, Default)]
+struct Route {
+    recipient_token_account: Option<Pubkey>,
+    escrow: bool,
+    inbox: bool,
+}
+
+impl Route {
+    fn direct(wallet: &Pubkey, mint: &Pubkey) -> Self {
+        Self {
+            recipient_token_account: Some(get_associated_token_address(wallet, mint)),
+            ..Self::default()
+        }
+    }
+
+    fn escrow() -> Self {
+        Self {
+            escrow: true,
+            ..Self::default()
+        }
+    }
+}
+
+fn send_ix(setup: &Setup, route: Route) -> Instruction {
+    let transfer = pda::transfer_address(&setup.sender, &EMAIL_HASH);
+    instruction(
+        payinbox::accounts::SendToEmail {
+            registry: pda::registry_address(&EMAIL_HASH),
+            sender: setup.sender,
+            sender_token_account: get_associated_token_address(&setup.sender, &setup.mint),
+            token_mint: setup.mint,
+            recipient_token_account: route.recipient_token_account,
+            transfer: route.escrow.then_some(transfer),
+            escrow_token_account: route.escrow.then(|| pda::escrow_address(&transfer)),
+            token_program: token::ID,
+            system_program: system_program::ID,
+            inbox: route.inbox.then(|| pda::inbox_address(&EMAIL_HASH)),
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::SendToEmail {
+            email_hash: EMAIL_HASH,
+            claim_code_hash: claim_code_hash(CLAIM_CODE),
+            amount: AMOUNT,
+            expiry_hours: EXPIRY_HOURS,
+            options: TransferOptions::default(),
+        },
+    )
+}
+
+fn send(setup: &mut Setup, route: Route) -> Result<(), ProgramError> {
+    let ix = send_ix(setup, route);
+    setup.svm.process_instruction(ix)
+}
+
+/// A setup whose recipient is registered for `EMAIL_HASH`.
+fn registered() -> Setup {
+    let mut setup = Setup::new();
+    let verifier = setup.add_verifier();
+    register(&mut setup, &verifier).unwrap();
+    setup
+}
+
+// ============================================================================
+// register_wallet
+// ============================================================================
+
+#[test]
+fn register_records_the_attested_wallet() {
+    let mut setup = Setup::new();
+    let verifier = setup.add_verifier();
+    register(&mut setup, &verifier).unwrap();
+
+    let entry: RegistryEntry = setup
+        .svm
+        .anchor_account(&pda::registry_address(&EMAIL_HASH))
+        .unwrap();
+    assert_eq!(entry.email_hash, EMAIL_HASH);
+    assert_eq!(entry.wallet, setup.recipient);
+    assert_eq!(entry.verifier, verifier.pubkey());
+    assert_eq!(entry.registered_at, setup.svm.clock().unix_timestamp);
+    let registered = setup.svm.events::<WalletRegistered>();
+    assert_eq!(registered.len(), 1);
+    assert_eq!(registered[0].wallet, setup.recipient);
+
+    // The same attestation can't be replayed
+    assert!(register(&mut setup, &verifier).is_err());
+}
+
+#[test]
+fn register_needs_a_registered_verifier() {
+    let mut setup = Setup::new();
+    setup.add_verifier();
+    assert_error(
+        register(&mut setup, &Keypair::new()),
+        ErrorCode::UnknownVerifier,
+    );
+}
+
+#[test]
+fn attestations_for_binding_and_registering_are_not_interchangeable() {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    let verifier = setup.add_verifier();
+    let recipient = setup.recipient;
+
+    // An inbox binding attestation doesn't register the wallet
+    let expires_at = setup.svm.clock().unix_timestamp + 3600;
+    let bind_message = payinbox::bind_attestation_message(&EMAIL_HASH, &recipient, expires_at);
+    let ix = register_ix(recipient, expires_at);
+    assert_error(
+        setup
+            .svm
+            .process_transaction(&[ed25519_instruction(&verifier, &bind_message), ix]),
+        ErrorCode::InvalidAttestation,
+    );
+
+    // Nor does a registration attestation bind the inbox
+    let register_message =
+        payinbox::register_attestation_message(&EMAIL_HASH, &recipient, expires_at);
+    let ix = instruction(
+        payinbox::accounts::BindInbox {
+            inbox: pda::inbox_address(&EMAIL_HASH),
+            config: pda::config_address(),
+            attestation_record: pda::attestation_record_address(&bind_message),
+            wallet: recipient,
+            instructions: sysvar::instructions::ID,
+            system_program: system_program::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        },
+        payinbox::instruction::BindInbox { expires_at },
+    );
+    assert_error(
+        setup
+            .svm
+            .process_transaction(&[ed25519_instruction(&verifier, &register_message), ix]),
+        ErrorCode::InvalidAttestation,
+    );
+
+    // Each works for its own instruction
+    setup.bind_inbox(&verifier, recipient).unwrap();
+    register(&mut setup, &verifier).unwrap();
+}
+
+// ============================================================================
+// send_to_email
+// ============================================================================
+
+#[test]
+fn send_pays_a_registered_wallet_directly() {
+    let mut setup = registered();
+    let route = Route::direct(&setup.recipient, &setup.mint);
+    send(&mut setup, route).unwrap();
+
+    let recipient_account = get_associated_token_address(&setup.recipient, &setup.mint);
+    assert_eq!(setup.svm.token_balance(&recipient_account), AMOUNT);
+    let sender_account = get_associated_token_address(&setup.sender, &setup.mint);
+    assert_eq!(
+        setup.svm.token_balance(&sender_account),
+        SENDER_BALANCE - AMOUNT
+    );
+    assert!(setup
+        .svm
+        .account(&pda::transfer_address(&setup.sender, &EMAIL_HASH))
+        .is_none());
+
+    let sent = setup.svm.events::<DirectPaymentSent>();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].recipient, setup.recipient);
+    assert_eq!(sent[0].amount, AMOUNT);
+}
+
+#[test]
+fn direct_payment_goes_to_the_registered_wallet_only() {
+    let mut setup = registered();
+
+    assert_error(
+        send(&mut setup, Route::default()),
+        ErrorCode::RecipientAccountRequired,
+    );
+    let route = Route {
+        escrow: true,
+        ..Route::direct(&setup.recipient, &setup.mint)
+    };
+    assert_error(send(&mut setup, route), ErrorCode::EscrowAccountsRequired);
+
+    let other = Pubkey::new_unique();
+    setup.svm.create_token_account(&other, &setup.mint, 0);
+    let route = Route::direct(&other, &setup.mint);
+    assert_error(send(&mut setup, route), ErrorCode::InvalidTokenAccount);
+}
+
+#[test]
+fn send_to_an_unregistered_email_escrows() {
+    let mut setup = Setup::new();
+    send(&mut setup, Route::escrow()).unwrap();
+
+    let transfer = pda::transfer_address(&setup.sender, &EMAIL_HASH);
+    let state = setup.transfer(&transfer);
+    assert_eq!(state.status, TransferStatus::Active);
+    assert_eq!(state.amount, AMOUNT);
+    assert_eq!(state.claim_code_hash, claim_code_hash(CLAIM_CODE));
+    assert_eq!(
+        state.expiry,
+        setup.svm.clock().unix_timestamp + EXPIRY_HOURS * 3600
+    );
+    assert!(!state.in_inbox);
+    assert_eq!(
+        setup.svm.token_balance(&pda::escrow_address(&transfer)),
+        AMOUNT
+    );
+    assert_eq!(setup.svm.events::<TransferCreated>().len(), 1);
+
+    // And is claimed like any other transfer
+    setup.claim(&transfer, CLAIM_CODE).unwrap();
+}
+
+#[test]
+fn escrow_fallback_needs_the_escrow_accounts() {
+    let mut setup = Setup::new();
+    assert_error(
+        send(&mut setup, Route::default()),
+        ErrorCode::EscrowAccountsRequired,
+    );
+    // A token account for an unregistered email is no way around it
+    let route = Route::direct(&setup.recipient, &setup.mint);
+    assert_error(send(&mut setup, route), ErrorCode::EscrowAccountsRequired);
+}
+
+#[test]
+fn escrow_fallback_is_tracked_by_an_open_inbox() {
+    let mut setup = Setup::new();
+    setup.open_inbox();
+    let route = Route {
+        inbox: true,
+        ..Route::escrow()
+    };
+    send(&mut setup, route).unwrap();
+
+    let transfer = pda::transfer_address(&setup.sender, &EMAIL_HASH);
+    assert!(setup.transfer(&transfer).in_inbox);
+    let inbox: payinbox::Inbox = setup
+        .svm
+        .anchor_account(&pda::inbox_address(&EMAIL_HASH))
+        .unwrap();
+    assert_eq!(inbox.pending_count, 1);
+}
@@ -0,0 +1,342 @@
+//! A reference model of `TransferStatus`, run side by side with the
+//! program on random operation sequences: every op must be accepted or
+//! rejected by both, with the same error, and leave the same status.
+//!
+//! The only edges are Active -> Claimed, Active -> Cancelled and
+//! Active -> Expired. A new status or instruction has to be added here
+//! before these tests pass again.
+
+mod common;
+
+use std::collections::HashMap;
+
+use anchor_lang::error::ErrorCode as AnchorErrorCode;
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_spl::associated_token::get_associated_token_address;
+use payinbox::{ErrorCode, TransferOptions, TransferStatus};
+use payinbox_client::hash::claim_code_hash;
+use payinbox_client::instructions::CreateTransfer;
+use payinbox_client::pda;
+use proptest::prelude::*;
+
+use common::*;
+
+/// Transfers are created for one of this many recipient emails.
+const SLOTS: u8 = 3;
+
+#[derive(Clone, Copy, Debug)]
+enum Code {
+    Right,
+    Wrong,
+    TooLong,
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Create {
+        slot: u8,
+        amount: u64,
+        expiry_hours: i64,
+        irrevocable: bool,
+    },
+    Claim {
+        slot: u8,
+        code: Code,
+    },
+    Cancel {
+        slot: u8,
+        by_sender: bool,
+    },
+    Reclaim {
+        slot: u8,
+    },
+    Warp {
+        seconds: i64,
+    },
+    /// Moves the clock to `offset` seconds from the slot's expiry
+    WarpToExpiry {
+        slot: u8,
+        offset: i64,
+    },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let slot = 0..SLOTS;
+    prop_oneof![
+        (
+            slot.clone(),
+            prop_oneof![Just(0), 1..=3 * AMOUNT],
+            -1i64..=170,
+            any::<bool>()
+        )
+            .prop_map(|(slot, amount, expiry_hours, irrevocable)| Op::Create {
+                slot,
+                amount,
+                expiry_hours,
+                irrevocable,
+            }),
+        (
+            slot.clone(),
+            prop_oneof![Just(Code::Right), Just(Code::Wrong), Just(Code::TooLong)]
+        )
+            .prop_map(|(slot, code)| Op::Claim { slot, code }),
+        (slot.clone(), any::<bool>()).prop_map(|(slot, by_sender)| Op::Cancel { slot, by_sender }),
+        slot.clone().prop_map(|slot| Op::Reclaim { slot }),
+        (0i64..=48 * 3600).prop_map(|seconds| Op::Warp { seconds }),
+        (slot, -1i64..=1).prop_map(|(slot, offset)| Op::WarpToExpiry { slot, offset }),
+    ]
+}
+
+/// What the model knows about a created transfer.
+#[derive(Clone, Copy, Debug)]
+struct ModelTransfer {
+    status: TransferStatus,
+    amount: u64,
+    expiry: i64,
+    irrevocable: bool,
+}
+
+/// The model's prediction for one op.
+#[derive(Debug)]
+enum Expected {
+    Ok,
+    Program(ErrorCode),
+    Anchor(AnchorErrorCode),
+    /// `init` of an account that already exists fails in the system
+    /// program
+    AccountInUse,
+}
+
+impl Expected {
+    fn result(&self) -> Result<(), ProgramError> {
+        match self {
+            Expected::Ok => Ok(()),
+            Expected::Program(error) => Err(ProgramError::Custom(u32::from(*error))),
+            Expected::Anchor(error) => Err(ProgramError::Custom(u32::from(*error))),
+            Expected::AccountInUse => Err(ProgramError::Custom(0)),
+        }
+    }
+}
+
+struct Model {
+    now: i64,
+    sender_balance: u64,
+    transfers: HashMap<u8, ModelTransfer>,
+}
+
+impl Model {
+    fn new(now: i64) -> Self {
+        Self {
+            now,
+            sender_balance: SENDER_BALANCE,
+            transfers: HashMap::new(),
+        }
+    }
+
+    /// Predicts the outcome of `op` and applies it if it succeeds. `None`
+    /// for ops that aren't submitted: clock moves, and ops on transfers
+    /// that were never created.
+    fn apply(&mut self, op: &Op) -> Option<Expected> {
+        match *op {
+            Op::Create {
+                slot,
+                amount,
+                expiry_hours,
+                irrevocable,
+            } => {
+                if self.transfers.contains_key(&slot) {
+                    return Some(Expected::AccountInUse);
+                }
+                // Account constraints run before the handler's checks.
+                if amount > self.sender_balance {
+                    return Some(Expected::Program(ErrorCode::InsufficientFunds));
+                }
+                if amount == 0 {
+                    return Some(Expected::Program(ErrorCode::InvalidAmount));
+                }
+                if !(1..=168).contains(&expiry_hours) {
+                    return Some(Expected::Program(ErrorCode::InvalidExpiry));
+                }
+                self.sender_balance -= amount;
+                self.transfers.insert(
+                    slot,
+                    ModelTransfer {
+                        status: TransferStatus::Active,
+                        amount,
+                        expiry: self.now + expiry_hours * 3600,
+                        irrevocable,
+                    },
+                );
+                Some(Expected::Ok)
+            }
+            Op::Claim { slot, code } => {
+                let now = self.now;
+                let transfer = self.transfers.get_mut(&slot)?;
+                let outcome = if let Some(error) = Self::escrow_closed(transfer) {
+                    error
+                } else if let Code::TooLong = code {
+                    Expected::Program(ErrorCode::ClaimCodeTooLong)
+                } else if let Code::Wrong = code {
+                    Expected::Program(ErrorCode::InvalidClaimCode)
+                } else if now >= transfer.expiry {
+                    Expected::Program(ErrorCode::TransferExpired)
+                } else {
+                    transfer.status = TransferStatus::Claimed;
+                    Expected::Ok
+                };
+                Some(outcome)
+            }
+            Op::Cancel { slot, by_sender } => {
+                let transfer = self.transfers.get_mut(&slot)?;
+                // Cancelling is allowed after expiry, until someone reclaims.
+                let outcome = if let Some(error) = Self::escrow_closed(transfer) {
+                    error
+                } else if !by_sender {
+                    Expected::Program(ErrorCode::Unauthorized)
+                } else if transfer.irrevocable {
+                    Expected::Program(ErrorCode::IrrevocableTransfer)
+                } else {
+                    transfer.status = TransferStatus::Cancelled;
+                    self.sender_balance += transfer.amount;
+                    Expected::Ok
+                };
+                Some(outcome)
+            }
+            Op::Reclaim { slot } => {
+                let now = self.now;
+                let transfer = self.transfers.get_mut(&slot)?;
+                let outcome = if let Some(error) = Self::escrow_closed(transfer) {
+                    error
+                } else if now < transfer.expiry {
+                    Expected::Program(ErrorCode::NotExpired)
+                } else {
+                    transfer.status = TransferStatus::Expired;
+                    self.sender_balance += transfer.amount;
+                    Expected::Ok
+                };
+                Some(outcome)
+            }
+            Op::Warp { seconds } => {
+                self.now += seconds;
+                None
+            }
+            Op::WarpToExpiry { slot, offset } => {
+                if let Some(transfer) = self.transfers.get(&slot) {
+                    self.now = transfer.expiry + offset;
+                }
+                None
+            }
+        }
+    }
+
+    /// Every terminal status closes the escrow, so claim, cancel and
+    /// reclaim fail loading it before any status check.
+    fn escrow_closed(transfer: &ModelTransfer) -> Option<Expected> {
+        match transfer.status {
+            TransferStatus::Active => None,
+            TransferStatus::Claimed | TransferStatus::Cancelled | TransferStatus::Expired => {
+                Some(Expected::Anchor(AnchorErrorCode::AccountNotInitialized))
+            }
+        }
+    }
+}
+
+fn email_hash(slot: u8) -> [u8; 32] {
+    [slot + 1; 32]
+}
+
+fn transfer_address(setup: &Setup, slot: u8) -> Pubkey {
+    pda::transfer_address(&setup.sender, &email_hash(slot))
+}
+
+/// Submits `op` to the program. `None` where the model skips it too.
+fn execute(setup: &mut Setup, op: &Op) -> Option<Result<(), ProgramError>> {
+    let created = |setup: &Setup, slot| {
+        let transfer = transfer_address(setup, slot);
+        setup.svm.account(&transfer).map(|_| transfer)
+    };
+    match *op {
+        Op::Create {
+            slot,
+            amount,
+            expiry_hours,
+            irrevocable,
+        } => {
+            let ix = CreateTransfer::new(
+                setup.sender,
+                setup.mint,
+                email_hash(slot),
+                claim_code_hash(CLAIM_CODE),
+                amount,
+                expiry_hours,
+            )
+            .options(TransferOptions {
+                irrevocable,
+                ..TransferOptions::default()
+            })
+            .instruction();
+            Some(setup.svm.process_instruction(ix))
+        }
+        Op::Claim { slot, code } => {
+            let transfer = created(setup, slot)?;
+            let code = match code {
+                Code::Right => CLAIM_CODE.to_string(),
+                Code::Wrong => "incorrect-horse".to_string(),
+                Code::TooLong => "x".repeat(257),
+            };
+            Some(setup.claim(&transfer, &code))
+        }
+        Op::Cancel { slot, by_sender } => {
+            let transfer = created(setup, slot)?;
+            let authority = if by_sender {
+                setup.sender
+            } else {
+                setup.recipient
+            };
+            Some(setup.cancel(&transfer, authority))
+        }
+        Op::Reclaim { slot } => {
+            let transfer = created(setup, slot)?;
+            Some(setup.reclaim(&transfer, setup.recipient))
+        }
+        Op::Warp { seconds } => {
+            setup.svm.advance_clock(seconds);
+            None
+        }
+        Op::WarpToExpiry { slot, offset } => {
+            if let Some(transfer) = created(setup, slot) {
+                let expiry = setup.expiry(&transfer);
+                setup.svm.set_unix_timestamp(expiry + offset);
+            }
+            None
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn program_matches_status_model(ops in prop::collection::vec(op(), 1..40)) {
+        let mut setup = Setup::new();
+        let mut model = Model::new(setup.svm.clock().unix_timestamp);
+
+        for (step, op) in ops.iter().enumerate() {
+            let expected = model.apply(op);
+            let actual = execute(&mut setup, op);
+            prop_assert_eq!(
+                actual,
+                expected.as_ref().map(Expected::result),
+                "step {}: {:?} (expected {:?})",
+                step,
+                op,
+                expected
+            );
+
+            for (slot, transfer) in &model.transfers {
+                let address = transfer_address(&setup, *slot);
+                prop_assert_eq!(setup.transfer(&address).status, transfer.status);
+            }
+            let sender_ata = get_associated_token_address(&setup.sender, &setup.mint);
+            prop_assert_eq!(setup.svm.token_balance(&sender_ata), model.sender_balance);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Indexer for the payinbox program.
+//!
+//! Reads the program's transactions in order, decodes the
+//! `TransferCreated`, `TransferClaimed`, `TransferCancelled`,
+//! `TransferReclaimed` and `GroupTransferRefunded` events from their inner
+//! instructions (the program emits them with `emit_cpi!`) and materializes
+//! them into a SQLite table keyed by transfer address:
+//!
+//! - [`store`]: the `transfers` table and the cursor (last processed
+//!   signature) the next run resumes from
+//! - [`source`]: transactions from an RPC node or from recorded fixtures
+
+pub mod source;
+pub mod store;
+
+use anyhow::Result;
+
+use source::Source;
+use store::Store;
+
+/// Outcome of one [`sync`] pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyncStats {
+    pub transactions: usize,
+    pub events: usize,
+}
+
+/// Processes every transaction after the store's cursor, oldest first.
+///
+/// Each transaction is committed together with the cursor, so an
+/// interrupted pass resumes exactly where it stopped. The pass ends early
+/// at a transaction the source can't return yet.
+pub fn sync(store: &mut Store, source: &impl Source) -> Result<SyncStats> {
+    let mut stats = SyncStats::default();
+    let cursor = store.cursor()?;
+    for signature in source.signatures_after(cursor.as_deref())? {
+        let Some(record) = source.transaction(&signature)? else {
+            break;
+        };
+        stats.events += store.apply(&signature, &record)?;
+        stats.transactions += 1;
+    }
+    Ok(stats)
+}
@@ -0,0 +1,95 @@
+//! `payinbox-indexer`: keeps a SQLite index of payinbox transfers up to
+//! date with an RPC node, or builds one from recorded transactions.
+
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use payinbox_client::rpc::RpcClient;
+use payinbox_indexer::source::FixtureSource;
+use payinbox_indexer::store::Store;
+use payinbox_indexer::sync;
+
+#[derive(Parser)]
+#[command(
+    name = "payinbox-indexer",
+    version,
+    about = "Index payinbox transfer events into SQLite"
+)]
+struct Cli {
+    /// RPC endpoint
+    #[arg(
+        long,
+        env = "PAYINBOX_RPC_URL",
+        default_value = "https://api.devnet.solana.com"
+    )]
+    url: String,
+
+    /// SQLite database file
+    #[arg(
+        long,
+        env = "PAYINBOX_INDEX_DB",
+        default_value = "payinbox-index.sqlite"
+    )]
+    db: PathBuf,
+
+    /// Seconds between polls
+    #[arg(long, default_value_t = 5)]
+    poll_interval: u64,
+
+    /// Catch up once and exit instead of polling
+    #[arg(long)]
+    once: bool,
+
+    /// Replay recorded transactions from a JSON file instead of the RPC node
+    #[arg(long)]
+    fixtures: Option<PathBuf>,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let mut store = Store::open(&cli.db)?;
+
+    if let Some(path) = &cli.fixtures {
+        let json =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let stats = sync(&mut store, &FixtureSource::from_json(&json)?)?;
+        report(&store, stats.transactions, stats.events)?;
+        return Ok(());
+    }
+
+    let rpc = RpcClient::new(&cli.url);
+    loop {
+        match sync(&mut store, &rpc) {
+            Ok(stats) => {
+                if stats.transactions > 0 || cli.once {
+                    report(&store, stats.transactions, stats.events)?;
+                }
+            }
+            // A poll failing (node unreachable, rate limited) is retried
+            // from the same cursor on the next one.
+            Err(err) if !cli.once => eprintln!("error: {err:#}"),
+            Err(err) => return Err(err),
+        }
+        if cli.once {
+            return Ok(());
+        }
+        sleep(Duration::from_secs(cli.poll_interval));
+    }
+}
+
+fn report(store: &Store, transactions: usize, events: usize) -> Result<()> {
+    let counts = store
+        .status_counts()?
+        .iter()
+        .map(|(status, count)| format!("{status}={count}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    println!(
+        "Indexed {transactions} transactions ({events} events), cursor {}; {counts}",
+        store.cursor()?.as_deref().unwrap_or("-")
+    );
+    Ok(())
+}
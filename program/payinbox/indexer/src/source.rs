@@ -0,0 +1,110 @@
+//! Where the indexer reads program transactions from.
+
+use anyhow::{bail, Context, Result};
+use payinbox_client::rpc::{RpcClient, TransactionRecord};
+use serde_json::Value;
+
+/// Signatures fetched per `getSignaturesForAddress` page (the RPC maximum).
+const PAGE_SIZE: usize = 1000;
+
+/// Transactions that invoked the payinbox program.
+pub trait Source {
+    /// Signatures of the transactions after `cursor` (all of them if
+    /// `None`), oldest first.
+    fn signatures_after(&self, cursor: Option<&str>) -> Result<Vec<String>>;
+
+    /// The transaction `signature`, or `None` if it isn't available yet.
+    fn transaction(&self, signature: &str) -> Result<Option<TransactionRecord>>;
+}
+
+impl Source for RpcClient {
+    fn signatures_after(&self, cursor: Option<&str>) -> Result<Vec<String>> {
+        let mut signatures = Vec::new();
+        let mut before: Option<String> = None;
+        loop {
+            let page = self
+                .get_signatures_for_address(&payinbox::ID, before.as_deref(), cursor, PAGE_SIZE)
+                .context("fetching program signatures")?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            before = Some(last.signature.clone());
+            let full = page.len() == PAGE_SIZE;
+            signatures.extend(page.into_iter().map(|info| info.signature));
+            if !full {
+                break;
+            }
+        }
+        signatures.reverse();
+        Ok(signatures)
+    }
+
+    fn transaction(&self, signature: &str) -> Result<Option<TransactionRecord>> {
+        self.get_transaction(signature)
+            .with_context(|| format!("fetching transaction {signature}"))
+    }
+}
+
+/// Recorded transactions, replayed in order.
+///
+/// The JSON form is an array of `{"signature": ..., "transaction": ...}`
+/// objects, oldest first, where `transaction` is the `getTransaction`
+/// result as the node returned it.
+#[derive(Default)]
+pub struct FixtureSource {
+    transactions: Vec<(String, TransactionRecord)>,
+}
+
+impl FixtureSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses recorded transactions.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let entries: Vec<Value> = serde_json::from_str(json).context("parsing fixtures")?;
+        let mut source = Self::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let signature = entry["signature"]
+                .as_str()
+                .with_context(|| format!("fixture {index}: missing signature"))?;
+            let record = TransactionRecord::from_rpc(&entry["transaction"])
+                .with_context(|| format!("fixture {index}: invalid transaction"))?;
+            source.push(signature, record);
+        }
+        Ok(source)
+    }
+
+    /// Appends a transaction newer than all recorded ones.
+    pub fn push(&mut self, signature: impl Into<String>, record: TransactionRecord) {
+        self.transactions.push((signature.into(), record));
+    }
+}
+
+impl Source for FixtureSource {
+    fn signatures_after(&self, cursor: Option<&str>) -> Result<Vec<String>> {
+        let start = match cursor {
+            None => 0,
+            Some(cursor) => match self
+                .transactions
+                .iter()
+                .position(|(signature, _)| signature == cursor)
+            {
+                Some(index) => index + 1,
+                None => bail!("cursor {cursor} is not among the fixtures"),
+            },
+        };
+        Ok(self.transactions[start..]
+            .iter()
+            .map(|(signature, _)| signature.clone())
+            .collect())
+    }
+
+    fn transaction(&self, signature: &str) -> Result<Option<TransactionRecord>> {
+        Ok(self
+            .transactions
+            .iter()
+            .find(|(recorded, _)| recorded == signature)
+            .map(|(_, record)| record.clone()))
+    }
+}
@@ -0,0 +1,291 @@
+//! SQLite tables the indexer materializes events into.
+
+use std::path::Path;
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{bail, Context, Result};
+use payinbox_client::events::{parse_inner_instructions, TransferEvent};
+use payinbox_client::rpc::TransactionRecord;
+use rusqlite::types::Type;
+use rusqlite::{named_params, Connection, OptionalExtension, Row};
+
+/// Stored in `PRAGMA user_version`; bumped with every table change.
+const SCHEMA_VERSION: i64 = 2;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transfers (
+    address             TEXT PRIMARY KEY,
+    sender              TEXT NOT NULL,
+    email_hash          TEXT NOT NULL,
+    token_mint          TEXT NOT NULL,
+    amount              TEXT,
+    irrevocable         INTEGER,
+    created_at          INTEGER NOT NULL,
+    expiry              INTEGER NOT NULL,
+    status              TEXT NOT NULL,
+    paid_to             TEXT,
+    paid_amount         TEXT,
+    created_slot        INTEGER,
+    created_signature   TEXT,
+    finalized_at        INTEGER,
+    finalized_slot      INTEGER,
+    finalized_signature TEXT
+);
+CREATE INDEX IF NOT EXISTS transfers_by_status ON transfers (status, expiry);
+CREATE INDEX IF NOT EXISTS transfers_by_email ON transfers (email_hash);
+CREATE TABLE IF NOT EXISTS cursor (
+    id        INTEGER PRIMARY KEY CHECK (id = 0),
+    signature TEXT NOT NULL,
+    slot      INTEGER NOT NULL
+);
+";
+
+/// A row of the `transfers` table.
+///
+/// Token amounts are stored as decimal TEXT: they span all of `u64`, which
+/// SQLite integers can't hold.
+///
+/// Every event names the transfer's sender, email hash, mint, creation
+/// time and expiry. The fields only `TransferCreated` carries are `None`
+/// while the creation hasn't been indexed (it predates the cursor the
+/// index was started from).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferRow {
+    pub address: String,
+    pub sender: String,
+    /// Hex-encoded
+    pub email_hash: String,
+    pub token_mint: String,
+    /// Amount escrowed at creation
+    pub amount: Option<u64>,
+    pub irrevocable: Option<bool>,
+    pub created_at: i64,
+    pub expiry: i64,
+    /// `active`, `claimed`, `cancelled` or `expired`
+    pub status: String,
+    /// Recipient, refund owner, reclaim recipient or last refunded
+    /// contributor, once finalized
+    pub paid_to: Option<String>,
+    pub paid_amount: Option<u64>,
+    pub created_slot: Option<u64>,
+    pub created_signature: Option<String>,
+    pub finalized_at: Option<i64>,
+    pub finalized_slot: Option<u64>,
+    pub finalized_signature: Option<String>,
+}
+
+/// The index database.
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    /// Opens (creating if needed) the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Connection::open(path)
+            .map_err(anyhow::Error::from)
+            .and_then(Self::init)
+            .with_context(|| format!("opening {}", path.display()))
+    }
+
+    /// An empty database that lives as long as the `Store`.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        let tables: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'transfers'",
+            [],
+            |row| row.get(0),
+        )?;
+        if tables > 0 && version != SCHEMA_VERSION {
+            bail!(
+                "index has schema version {version}, expected {SCHEMA_VERSION}; \
+                 delete it to rebuild"
+            );
+        }
+        conn.execute_batch(SCHEMA)
+            .context("creating the index tables")?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self { conn })
+    }
+
+    /// Signature of the last processed transaction.
+    pub fn cursor(&self) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row("SELECT signature FROM cursor WHERE id = 0", [], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    /// Applies the events of transaction `signature` and moves the cursor
+    /// to it, atomically. Failed transactions only move the cursor.
+    /// Returns the number of events applied.
+    pub fn apply(&mut self, signature: &str, record: &TransactionRecord) -> Result<usize> {
+        let events = if record.failed {
+            Vec::new()
+        } else {
+            parse_inner_instructions(&record.inner_instructions)
+        };
+
+        let tx = self.conn.transaction()?;
+        for event in &events {
+            apply_event(&tx, event, signature, record.slot)
+                .with_context(|| format!("applying an event of {signature}"))?;
+        }
+        tx.execute(
+            "INSERT INTO cursor (id, signature, slot) VALUES (0, ?1, ?2)
+             ON CONFLICT (id) DO UPDATE SET signature = excluded.signature, slot = excluded.slot",
+            (signature, to_sql(record.slot)?),
+        )?;
+        tx.commit()?;
+        Ok(events.len())
+    }
+
+    /// The row of the transfer at `address`.
+    pub fn transfer(&self, address: &Pubkey) -> Result<Option<TransferRow>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT address, sender, email_hash, token_mint, amount, irrevocable,
+                        created_at, expiry, status, paid_to, paid_amount, created_slot,
+                        created_signature, finalized_at, finalized_slot, finalized_signature
+                 FROM transfers WHERE address = ?1",
+                [address.to_string()],
+                |row| {
+                    Ok(TransferRow {
+                        address: row.get(0)?,
+                        sender: row.get(1)?,
+                        email_hash: row.get(2)?,
+                        token_mint: row.get(3)?,
+                        amount: get_amount(row, 4)?,
+                        irrevocable: row.get(5)?,
+                        created_at: row.get(6)?,
+                        expiry: row.get(7)?,
+                        status: row.get(8)?,
+                        paid_to: row.get(9)?,
+                        paid_amount: get_amount(row, 10)?,
+                        created_slot: row.get::<_, Option<i64>>(11)?.map(|v| v as u64),
+                        created_signature: row.get(12)?,
+                        finalized_at: row.get(13)?,
+                        finalized_slot: row.get::<_, Option<i64>>(14)?.map(|v| v as u64),
+                        finalized_signature: row.get(15)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// Number of indexed transfers per status.
+    pub fn status_counts(&self) -> Result<Vec<(String, u64)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT status, COUNT(*) FROM transfers GROUP BY status ORDER BY status")?;
+        let counts = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?
+            .collect::<Result<_, _>>()?;
+        Ok(counts)
+    }
+}
+
+/// SQLite integers are signed; slots above `i64::MAX` can't occur on
+/// chain, so they are rejected rather than wrapped.
+fn to_sql(value: u64) -> Result<i64> {
+    i64::try_from(value).context("value out of SQLite integer range")
+}
+
+/// Reads a token amount stored as decimal TEXT.
+fn get_amount(row: &Row, index: usize) -> rusqlite::Result<Option<u64>> {
+    row.get::<_, Option<String>>(index)?
+        .map(|amount| {
+            amount.parse().map_err(|error| {
+                rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(error))
+            })
+        })
+        .transpose()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn apply_event(
+    tx: &rusqlite::Transaction,
+    event: &TransferEvent,
+    signature: &str,
+    slot: u64,
+) -> Result<()> {
+    let header = event.header();
+    let (status, paid_to, paid_amount) = match event {
+        TransferEvent::Created(created) => {
+            tx.execute(
+                "INSERT INTO transfers (address, sender, email_hash, token_mint, amount,
+                                       irrevocable, created_at, expiry, status, created_slot,
+                                       created_signature)
+                 VALUES (:address, :sender, :email_hash, :token_mint, :amount, :irrevocable,
+                         :created_at, :expiry, 'active', :slot, :signature)
+                 ON CONFLICT (address) DO UPDATE SET
+                     amount = excluded.amount,
+                     irrevocable = excluded.irrevocable,
+                     created_slot = excluded.created_slot,
+                     created_signature = excluded.created_signature",
+                named_params! {
+                    ":address": header.transfer.to_string(),
+                    ":sender": header.sender.to_string(),
+                    ":email_hash": hex(&header.email_hash),
+                    ":token_mint": header.token_mint.to_string(),
+                    ":amount": created.amount.to_string(),
+                    ":irrevocable": created.irrevocable,
+                    ":created_at": header.created_at,
+                    ":expiry": header.expiry,
+                    ":slot": to_sql(slot)?,
+                    ":signature": signature,
+                },
+            )?;
+            return Ok(());
+        }
+        TransferEvent::Claimed(claimed) => ("claimed", claimed.recipient, claimed.amount),
+        TransferEvent::Cancelled(cancelled) => ("cancelled", cancelled.refund_to, cancelled.amount),
+        TransferEvent::Reclaimed(reclaimed) => ("expired", reclaimed.recipient, reclaimed.amount),
+        TransferEvent::GroupRefunded(refunded) => {
+            ("expired", refunded.contributor, refunded.amount)
+        }
+    };
+
+    // The row doesn't exist yet if the index was started after the
+    // transfer was created.
+    tx.execute(
+        "INSERT INTO transfers (address, sender, email_hash, token_mint, created_at, expiry,
+                               status, paid_to, paid_amount, finalized_at, finalized_slot,
+                               finalized_signature)
+         VALUES (:address, :sender, :email_hash, :token_mint, :created_at, :expiry, :status,
+                 :paid_to, :paid_amount, :finalized_at, :slot, :signature)
+         ON CONFLICT (address) DO UPDATE SET
+             status = excluded.status,
+             paid_to = excluded.paid_to,
+             paid_amount = excluded.paid_amount,
+             finalized_at = excluded.finalized_at,
+             finalized_slot = excluded.finalized_slot,
+             finalized_signature = excluded.finalized_signature",
+        named_params! {
+            ":address": header.transfer.to_string(),
+            ":sender": header.sender.to_string(),
+            ":email_hash": hex(&header.email_hash),
+            ":token_mint": header.token_mint.to_string(),
+            ":created_at": header.created_at,
+            ":expiry": header.expiry,
+            ":status": status,
+            ":paid_to": paid_to.to_string(),
+            ":paid_amount": paid_amount.to_string(),
+            ":finalized_at": header.timestamp,
+            ":slot": to_sql(slot)?,
+            ":signature": signature,
+        },
+    )?;
+    Ok(())
+}
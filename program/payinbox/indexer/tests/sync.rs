@@ -0,0 +1,383 @@
+//! Indexes transactions run in the in-process runtime, so the events are
+//! the ones the program really emits.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token;
+use payinbox::TransferAccount;
+use payinbox_client::hash::claim_code_hash;
+use payinbox_client::instructions::{
+    CancelTransfer, ClaimTransfer, CreateTransfer, ReclaimExpired,
+};
+use payinbox_client::pda;
+use payinbox_client::rpc::{InnerInstruction, TransactionRecord};
+use payinbox_harness::Svm;
+use payinbox_indexer::source::{FixtureSource, Source};
+use payinbox_indexer::store::Store;
+use payinbox_indexer::{sync, SyncStats};
+
+const AMOUNT: u64 = 1_000_000;
+const CLAIM_CODE: &str = "correct-horse-battery-staple";
+const EXPIRY_HOURS: i64 = 72;
+
+/// A sender and recipient whose transactions are recorded as fixtures.
+struct Chain {
+    svm: Svm,
+    mint: Pubkey,
+    sender: Pubkey,
+    recipient: Pubkey,
+    source: FixtureSource,
+    next_signature: u32,
+}
+
+impl Chain {
+    fn new() -> Self {
+        let mut svm = Svm::new();
+        let mint = svm.create_mint(&Pubkey::new_unique(), 6);
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        svm.airdrop(&sender, 10_000_000_000);
+        svm.airdrop(&recipient, 1_000_000_000);
+        svm.create_token_account(&sender, &mint, 10 * AMOUNT);
+        svm.create_token_account(&recipient, &mint, 0);
+        Self {
+            svm,
+            mint,
+            sender,
+            recipient,
+            source: FixtureSource::new(),
+            next_signature: 0,
+        }
+    }
+
+    /// Runs `ix` and records it, failed or not. Returns its signature.
+    fn record(&mut self, ix: Instruction) -> String {
+        let failed = self.svm.process_instruction(ix).is_err();
+        let clock = self.svm.clock();
+        let record = TransactionRecord {
+            slot: clock.slot,
+            block_time: Some(clock.unix_timestamp),
+            failed,
+            logs: self.svm.logs().to_vec(),
+            inner_instructions: self
+                .svm
+                .inner_instructions()
+                .iter()
+                .map(|ix| InnerInstruction {
+                    program_id: ix.program_id,
+                    accounts: ix.accounts.iter().map(|meta| meta.pubkey).collect(),
+                    data: ix.data.clone(),
+                })
+                .collect(),
+        };
+        self.next_signature += 1;
+        let signature = format!("sig{}", self.next_signature);
+        self.source.push(&signature, record);
+        signature
+    }
+
+    fn create(&mut self, email: u8) -> Pubkey {
+        self.create_for(email, AMOUNT)
+    }
+
+    fn create_for(&mut self, email: u8, amount: u64) -> Pubkey {
+        let create = CreateTransfer::new(
+            self.sender,
+            self.mint,
+            [email; 32],
+            claim_code_hash(CLAIM_CODE),
+            amount,
+            EXPIRY_HOURS,
+        );
+        self.record(create.instruction());
+        create.transfer_address()
+    }
+
+    /// A group transfer of `AMOUNT` the sender alone contributes to.
+    fn create_group(&mut self, email: u8) -> Pubkey {
+        let transfer = pda::transfer_address(&self.sender, &[email; 32]);
+        let accounts = payinbox::accounts::CreateGroupTransfer {
+            transfer,
+            contribution: pda::contribution_address(&transfer, &self.sender),
+            organizer: self.sender,
+            organizer_token_account: get_associated_token_address(&self.sender, &self.mint),
+            token_mint: self.mint,
+            escrow_token_account: pda::escrow_address(&transfer),
+            token_program: token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        };
+        let args = payinbox::instruction::CreateGroupTransfer {
+            email_hash: [email; 32],
+            claim_code_hash: claim_code_hash(CLAIM_CODE),
+            amount: AMOUNT,
+            expiry_hours: EXPIRY_HOURS,
+            contribution_hours: EXPIRY_HOURS,
+        };
+        self.record(Instruction::new_with_bytes(
+            payinbox::ID,
+            &args.data(),
+            accounts.to_account_metas(None),
+        ));
+        transfer
+    }
+
+    fn refund_contribution(&mut self, transfer: &Pubkey) -> String {
+        let accounts = payinbox::accounts::RefundContribution {
+            transfer: *transfer,
+            contribution: pda::contribution_address(transfer, &self.sender),
+            contributor: self.sender,
+            contributor_token_account: get_associated_token_address(&self.sender, &self.mint),
+            escrow_token_account: pda::escrow_address(transfer),
+            organizer: self.sender,
+            token_program: token::ID,
+            event_authority: pda::event_authority_address(),
+            program: payinbox::ID,
+        };
+        self.record(Instruction::new_with_bytes(
+            payinbox::ID,
+            &payinbox::instruction::RefundContribution {}.data(),
+            accounts.to_account_metas(None),
+        ))
+    }
+
+    fn transfer(&self, address: &Pubkey) -> TransferAccount {
+        self.svm.anchor_account(address).expect("transfer exists")
+    }
+
+    fn claim(&mut self, transfer: &Pubkey, code: &str) -> String {
+        let ix = ClaimTransfer::new(&self.transfer(transfer), self.recipient, code).instruction();
+        self.record(ix)
+    }
+
+    fn cancel(&mut self, transfer: &Pubkey) -> String {
+        let ix = CancelTransfer::new(&self.transfer(transfer), self.sender).instruction();
+        self.record(ix)
+    }
+
+    fn reclaim(&mut self, transfer: &Pubkey) -> String {
+        let ix = ReclaimExpired::new(&self.transfer(transfer), self.recipient).instruction();
+        self.record(ix)
+    }
+}
+
+#[test]
+fn materializes_each_final_status() {
+    let mut chain = Chain::new();
+    let claimed = chain.create(1);
+    let cancelled = chain.create(2);
+    let reclaimed = chain.create(3);
+    let active = chain.create(4);
+    let claim_signature = chain.claim(&claimed, CLAIM_CODE);
+    chain.cancel(&cancelled);
+    chain.svm.advance_clock(EXPIRY_HOURS * 3600);
+    let reclaim_signature = chain.reclaim(&reclaimed);
+
+    let mut store = Store::open_in_memory().unwrap();
+    let stats = sync(&mut store, &chain.source).unwrap();
+    assert_eq!(
+        stats,
+        SyncStats {
+            transactions: 7,
+            events: 7
+        }
+    );
+    assert_eq!(store.cursor().unwrap().as_deref(), Some("sig7"));
+
+    let row = store.transfer(&active).unwrap().unwrap();
+    assert_eq!(row.status, "active");
+    assert_eq!(row.sender, chain.sender.to_string());
+    assert_eq!(row.email_hash, "04".repeat(32));
+    assert_eq!(row.token_mint, chain.mint.to_string());
+    assert_eq!(row.amount, Some(AMOUNT));
+    assert_eq!(row.expiry, chain.transfer(&active).expiry);
+    assert_eq!(row.irrevocable, Some(false));
+    assert_eq!(row.created_signature.as_deref(), Some("sig4"));
+    assert_eq!(row.finalized_signature, None);
+
+    let row = store.transfer(&claimed).unwrap().unwrap();
+    assert_eq!(row.status, "claimed");
+    assert_eq!(row.paid_to, Some(chain.recipient.to_string()));
+    assert_eq!(row.paid_amount, Some(AMOUNT));
+    assert_eq!(row.finalized_signature, Some(claim_signature));
+
+    let row = store.transfer(&cancelled).unwrap().unwrap();
+    assert_eq!(row.status, "cancelled");
+    assert_eq!(row.paid_to, Some(chain.sender.to_string()));
+
+    let row = store.transfer(&reclaimed).unwrap().unwrap();
+    assert_eq!(row.status, "expired");
+    assert_eq!(row.paid_to, Some(chain.sender.to_string()));
+    assert_eq!(row.finalized_signature, Some(reclaim_signature));
+    assert_eq!(row.finalized_at, Some(row.expiry));
+
+    assert_eq!(
+        store.status_counts().unwrap(),
+        ["active", "cancelled", "claimed", "expired"]
+            .map(|status| (status.to_string(), 1))
+            .to_vec()
+    );
+}
+
+#[test]
+fn last_group_refund_expires_the_transfer() {
+    let mut chain = Chain::new();
+    let transfer = chain.create_group(1);
+    chain.svm.advance_clock(EXPIRY_HOURS * 3600);
+    let refund_signature = chain.refund_contribution(&transfer);
+
+    let mut store = Store::open_in_memory().unwrap();
+    assert_eq!(
+        sync(&mut store, &chain.source).unwrap(),
+        SyncStats {
+            transactions: 2,
+            events: 2
+        }
+    );
+    let row = store.transfer(&transfer).unwrap().unwrap();
+    assert_eq!(row.status, "expired");
+    assert_eq!(row.paid_to, Some(chain.sender.to_string()));
+    assert_eq!(row.paid_amount, Some(AMOUNT));
+    assert_eq!(row.finalized_signature, Some(refund_signature));
+}
+
+#[test]
+fn stores_amounts_beyond_the_sqlite_integer_range() {
+    let mut chain = Chain::new();
+    chain.mint = chain.svm.create_mint(&Pubkey::new_unique(), 6);
+    chain
+        .svm
+        .create_token_account(&chain.sender, &chain.mint, u64::MAX);
+    chain
+        .svm
+        .create_token_account(&chain.recipient, &chain.mint, 0);
+    let transfer = chain.create_for(1, u64::MAX);
+    chain.claim(&transfer, CLAIM_CODE);
+
+    let mut store = Store::open_in_memory().unwrap();
+    assert_eq!(sync(&mut store, &chain.source).unwrap().events, 2);
+    let row = store.transfer(&transfer).unwrap().unwrap();
+    assert_eq!(row.status, "claimed");
+    assert_eq!(row.amount, Some(u64::MAX));
+    assert_eq!(row.paid_amount, Some(u64::MAX));
+}
+
+#[test]
+fn resumes_from_the_cursor() {
+    let path = std::env::temp_dir().join(format!("payinbox-index-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut chain = Chain::new();
+    let transfer = chain.create(1);
+    {
+        let mut store = Store::open(&path).unwrap();
+        assert_eq!(sync(&mut store, &chain.source).unwrap().transactions, 1);
+        assert_eq!(
+            sync(&mut store, &chain.source).unwrap(),
+            SyncStats::default()
+        );
+    }
+
+    chain.claim(&transfer, CLAIM_CODE);
+    let mut store = Store::open(&path).unwrap();
+    assert_eq!(store.cursor().unwrap().as_deref(), Some("sig1"));
+    assert_eq!(
+        sync(&mut store, &chain.source).unwrap(),
+        SyncStats {
+            transactions: 1,
+            events: 1
+        }
+    );
+    let row = store.transfer(&transfer).unwrap().unwrap();
+    assert_eq!(row.status, "claimed");
+    assert_eq!(row.created_signature.as_deref(), Some("sig1"));
+
+    drop(store);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn failed_transactions_only_move_the_cursor() {
+    let mut chain = Chain::new();
+    let transfer = chain.create(1);
+    chain.claim(&transfer, "incorrect-horse");
+
+    let mut store = Store::open_in_memory().unwrap();
+    assert_eq!(
+        sync(&mut store, &chain.source).unwrap(),
+        SyncStats {
+            transactions: 2,
+            events: 1
+        }
+    );
+    assert_eq!(store.cursor().unwrap().as_deref(), Some("sig2"));
+    assert_eq!(store.transfer(&transfer).unwrap().unwrap().status, "active");
+}
+
+#[test]
+fn finalization_without_creation_keeps_a_partial_row() {
+    let mut chain = Chain::new();
+    let transfer = chain.create(1);
+    chain.cancel(&transfer);
+
+    // An index started after the transfer was created only sees the cancel.
+    let mut source = FixtureSource::new();
+    let signature = chain
+        .source
+        .signatures_after(Some("sig1"))
+        .unwrap()
+        .remove(0);
+    source.push(
+        &signature,
+        chain.source.transaction(&signature).unwrap().unwrap(),
+    );
+
+    let mut store = Store::open_in_memory().unwrap();
+    sync(&mut store, &source).unwrap();
+    let row = store.transfer(&transfer).unwrap().unwrap();
+    assert_eq!(row.status, "cancelled");
+    assert_eq!(row.sender, chain.sender.to_string());
+    assert_eq!(row.email_hash, "01".repeat(32));
+    assert_eq!(row.expiry, chain.transfer(&transfer).expiry);
+    assert_eq!(row.amount, None);
+    assert_eq!(row.paid_amount, Some(AMOUNT));
+}
+
+#[test]
+fn replays_recorded_rpc_responses() {
+    let source = FixtureSource::from_json(include_str!("fixtures/transactions.json")).unwrap();
+    let mut store = Store::open_in_memory().unwrap();
+    let stats = sync(&mut store, &source).unwrap();
+    assert_eq!(
+        stats,
+        SyncStats {
+            transactions: 3,
+            events: 2
+        }
+    );
+    assert_eq!(
+        store.status_counts().unwrap(),
+        vec![("claimed".to_string(), 1)]
+    );
+}
+
+#[test]
+fn refuses_an_index_of_another_schema_version() {
+    let path =
+        std::env::temp_dir().join(format!("payinbox-index-old-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    drop(Store::open(&path).unwrap());
+    rusqlite::Connection::open(&path)
+        .unwrap()
+        .pragma_update(None, "user_version", 0)
+        .unwrap();
+
+    let error = Store::open(&path).err().expect("old index refused");
+    assert!(format!("{error:#}").contains("delete it to rebuild"));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,232 @@
+//! Groth16 proof verification over BN254 using the alt_bn128 syscalls.
+//!
+//! Points use the big-endian EIP-197 encoding the syscalls expect:
+//! G1 is `x || y` and G2 is `x.c1 || x.c0 || y.c1 || y.c0`. Public inputs
+//! are big-endian scalars that must be below the BN254 group order.
+
+use anchor_lang::prelude::*;
+use solana_bn254::prelude::{alt_bn128_addition, alt_bn128_multiplication, alt_bn128_pairing};
+
+use crate::ErrorCode;
+
+/// BN254 base field modulus, big-endian (used to negate G1 points)
+const FIELD_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d, 0x3c, 0x20, 0x8c, 0x16, 0xd8, 0x7c, 0xfd, 0x47,
+];
+
+/// A Groth16 proof as produced by the prover (`a` is not negated).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Groth16Proof {
+    /// Proof point A (G1)
+    pub a: [u8; 64],
+    /// Proof point B (G2)
+    pub b: [u8; 128],
+    /// Proof point C (G1)
+    pub c: [u8; 64],
+}
+
+/// Borrowed view of a Groth16 verifying key.
+pub struct VerifyingKey<'a> {
+    pub alpha_g1: &'a [u8; 64],
+    pub beta_g2: &'a [u8; 128],
+    pub gamma_g2: &'a [u8; 128],
+    pub delta_g2: &'a [u8; 128],
+    /// One G1 point per public input, plus the constant term first
+    pub ic: &'a [[u8; 64]],
+}
+
+/// Splits 32 bytes into two field elements (high and low 128 bits), so
+/// arbitrary hashes and pubkeys fit below the group order.
+pub fn split_to_field_elements(bytes: &[u8; 32]) -> [[u8; 32]; 2] {
+    let mut hi = [0u8; 32];
+    let mut lo = [0u8; 32];
+    hi[16..].copy_from_slice(&bytes[..16]);
+    lo[16..].copy_from_slice(&bytes[16..]);
+    [hi, lo]
+}
+
+/// Verifies `proof` against `vk` and `public_inputs`.
+///
+/// Checks `e(-A, B) * e(alpha, beta) * e(vk_x, gamma) * e(C, delta) == 1`
+/// where `vk_x = IC[0] + sum(input_i * IC[i + 1])`.
+///
+/// # Errors
+/// * `InvalidProof` - Input count mismatch, malformed points, or the
+///   pairing check failed
+pub fn verify(vk: &VerifyingKey, proof: &Groth16Proof, public_inputs: &[[u8; 32]]) -> Result<()> {
+    require!(
+        vk.ic.len() == public_inputs.len() + 1,
+        ErrorCode::InvalidProof
+    );
+
+    // === Linear Combination of Public Inputs ===
+    let mut vk_x = vk.ic[0];
+    for (input, ic) in public_inputs.iter().zip(&vk.ic[1..]) {
+        let mut mul_input = [0u8; 96];
+        mul_input[..64].copy_from_slice(ic);
+        mul_input[64..].copy_from_slice(input);
+        let product = alt_bn128_multiplication(&mul_input).map_err(|_| ErrorCode::InvalidProof)?;
+
+        let mut add_input = [0u8; 128];
+        add_input[..64].copy_from_slice(&vk_x);
+        add_input[64..].copy_from_slice(&product);
+        let sum = alt_bn128_addition(&add_input).map_err(|_| ErrorCode::InvalidProof)?;
+        vk_x.copy_from_slice(&sum);
+    }
+
+    // === Pairing Check ===
+    let mut pairing_input = Vec::with_capacity(4 * 192);
+    pairing_input.extend_from_slice(&negate_g1(&proof.a));
+    pairing_input.extend_from_slice(&proof.b);
+    pairing_input.extend_from_slice(vk.alpha_g1);
+    pairing_input.extend_from_slice(vk.beta_g2);
+    pairing_input.extend_from_slice(&vk_x);
+    pairing_input.extend_from_slice(vk.gamma_g2);
+    pairing_input.extend_from_slice(&proof.c);
+    pairing_input.extend_from_slice(vk.delta_g2);
+    let result = alt_bn128_pairing(&pairing_input).map_err(|_| ErrorCode::InvalidProof)?;
+
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    require!(result == one, ErrorCode::InvalidProof);
+
+    Ok(())
+}
+
+/// Negates a G1 point by replacing `y` with `p - y` (infinity stays as is).
+fn negate_g1(point: &[u8; 64]) -> [u8; 64] {
+    let mut negated = *point;
+    if point[32..].iter().all(|b| *b == 0) {
+        return negated;
+    }
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let mut diff = FIELD_MODULUS[i] as i16 - point[32 + i] as i16 - borrow;
+        borrow = (diff < 0) as i16;
+        if diff < 0 {
+            diff += 256;
+        }
+        negated[32 + i] = diff as u8;
+    }
+    negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fixed vectors from an 8-input test circuit (arkworks, seed 42):
+    // email_hash = [0x11; 32], domain_hash = [0x22; 32],
+    // dkim_key_hash = [0x33; 32], destination = DESTINATION.
+
+    const ALPHA_G1: &str = "16cde7672d240777d5e1e109af2a17cf9c6f65aabd05a76d82461ed92edabcfb21d9f38f3d392cb6605fe0986b27e2ce9c23dc0b8936d7af05eb213dfdaed020";
+    const BETA_G2: &str = "135410eb08cc99678e973f836aac67573b37106e8f6b65ddc7b2976fc16ad2eb2fa32830b84e4463e4dede31a16278eb742416eae4bef6b156903587cff983801489d87c0678fe268212b8a9f9e57aa5ee218a972b6aa066a6d9229f4fea9b991788fe169ad482a20fd2c4a70f3c65a55fed2022df58aca286c1acdf8c34c5f9";
+    const GAMMA_G2: &str = "2bbec1646afc4b6f9eaa3926da6b62021e69922d4f2ffcacf2896a7bb636d4a92cf2bb37e9ec0305b71e42f5cfb5a6117a30ba1086fa0600a9720def28ba4ee70d1b2b86dac682bd0e36ee8de9c63750ffeeb520c92681976e362d1b3dc0e85d062f3d3b4d162be8243a46c651e1fe735841bcaed0b7b2ec9d4979fa97d48ff0";
+    const DELTA_G2: &str = "23391ed97851cfb5d0bb6bc901ec00feaa36235fac6a8dba43d0ee75e12c2aa201135fa9c047229ca25627c2bf2194c043e03f34a8f6cc94b5ff1d61f19b9a7e137ac003c9aff19bd67336083096af7eee88535985cbb6e0b40d74e8404559d42d270a276a2590d9e1b2b8bd70447b7880e92942981c2b0136ac58bafb389d87";
+    const IC: [&str; 9] = [
+        "1c53e8d304d1fea670bb1a4faca61aa409ac1428766c219270ad166dc3936b5d099e6c6bde96c2469f88d9434eba3cd53a2cb1428ec699a757942d916cbc4488",
+        "0f3ac01352ada1e435fd6d4ac5827caa97dc8a5d5cce863107148abbd72ba07726cfe625e3f00f8a856c61e1a7d103894a8eb936c87a946ddbafc9781deec368",
+        "1463fddf5fb317c76bfcb7c8214659e7ce763fda12571ca04063d22249bf14140a99a67140a56cde6ebd63d99b45f266ce001f8bd5c3774b8c306ca59b56d1cf",
+        "18d3ba65db6c3c45779567590bec8690013fd8af5365e23289b718027a3a09392009e2b3653e464878459ec9852390157dfd896cd22e05c34c89f6ff54c54662",
+        "212ac6eac686c8979f3b5ffc7c9da7f05eafbc4a148672565e8db9c4a516fbf70b114c7ec376a58f1653df040d3bb55b3747defc1f36ac9df2d57b49827b5a27",
+        "2edd3f8cffdaf5ad78960e11d666e8f7a7ff4e369086cd41c2c5b34f40e226471118c0f8c19c630ec29cd049d9d1127398324c3c00a3f61d3476f05e0ddf3074",
+        "0d6e7a88e17f8ab1d4b949b6daa7ebfe4363496fc43ea078f84849edfba502ff2c4fb503d3560c305cf6f12fa3c4fd5ec455c923549736fc3e8b92d6e56c7cd4",
+        "0e6e761e8475554f95f5e2b73bc869b5dba967ce8f429668788e2312dd085771207f5b08de4f2a9e6bfd4a7dca3578ee56cdba21677911719e3813168f7ca7d7",
+        "2c1da4b644c3624762c2013251cd36991c14a085c58bbfbecf6d3d4fc601610b0d9f57a3cdfaeac7f1864de91288259bf822cc0ca916d10b570029775df3f0dd",
+    ];
+    const PROOF_A: &str = "1d682715c4fde423be448c19f6b68c69efd486662e2088f384f97ffc309165e9117fb8d88be3e8005e73e0b45d126eab29b310e15b33537161e9ea5f5299c5cd";
+    const PROOF_B: &str = "1a0b661b7da961eee4cca89c8c56b8157775eca10929d62a25fe0e16501d4a220ac9d9e979bca656f4127e8ea98a7a7171670bbb47d77098bdfbf81ece7104f122eaf094bc911edecd5ed92eb059960737afe5ae453c0d8284da295a8057c4642cdba7d1f0be21f3de3a805178ea7adf9a1df932a0f6ba50c72740645b36b9d2";
+    const PROOF_C: &str = "0eb4efc3ce27b6003912ff9ca9399b625556a9ca5e07eabacb11b05063523d3d1d6f2c83bc293d943f292b5d7db04df0ad331f1d04cdf93884ee453d01e9adca";
+    const DESTINATION: &str = "01080f161d242b323940474e555c636a71787f868d949ba2a9b0b7bec5ccd3da";
+
+    fn unhex<const N: usize>(s: &str) -> [u8; N] {
+        let mut out = [0u8; N];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap();
+        }
+        out
+    }
+
+    struct Fixture {
+        alpha_g1: [u8; 64],
+        beta_g2: [u8; 128],
+        gamma_g2: [u8; 128],
+        delta_g2: [u8; 128],
+        ic: Vec<[u8; 64]>,
+        proof: Groth16Proof,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            Self {
+                alpha_g1: unhex(ALPHA_G1),
+                beta_g2: unhex(BETA_G2),
+                gamma_g2: unhex(GAMMA_G2),
+                delta_g2: unhex(DELTA_G2),
+                ic: IC.iter().map(|p| unhex(p)).collect(),
+                proof: Groth16Proof {
+                    a: unhex(PROOF_A),
+                    b: unhex(PROOF_B),
+                    c: unhex(PROOF_C),
+                },
+            }
+        }
+
+        fn vk(&self) -> VerifyingKey<'_> {
+            VerifyingKey {
+                alpha_g1: &self.alpha_g1,
+                beta_g2: &self.beta_g2,
+                gamma_g2: &self.gamma_g2,
+                delta_g2: &self.delta_g2,
+                ic: &self.ic,
+            }
+        }
+    }
+
+    fn inputs(destination: &[u8; 32]) -> Vec<[u8; 32]> {
+        [[0x11u8; 32], [0x22; 32], [0x33; 32], *destination]
+            .iter()
+            .flat_map(split_to_field_elements)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_valid_proof() {
+        let fixture = Fixture::new();
+        let inputs = inputs(&unhex(DESTINATION));
+        assert!(verify(&fixture.vk(), &fixture.proof, &inputs).is_ok());
+    }
+
+    #[test]
+    fn rejects_other_destination() {
+        let fixture = Fixture::new();
+        let mut destination: [u8; 32] = unhex(DESTINATION);
+        destination[31] ^= 1;
+        let inputs = inputs(&destination);
+        assert!(verify(&fixture.vk(), &fixture.proof, &inputs).is_err());
+    }
+
+    #[test]
+    fn rejects_tampered_proof() {
+        let mut fixture = Fixture::new();
+        fixture.proof.c = fixture.proof.a;
+        let inputs = inputs(&unhex(DESTINATION));
+        assert!(verify(&fixture.vk(), &fixture.proof, &inputs).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_input_count() {
+        let fixture = Fixture::new();
+        let inputs = inputs(&unhex(DESTINATION));
+        assert!(verify(&fixture.vk(), &fixture.proof, &inputs[..7]).is_err());
+    }
+
+    #[test]
+    fn negation_round_trips() {
+        let a: [u8; 64] = unhex(PROOF_A);
+        assert_ne!(negate_g1(&a), a);
+        assert_eq!(negate_g1(&negate_g1(&a)), a);
+        assert_eq!(negate_g1(&[0u8; 64]), [0u8; 64]);
+    }
+}
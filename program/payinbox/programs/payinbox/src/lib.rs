@@ -17,9 +17,21 @@
 //!                        cancel/reclaim_expired() -> Sender (refund)
 //! ```
 
+pub mod groth16;
+
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, CloseAccount, Token, TokenAccount, Transfer};
+use anchor_lang::system_program;
+use anchor_spl::associated_token::{self, get_associated_token_address, AssociatedToken};
+use anchor_spl::token::{
+    self, spl_token::instruction::AuthorityType, CloseAccount, Mint, SetAuthority, Token,
+    TokenAccount, Transfer,
+};
+use solana_instructions_sysvar::{load_current_index_checked, load_instruction_at_checked};
 use solana_keccak_hasher as keccak;
+use solana_sdk_ids::{ed25519_program, secp256r1_program, sysvar};
+use solana_sha256_hasher as sha256;
+
+use groth16::Groth16Proof;
 
 declare_id!("14bVLKMUaYx9qL8NPNvhEJS4qtemH8hGZSDyF5qjXS8h");
 
@@ -36,9 +48,78 @@ const MIN_EXPIRY_SECONDS: i64 = 3600;
 /// Maximum claim code length to prevent memory/compute DoS
 const MAX_CLAIM_CODE_LEN: usize = 256;
 
+/// Maximum WebAuthn clientDataJSON length on passkey claims, which browsers
+/// pad with origin, extension and cross-origin fields well past a claim code
+const MAX_CLIENT_DATA_LEN: usize = 1024;
+
 /// Minimum amount to transfer (prevents dust attacks)
 const MIN_TRANSFER_AMOUNT: u64 = 1;
 
+/// Maximum number of claim codes on an M-of-N transfer
+const MAX_CLAIM_CODES: usize = 8;
+
+/// Maximum number of registered attestation verifiers
+const MAX_VERIFIERS: usize = 4;
+
+/// Maximum number of distinct mints tracked per inbox
+const MAX_INBOX_MINTS: usize = 8;
+
+/// Remaining accounts per transfer in `sweep_inbox`:
+/// transfer, escrow, destination token account, sender
+const SWEEP_ACCOUNTS_PER_TRANSFER: usize = 4;
+
+/// How long a `commit_claim` reservation lasts: 5 minutes
+const CLAIM_RESERVATION_SECONDS: i64 = 5 * 60;
+
+/// Reservations allowed per transfer, bounding how long commits can delay
+/// a cancel
+const MAX_CLAIM_RESERVATIONS: u8 = 3;
+
+/// Maximum token crank bounty: 5% of the transfer amount
+const MAX_CRANK_BOUNTY_BPS: u16 = 500;
+
+/// Basis point denominator
+const BPS_DENOMINATOR: u128 = 10_000;
+
+/// Schema version carried by the transfer lifecycle events. Bumped whenever
+/// their layout changes, so indexers can tell layouts apart.
+pub const TRANSFER_EVENT_VERSION: u8 = 1;
+
+/// Ed25519/secp256r1 instruction header: count + padding + one offsets struct
+const SIGNATURE_HEADER_LEN: usize = 16;
+
+/// Compressed secp256r1 public key length
+const PASSKEY_LEN: usize = 33;
+
+/// WebAuthn authenticator data: rpIdHash (32) + flags (1) + signCount (4)
+const MIN_AUTHENTICATOR_DATA_LEN: usize = 37;
+
+/// Authenticator flags required on passkey claims: user present + user verified
+const PASSKEY_REQUIRED_FLAGS: u8 = 0x01 | 0x04;
+
+/// Domain separator for wallet-binding attestations
+pub const BIND_ATTESTATION_DOMAIN: &[u8] = b"payinbox:bind:v1";
+
+/// Domain separator for wallet-registration attestations
+pub const REGISTER_ATTESTATION_DOMAIN: &[u8] = b"payinbox:register:v1";
+
+/// Domain separator for claim attestations
+pub const CLAIM_ATTESTATION_DOMAIN: &[u8] = b"payinbox:claim:v1";
+
+/// Domain separator for passkey claim challenges
+pub const PASSKEY_CHALLENGE_DOMAIN: &[u8] = b"payinbox:passkey:v1";
+
+/// Domain separator for the seed of a claim code's commit key, derived
+/// off-chain as the Ed25519 key with seed keccak(domain || claim_code)
+pub const COMMIT_KEY_DOMAIN: &[u8] = b"payinbox:commit:v1";
+
+/// Maximum number of active DKIM key hashes per domain (allows rotation)
+const MAX_DKIM_KEYS: usize = 4;
+
+/// Public inputs of the zk-DKIM circuit: email_hash, domain_hash,
+/// dkim_key_hash and destination, each split into two 128-bit halves
+pub const DKIM_PUBLIC_INPUTS: usize = 8;
+
 // ============================================================================
 // Program Instructions
 // ============================================================================
@@ -57,30 +138,26 @@ pub mod solrelay {
     /// * `claim_code_hash` - SHA256(claim_code) for verification
     /// * `amount` - Number of token base units to transfer
     /// * `expiry_hours` - Hours until transfer expires (1-168)
+    /// * `options` - Irrevocability and refund destinations (see `TransferOptions`)
+    ///
+    /// If the recipient's `Inbox` is passed, the transfer is added to its
+    /// pending count and per-mint totals.
     ///
     /// # Errors
     /// * `InvalidAmount` - Amount is zero
     /// * `InvalidExpiry` - Expiry not in valid range (1-168 hours)
     /// * `InsufficientFunds` - Sender doesn't have enough tokens
+    /// * `InboxFull` - Inbox already tracks the maximum number of mints
     pub fn create_transfer(
         ctx: Context<CreateTransfer>,
         email_hash: [u8; 32],
         claim_code_hash: [u8; 32],
         amount: u64,
         expiry_hours: i64,
+        options: TransferOptions,
     ) -> Result<()> {
         // === Input Validation ===
-        require!(amount >= MIN_TRANSFER_AMOUNT, ErrorCode::InvalidAmount);
-
-        // Safe multiplication with overflow check
-        let expiry_seconds = expiry_hours
-            .checked_mul(3600)
-            .ok_or(ErrorCode::InvalidExpiry)?;
-        
-        require!(
-            expiry_seconds >= MIN_EXPIRY_SECONDS && expiry_seconds <= MAX_EXPIRY_SECONDS,
-            ErrorCode::InvalidExpiry
-        );
+        let expiry_seconds = validate_create_args(amount, expiry_hours)?;
 
         // === Initialize Transfer State ===
         let transfer = &mut ctx.accounts.transfer;
@@ -100,6 +177,20 @@ pub mod solrelay {
         transfer.status = TransferStatus::Active;
         transfer.bump = ctx.bumps.transfer;
         transfer.escrow_bump = ctx.bumps.escrow_token_account;
+        transfer.claim_mode = ClaimMode::SingleCode;
+        transfer.apply_options(&options)?;
+        deposit_crank_bounty(
+            &ctx.accounts.system_program,
+            &ctx.accounts.sender,
+            &transfer.to_account_info(),
+            options.crank_bounty_lamports,
+        )?;
+
+        // === Track in Recipient Inbox ===
+        if let Some(inbox) = ctx.accounts.inbox.as_mut() {
+            inbox.add_transfer(transfer.token_mint, amount)?;
+            transfer.in_inbox = true;
+        }
 
         // === Execute Token Transfer ===
         let cpi_ctx = CpiContext::new(
@@ -113,13 +204,7 @@ pub mod solrelay {
         token::transfer(cpi_ctx, amount)?;
 
         // === Emit Event ===
-        emit!(TransferCreated {
-            transfer: transfer.key(),
-            sender: transfer.sender,
-            token_mint: transfer.token_mint,
-            amount,
-            expiry: transfer.expiry,
-        });
+        emit_cpi!(TransferCreated::new(transfer.key(), transfer, amount));
 
         Ok(())
     }
@@ -129,8 +214,17 @@ pub mod solrelay {
     /// Verifies the claim code against the stored hash and transfers
     /// the escrowed tokens to the recipient's token account.
     ///
+    /// If a passkey is set on the transfer (or, failing that, on the inbox
+    /// tracking it), the passkey replaces the claim code: `claim_code`
+    /// carries the WebAuthn clientDataJSON, and the instruction right before
+    /// this one must be a secp256r1 signature by the passkey over
+    /// `authenticatorData || SHA256(clientDataJSON)`. The client data's
+    /// challenge must be `passkey_challenge(transfer, recipient)`, binding
+    /// the claim to the destination wallet.
+    ///
     /// # Arguments
-    /// * `claim_code` - The plaintext secret claim code
+    /// * `claim_code` - The plaintext secret claim code, or the
+    ///   clientDataJSON for passkey claims
     ///
     /// # Security
     /// - Uses constant-time comparison to prevent timing attacks
@@ -138,26 +232,59 @@ pub mod solrelay {
     ///
     /// # Errors
     /// * `ClaimCodeTooLong` - Code exceeds 256 bytes
+    /// * `ClientDataTooLong` - clientDataJSON exceeds 1024 bytes
     /// * `InvalidClaimCode` - Hash doesn't match
+    /// * `InvalidPasskeySignature` - Passkey set but no valid assertion
+    /// * `ClaimReserved` - Another claimer holds a live `commit_claim` reservation
     /// * `TransferExpired` - Past expiry timestamp
-    /// * `AlreadyClaimed` / `AlreadyRefunded` - Invalid state
+    /// * `InvalidTransferState` - Transfer is no longer active
+    /// * `InboxRequired` - Transfer is tracked by an inbox that wasn't passed
     pub fn claim_transfer(ctx: Context<ClaimTransfer>, claim_code: String) -> Result<()> {
+        let transfer = &ctx.accounts.transfer;
+        let passkey = active_passkey(transfer, ctx.accounts.inbox.as_ref());
+
         // === Input Validation ===
-        require!(
-            claim_code.len() <= MAX_CLAIM_CODE_LEN,
-            ErrorCode::ClaimCodeTooLong
-        );
+        if passkey.is_some() {
+            require!(
+                claim_code.len() <= MAX_CLIENT_DATA_LEN,
+                ErrorCode::ClientDataTooLong
+            );
+        } else {
+            require!(
+                claim_code.len() <= MAX_CLAIM_CODE_LEN,
+                ErrorCode::ClaimCodeTooLong
+            );
+        }
 
-        let transfer = &ctx.accounts.transfer;
         let clock = Clock::get()?;
 
-        // === Verify Claim Code (constant-time) ===
-        let claim_code_hash = keccak::hash(claim_code.as_bytes()).to_bytes();
         require!(
-            constant_time_eq(&claim_code_hash, &transfer.claim_code_hash),
-            ErrorCode::InvalidClaimCode
+            transfer.claim_mode == ClaimMode::SingleCode,
+            ErrorCode::InvalidClaimMode
+        );
+
+        require!(
+            !transfer.is_reserved(clock.unix_timestamp),
+            ErrorCode::ClaimReserved
         );
 
+        // === Verify Passkey Assertion or Claim Code (constant-time) ===
+        if let Some(passkey) = passkey {
+            let instructions = ctx
+                .accounts
+                .instructions
+                .as_ref()
+                .ok_or(ErrorCode::InvalidPasskeySignature)?;
+            let challenge = passkey_challenge(&transfer.key(), &ctx.accounts.recipient.key());
+            verify_passkey_assertion(instructions, &passkey, claim_code.as_bytes(), &challenge)?;
+        } else {
+            let claim_code_hash = keccak::hash(claim_code.as_bytes()).to_bytes();
+            require!(
+                constant_time_eq(&claim_code_hash, &transfer.claim_code_hash),
+                ErrorCode::InvalidClaimCode
+            );
+        }
+
         // === State Checks ===
         require!(
             clock.unix_timestamp < transfer.expiry,
@@ -168,67 +295,52 @@ pub mod solrelay {
             ErrorCode::InvalidTransferState
         );
 
-        // === Cache Values Before Mutation ===
+        // === Release Escrow to Recipient (rent recovery to sender) ===
         let amount = transfer.amount;
-        let sender_key = transfer.sender;
-        let email_hash = transfer.email_hash;
-        let bump = transfer.bump;
-
-        // === Execute Token Transfer from Escrow ===
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            b"transfer",
-            sender_key.as_ref(),
-            email_hash.as_ref(),
-            &[bump],
-        ]];
-
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.escrow_token_account.to_account_info(),
-                to: ctx.accounts.recipient_token_account.to_account_info(),
-                authority: ctx.accounts.transfer.to_account_info(),
-            },
-            signer_seeds,
-        );
-        token::transfer(cpi_ctx, amount)?;
-
-        // === Close Escrow Account (rent recovery to sender) ===
-        let close_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            CloseAccount {
-                account: ctx.accounts.escrow_token_account.to_account_info(),
-                destination: ctx.accounts.sender.to_account_info(),
-                authority: ctx.accounts.transfer.to_account_info(),
-            },
-            signer_seeds,
-        );
-        token::close_account(close_ctx)?;
+        release_escrow(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.recipient_token_account.to_account_info(),
+            &ctx.accounts.sender,
+            transfer,
+            amount,
+        )?;
 
         // === Update State ===
         ctx.accounts.transfer.status = TransferStatus::Claimed;
+        settle_inbox(ctx.accounts.inbox.as_mut(), &ctx.accounts.transfer)?;
 
         // === Emit Event ===
-        emit!(TransferClaimed {
-            transfer: ctx.accounts.transfer.key(),
-            recipient: ctx.accounts.recipient.key(),
+        emit_cpi!(TransferClaimed::new(
+            ctx.accounts.transfer.key(),
+            &ctx.accounts.transfer,
+            ctx.accounts.recipient.key(),
             amount,
-        });
+            clock.unix_timestamp,
+        ));
 
         Ok(())
     }
 
-    /// Cancel an active transfer (sender only).
+    /// Cancel an active transfer (sender or cancel authority).
     ///
-    /// Returns escrowed tokens to the sender. Can be called at any time
-    /// before the transfer is claimed, even if not expired.
+    /// Returns escrowed tokens to the transfer's `refund_to` owner (the
+    /// sender unless set otherwise at creation). Can be called at any time
+    /// before the transfer is claimed, even if not expired, except while a
+    /// `commit_claim` reservation is live.
     ///
     /// # Authorization
-    /// Only the original sender can cancel.
+    /// The original sender, or the transfer's `cancel_authority` if set.
+    /// Either way the refund and the escrow rent go to the sender's side.
     ///
     /// # Errors
-    /// * `Unauthorized` - Caller is not the sender
-    /// * `AlreadyClaimed` / `AlreadyRefunded` - Invalid state
+    /// * `Unauthorized` - Caller is neither the sender nor the cancel authority
+    /// * `InvalidTransferState` - Transfer is no longer active
+    /// * `GroupTransferRefund` - Group transfers are refunded per contributor
+    /// * `ClaimReserved` - A claim is committed and awaiting reveal
+    /// * `IrrevocableTransfer` - Transfer was created irrevocable
+    /// * `InboxRequired` - Transfer is tracked by an inbox that wasn't passed
+    /// * `ApprovalsRequired` - M-of-N transfer without its approvals account
     pub fn cancel_transfer(ctx: Context<CancelTransfer>) -> Result<()> {
         let transfer = &ctx.accounts.transfer;
 
@@ -237,53 +349,37 @@ pub mod solrelay {
             transfer.status == TransferStatus::Active,
             ErrorCode::InvalidTransferState
         );
-
-        // === Cache Values ===
-        let amount = transfer.amount;
-        let sender_key = transfer.sender;
-        let email_hash = transfer.email_hash;
-        let bump = transfer.bump;
-
-        // === Return Tokens to Sender ===
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            b"transfer",
-            sender_key.as_ref(),
-            email_hash.as_ref(),
-            &[bump],
-        ]];
-
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.escrow_token_account.to_account_info(),
-                to: ctx.accounts.sender_token_account.to_account_info(),
-                authority: ctx.accounts.transfer.to_account_info(),
-            },
-            signer_seeds,
+        require!(!transfer.is_group(), ErrorCode::GroupTransferRefund);
+        require!(!transfer.irrevocable, ErrorCode::IrrevocableTransfer);
+        require!(
+            !transfer.is_reserved(Clock::get()?.unix_timestamp),
+            ErrorCode::ClaimReserved
         );
-        token::transfer(cpi_ctx, amount)?;
+        require_approvals(transfer, ctx.accounts.approvals.as_ref())?;
 
-        // === Close Escrow Account ===
-        let close_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            CloseAccount {
-                account: ctx.accounts.escrow_token_account.to_account_info(),
-                destination: ctx.accounts.sender.to_account_info(),
-                authority: ctx.accounts.transfer.to_account_info(),
-            },
-            signer_seeds,
-        );
-        token::close_account(close_ctx)?;
+        // === Return Tokens to Refund Owner ===
+        let amount = transfer.amount;
+        release_escrow(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.refund_token_account.to_account_info(),
+            &ctx.accounts.sender,
+            transfer,
+            amount,
+        )?;
 
         // === Update State ===
         ctx.accounts.transfer.status = TransferStatus::Cancelled;
+        settle_inbox(ctx.accounts.inbox.as_mut(), &ctx.accounts.transfer)?;
 
         // === Emit Event ===
-        emit!(TransferCancelled {
-            transfer: ctx.accounts.transfer.key(),
-            sender: sender_key,
+        emit_cpi!(TransferCancelled::new(
+            ctx.accounts.transfer.key(),
+            &ctx.accounts.transfer,
+            ctx.accounts.authority.key(),
             amount,
-        });
+            Clock::get()?.unix_timestamp,
+        ));
 
         Ok(())
     }
@@ -291,17 +387,30 @@ pub mod solrelay {
     /// Reclaim an expired transfer.
     ///
     /// Anyone can call this to help clean up expired transfers and return
-    /// funds to the original sender. This enables permissionless cleanup
-    /// which improves network efficiency.
+    /// the funds. The `cranker` who signs collects the transfer's crank
+    /// bounty, if the sender set one at creation: the deposited lamports,
+    /// plus `crank_bounty_bps` of the escrowed tokens when
+    /// `cranker_token_account` is passed.
     ///
     /// # Notes
-    /// - Funds always return to the original sender
+    /// - Funds go to the expiry beneficiary if one was set at creation,
+    ///   otherwise to the `refund_to` owner (by default the sender)
     /// - Rent is recovered to the original sender
-    /// - Caller receives no reward (altruistic cleanup)
+    /// - If the refund owner closed their token account, pass their
+    ///   associated token account address: it is created with rent paid by
+    ///   the cranker, who is reimbursed with the escrow's rent
+    /// - If the refund token account is frozen, the escrow is not closed but
+    ///   handed over to the refund owner (token account authority)
+    /// - The approvals of an M-of-N transfer are closed to the sender
     ///
     /// # Errors
     /// * `NotExpired` - Transfer hasn't expired yet
-    /// * `AlreadyClaimed` / `AlreadyRefunded` - Invalid state
+    /// * `InvalidTransferState` - Transfer is no longer active
+    /// * `GroupTransferRefund` - Group transfers are refunded per contributor
+    /// * `InboxRequired` - Transfer is tracked by an inbox that wasn't passed
+    /// * `ApprovalsRequired` - M-of-N transfer without its approvals account
+    /// * `InvalidTokenAccount` - Refund account is neither a token account of
+    ///   the refund owner nor their missing associated token account
     pub fn reclaim_expired(ctx: Context<ReclaimExpired>) -> Result<()> {
         let transfer = &ctx.accounts.transfer;
         let clock = Clock::get()?;
@@ -317,224 +426,3156 @@ pub mod solrelay {
             transfer.status == TransferStatus::Active,
             ErrorCode::InvalidTransferState
         );
+        require!(!transfer.is_group(), ErrorCode::GroupTransferRefund);
+        require_approvals(transfer, ctx.accounts.approvals.as_ref())?;
 
-        // === Cache Values ===
-        let amount = transfer.amount;
-        let sender_key = transfer.sender;
-        let email_hash = transfer.email_hash;
-        let bump = transfer.bump;
-
-        // === Return Tokens to Original Sender ===
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            b"transfer",
-            sender_key.as_ref(),
-            email_hash.as_ref(),
-            &[bump],
-        ]];
-
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.escrow_token_account.to_account_info(),
-                to: ctx.accounts.sender_token_account.to_account_info(),
-                authority: ctx.accounts.transfer.to_account_info(),
-            },
-            signer_seeds,
-        );
-        token::transfer(cpi_ctx, amount)?;
+        // === Pay Crank Bounty ===
+        let cranker = ctx.accounts.cranker.to_account_info();
+        let bounty_lamports = pay_crank_bounty(&transfer.to_account_info(), &cranker)?;
+        let bounty_tokens = match ctx.accounts.cranker_token_account.as_ref() {
+            Some(cranker_token_account) => {
+                let bounty_tokens = (transfer.amount as u128 * transfer.crank_bounty_bps as u128
+                    / BPS_DENOMINATOR) as u64;
+                if bounty_tokens > 0 {
+                    transfer_from_escrow(
+                        &ctx.accounts.token_program,
+                        &ctx.accounts.escrow_token_account,
+                        &cranker_token_account.to_account_info(),
+                        transfer,
+                        bounty_tokens,
+                    )?;
+                }
+                bounty_tokens
+            }
+            None => 0,
+        };
 
-        // === Close Escrow Account ===
-        let close_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            CloseAccount {
-                account: ctx.accounts.escrow_token_account.to_account_info(),
-                destination: ctx.accounts.original_sender.to_account_info(),
-                authority: ctx.accounts.transfer.to_account_info(),
-            },
-            signer_seeds,
-        );
-        token::close_account(close_ctx)?;
+        // === Return Tokens to Beneficiary or Refund Owner ===
+        let amount = transfer.amount - bounty_tokens;
+        let recipient = transfer.expiry_recipient();
+        let refund_token_account = ctx.accounts.refund_token_account.to_account_info();
+        match refund_route(&refund_token_account, &recipient, &transfer.token_mint)? {
+            RefundRoute::TokenAccount => release_escrow(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &refund_token_account,
+                &ctx.accounts.original_sender,
+                transfer,
+                amount,
+            )?,
+            RefundRoute::CreateAssociatedAccount => {
+                // The cranker funds the new account; an associated token
+                // account has the same size as the escrow, so the escrow's
+                // rent reimburses it exactly.
+                associated_token::create(CpiContext::new(
+                    ctx.accounts.associated_token_program.to_account_info(),
+                    associated_token::Create {
+                        payer: ctx.accounts.cranker.to_account_info(),
+                        associated_token: refund_token_account.clone(),
+                        authority: ctx.accounts.refund_owner.to_account_info(),
+                        mint: ctx.accounts.token_mint.to_account_info(),
+                        system_program: ctx.accounts.system_program.to_account_info(),
+                        token_program: ctx.accounts.token_program.to_account_info(),
+                    },
+                ))?;
+                release_escrow(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.escrow_token_account,
+                    &refund_token_account,
+                    &cranker,
+                    transfer,
+                    amount,
+                )?;
+            }
+            RefundRoute::HandOverEscrow => hand_over_escrow(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                recipient,
+                transfer,
+            )?,
+        }
 
         // === Update State ===
         ctx.accounts.transfer.status = TransferStatus::Expired;
+        settle_inbox(ctx.accounts.inbox.as_mut(), &ctx.accounts.transfer)?;
 
         // === Emit Event ===
-        emit!(TransferReclaimed {
-            transfer: ctx.accounts.transfer.key(),
-            sender: sender_key,
+        emit_cpi!(TransferReclaimed::new(
+            ctx.accounts.transfer.key(),
+            &ctx.accounts.transfer,
             amount,
-        });
+            cranker.key(),
+            bounty_lamports,
+            bounty_tokens,
+            clock.unix_timestamp,
+        ));
 
         Ok(())
     }
-}
 
-// ============================================================================
-// Helper Functions
-// ============================================================================
+    /// Create a transfer that needs M of N claim codes to release.
+    ///
+    /// Works like `create_transfer`, but stores one key per claim code in a
+    /// `ClaimApprovals` PDA instead of a code hash on the transfer. Each
+    /// key is derived from its code like a commit key (see
+    /// `COMMIT_KEY_DOMAIN`) and signs `approve_claim`, so codes never
+    /// appear on chain. The funds are paid out with `claim_approved` once
+    /// `threshold` codes have approved the same wallet.
+    ///
+    /// # Arguments
+    /// * `email_hash` - SHA256(salt + email) to identify the recipient
+    /// * `code_keys` - Key derived from each claim code (1-8, distinct)
+    /// * `threshold` - Number of approvals required (1..=N)
+    /// * `amount` - Number of token base units to transfer
+    /// * `expiry_hours` - Hours until transfer expires (1-168)
+    /// * `options` - Irrevocability and refund destinations
+    ///
+    /// # Errors
+    /// * `InvalidAmount` - Amount is zero
+    /// * `InvalidExpiry` - Expiry not in valid range (1-168 hours)
+    /// * `InvalidClaimCodeSet` - No codes, too many codes or duplicates
+    /// * `InvalidThreshold` - Threshold is zero or greater than N
+    /// * `InsufficientFunds` - Sender doesn't have enough tokens
+    pub fn create_multi_code_transfer(
+        ctx: Context<CreateMultiCodeTransfer>,
+        email_hash: [u8; 32],
+        code_keys: Vec<Pubkey>,
+        threshold: u8,
+        amount: u64,
+        expiry_hours: i64,
+        options: TransferOptions,
+    ) -> Result<()> {
+        // === Input Validation ===
+        let expiry_seconds = validate_create_args(amount, expiry_hours)?;
 
-/// Constant-time byte comparison to prevent timing attacks.
-///
-/// This function compares two 32-byte arrays in constant time,
-/// regardless of where they differ. This prevents attackers from
-/// using timing analysis to guess claim codes byte-by-byte.
-///
-/// # Implementation Notes
-/// - Uses XOR and OR to accumulate differences
-/// - The `#[inline(never)]` prevents compiler optimizations that
-///   could introduce timing variations
-/// - The volatile read ensures the comparison isn't optimized away
-#[inline(never)]
-fn constant_time_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
-    let mut result = 0u8;
-    for i in 0..32 {
-        // XOR detects differences, OR accumulates them
-        result |= a[i] ^ b[i];
-    }
-    // Use volatile to prevent optimization
-    // Any difference results in non-zero result
-    unsafe { std::ptr::read_volatile(&result) == 0 }
-}
+        require!(
+            !code_keys.is_empty() && code_keys.len() <= MAX_CLAIM_CODES,
+            ErrorCode::InvalidClaimCodeSet
+        );
+        for (i, key) in code_keys.iter().enumerate() {
+            require!(
+                !code_keys[..i].contains(key),
+                ErrorCode::InvalidClaimCodeSet
+            );
+        }
+        require!(
+            threshold >= 1 && threshold as usize <= code_keys.len(),
+            ErrorCode::InvalidThreshold
+        );
 
-// ============================================================================
-// Account Contexts
-// ============================================================================
+        // === Initialize Transfer State ===
+        let transfer = &mut ctx.accounts.transfer;
+        let clock = Clock::get()?;
 
-/// Context for creating a new transfer escrow.
-#[derive(Accounts)]
-#[instruction(email_hash: [u8; 32], claim_code_hash: [u8; 32], amount: u64)]
-pub struct CreateTransfer<'info> {
-    /// The transfer escrow state account (PDA).
-    /// Seeds: ["transfer", sender, email_hash]
-    #[account(
-        init,
-        payer = sender,
-        space = 8 + TransferAccount::LEN,
-        seeds = [b"transfer", sender.key().as_ref(), email_hash.as_ref()],
-        bump
-    )]
-    pub transfer: Account<'info, TransferAccount>,
+        transfer.sender = ctx.accounts.sender.key();
+        transfer.email_hash = email_hash;
+        transfer.claim_code_hash = [0u8; 32];
+        transfer.amount = amount;
+        transfer.token_mint = ctx.accounts.token_mint.key();
+        transfer.escrow_token_account = ctx.accounts.escrow_token_account.key();
+        transfer.created_at = clock.unix_timestamp;
+        transfer.expiry = clock
+            .unix_timestamp
+            .checked_add(expiry_seconds)
+            .ok_or(ErrorCode::Overflow)?;
+        transfer.status = TransferStatus::Active;
+        transfer.bump = ctx.bumps.transfer;
+        transfer.escrow_bump = ctx.bumps.escrow_token_account;
+        transfer.claim_mode = ClaimMode::MultiCode;
+        transfer.apply_options(&options)?;
+        deposit_crank_bounty(
+            &ctx.accounts.system_program,
+            &ctx.accounts.sender,
+            &transfer.to_account_info(),
+            options.crank_bounty_lamports,
+        )?;
 
-    /// The sender creating and funding the transfer.
-    #[account(mut)]
-    pub sender: Signer<'info>,
+        // === Initialize Approvals ===
+        let approvals = &mut ctx.accounts.approvals;
+        approvals.transfer = transfer.key();
+        approvals.threshold = threshold;
+        approvals.votes = vec![None; code_keys.len()];
+        approvals.code_keys = code_keys;
+        approvals.bump = ctx.bumps.approvals;
 
-    /// Sender's token account holding the tokens to transfer.
-    #[account(
-        mut,
-        constraint = sender_token_account.owner == sender.key() @ ErrorCode::InvalidTokenAccount,
-        constraint = sender_token_account.mint == token_mint.key() @ ErrorCode::InvalidTokenMint,
-        constraint = sender_token_account.amount >= amount @ ErrorCode::InsufficientFunds
-    )]
-    pub sender_token_account: Account<'info, TokenAccount>,
+        // === Execute Token Transfer ===
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.sender_token_account.to_account_info(),
+                to: ctx.accounts.escrow_token_account.to_account_info(),
+                authority: ctx.accounts.sender.to_account_info(),
+            },
+        );
+        token::transfer(cpi_ctx, amount)?;
 
-    /// The SPL token mint being transferred.
-    /// CHECK: Validated via sender_token_account constraint
-    pub token_mint: AccountInfo<'info>,
+        // === Emit Event ===
+        emit_cpi!(TransferCreated::new(transfer.key(), transfer, amount));
 
-    /// Escrow token account (PDA-controlled).
-    /// Seeds: ["escrow", transfer_pda]
-    #[account(
-        init,
-        payer = sender,
-        token::mint = token_mint,
-        token::authority = transfer,
-        seeds = [b"escrow", transfer.key().as_ref()],
-        bump
-    )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
+        Ok(())
+    }
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+    /// Approve an M-of-N transfer with one of its claim codes.
+    ///
+    /// Signed by the key derived from the code, so the code itself stays
+    /// off chain and an approval can't be replayed for another wallet.
+    /// Each code approves one wallet at a time; approving again moves only
+    /// that code's approval, so a wrong wallet can be corrected without
+    /// touching the other codes' approvals. Once `threshold` codes approve
+    /// the same wallet, that wallet is fixed.
+    ///
+    /// # Arguments
+    /// * `recipient` - Wallet that will be allowed to claim the funds
+    ///
+    /// # Errors
+    /// * `InvalidClaimCode` - Signer is not the key of any claim code
+    /// * `TransferExpired` - Past expiry timestamp
+    /// * `ApprovalRecipientMismatch` - Threshold already met for another wallet
+    pub fn approve_claim(ctx: Context<ApproveClaim>, recipient: Pubkey) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp < ctx.accounts.transfer.expiry,
+            ErrorCode::TransferExpired
+        );
 
-/// Context for claiming a transfer with the secret code.
-#[derive(Accounts)]
-pub struct ClaimTransfer<'info> {
-    /// The transfer escrow state account.
-    #[account(
-        mut,
-        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
-        bump = transfer.bump,
-        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
-    )]
-    pub transfer: Account<'info, TransferAccount>,
+        // === Match Code Key ===
+        let approver = ctx.accounts.approver.key();
+        let approvals = &mut ctx.accounts.approvals;
+        let code_index = approvals
+            .code_keys
+            .iter()
+            .position(|key| *key == approver)
+            .ok_or(ErrorCode::InvalidClaimCode)?;
 
-    /// The recipient claiming the transfer (must sign).
-    #[account(mut)]
-    pub recipient: Signer<'info>,
+        // === Check Recipient ===
+        if let Some(approved) = approvals.approved_recipient() {
+            require_keys_eq!(approved, recipient, ErrorCode::ApprovalRecipientMismatch);
+        }
 
-    /// Recipient's token account (must match transfer's token mint).
-    #[account(
-        mut,
-        constraint = recipient_token_account.owner == recipient.key() @ ErrorCode::InvalidTokenAccount,
-        constraint = recipient_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint
-    )]
-    pub recipient_token_account: Account<'info, TokenAccount>,
+        // === Record Approval ===
+        approvals.votes[code_index] = Some(recipient);
 
-    /// Escrow token account holding the escrowed tokens.
-    #[account(
-        mut,
-        constraint = escrow_token_account.key() == transfer.escrow_token_account @ ErrorCode::InvalidEscrowAccount,
-        seeds = [b"escrow", transfer.key().as_ref()],
-        bump = transfer.escrow_bump
-    )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
+        emit_cpi!(ClaimCodeApproved {
+            transfer: ctx.accounts.transfer.key(),
+            approver,
+            recipient,
+            code_index: code_index as u8,
+            approvals: approvals.approval_count(&recipient),
+            threshold: approvals.threshold,
+        });
 
-    /// Original sender (receives rent from closed escrow).
-    /// CHECK: Validated against transfer.sender
-    #[account(
-        mut,
-        constraint = sender.key() == transfer.sender @ ErrorCode::InvalidSender
-    )]
+        Ok(())
+    }
+
+    /// Claim an M-of-N transfer once enough codes have been approved.
+    ///
+    /// Must be signed by the wallet `threshold` codes approved. The
+    /// approvals account is closed to the sender.
+    ///
+    /// # Errors
+    /// * `InvalidClaimMode` - Transfer is not an M-of-N transfer
+    /// * `InsufficientApprovals` - Fewer than `threshold` codes approved
+    /// * `ApprovalRecipientMismatch` - Signer is not the approved recipient
+    /// * `TransferExpired` - Past expiry timestamp
+    pub fn claim_approved(ctx: Context<ClaimApproved>) -> Result<()> {
+        let transfer = &ctx.accounts.transfer;
+        let approvals = &ctx.accounts.approvals;
+        let clock = Clock::get()?;
+
+        // === State Checks ===
+        require!(
+            transfer.claim_mode == ClaimMode::MultiCode,
+            ErrorCode::InvalidClaimMode
+        );
+        require!(
+            clock.unix_timestamp < transfer.expiry,
+            ErrorCode::TransferExpired
+        );
+        match approvals.approved_recipient() {
+            Some(approved) => require_keys_eq!(
+                approved,
+                ctx.accounts.recipient.key(),
+                ErrorCode::ApprovalRecipientMismatch
+            ),
+            None => return err!(ErrorCode::InsufficientApprovals),
+        }
+
+        // === Release Escrow to Recipient (rent recovery to sender) ===
+        let amount = transfer.amount;
+        release_escrow(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.recipient_token_account.to_account_info(),
+            &ctx.accounts.sender,
+            transfer,
+            amount,
+        )?;
+
+        // === Update State ===
+        ctx.accounts.transfer.status = TransferStatus::Claimed;
+
+        // === Emit Event ===
+        emit_cpi!(TransferClaimed::new(
+            ctx.accounts.transfer.key(),
+            &ctx.accounts.transfer,
+            ctx.accounts.recipient.key(),
+            amount,
+            clock.unix_timestamp,
+        ));
+
+        Ok(())
+    }
+
+    /// Create a group-funded transfer that other wallets can contribute to.
+    ///
+    /// The organizer opens the escrow with the first contribution. Anyone
+    /// can add to it with `contribute` until the contribution deadline, and
+    /// the recipient claims the total with `claim_transfer` as usual. If the
+    /// transfer expires, each contributor is refunded with
+    /// `refund_contribution`; `cancel_transfer` and `reclaim_expired` are
+    /// not available for group transfers.
+    ///
+    /// # Arguments
+    /// * `email_hash` - SHA256(salt + email) to identify the recipient
+    /// * `claim_code_hash` - SHA256(claim_code) for verification
+    /// * `amount` - Organizer's own contribution in token base units
+    /// * `expiry_hours` - Hours until transfer expires (1-168)
+    /// * `contribution_hours` - Hours contributions stay open (1..=expiry_hours)
+    ///
+    /// # Errors
+    /// * `InvalidAmount` - Amount is zero
+    /// * `InvalidExpiry` - Expiry not in valid range (1-168 hours)
+    /// * `InvalidContributionDeadline` - Deadline not within the expiry window
+    /// * `InsufficientFunds` - Organizer doesn't have enough tokens
+    pub fn create_group_transfer(
+        ctx: Context<CreateGroupTransfer>,
+        email_hash: [u8; 32],
+        claim_code_hash: [u8; 32],
+        amount: u64,
+        expiry_hours: i64,
+        contribution_hours: i64,
+    ) -> Result<()> {
+        // === Input Validation ===
+        let expiry_seconds = validate_create_args(amount, expiry_hours)?;
+        require!(
+            contribution_hours >= 1 && contribution_hours <= expiry_hours,
+            ErrorCode::InvalidContributionDeadline
+        );
+
+        // === Initialize Transfer State ===
+        let transfer = &mut ctx.accounts.transfer;
+        let clock = Clock::get()?;
+
+        transfer.sender = ctx.accounts.organizer.key();
+        transfer.email_hash = email_hash;
+        transfer.claim_code_hash = claim_code_hash;
+        transfer.amount = amount;
+        transfer.token_mint = ctx.accounts.token_mint.key();
+        transfer.escrow_token_account = ctx.accounts.escrow_token_account.key();
+        transfer.created_at = clock.unix_timestamp;
+        transfer.expiry = clock
+            .unix_timestamp
+            .checked_add(expiry_seconds)
+            .ok_or(ErrorCode::Overflow)?;
+        transfer.status = TransferStatus::Active;
+        transfer.bump = ctx.bumps.transfer;
+        transfer.escrow_bump = ctx.bumps.escrow_token_account;
+        transfer.claim_mode = ClaimMode::SingleCode;
+        transfer.refund_to = transfer.sender;
+        transfer.contribution_deadline = clock
+            .unix_timestamp
+            .checked_add(contribution_hours * 3600)
+            .ok_or(ErrorCode::Overflow)?;
+
+        // === Record Organizer's Contribution ===
+        let contribution = &mut ctx.accounts.contribution;
+        contribution.transfer = transfer.key();
+        contribution.contributor = ctx.accounts.organizer.key();
+        contribution.amount = amount;
+        contribution.bump = ctx.bumps.contribution;
+
+        // === Execute Token Transfer ===
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.organizer_token_account.to_account_info(),
+                to: ctx.accounts.escrow_token_account.to_account_info(),
+                authority: ctx.accounts.organizer.to_account_info(),
+            },
+        );
+        token::transfer(cpi_ctx, amount)?;
+
+        // === Emit Events ===
+        emit_cpi!(TransferCreated::new(transfer.key(), transfer, amount));
+        emit_cpi!(ContributionAdded {
+            transfer: transfer.key(),
+            contributor: contribution.contributor,
+            amount,
+            total: amount,
+        });
+
+        Ok(())
+    }
+
+    /// Add tokens to a group transfer before its contribution deadline.
+    ///
+    /// Repeated contributions from the same wallet accumulate in one
+    /// `Contribution` PDA, which later determines that wallet's refund.
+    ///
+    /// # Errors
+    /// * `InvalidAmount` - Amount is zero
+    /// * `NotGroupTransfer` - Transfer does not accept contributions
+    /// * `ContributionsClosed` - Past the contribution deadline
+    /// * `InsufficientFunds` - Contributor doesn't have enough tokens
+    pub fn contribute(ctx: Context<Contribute>, amount: u64) -> Result<()> {
+        // === Input Validation ===
+        require!(amount >= MIN_TRANSFER_AMOUNT, ErrorCode::InvalidAmount);
+
+        let clock = Clock::get()?;
+        let transfer = &mut ctx.accounts.transfer;
+
+        // === State Checks ===
+        require!(transfer.is_group(), ErrorCode::NotGroupTransfer);
+        require!(
+            clock.unix_timestamp < transfer.contribution_deadline,
+            ErrorCode::ContributionsClosed
+        );
+
+        // === Record Contribution ===
+        let contribution = &mut ctx.accounts.contribution;
+        if contribution.transfer == Pubkey::default() {
+            contribution.transfer = transfer.key();
+            contribution.contributor = ctx.accounts.contributor.key();
+            contribution.bump = ctx.bumps.contribution;
+        }
+        contribution.amount = contribution
+            .amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        transfer.amount = transfer
+            .amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+
+        // === Execute Token Transfer ===
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.contributor_token_account.to_account_info(),
+                to: ctx.accounts.escrow_token_account.to_account_info(),
+                authority: ctx.accounts.contributor.to_account_info(),
+            },
+        );
+        token::transfer(cpi_ctx, amount)?;
+
+        // === Emit Event ===
+        emit_cpi!(ContributionAdded {
+            transfer: transfer.key(),
+            contributor: contribution.contributor,
+            amount,
+            total: transfer.amount,
+        });
+
+        Ok(())
+    }
+
+    /// Refund one contributor's share of an expired group transfer.
+    ///
+    /// Anyone can call this. The contributor receives their pro-rata share
+    /// of the remaining escrow and the rent of their `Contribution` PDA.
+    /// The last refund closes the escrow (rent to the organizer), marks
+    /// the transfer `Expired` and emits `GroupTransferRefunded`.
+    ///
+    /// # Errors
+    /// * `InvalidTransferState` - Transfer is no longer active
+    /// * `NotGroupTransfer` - Transfer has no contributions
+    /// * `NotExpired` - Transfer hasn't expired yet
+    pub fn refund_contribution(ctx: Context<RefundContribution>) -> Result<()> {
+        let transfer = &ctx.accounts.transfer;
+        let clock = Clock::get()?;
+
+        // === State Checks ===
+        require!(
+            transfer.status == TransferStatus::Active,
+            ErrorCode::InvalidTransferState
+        );
+        require!(transfer.is_group(), ErrorCode::NotGroupTransfer);
+        require!(
+            clock.unix_timestamp >= transfer.expiry,
+            ErrorCode::NotExpired
+        );
+
+        // === Compute Pro-Rata Share ===
+        let contributed = ctx.accounts.contribution.amount;
+        let remaining_total = transfer.amount;
+        let escrow_balance = ctx.accounts.escrow_token_account.amount;
+        let share = u64::try_from(
+            (escrow_balance as u128)
+                .checked_mul(contributed as u128)
+                .ok_or(ErrorCode::Overflow)?
+                / remaining_total as u128,
+        )
+        .map_err(|_| ErrorCode::Overflow)?;
+        let is_last = contributed == remaining_total;
+
+        // === Refund Contributor ===
+        if is_last {
+            release_escrow(
+                &ctx.accounts.token_program,
+                &ctx.accounts.escrow_token_account,
+                &ctx.accounts.contributor_token_account.to_account_info(),
+                &ctx.accounts.organizer,
+                transfer,
+                escrow_balance,
+            )?;
+        } else {
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                b"transfer",
+                transfer.sender.as_ref(),
+                transfer.email_hash.as_ref(),
+                &[transfer.bump],
+            ]];
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.escrow_token_account.to_account_info(),
+                    to: ctx.accounts.contributor_token_account.to_account_info(),
+                    authority: ctx.accounts.transfer.to_account_info(),
+                },
+                signer_seeds,
+            );
+            token::transfer(cpi_ctx, share)?;
+        }
+
+        // === Update State ===
+        let transfer = &mut ctx.accounts.transfer;
+        transfer.amount = remaining_total - contributed;
+        if is_last {
+            transfer.status = TransferStatus::Expired;
+        }
+
+        // === Emit Events ===
+        let contributor = ctx.accounts.contribution.contributor;
+        let amount = if is_last { escrow_balance } else { share };
+        emit_cpi!(ContributionRefunded {
+            transfer: transfer.key(),
+            contributor,
+            amount,
+        });
+        if is_last {
+            emit_cpi!(GroupTransferRefunded {
+                version: TRANSFER_EVENT_VERSION,
+                transfer: transfer.key(),
+                sender: transfer.sender,
+                email_hash: transfer.email_hash,
+                token_mint: transfer.token_mint,
+                contributor,
+                amount,
+                created_at: transfer.created_at,
+                expiry: transfer.expiry,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Close a contributor's PDA after the group transfer was claimed.
+    ///
+    /// Returns the PDA rent to the contributor; no tokens move.
+    ///
+    /// # Errors
+    /// * `InvalidTransferState` - Transfer has not been claimed
+    pub fn close_contribution(ctx: Context<CloseContribution>) -> Result<()> {
+        require!(
+            ctx.accounts.transfer.status == TransferStatus::Claimed,
+            ErrorCode::InvalidTransferState
+        );
+
+        Ok(())
+    }
+
+    /// Create the program config. The signer becomes its admin.
+    ///
+    /// The config holds the Ed25519 keys of the off-chain verifiers whose
+    /// attestations the program trusts. It can only be created once.
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.verifiers = Vec::new();
+        config.bump = ctx.bumps.config;
+
+        Ok(())
+    }
+
+    /// Register an attestation verifier key (admin only).
+    ///
+    /// # Errors
+    /// * `NotConfigAdmin` - Caller is not the config admin
+    /// * `VerifierListFull` - Already at the maximum of 4 verifiers
+    pub fn add_verifier(ctx: Context<UpdateConfig>, verifier: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        if !config.verifiers.contains(&verifier) {
+            require!(
+                config.verifiers.len() < MAX_VERIFIERS,
+                ErrorCode::VerifierListFull
+            );
+            config.verifiers.push(verifier);
+        }
+
+        emit_cpi!(VerifierUpdated {
+            verifier,
+            active: true,
+        });
+
+        Ok(())
+    }
+
+    /// Remove an attestation verifier key (admin only).
+    ///
+    /// # Errors
+    /// * `NotConfigAdmin` - Caller is not the config admin
+    /// * `UnknownVerifier` - Key is not registered
+    pub fn remove_verifier(ctx: Context<UpdateConfig>, verifier: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let position = config
+            .verifiers
+            .iter()
+            .position(|v| *v == verifier)
+            .ok_or(ErrorCode::UnknownVerifier)?;
+        config.verifiers.remove(position);
+
+        emit_cpi!(VerifierUpdated {
+            verifier,
+            active: false,
+        });
+
+        Ok(())
+    }
+
+    /// Open the inbox for an email hash.
+    ///
+    /// Anyone can pay for an inbox; it starts unbound. Senders then pass it
+    /// to `create_transfer` so the recipient can see everything pending.
+    ///
+    /// # Arguments
+    /// * `email_hash` - SHA256(salt + email) of the inbox owner
+    pub fn open_inbox(ctx: Context<OpenInbox>, email_hash: [u8; 32]) -> Result<()> {
+        let inbox = &mut ctx.accounts.inbox;
+        inbox.email_hash = email_hash;
+        inbox.owner = Pubkey::default();
+        inbox.pending_count = 0;
+        inbox.totals = Vec::new();
+        inbox.bump = ctx.bumps.inbox;
+
+        Ok(())
+    }
+
+    /// Bind a wallet to an inbox using a verifier attestation.
+    ///
+    /// The transaction must contain, immediately before this instruction,
+    /// an Ed25519 program instruction in which a registered verifier signs
+    /// `bind_attestation_message(email_hash, wallet, expires_at)`. The wallet
+    /// must also sign. Binding again replaces the previous owner. Each
+    /// attestation can only be used once.
+    ///
+    /// # Arguments
+    /// * `expires_at` - Unix timestamp after which the attestation is void
+    ///
+    /// # Errors
+    /// * `AttestationExpired` - `expires_at` has passed
+    /// * Account already in use - Attestation was used before
+    /// * `InvalidAttestation` - Missing or malformed Ed25519 instruction
+    /// * `UnknownVerifier` - Attestation not signed by a registered verifier
+    pub fn bind_inbox(ctx: Context<BindInbox>, expires_at: i64) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp < expires_at,
+            ErrorCode::AttestationExpired
+        );
+
+        // === Verify Attestation ===
+        let inbox = &mut ctx.accounts.inbox;
+        let wallet = ctx.accounts.wallet.key();
+        let message = bind_attestation_message(&inbox.email_hash, &wallet, expires_at);
+        let verifier =
+            verify_attestation(&ctx.accounts.instructions, &ctx.accounts.config, &message)?;
+
+        // === Bind Wallet ===
+        inbox.owner = wallet;
+        ctx.accounts.attestation_record.expires_at = expires_at;
+
+        emit_cpi!(InboxBound {
+            inbox: inbox.key(),
+            email_hash: inbox.email_hash,
+            owner: wallet,
+            verifier,
+        });
+
+        Ok(())
+    }
+
+    /// Claim every listed transfer in a bound inbox (owner only).
+    ///
+    /// Transfers are passed as remaining accounts, four per transfer:
+    /// `[transfer, escrow, destination_token_account, sender]`. Each must be
+    /// an active, unexpired transfer tracked by this inbox. Tokens go to the
+    /// owner's token account for the transfer's mint and escrow rent returns
+    /// to each sender, exactly as with `claim_transfer`. Transfers that
+    /// need a passkey, or are reserved by a `commit_claim`, can't be swept.
+    ///
+    /// # Errors
+    /// * `NotInboxOwner` - Signer is not the bound wallet
+    /// * `InvalidSweepAccounts` - Accounts missing or not in this inbox
+    /// * `TransferExpired` - A listed transfer is past its expiry
+    /// * `ClaimReserved` - A listed transfer is committed and awaiting reveal
+    /// * `InvalidClaimMode` - A listed transfer (or the inbox) has a passkey
+    pub fn sweep_inbox<'info>(ctx: Context<'_, '_, 'info, 'info, SweepInbox<'info>>) -> Result<()> {
+        let remaining = ctx.remaining_accounts;
+        require!(
+            !remaining.is_empty() && remaining.len() % SWEEP_ACCOUNTS_PER_TRANSFER == 0,
+            ErrorCode::InvalidSweepAccounts
+        );
+
+        let clock = Clock::get()?;
+        let owner = ctx.accounts.owner.key();
+        let mut swept: u32 = 0;
+
+        for accounts in remaining.chunks(SWEEP_ACCOUNTS_PER_TRANSFER) {
+            let mut transfer: Account<'info, TransferAccount> = Account::try_from(&accounts[0])?;
+            let escrow: Account<'info, TokenAccount> = Account::try_from(&accounts[1])?;
+            let destination: Account<'info, TokenAccount> = Account::try_from(&accounts[2])?;
+            let sender = &accounts[3];
+
+            // === Per-Transfer Checks ===
+            require!(
+                transfer.in_inbox && transfer.email_hash == ctx.accounts.inbox.email_hash,
+                ErrorCode::InvalidSweepAccounts
+            );
+            require!(
+                transfer.status == TransferStatus::Active,
+                ErrorCode::InvalidTransferState
+            );
+            require!(
+                clock.unix_timestamp < transfer.expiry,
+                ErrorCode::TransferExpired
+            );
+            require!(
+                !transfer.is_reserved(clock.unix_timestamp),
+                ErrorCode::ClaimReserved
+            );
+            require!(
+                active_passkey(&transfer, Some(&ctx.accounts.inbox)).is_none(),
+                ErrorCode::InvalidClaimMode
+            );
+            require_keys_eq!(
+                escrow.key(),
+                transfer.escrow_token_account,
+                ErrorCode::InvalidEscrowAccount
+            );
+            require_keys_eq!(destination.owner, owner, ErrorCode::InvalidTokenAccount);
+            require_keys_eq!(
+                destination.mint,
+                transfer.token_mint,
+                ErrorCode::InvalidTokenMint
+            );
+            require_keys_eq!(sender.key(), transfer.sender, ErrorCode::InvalidSender);
+
+            // === Release Escrow to Owner ===
+            let amount = transfer.amount;
+            release_escrow(
+                &ctx.accounts.token_program,
+                &escrow,
+                &destination.to_account_info(),
+                sender,
+                &transfer,
+                amount,
+            )?;
+
+            transfer.status = TransferStatus::Claimed;
+            transfer.exit(&crate::ID)?;
+            ctx.accounts
+                .inbox
+                .remove_transfer(transfer.token_mint, amount)?;
+
+            emit_cpi!(TransferClaimed::new(
+                transfer.key(),
+                &transfer,
+                owner,
+                amount,
+                clock.unix_timestamp,
+            ));
+            swept += 1;
+        }
+
+        emit_cpi!(InboxSwept {
+            inbox: ctx.accounts.inbox.key(),
+            owner,
+            transfers: swept,
+        });
+
+        Ok(())
+    }
+
+    /// Register the wallet for an email hash using a verifier attestation.
+    ///
+    /// Typically called right after a recipient's first attested claim, so
+    /// later payments can go straight to their wallet via `send_to_email`.
+    /// The transaction must contain, immediately before this instruction,
+    /// an Ed25519 program instruction in which a registered verifier signs
+    /// `register_attestation_message(email_hash, wallet, expires_at)`, so an
+    /// attestation meant for `bind_inbox` can't register the wallet (or the
+    /// other way round). Each attestation can only be used once.
+    /// Registering again replaces the previous wallet.
+    ///
+    /// # Arguments
+    /// * `email_hash` - SHA256(salt + email) being registered
+    /// * `expires_at` - Unix timestamp after which the attestation is void
+    ///
+    /// # Errors
+    /// * `AttestationExpired` - `expires_at` has passed
+    /// * Account already in use - Attestation was used before
+    /// * `InvalidAttestation` - Missing or malformed Ed25519 instruction
+    /// * `UnknownVerifier` - Attestation not signed by a registered verifier
+    pub fn register_wallet(
+        ctx: Context<RegisterWallet>,
+        email_hash: [u8; 32],
+        expires_at: i64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp < expires_at,
+            ErrorCode::AttestationExpired
+        );
+
+        // === Verify Attestation ===
+        let wallet = ctx.accounts.wallet.key();
+        let message = register_attestation_message(&email_hash, &wallet, expires_at);
+        let verifier =
+            verify_attestation(&ctx.accounts.instructions, &ctx.accounts.config, &message)?;
+
+        // === Record Binding ===
+        let entry = &mut ctx.accounts.registry;
+        entry.email_hash = email_hash;
+        entry.wallet = wallet;
+        entry.verifier = verifier;
+        entry.registered_at = clock.unix_timestamp;
+        entry.bump = ctx.bumps.registry;
+        ctx.accounts.attestation_record.expires_at = expires_at;
+
+        emit_cpi!(WalletRegistered {
+            email_hash,
+            wallet,
+            verifier,
+        });
+
+        Ok(())
+    }
+
+    /// Pay an email hash, directly if it has a registered wallet.
+    ///
+    /// If the registry PDA for `email_hash` holds a binding, the tokens go
+    /// straight to `recipient_token_account` (owned by the bound wallet) and
+    /// no escrow is created. Otherwise this behaves exactly like
+    /// `create_transfer`, using the optional `transfer` and
+    /// `escrow_token_account` accounts.
+    ///
+    /// # Arguments
+    /// Same as `create_transfer`. Everything but `email_hash` and `amount`
+    /// is only used when falling back to an escrow.
+    ///
+    /// # Errors
+    /// * `InvalidAmount` - Amount is zero
+    /// * `InvalidExpiry` - Expiry not in valid range (escrow path only)
+    /// * `RecipientAccountRequired` - Binding exists but no recipient account
+    /// * `EscrowAccountsRequired` - Escrow accounts missing, or passed with a binding
+    /// * `InvalidTokenAccount` - Recipient account not owned by the bound wallet
+    pub fn send_to_email(
+        ctx: Context<SendToEmail>,
+        email_hash: [u8; 32],
+        claim_code_hash: [u8; 32],
+        amount: u64,
+        expiry_hours: i64,
+        options: TransferOptions,
+    ) -> Result<()> {
+        require!(amount >= MIN_TRANSFER_AMOUNT, ErrorCode::InvalidAmount);
+
+        // === Direct Payment to Registered Wallet ===
+        if let Some(entry) = load_registry_entry(&ctx.accounts.registry)? {
+            require!(
+                ctx.accounts.transfer.is_none() && ctx.accounts.escrow_token_account.is_none(),
+                ErrorCode::EscrowAccountsRequired
+            );
+            let recipient_token_account = ctx
+                .accounts
+                .recipient_token_account
+                .as_ref()
+                .ok_or(ErrorCode::RecipientAccountRequired)?;
+            require_keys_eq!(
+                recipient_token_account.owner,
+                entry.wallet,
+                ErrorCode::InvalidTokenAccount
+            );
+
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.sender_token_account.to_account_info(),
+                    to: recipient_token_account.to_account_info(),
+                    authority: ctx.accounts.sender.to_account_info(),
+                },
+            );
+            token::transfer(cpi_ctx, amount)?;
+
+            emit_cpi!(DirectPaymentSent {
+                email_hash,
+                sender: ctx.accounts.sender.key(),
+                recipient: entry.wallet,
+                token_mint: ctx.accounts.token_mint.key(),
+                amount,
+            });
+
+            return Ok(());
+        }
+
+        // === Fall Back to Escrow ===
+        let expiry_seconds = validate_create_args(amount, expiry_hours)?;
+        let (Some(transfer), Some(escrow_token_account)) = (
+            ctx.accounts.transfer.as_mut(),
+            ctx.accounts.escrow_token_account.as_ref(),
+        ) else {
+            return err!(ErrorCode::EscrowAccountsRequired);
+        };
+        let clock = Clock::get()?;
+
+        transfer.sender = ctx.accounts.sender.key();
+        transfer.email_hash = email_hash;
+        transfer.claim_code_hash = claim_code_hash;
+        transfer.amount = amount;
+        transfer.token_mint = ctx.accounts.token_mint.key();
+        transfer.escrow_token_account = escrow_token_account.key();
+        transfer.created_at = clock.unix_timestamp;
+        transfer.expiry = clock
+            .unix_timestamp
+            .checked_add(expiry_seconds)
+            .ok_or(ErrorCode::Overflow)?;
+        transfer.status = TransferStatus::Active;
+        transfer.bump = ctx
+            .bumps
+            .transfer
+            .ok_or(ErrorCode::EscrowAccountsRequired)?;
+        transfer.escrow_bump = ctx
+            .bumps
+            .escrow_token_account
+            .ok_or(ErrorCode::EscrowAccountsRequired)?;
+        transfer.claim_mode = ClaimMode::SingleCode;
+        transfer.apply_options(&options)?;
+        deposit_crank_bounty(
+            &ctx.accounts.system_program,
+            &ctx.accounts.sender,
+            &transfer.to_account_info(),
+            options.crank_bounty_lamports,
+        )?;
+
+        if let Some(inbox) = ctx.accounts.inbox.as_mut() {
+            inbox.add_transfer(transfer.token_mint, amount)?;
+            transfer.in_inbox = true;
+        }
+
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.sender_token_account.to_account_info(),
+                to: escrow_token_account.to_account_info(),
+                authority: ctx.accounts.sender.to_account_info(),
+            },
+        );
+        token::transfer(cpi_ctx, amount)?;
+
+        emit_cpi!(TransferCreated::new(transfer.key(), transfer, amount));
+
+        Ok(())
+    }
+
+    /// Claim a transfer with an attestation from the email verifier.
+    ///
+    /// Replaces the claim code for recipients whose email ownership was
+    /// checked off-chain (e.g. by OTP). The transaction must contain,
+    /// immediately before this instruction, an Ed25519 program instruction
+    /// in which a registered verifier signs
+    /// `claim_attestation_message(email_hash, transfer, destination, expires_at)`.
+    ///
+    /// Funds go to `destination_token_account`, which must belong to the
+    /// attested destination wallet. Neither the destination nor the fee
+    /// payer has to be the recipient, so a relayer can submit the claim.
+    /// Each attestation can only be used once.
+    ///
+    /// # Arguments
+    /// * `expires_at` - Unix timestamp after which the attestation is void
+    ///
+    /// # Errors
+    /// * `InvalidClaimMode` - M-of-N transfers need every approval
+    /// * `ClaimReserved` - A claim is committed and awaiting reveal
+    /// * `AttestationExpired` - `expires_at` has passed
+    /// * `InvalidAttestation` - Missing or malformed Ed25519 instruction
+    /// * `UnknownVerifier` - Attestation not signed by a registered verifier
+    /// * `TransferExpired` - Past the transfer's expiry timestamp
+    /// * Account already in use - Attestation was used before
+    pub fn claim_with_attestation(
+        ctx: Context<ClaimWithAttestation>,
+        expires_at: i64,
+    ) -> Result<()> {
+        let transfer = &ctx.accounts.transfer;
+        let clock = Clock::get()?;
+
+        // === State Checks ===
+        require!(
+            transfer.claim_mode == ClaimMode::SingleCode,
+            ErrorCode::InvalidClaimMode
+        );
+        require!(
+            !transfer.is_reserved(clock.unix_timestamp),
+            ErrorCode::ClaimReserved
+        );
+        require!(
+            clock.unix_timestamp < transfer.expiry,
+            ErrorCode::TransferExpired
+        );
+        require!(
+            clock.unix_timestamp < expires_at,
+            ErrorCode::AttestationExpired
+        );
+
+        // === Verify Attestation ===
+        let destination = ctx.accounts.destination.key();
+        let message = claim_attestation_message(
+            &transfer.email_hash,
+            &transfer.key(),
+            &destination,
+            expires_at,
+        );
+        verify_attestation(&ctx.accounts.instructions, &ctx.accounts.config, &message)?;
+        ctx.accounts.attestation_record.expires_at = expires_at;
+
+        // === Release Escrow to Destination (rent recovery to sender) ===
+        let amount = transfer.amount;
+        release_escrow(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.destination_token_account.to_account_info(),
+            &ctx.accounts.sender,
+            transfer,
+            amount,
+        )?;
+
+        // === Update State ===
+        ctx.accounts.transfer.status = TransferStatus::Claimed;
+        settle_inbox(ctx.accounts.inbox.as_mut(), &ctx.accounts.transfer)?;
+
+        // === Emit Event ===
+        emit_cpi!(TransferClaimed::new(
+            ctx.accounts.transfer.key(),
+            &ctx.accounts.transfer,
+            destination,
+            amount,
+            clock.unix_timestamp,
+        ));
+
+        Ok(())
+    }
+
+    /// Set the zk-DKIM verifying key curve points (admin only).
+    ///
+    /// The full key does not fit in one transaction, so this writes the
+    /// fixed points and clears the IC vector; `set_dkim_verifying_key_ic`
+    /// must follow before proofs are accepted.
+    ///
+    /// # Arguments
+    /// * `alpha_g1`, `beta_g2`, `gamma_g2`, `delta_g2` - EIP-197 encoded points
+    ///
+    /// # Errors
+    /// * `NotConfigAdmin` - Signer is not the config admin
+    pub fn set_dkim_verifying_key(
+        ctx: Context<SetDkimVerifyingKey>,
+        alpha_g1: [u8; 64],
+        beta_g2: [u8; 128],
+        gamma_g2: [u8; 128],
+        delta_g2: [u8; 128],
+    ) -> Result<()> {
+        let verifier = &mut ctx.accounts.dkim_verifier;
+        verifier.alpha_g1 = alpha_g1;
+        verifier.beta_g2 = beta_g2;
+        verifier.gamma_g2 = gamma_g2;
+        verifier.delta_g2 = delta_g2;
+        verifier.ic = Vec::new();
+        verifier.bump = ctx.bumps.dkim_verifier;
+        Ok(())
+    }
+
+    /// Set the zk-DKIM verifying key IC points (admin only).
+    ///
+    /// # Arguments
+    /// * `ic` - One G1 point per public input, constant term first
+    ///
+    /// # Errors
+    /// * `NotConfigAdmin` - Signer is not the config admin
+    /// * `DkimVerifierNotReady` - Wrong number of IC points
+    pub fn set_dkim_verifying_key_ic(
+        ctx: Context<SetDkimVerifyingKeyIc>,
+        ic: Vec<[u8; 64]>,
+    ) -> Result<()> {
+        require!(
+            ic.len() == DKIM_PUBLIC_INPUTS + 1,
+            ErrorCode::DkimVerifierNotReady
+        );
+        ctx.accounts.dkim_verifier.ic = ic;
+        Ok(())
+    }
+
+    /// Register a DKIM key hash for a domain (admin only).
+    ///
+    /// # Arguments
+    /// * `domain_hash` - Hash of the email domain
+    /// * `key_hash` - Hash of the domain's DKIM public key
+    ///
+    /// # Errors
+    /// * `NotConfigAdmin` - Signer is not the config admin
+    /// * `DkimKeyListFull` - Domain already has `MAX_DKIM_KEYS` keys
+    pub fn add_dkim_key(
+        ctx: Context<UpdateDkimKeys>,
+        domain_hash: [u8; 32],
+        key_hash: [u8; 32],
+    ) -> Result<()> {
+        let domain = &mut ctx.accounts.dkim_domain;
+        domain.domain_hash = domain_hash;
+        domain.bump = ctx.bumps.dkim_domain;
+        if !domain.key_hashes.contains(&key_hash) {
+            require!(
+                domain.key_hashes.len() < MAX_DKIM_KEYS,
+                ErrorCode::DkimKeyListFull
+            );
+            domain.key_hashes.push(key_hash);
+        }
+
+        emit_cpi!(DkimKeyUpdated {
+            domain_hash,
+            key_hash,
+            active: true,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke a DKIM key hash for a domain (admin only).
+    ///
+    /// # Errors
+    /// * `NotConfigAdmin` - Signer is not the config admin
+    /// * `UnknownDkimKey` - Key hash is not registered for the domain
+    pub fn remove_dkim_key(
+        ctx: Context<UpdateDkimKeys>,
+        domain_hash: [u8; 32],
+        key_hash: [u8; 32],
+    ) -> Result<()> {
+        let domain = &mut ctx.accounts.dkim_domain;
+        let index = domain
+            .key_hashes
+            .iter()
+            .position(|k| *k == key_hash)
+            .ok_or(ErrorCode::UnknownDkimKey)?;
+        domain.key_hashes.remove(index);
+
+        emit_cpi!(DkimKeyUpdated {
+            domain_hash,
+            key_hash,
+            active: false,
+        });
+
+        Ok(())
+    }
+
+    /// Claim a transfer with a zk proof of a DKIM-signed email.
+    ///
+    /// Removes trust in the email relay: the Groth16 proof shows the
+    /// claimer holds an email to the address behind `email_hash`, signed
+    /// by a DKIM key registered for `domain_hash`. The public inputs are
+    /// `dkim_public_inputs(email_hash, domain_hash, dkim_key_hash, destination)`,
+    /// so a proof only pays out to the wallet it was generated for and can
+    /// be submitted by anyone.
+    ///
+    /// # Arguments
+    /// * `proof` - Groth16 proof (A, B, C) in EIP-197 encoding
+    /// * `domain_hash` - Hash of the email domain
+    /// * `dkim_key_hash` - Hash of the DKIM key that signed the email
+    ///
+    /// # Errors
+    /// * `InvalidClaimMode` - M-of-N transfers need every approval
+    /// * `ClaimReserved` - A claim is committed and awaiting reveal
+    /// * `TransferExpired` - Past the transfer's expiry timestamp
+    /// * `UnknownDkimKey` - Key hash not registered for the domain
+    /// * `DkimVerifierNotReady` - Verifying key incomplete
+    /// * `InvalidProof` - Proof does not verify
+    pub fn claim_with_dkim_proof(
+        ctx: Context<ClaimWithDkimProof>,
+        proof: Groth16Proof,
+        domain_hash: [u8; 32],
+        dkim_key_hash: [u8; 32],
+    ) -> Result<()> {
+        let transfer = &ctx.accounts.transfer;
+        let clock = Clock::get()?;
+
+        // === State Checks ===
+        require!(
+            transfer.claim_mode == ClaimMode::SingleCode,
+            ErrorCode::InvalidClaimMode
+        );
+        require!(
+            !transfer.is_reserved(clock.unix_timestamp),
+            ErrorCode::ClaimReserved
+        );
+        require!(
+            clock.unix_timestamp < transfer.expiry,
+            ErrorCode::TransferExpired
+        );
+        require!(
+            ctx.accounts.dkim_domain.key_hashes.contains(&dkim_key_hash),
+            ErrorCode::UnknownDkimKey
+        );
+
+        // === Verify Proof ===
+        let verifier = &ctx.accounts.dkim_verifier;
+        require!(
+            verifier.ic.len() == DKIM_PUBLIC_INPUTS + 1,
+            ErrorCode::DkimVerifierNotReady
+        );
+        let destination = ctx.accounts.destination.key();
+        let public_inputs = dkim_public_inputs(
+            &transfer.email_hash,
+            &domain_hash,
+            &dkim_key_hash,
+            &destination,
+        );
+        groth16::verify(&verifier.verifying_key(), &proof, &public_inputs)?;
+
+        // === Release Escrow to Destination (rent recovery to sender) ===
+        let amount = transfer.amount;
+        release_escrow(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.destination_token_account.to_account_info(),
+            &ctx.accounts.sender,
+            transfer,
+            amount,
+        )?;
+
+        // === Update State ===
+        ctx.accounts.transfer.status = TransferStatus::Claimed;
+        settle_inbox(ctx.accounts.inbox.as_mut(), &ctx.accounts.transfer)?;
+
+        // === Emit Event ===
+        emit_cpi!(TransferClaimed::new(
+            ctx.accounts.transfer.key(),
+            &ctx.accounts.transfer,
+            destination,
+            amount,
+            clock.unix_timestamp,
+        ));
+
+        Ok(())
+    }
+
+    /// Set or clear the passkey that authorizes claims (sender only).
+    ///
+    /// While set, `claim_transfer` requires a passkey assertion instead of
+    /// the claim code. Irrevocable transfers keep the claim code they were
+    /// created with: otherwise the sender could lock out the recipient and
+    /// claim with their own passkey. The passkey can't change while a
+    /// `commit_claim` reservation is live, so the reveal can't be blocked.
+    ///
+    /// # Arguments
+    /// * `passkey` - Compressed secp256r1 public key, or `None` to go back
+    ///   to the claim code
+    ///
+    /// # Errors
+    /// * `InvalidSender` - Signer is not the sender
+    /// * `IrrevocableTransfer` - Transfer was created irrevocable
+    /// * `ClaimReserved` - A claim is committed and awaiting reveal
+    /// * `InvalidPasskey` - Not a compressed secp256r1 key
+    pub fn set_claim_passkey(
+        ctx: Context<SetClaimPasskey>,
+        passkey: Option<[u8; PASSKEY_LEN]>,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(
+            !ctx.accounts.transfer.is_reserved(clock.unix_timestamp),
+            ErrorCode::ClaimReserved
+        );
+        validate_passkey(passkey.as_ref())?;
+        ctx.accounts.transfer.claim_passkey = passkey;
+
+        emit_cpi!(ClaimPasskeyUpdated {
+            account: ctx.accounts.transfer.key(),
+            passkey,
+        });
+
+        Ok(())
+    }
+
+    /// Set or clear the passkey for every transfer tracked by an inbox
+    /// (bound wallet only).
+    ///
+    /// A passkey set directly on a transfer takes precedence.
+    ///
+    /// # Errors
+    /// * `NotInboxOwner` - Signer is not the bound wallet
+    /// * `InvalidPasskey` - Not a compressed secp256r1 key
+    pub fn set_inbox_passkey(
+        ctx: Context<SetInboxPasskey>,
+        passkey: Option<[u8; PASSKEY_LEN]>,
+    ) -> Result<()> {
+        validate_passkey(passkey.as_ref())?;
+        ctx.accounts.inbox.passkey = passkey;
+
+        emit_cpi!(ClaimPasskeyUpdated {
+            account: ctx.accounts.inbox.key(),
+            passkey,
+        });
+
+        Ok(())
+    }
+
+    /// Reserve a transfer for a claim without revealing the code.
+    ///
+    /// First step of a front-running resistant claim. The commitment is
+    /// `claim_commitment(claim_code, destination, salt)`. For
+    /// `CLAIM_RESERVATION_SECONDS` (capped at the transfer's expiry) only
+    /// `reveal_claim` with the matching preimage can claim, and the sender
+    /// cannot cancel. Reservations lapse on their own once the window
+    /// passes; each transfer allows `MAX_CLAIM_RESERVATIONS` of them.
+    ///
+    /// The committer must be the transfer's `commit_key`, which is derived
+    /// from the claim code (see `COMMIT_KEY_DOMAIN`). Its signature proves
+    /// knowledge of the code without revealing it, so nobody else can
+    /// hold up claims and cancels or use up the reservations.
+    ///
+    /// # Arguments
+    /// * `commitment` - keccak(claim_code || destination || salt)
+    ///
+    /// # Errors
+    /// * `InvalidCommitter` - Signer is not the transfer's commit key
+    /// * `InvalidClaimMode` - Transfer is not claimable with a code
+    /// * `InboxRequired` - Transfer is tracked by an inbox that wasn't passed
+    /// * `TransferExpired` - Past the transfer's expiry timestamp
+    /// * `ClaimReserved` - Another reservation is still live
+    /// * `ReservationLimitReached` - No reservations left on this transfer
+    pub fn commit_claim(ctx: Context<CommitClaim>, commitment: [u8; 32]) -> Result<()> {
+        let transfer = &ctx.accounts.transfer;
+        let clock = Clock::get()?;
+
+        // === State Checks ===
+        require!(
+            !transfer.in_inbox || ctx.accounts.inbox.is_some(),
+            ErrorCode::InboxRequired
+        );
+        require!(
+            transfer.claim_mode == ClaimMode::SingleCode
+                && active_passkey(transfer, ctx.accounts.inbox.as_ref()).is_none(),
+            ErrorCode::InvalidClaimMode
+        );
+        require!(
+            clock.unix_timestamp < transfer.expiry,
+            ErrorCode::TransferExpired
+        );
+        require!(
+            !transfer.is_reserved(clock.unix_timestamp),
+            ErrorCode::ClaimReserved
+        );
+        require!(
+            transfer.reservation_count < MAX_CLAIM_RESERVATIONS,
+            ErrorCode::ReservationLimitReached
+        );
+
+        // === Reserve ===
+        let transfer = &mut ctx.accounts.transfer;
+        let expires_at = clock
+            .unix_timestamp
+            .checked_add(CLAIM_RESERVATION_SECONDS)
+            .ok_or(ErrorCode::Overflow)?
+            .min(transfer.expiry);
+        transfer.claim_commitment = commitment;
+        transfer.reservation_expiry = expires_at;
+        transfer.reservation_count += 1;
+
+        emit_cpi!(ClaimCommitted {
+            transfer: transfer.key(),
+            committer: ctx.accounts.committer.key(),
+            expires_at,
+        });
+
+        Ok(())
+    }
+
+    /// Complete a claim reserved with `commit_claim`.
+    ///
+    /// Checks the preimage against the live commitment and the claim code
+    /// against the stored hash, then pays `destination_token_account`.
+    /// Anyone can submit the reveal, since the destination is fixed by the
+    /// commitment.
+    ///
+    /// # Arguments
+    /// * `claim_code` - The plaintext secret claim code
+    /// * `salt` - The salt used in the commitment
+    ///
+    /// # Errors
+    /// * `ClaimCodeTooLong` - Code exceeds 256 bytes
+    /// * `NoActiveReservation` - No reservation, or it has lapsed
+    /// * `InvalidCommitment` - Preimage does not match the commitment
+    /// * `InvalidClaimCode` - Hash doesn't match
+    /// * `InvalidClaimMode` - A passkey replaces the claim code
+    pub fn reveal_claim(
+        ctx: Context<RevealClaim>,
+        claim_code: String,
+        salt: [u8; 32],
+    ) -> Result<()> {
+        require!(
+            claim_code.len() <= MAX_CLAIM_CODE_LEN,
+            ErrorCode::ClaimCodeTooLong
+        );
+
+        let transfer = &ctx.accounts.transfer;
+        let clock = Clock::get()?;
+
+        // === State Checks ===
+        require!(
+            transfer.is_reserved(clock.unix_timestamp),
+            ErrorCode::NoActiveReservation
+        );
+        require!(
+            active_passkey(transfer, ctx.accounts.inbox.as_ref()).is_none(),
+            ErrorCode::InvalidClaimMode
+        );
+
+        // === Verify Commitment and Claim Code (constant-time) ===
+        let destination = ctx.accounts.destination.key();
+        let commitment = claim_commitment(claim_code.as_bytes(), &destination, &salt);
+        require!(
+            constant_time_eq(&commitment, &transfer.claim_commitment),
+            ErrorCode::InvalidCommitment
+        );
+        let claim_code_hash = keccak::hash(claim_code.as_bytes()).to_bytes();
+        require!(
+            constant_time_eq(&claim_code_hash, &transfer.claim_code_hash),
+            ErrorCode::InvalidClaimCode
+        );
+
+        // === Release Escrow to Destination (rent recovery to sender) ===
+        let amount = transfer.amount;
+        release_escrow(
+            &ctx.accounts.token_program,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.destination_token_account.to_account_info(),
+            &ctx.accounts.sender,
+            transfer,
+            amount,
+        )?;
+
+        // === Update State ===
+        let transfer = &mut ctx.accounts.transfer;
+        transfer.status = TransferStatus::Claimed;
+        transfer.reservation_expiry = 0;
+        settle_inbox(ctx.accounts.inbox.as_mut(), &ctx.accounts.transfer)?;
+
+        // === Emit Event ===
+        emit_cpi!(TransferClaimed::new(
+            ctx.accounts.transfer.key(),
+            &ctx.accounts.transfer,
+            destination,
+            amount,
+            clock.unix_timestamp,
+        ));
+
+        Ok(())
+    }
+
+    /// Set or clear the transfer's cancel authority (sender only).
+    ///
+    /// # Arguments
+    /// * `cancel_authority` - Wallet that may cancel besides the sender,
+    ///   or `None` to leave cancelling to the sender alone
+    ///
+    /// # Errors
+    /// * `Unauthorized` - Signer is not the sender
+    pub fn set_cancel_authority(
+        ctx: Context<SetCancelAuthority>,
+        cancel_authority: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.transfer.cancel_authority = cancel_authority;
+
+        emit_cpi!(CancelAuthorityUpdated {
+            transfer: ctx.accounts.transfer.key(),
+            cancel_authority,
+        });
+
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Validates the amount and expiry shared by every create instruction.
+///
+/// Returns the expiry window in seconds.
+fn validate_create_args(amount: u64, expiry_hours: i64) -> Result<i64> {
+    require!(amount >= MIN_TRANSFER_AMOUNT, ErrorCode::InvalidAmount);
+
+    // Safe multiplication with overflow check
+    let expiry_seconds = expiry_hours
+        .checked_mul(3600)
+        .ok_or(ErrorCode::InvalidExpiry)?;
+
+    require!(
+        (MIN_EXPIRY_SECONDS..=MAX_EXPIRY_SECONDS).contains(&expiry_seconds),
+        ErrorCode::InvalidExpiry
+    );
+
+    Ok(expiry_seconds)
+}
+
+/// Moves `amount` out of a transfer's escrow, signed by the transfer PDA.
+fn transfer_from_escrow<'info>(
+    token_program: &Program<'info, Token>,
+    escrow_token_account: &Account<'info, TokenAccount>,
+    destination: &AccountInfo<'info>,
+    transfer: &Account<'info, TransferAccount>,
+    amount: u64,
+) -> Result<()> {
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"transfer",
+        transfer.sender.as_ref(),
+        transfer.email_hash.as_ref(),
+        &[transfer.bump],
+    ]];
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        Transfer {
+            from: escrow_token_account.to_account_info(),
+            to: destination.clone(),
+            authority: transfer.to_account_info(),
+        },
+        signer_seeds,
+    );
+    token::transfer(cpi_ctx, amount)
+}
+
+/// Moves `amount` out of a transfer's escrow and closes the escrow.
+///
+/// The transfer PDA signs both CPIs. Rent from the closed escrow goes to
+/// `rent_destination`, which should be whoever funded the escrow, along
+/// with any crank bounty still held by the transfer account.
+fn release_escrow<'info>(
+    token_program: &Program<'info, Token>,
+    escrow_token_account: &Account<'info, TokenAccount>,
+    destination: &AccountInfo<'info>,
+    rent_destination: &AccountInfo<'info>,
+    transfer: &Account<'info, TransferAccount>,
+    amount: u64,
+) -> Result<()> {
+    transfer_from_escrow(
+        token_program,
+        escrow_token_account,
+        destination,
+        transfer,
+        amount,
+    )?;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"transfer",
+        transfer.sender.as_ref(),
+        transfer.email_hash.as_ref(),
+        &[transfer.bump],
+    ]];
+    let close_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: escrow_token_account.to_account_info(),
+            destination: rent_destination.clone(),
+            authority: transfer.to_account_info(),
+        },
+        signer_seeds,
+    );
+    token::close_account(close_ctx)?;
+
+    pay_crank_bounty(&transfer.to_account_info(), rent_destination)?;
+    Ok(())
+}
+
+/// Moves the sender's crank bounty deposit onto the transfer account.
+fn deposit_crank_bounty<'info>(
+    system_program: &Program<'info, System>,
+    sender: &Signer<'info>,
+    transfer: &AccountInfo<'info>,
+    lamports: u64,
+) -> Result<()> {
+    if lamports == 0 {
+        return Ok(());
+    }
+    let cpi_ctx = CpiContext::new(
+        system_program.to_account_info(),
+        system_program::Transfer {
+            from: sender.to_account_info(),
+            to: transfer.clone(),
+        },
+    );
+    system_program::transfer(cpi_ctx, lamports)
+}
+
+/// Pays out the lamport crank bounty: everything on the transfer account
+/// above its rent-exempt minimum. Returns the amount paid, which is zero
+/// once the bounty has been paid or returned.
+fn pay_crank_bounty(transfer: &AccountInfo, to: &AccountInfo) -> Result<u64> {
+    let rent_exempt = Rent::get()?.minimum_balance(transfer.data_len());
+    let bounty = transfer.lamports().saturating_sub(rent_exempt);
+    if bounty > 0 {
+        transfer.sub_lamports(bounty)?;
+        to.add_lamports(bounty)?;
+    }
+    Ok(bounty)
+}
+
+/// Makes `new_owner` the authority of a transfer's escrow instead of
+/// closing it, for refunds whose destination account is frozen.
+fn hand_over_escrow<'info>(
+    token_program: &Program<'info, Token>,
+    escrow_token_account: &Account<'info, TokenAccount>,
+    new_owner: Pubkey,
+    transfer: &Account<'info, TransferAccount>,
+) -> Result<()> {
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"transfer",
+        transfer.sender.as_ref(),
+        transfer.email_hash.as_ref(),
+        &[transfer.bump],
+    ]];
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        SetAuthority {
+            account_or_mint: escrow_token_account.to_account_info(),
+            current_authority: transfer.to_account_info(),
+        },
+        signer_seeds,
+    );
+    token::set_authority(cpi_ctx, AuthorityType::AccountOwner, Some(new_owner))
+}
+
+/// How `reclaim_expired` delivers funds to the refund owner.
+#[derive(Debug, PartialEq, Eq)]
+enum RefundRoute {
+    /// Transfer into the existing token account and close the escrow
+    TokenAccount,
+    /// Create the owner's associated token account first
+    CreateAssociatedAccount,
+    /// Destination is frozen: give the escrow itself to the owner
+    HandOverEscrow,
+}
+
+/// Picks the refund route for the account passed as the refund destination.
+///
+/// A missing account is only accepted at the owner's associated token
+/// address; an existing one must be a token account of `owner` for `mint`.
+fn refund_route(account: &AccountInfo, owner: &Pubkey, mint: &Pubkey) -> Result<RefundRoute> {
+    if account.owner == &System::id() && account.data_is_empty() {
+        require_keys_eq!(
+            account.key(),
+            get_associated_token_address(owner, mint),
+            ErrorCode::InvalidTokenAccount
+        );
+        return Ok(RefundRoute::CreateAssociatedAccount);
+    }
+
+    require_keys_eq!(*account.owner, token::ID, ErrorCode::InvalidTokenAccount);
+    let data = account.try_borrow_data()?;
+    let token_account = TokenAccount::try_deserialize(&mut &data[..])
+        .map_err(|_| ErrorCode::InvalidTokenAccount)?;
+    require_keys_eq!(token_account.owner, *owner, ErrorCode::InvalidTokenAccount);
+    require_keys_eq!(token_account.mint, *mint, ErrorCode::InvalidTokenMint);
+
+    if token_account.is_frozen() {
+        Ok(RefundRoute::HandOverEscrow)
+    } else {
+        Ok(RefundRoute::TokenAccount)
+    }
+}
+
+/// Removes a settled transfer from its recipient's inbox counters.
+///
+/// Transfers created without an inbox are ignored. For inbox transfers the
+/// inbox must be supplied, so its totals never drift from the chain.
+fn settle_inbox(inbox: Option<&mut Account<Inbox>>, transfer: &TransferAccount) -> Result<()> {
+    if !transfer.in_inbox {
+        return Ok(());
+    }
+    let inbox = inbox.ok_or(ErrorCode::InboxRequired)?;
+    inbox.remove_transfer(transfer.token_mint, transfer.amount)
+}
+
+/// Requires the approvals account of an M-of-N transfer, so that it is
+/// closed along with the escrow.
+fn require_approvals(
+    transfer: &TransferAccount,
+    approvals: Option<&Account<ClaimApprovals>>,
+) -> Result<()> {
+    require!(
+        transfer.claim_mode != ClaimMode::MultiCode || approvals.is_some(),
+        ErrorCode::ApprovalsRequired
+    );
+    Ok(())
+}
+
+/// Derives the transfer PDA for a sender and recipient email hash.
+///
+/// Used where the transfer account itself is optional and cannot be named
+/// in another account's seeds.
+fn transfer_address(sender: &Pubkey, email_hash: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"transfer", sender.as_ref(), email_hash.as_ref()],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Reads the registry entry at `registry`, if one has been created.
+///
+/// The address is already pinned by the caller's seeds constraint; an
+/// account that was never initialized simply means "no binding".
+fn load_registry_entry(registry: &AccountInfo) -> Result<Option<RegistryEntry>> {
+    if registry.owner != &crate::ID || registry.data_is_empty() {
+        return Ok(None);
+    }
+    let data = registry.try_borrow_data()?;
+    Ok(Some(RegistryEntry::try_deserialize(&mut &data[..])?))
+}
+
+/// Builds the message a verifier signs to attest that `wallet` controls
+/// the email address behind `email_hash`.
+///
+/// Layout: `BIND_ATTESTATION_DOMAIN || email_hash || wallet || expires_at (LE)`
+pub fn bind_attestation_message(
+    email_hash: &[u8; 32],
+    wallet: &Pubkey,
+    expires_at: i64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(BIND_ATTESTATION_DOMAIN.len() + 32 + 32 + 8);
+    message.extend_from_slice(BIND_ATTESTATION_DOMAIN);
+    message.extend_from_slice(email_hash);
+    message.extend_from_slice(wallet.as_ref());
+    message.extend_from_slice(&expires_at.to_le_bytes());
+    message
+}
+
+/// Builds the message a verifier signs to attest that `wallet` controls
+/// the email address behind `email_hash` and should receive its payments
+/// directly.
+///
+/// Layout: `REGISTER_ATTESTATION_DOMAIN || email_hash || wallet || expires_at (LE)`
+pub fn register_attestation_message(
+    email_hash: &[u8; 32],
+    wallet: &Pubkey,
+    expires_at: i64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(REGISTER_ATTESTATION_DOMAIN.len() + 32 + 32 + 8);
+    message.extend_from_slice(REGISTER_ATTESTATION_DOMAIN);
+    message.extend_from_slice(email_hash);
+    message.extend_from_slice(wallet.as_ref());
+    message.extend_from_slice(&expires_at.to_le_bytes());
+    message
+}
+
+/// Builds the message a verifier signs to let `destination` claim
+/// `transfer` on behalf of the owner of `email_hash`.
+///
+/// Layout: `CLAIM_ATTESTATION_DOMAIN || email_hash || transfer || destination || expires_at (LE)`
+pub fn claim_attestation_message(
+    email_hash: &[u8; 32],
+    transfer: &Pubkey,
+    destination: &Pubkey,
+    expires_at: i64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(CLAIM_ATTESTATION_DOMAIN.len() + 32 + 32 + 32 + 8);
+    message.extend_from_slice(CLAIM_ATTESTATION_DOMAIN);
+    message.extend_from_slice(email_hash);
+    message.extend_from_slice(transfer.as_ref());
+    message.extend_from_slice(destination.as_ref());
+    message.extend_from_slice(&expires_at.to_le_bytes());
+    message
+}
+
+/// Seed of the `AttestationRecord` PDA that marks `message` as used.
+pub fn attestation_record_seed(message: &[u8]) -> [u8; 32] {
+    keccak::hash(message).to_bytes()
+}
+
+/// Public inputs for `claim_with_dkim_proof`, in circuit order.
+pub fn dkim_public_inputs(
+    email_hash: &[u8; 32],
+    domain_hash: &[u8; 32],
+    dkim_key_hash: &[u8; 32],
+    destination: &Pubkey,
+) -> Vec<[u8; 32]> {
+    [
+        email_hash,
+        domain_hash,
+        dkim_key_hash,
+        &destination.to_bytes(),
+    ]
+    .into_iter()
+    .flat_map(groth16::split_to_field_elements)
+    .collect()
+}
+
+/// Checks that the previous instruction is an Ed25519 signature over
+/// `message` by a registered verifier, and returns that verifier's key.
+///
+/// # Security
+/// - Exactly one signature, with signature, key and message all read from
+///   the Ed25519 instruction itself (offsets index `u16::MAX`), so the
+///   checked bytes are the ones the precompile actually verified
+/// - The message must match byte for byte, including the domain prefix
+fn verify_attestation(
+    instructions: &AccountInfo,
+    config: &Config,
+    message: &[u8],
+) -> Result<Pubkey> {
+    let current_index = load_current_index_checked(instructions)?;
+    require!(current_index > 0, ErrorCode::InvalidAttestation);
+    let ix = load_instruction_at_checked(current_index as usize - 1, instructions)?;
+    require_keys_eq!(
+        ix.program_id,
+        ed25519_program::ID,
+        ErrorCode::InvalidAttestation
+    );
+
+    // === Compare Signed Data ===
+    let (pubkey, signed_message) =
+        parse_single_signature(&ix.data, 32).ok_or(ErrorCode::InvalidAttestation)?;
+    require!(signed_message == message, ErrorCode::InvalidAttestation);
+
+    let verifier = Pubkey::try_from(pubkey).map_err(|_| ErrorCode::InvalidAttestation)?;
+    require!(
+        config.verifiers.contains(&verifier),
+        ErrorCode::UnknownVerifier
+    );
+
+    Ok(verifier)
+}
+
+/// Commitment for `commit_claim`: keccak(claim_code || destination || salt).
+pub fn claim_commitment(claim_code: &[u8], destination: &Pubkey, salt: &[u8; 32]) -> [u8; 32] {
+    keccak::hashv(&[claim_code, destination.as_ref(), salt]).to_bytes()
+}
+
+/// The passkey that replaces the claim code, if any: the transfer's own,
+/// else the one on the inbox tracking it.
+fn active_passkey(transfer: &TransferAccount, inbox: Option<&Account<Inbox>>) -> Option<[u8; 33]> {
+    let inbox_passkey = inbox
+        .filter(|_| transfer.in_inbox)
+        .and_then(|inbox| inbox.passkey);
+    transfer.claim_passkey.or(inbox_passkey)
+}
+
+/// Challenge a passkey must sign to claim `transfer` into `destination`.
+pub fn passkey_challenge(transfer: &Pubkey, destination: &Pubkey) -> [u8; 32] {
+    keccak::hashv(&[
+        PASSKEY_CHALLENGE_DOMAIN,
+        transfer.as_ref(),
+        destination.as_ref(),
+    ])
+    .to_bytes()
+}
+
+/// Rejects passkeys that are not compressed secp256r1 points.
+fn validate_passkey(passkey: Option<&[u8; PASSKEY_LEN]>) -> Result<()> {
+    if let Some(passkey) = passkey {
+        require!(
+            passkey[0] == 0x02 || passkey[0] == 0x03,
+            ErrorCode::InvalidPasskey
+        );
+    }
+    Ok(())
+}
+
+/// Checks that the previous instruction is a secp256r1 signature by
+/// `passkey` over a WebAuthn assertion for `challenge`.
+///
+/// # Security
+/// - Signature, key and message are read from the secp256r1 instruction
+///   itself, as in `verify_attestation`
+/// - The signed message must be `authenticatorData || SHA256(client_data_json)`
+///   with the user-present and user-verified flags set
+/// - `client_data_json` must be a `webauthn.get` assertion whose challenge
+///   is the base64url encoding of `challenge`
+fn verify_passkey_assertion(
+    instructions: &AccountInfo,
+    passkey: &[u8; PASSKEY_LEN],
+    client_data_json: &[u8],
+    challenge: &[u8; 32],
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions)?;
+    require!(current_index > 0, ErrorCode::InvalidPasskeySignature);
+    let ix = load_instruction_at_checked(current_index as usize - 1, instructions)?;
+    require_keys_eq!(
+        ix.program_id,
+        secp256r1_program::ID,
+        ErrorCode::InvalidPasskeySignature
+    );
+
+    // === Compare Signed Data ===
+    let (pubkey, signed_message) =
+        parse_single_signature(&ix.data, PASSKEY_LEN).ok_or(ErrorCode::InvalidPasskeySignature)?;
+    require!(pubkey == passkey, ErrorCode::InvalidPasskeySignature);
+    require!(
+        signed_message.len() >= MIN_AUTHENTICATOR_DATA_LEN + 32,
+        ErrorCode::InvalidPasskeySignature
+    );
+    let (authenticator_data, client_data_hash) = signed_message.split_at(signed_message.len() - 32);
+    require!(
+        authenticator_data[32] & PASSKEY_REQUIRED_FLAGS == PASSKEY_REQUIRED_FLAGS,
+        ErrorCode::InvalidPasskeySignature
+    );
+    require!(
+        client_data_hash == sha256::hash(client_data_json).as_ref(),
+        ErrorCode::InvalidPasskeySignature
+    );
+
+    // === Check Client Data ===
+    let mut expected_challenge = b"\"challenge\":\"".to_vec();
+    expected_challenge.extend_from_slice(&base64url_encode(challenge));
+    expected_challenge.push(b'"');
+    require!(
+        contains(client_data_json, b"\"type\":\"webauthn.get\"")
+            && contains(client_data_json, &expected_challenge),
+        ErrorCode::InvalidPasskeySignature
+    );
+
+    Ok(())
+}
+
+/// Reads the public key and message of a single-signature Ed25519 or
+/// secp256r1 precompile instruction.
+///
+/// Returns `None` unless there is exactly one signature and all offsets
+/// point into the instruction itself (index `u16::MAX`), so the returned
+/// bytes are the ones the precompile actually verified.
+fn parse_single_signature(data: &[u8], pubkey_len: usize) -> Option<(&[u8], &[u8])> {
+    if data.len() < SIGNATURE_HEADER_LEN || data[0] != 1 {
+        return None;
+    }
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let signature_ix = read_u16(4);
+    let pubkey_offset = read_u16(6) as usize;
+    let pubkey_ix = read_u16(8);
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    let message_ix = read_u16(14);
+    if signature_ix != u16::MAX || pubkey_ix != u16::MAX || message_ix != u16::MAX {
+        return None;
+    }
+
+    let pubkey = data.get(pubkey_offset..pubkey_offset + pubkey_len)?;
+    let message = data.get(message_offset..message_offset + message_size)?;
+    Some((pubkey, message))
+}
+
+/// Unpadded base64url encoding, as used for WebAuthn challenges.
+fn base64url_encode(bytes: &[u8]) -> Vec<u8> {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut out = Vec::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63]);
+        }
+    }
+    out
+}
+
+/// Whether `needle` occurs in `haystack`.
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Constant-time byte comparison to prevent timing attacks.
+///
+/// This function compares two 32-byte arrays in constant time,
+/// regardless of where they differ. This prevents attackers from
+/// using timing analysis to guess claim codes byte-by-byte.
+///
+/// # Implementation Notes
+/// - Uses XOR and OR to accumulate differences
+/// - The `#[inline(never)]` prevents compiler optimizations that
+///   could introduce timing variations
+/// - The volatile read ensures the comparison isn't optimized away
+#[inline(never)]
+fn constant_time_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    let mut result = 0u8;
+    for i in 0..32 {
+        // XOR detects differences, OR accumulates them
+        result |= a[i] ^ b[i];
+    }
+    // Use volatile to prevent optimization
+    // Any difference results in non-zero result
+    unsafe { std::ptr::read_volatile(&result) == 0 }
+}
+
+// ============================================================================
+// Account Contexts
+// ============================================================================
+
+/// Context for creating a new transfer escrow.
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(email_hash: [u8; 32], claim_code_hash: [u8; 32], amount: u64)]
+pub struct CreateTransfer<'info> {
+    /// The transfer escrow state account (PDA).
+    /// Seeds: ["transfer", sender, email_hash]
+    #[account(
+        init,
+        payer = sender,
+        space = 8 + TransferAccount::LEN,
+        seeds = [b"transfer", sender.key().as_ref(), email_hash.as_ref()],
+        bump
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// The sender creating and funding the transfer.
+    #[account(mut)]
+    pub sender: Signer<'info>,
+
+    /// Sender's token account holding the tokens to transfer.
+    #[account(
+        mut,
+        constraint = sender_token_account.owner == sender.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = sender_token_account.mint == token_mint.key() @ ErrorCode::InvalidTokenMint,
+        constraint = sender_token_account.amount >= amount @ ErrorCode::InsufficientFunds
+    )]
+    pub sender_token_account: Account<'info, TokenAccount>,
+
+    /// The SPL token mint being transferred.
+    /// CHECK: Validated via sender_token_account constraint
+    pub token_mint: AccountInfo<'info>,
+
+    /// Escrow token account (PDA-controlled).
+    /// Seeds: ["escrow", transfer_pda]
+    #[account(
+        init,
+        payer = sender,
+        token::mint = token_mint,
+        token::authority = transfer,
+        seeds = [b"escrow", transfer.key().as_ref()],
+        bump
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+
+    /// Recipient's inbox, if one has been opened for this email hash.
+    #[account(
+        mut,
+        seeds = [b"inbox", email_hash.as_ref()],
+        bump = inbox.bump
+    )]
+    pub inbox: Option<Account<'info, Inbox>>,
+}
+
+/// Context for claiming a transfer with the secret code.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimTransfer<'info> {
+    /// The transfer escrow state account.
+    #[account(
+        mut,
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// The recipient claiming the transfer (must sign).
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+
+    /// Recipient's token account (must match transfer's token mint).
+    #[account(
+        mut,
+        constraint = recipient_token_account.owner == recipient.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = recipient_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    /// Escrow token account holding the escrowed tokens.
+    #[account(
+        mut,
+        constraint = escrow_token_account.key() == transfer.escrow_token_account @ ErrorCode::InvalidEscrowAccount,
+        seeds = [b"escrow", transfer.key().as_ref()],
+        bump = transfer.escrow_bump
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    /// Original sender (receives rent from closed escrow).
+    /// CHECK: Validated against transfer.sender
+    #[account(
+        mut,
+        constraint = sender.key() == transfer.sender @ ErrorCode::InvalidSender
+    )]
+    pub sender: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// Recipient's inbox (required if the transfer is tracked by one).
+    #[account(
+        mut,
+        seeds = [b"inbox", transfer.email_hash.as_ref()],
+        bump = inbox.bump
+    )]
+    pub inbox: Option<Account<'info, Inbox>>,
+
+    /// Instructions sysvar (required for passkey claims).
+    /// CHECK: Address constraint
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+}
+
+/// Context for cancelling an active transfer (sender only).
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CancelTransfer<'info> {
+    /// The transfer escrow state account.
+    #[account(
+        mut,
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// The sender or the transfer's cancel authority (must sign).
+    #[account(
+        constraint = transfer.can_cancel(&authority.key()) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    /// Original sender (receives rent from closed escrow).
+    /// CHECK: Validated against transfer.sender
+    #[account(
+        mut,
+        constraint = sender.key() == transfer.sender @ ErrorCode::InvalidSender
+    )]
+    pub sender: AccountInfo<'info>,
+
+    /// Token account of `transfer.refund_to` to receive the refund.
+    #[account(
+        mut,
+        constraint = refund_token_account.owner == transfer.refund_to @ ErrorCode::InvalidTokenAccount,
+        constraint = refund_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint
+    )]
+    pub refund_token_account: Account<'info, TokenAccount>,
+
+    /// Escrow token account to close.
+    #[account(
+        mut,
+        constraint = escrow_token_account.key() == transfer.escrow_token_account @ ErrorCode::InvalidEscrowAccount,
+        seeds = [b"escrow", transfer.key().as_ref()],
+        bump = transfer.escrow_bump
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// Recipient's inbox (required if the transfer is tracked by one).
+    #[account(
+        mut,
+        seeds = [b"inbox", transfer.email_hash.as_ref()],
+        bump = inbox.bump
+    )]
+    pub inbox: Option<Account<'info, Inbox>>,
+
+    /// Approvals of an M-of-N transfer (required for those), closed to
+    /// the sender.
+    #[account(
+        mut,
+        close = sender,
+        seeds = [b"approvals", transfer.key().as_ref()],
+        bump = approvals.bump
+    )]
+    pub approvals: Option<Account<'info, ClaimApprovals>>,
+}
+
+/// Context for reclaiming an expired transfer.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ReclaimExpired<'info> {
+    /// The transfer escrow state account.
+    #[account(
+        mut,
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// Token account of the expiry beneficiary, or of `transfer.refund_to`
+    /// if there is none (receives the funds). May be the owner's missing
+    /// associated token account.
+    /// CHECK: Validated against transfer.expiry_recipient() by refund_route
+    #[account(mut)]
+    pub refund_token_account: UncheckedAccount<'info>,
+
+    /// Owner of the refund token account.
+    /// CHECK: Validated against transfer.expiry_recipient()
+    #[account(
+        constraint = refund_owner.key() == transfer.expiry_recipient() @ ErrorCode::InvalidTokenAccount
+    )]
+    pub refund_owner: UncheckedAccount<'info>,
+
+    /// The transfer's token mint (for creating the refund account).
+    #[account(address = transfer.token_mint @ ErrorCode::InvalidTokenMint)]
+    pub token_mint: Account<'info, Mint>,
+
+    /// Escrow token account to close.
+    #[account(
+        mut,
+        constraint = escrow_token_account.key() == transfer.escrow_token_account @ ErrorCode::InvalidEscrowAccount,
+        seeds = [b"escrow", transfer.key().as_ref()],
+        bump = transfer.escrow_bump
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    /// Original sender (receives rent from closed accounts).
+    /// CHECK: Validated against transfer.sender
+    #[account(
+        mut,
+        constraint = original_sender.key() == transfer.sender @ ErrorCode::InvalidSender
+    )]
+    pub original_sender: AccountInfo<'info>,
+
+    /// Whoever submits the reclaim (collects the crank bounty and pays for
+    /// a missing refund account).
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// Cranker's token account for the token bounty (optional).
+    #[account(
+        mut,
+        constraint = cranker_token_account.owner == cranker.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = cranker_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint
+    )]
+    pub cranker_token_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+
+    /// Recipient's inbox (required if the transfer is tracked by one).
+    #[account(
+        mut,
+        seeds = [b"inbox", transfer.email_hash.as_ref()],
+        bump = inbox.bump
+    )]
+    pub inbox: Option<Account<'info, Inbox>>,
+
+    /// Approvals of an M-of-N transfer (required for those), closed to
+    /// the sender.
+    #[account(
+        mut,
+        close = original_sender,
+        seeds = [b"approvals", transfer.key().as_ref()],
+        bump = approvals.bump
+    )]
+    pub approvals: Option<Account<'info, ClaimApprovals>>,
+}
+
+/// Context for creating an M-of-N claim code transfer.
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(email_hash: [u8; 32], code_keys: Vec<Pubkey>, threshold: u8, amount: u64)]
+pub struct CreateMultiCodeTransfer<'info> {
+    /// The transfer escrow state account (PDA).
+    /// Seeds: ["transfer", sender, email_hash]
+    #[account(
+        init,
+        payer = sender,
+        space = 8 + TransferAccount::LEN,
+        seeds = [b"transfer", sender.key().as_ref(), email_hash.as_ref()],
+        bump
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// Claim code keys and approvals (PDA).
+    /// Seeds: ["approvals", transfer_pda]
+    #[account(
+        init,
+        payer = sender,
+        space = 8 + ClaimApprovals::LEN,
+        seeds = [b"approvals", transfer.key().as_ref()],
+        bump
+    )]
+    pub approvals: Account<'info, ClaimApprovals>,
+
+    /// The sender creating and funding the transfer.
+    #[account(mut)]
+    pub sender: Signer<'info>,
+
+    /// Sender's token account holding the tokens to transfer.
+    #[account(
+        mut,
+        constraint = sender_token_account.owner == sender.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = sender_token_account.mint == token_mint.key() @ ErrorCode::InvalidTokenMint,
+        constraint = sender_token_account.amount >= amount @ ErrorCode::InsufficientFunds
+    )]
+    pub sender_token_account: Account<'info, TokenAccount>,
+
+    /// The SPL token mint being transferred.
+    /// CHECK: Validated via sender_token_account constraint
+    pub token_mint: AccountInfo<'info>,
+
+    /// Escrow token account (PDA-controlled).
+    /// Seeds: ["escrow", transfer_pda]
+    #[account(
+        init,
+        payer = sender,
+        token::mint = token_mint,
+        token::authority = transfer,
+        seeds = [b"escrow", transfer.key().as_ref()],
+        bump
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Context for approving an M-of-N transfer with one claim code.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ApproveClaim<'info> {
+    /// The transfer escrow state account.
+    #[account(
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// Claim code keys and approvals for the transfer.
+    #[account(
+        mut,
+        seeds = [b"approvals", transfer.key().as_ref()],
+        bump = approvals.bump
+    )]
+    pub approvals: Account<'info, ClaimApprovals>,
+
+    /// The key derived from the approving claim code (must sign).
+    pub approver: Signer<'info>,
+}
+
+/// Context for claiming an M-of-N transfer after approval.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimApproved<'info> {
+    /// The transfer escrow state account.
+    #[account(
+        mut,
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// Claim code hashes and approval bitmap for the transfer, closed to
+    /// the sender.
+    #[account(
+        mut,
+        close = sender,
+        seeds = [b"approvals", transfer.key().as_ref()],
+        bump = approvals.bump
+    )]
+    pub approvals: Account<'info, ClaimApprovals>,
+
+    /// The approved recipient (must sign).
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+
+    /// Recipient's token account (must match transfer's token mint).
+    #[account(
+        mut,
+        constraint = recipient_token_account.owner == recipient.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = recipient_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    /// Escrow token account holding the escrowed tokens.
+    #[account(
+        mut,
+        constraint = escrow_token_account.key() == transfer.escrow_token_account @ ErrorCode::InvalidEscrowAccount,
+        seeds = [b"escrow", transfer.key().as_ref()],
+        bump = transfer.escrow_bump
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    /// Original sender (receives rent from closed escrow).
+    /// CHECK: Validated against transfer.sender
+    #[account(
+        mut,
+        constraint = sender.key() == transfer.sender @ ErrorCode::InvalidSender
+    )]
+    pub sender: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Context for creating a group-funded transfer.
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(email_hash: [u8; 32], claim_code_hash: [u8; 32], amount: u64)]
+pub struct CreateGroupTransfer<'info> {
+    /// The transfer escrow state account (PDA).
+    /// Seeds: ["transfer", organizer, email_hash]
+    #[account(
+        init,
+        payer = organizer,
+        space = 8 + TransferAccount::LEN,
+        seeds = [b"transfer", organizer.key().as_ref(), email_hash.as_ref()],
+        bump
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// The organizer's contribution record (PDA).
+    /// Seeds: ["contribution", transfer_pda, organizer]
+    #[account(
+        init,
+        payer = organizer,
+        space = 8 + Contribution::LEN,
+        seeds = [b"contribution", transfer.key().as_ref(), organizer.key().as_ref()],
+        bump
+    )]
+    pub contribution: Account<'info, Contribution>,
+
+    /// The organizer creating the transfer and making the first contribution.
+    #[account(mut)]
+    pub organizer: Signer<'info>,
+
+    /// Organizer's token account holding the tokens to contribute.
+    #[account(
+        mut,
+        constraint = organizer_token_account.owner == organizer.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = organizer_token_account.mint == token_mint.key() @ ErrorCode::InvalidTokenMint,
+        constraint = organizer_token_account.amount >= amount @ ErrorCode::InsufficientFunds
+    )]
+    pub organizer_token_account: Account<'info, TokenAccount>,
+
+    /// The SPL token mint being transferred.
+    /// CHECK: Validated via organizer_token_account constraint
+    pub token_mint: AccountInfo<'info>,
+
+    /// Escrow token account (PDA-controlled).
+    /// Seeds: ["escrow", transfer_pda]
+    #[account(
+        init,
+        payer = organizer,
+        token::mint = token_mint,
+        token::authority = transfer,
+        seeds = [b"escrow", transfer.key().as_ref()],
+        bump
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Context for contributing to a group transfer.
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct Contribute<'info> {
+    /// The group transfer being funded.
+    #[account(
+        mut,
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// The contributor's running total (PDA, created on first contribution).
+    /// Seeds: ["contribution", transfer_pda, contributor]
+    #[account(
+        init_if_needed,
+        payer = contributor,
+        space = 8 + Contribution::LEN,
+        seeds = [b"contribution", transfer.key().as_ref(), contributor.key().as_ref()],
+        bump
+    )]
+    pub contribution: Account<'info, Contribution>,
+
+    /// The wallet contributing tokens.
+    #[account(mut)]
+    pub contributor: Signer<'info>,
+
+    /// Contributor's token account holding the tokens to contribute.
+    #[account(
+        mut,
+        constraint = contributor_token_account.owner == contributor.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = contributor_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint,
+        constraint = contributor_token_account.amount >= amount @ ErrorCode::InsufficientFunds
+    )]
+    pub contributor_token_account: Account<'info, TokenAccount>,
+
+    /// Escrow token account receiving the contribution.
+    #[account(
+        mut,
+        constraint = escrow_token_account.key() == transfer.escrow_token_account @ ErrorCode::InvalidEscrowAccount,
+        seeds = [b"escrow", transfer.key().as_ref()],
+        bump = transfer.escrow_bump
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for refunding a contributor of an expired group transfer.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RefundContribution<'info> {
+    /// The expired group transfer.
+    #[account(
+        mut,
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// The contribution being refunded (closed, rent to contributor).
+    #[account(
+        mut,
+        close = contributor,
+        seeds = [b"contribution", transfer.key().as_ref(), contributor.key().as_ref()],
+        bump = contribution.bump
+    )]
+    pub contribution: Account<'info, Contribution>,
+
+    /// The contributor (receives the PDA rent).
+    /// CHECK: Validated via the contribution PDA seeds
+    #[account(mut)]
+    pub contributor: AccountInfo<'info>,
+
+    /// Contributor's token account (receives the refund).
+    #[account(
+        mut,
+        constraint = contributor_token_account.owner == contributor.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = contributor_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint
+    )]
+    pub contributor_token_account: Account<'info, TokenAccount>,
+
+    /// Escrow token account holding the remaining contributions.
+    #[account(
+        mut,
+        constraint = escrow_token_account.key() == transfer.escrow_token_account @ ErrorCode::InvalidEscrowAccount,
+        seeds = [b"escrow", transfer.key().as_ref()],
+        bump = transfer.escrow_bump
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    /// The organizer (receives escrow rent after the last refund).
+    /// CHECK: Validated against transfer.sender
+    #[account(
+        mut,
+        constraint = organizer.key() == transfer.sender @ ErrorCode::InvalidSender
+    )]
+    pub organizer: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Context for closing a contribution after the group transfer was claimed.
+#[derive(Accounts)]
+pub struct CloseContribution<'info> {
+    /// The claimed group transfer.
+    #[account(
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// The contribution being closed (rent to contributor).
+    #[account(
+        mut,
+        close = contributor,
+        seeds = [b"contribution", transfer.key().as_ref(), contributor.key().as_ref()],
+        bump = contribution.bump
+    )]
+    pub contribution: Account<'info, Contribution>,
+
+    /// The contributor (must sign, receives the PDA rent).
+    #[account(mut)]
+    pub contributor: Signer<'info>,
+}
+
+/// Context for creating the program config.
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    /// Program config (PDA).
+    /// Seeds: ["config"]
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Config::LEN,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The wallet that becomes config admin.
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for admin updates to the program config.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    /// Program config.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::NotConfigAdmin
+    )]
+    pub config: Account<'info, Config>,
+
+    /// The config admin (must sign).
+    pub admin: Signer<'info>,
+}
+
+/// Context for opening an inbox for an email hash.
+#[derive(Accounts)]
+#[instruction(email_hash: [u8; 32])]
+pub struct OpenInbox<'info> {
+    /// The inbox (PDA).
+    /// Seeds: ["inbox", email_hash]
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Inbox::LEN,
+        seeds = [b"inbox", email_hash.as_ref()],
+        bump
+    )]
+    pub inbox: Account<'info, Inbox>,
+
+    /// Whoever pays the inbox rent.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for binding a wallet to an inbox.
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(expires_at: i64)]
+pub struct BindInbox<'info> {
+    /// The inbox being bound.
+    #[account(
+        mut,
+        seeds = [b"inbox", inbox.email_hash.as_ref()],
+        bump = inbox.bump
+    )]
+    pub inbox: Account<'info, Inbox>,
+
+    /// Program config holding the trusted verifier keys.
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// Marks the attestation as used (PDA).
+    /// Seeds: ["attestation", keccak(message)]
+    #[account(
+        init,
+        payer = wallet,
+        space = 8 + AttestationRecord::LEN,
+        seeds = [
+            b"attestation",
+            attestation_record_seed(&bind_attestation_message(
+                &inbox.email_hash,
+                &wallet.key(),
+                expires_at
+            ))
+            .as_ref()
+        ],
+        bump
+    )]
+    pub attestation_record: Account<'info, AttestationRecord>,
+
+    /// The wallet being bound (must sign, pays for the attestation record).
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+
+    /// Instructions sysvar, used to read the Ed25519 attestation.
+    /// CHECK: Address constraint
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for sweeping all listed transfers out of an inbox.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SweepInbox<'info> {
+    /// The bound inbox.
+    #[account(
+        mut,
+        seeds = [b"inbox", inbox.email_hash.as_ref()],
+        bump = inbox.bump,
+        constraint = inbox.owner == owner.key() @ ErrorCode::NotInboxOwner
+    )]
+    pub inbox: Account<'info, Inbox>,
+
+    /// The wallet bound to the inbox (must sign).
+    pub owner: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Context for registering the wallet of an email hash.
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(email_hash: [u8; 32], expires_at: i64)]
+pub struct RegisterWallet<'info> {
+    /// The registry entry (PDA, created on first registration).
+    /// Seeds: ["registry", email_hash]
+    #[account(
+        init_if_needed,
+        payer = wallet,
+        space = 8 + RegistryEntry::LEN,
+        seeds = [b"registry", email_hash.as_ref()],
+        bump
+    )]
+    pub registry: Account<'info, RegistryEntry>,
+
+    /// Program config holding the trusted verifier keys.
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// Marks the attestation as used (PDA).
+    /// Seeds: ["attestation", keccak(message)]
+    #[account(
+        init,
+        payer = wallet,
+        space = 8 + AttestationRecord::LEN,
+        seeds = [
+            b"attestation",
+            attestation_record_seed(&register_attestation_message(
+                &email_hash,
+                &wallet.key(),
+                expires_at
+            ))
+            .as_ref()
+        ],
+        bump
+    )]
+    pub attestation_record: Account<'info, AttestationRecord>,
+
+    /// The wallet being registered (must sign, pays rent).
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+
+    /// Instructions sysvar, used to read the Ed25519 attestation.
+    /// CHECK: Address constraint
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for paying an email hash directly or through an escrow.
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(email_hash: [u8; 32], claim_code_hash: [u8; 32], amount: u64)]
+pub struct SendToEmail<'info> {
+    /// Registry entry for the email hash; may not exist yet.
+    /// CHECK: Address pinned by seeds, contents read by load_registry_entry
+    #[account(seeds = [b"registry", email_hash.as_ref()], bump)]
+    pub registry: UncheckedAccount<'info>,
+
+    /// The sender funding the payment.
+    #[account(mut)]
+    pub sender: Signer<'info>,
+
+    /// Sender's token account holding the tokens to transfer.
+    #[account(
+        mut,
+        constraint = sender_token_account.owner == sender.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = sender_token_account.mint == token_mint.key() @ ErrorCode::InvalidTokenMint,
+        constraint = sender_token_account.amount >= amount @ ErrorCode::InsufficientFunds
+    )]
+    pub sender_token_account: Account<'info, TokenAccount>,
+
+    /// The SPL token mint being transferred.
+    /// CHECK: Validated via sender_token_account constraint
+    pub token_mint: AccountInfo<'info>,
+
+    /// Registered wallet's token account (direct path only).
+    #[account(
+        mut,
+        constraint = recipient_token_account.mint == token_mint.key() @ ErrorCode::InvalidTokenMint
+    )]
+    pub recipient_token_account: Option<Account<'info, TokenAccount>>,
+
+    /// The transfer escrow state account (escrow path only).
+    /// Seeds: ["transfer", sender, email_hash]
+    #[account(
+        init,
+        payer = sender,
+        space = 8 + TransferAccount::LEN,
+        seeds = [b"transfer", sender.key().as_ref(), email_hash.as_ref()],
+        bump
+    )]
+    pub transfer: Option<Account<'info, TransferAccount>>,
+
+    /// Escrow token account (escrow path only).
+    /// Seeds: ["escrow", transfer_pda]
+    #[account(
+        init,
+        payer = sender,
+        token::mint = token_mint,
+        token::authority = transfer,
+        seeds = [b"escrow", transfer_address(&sender.key(), &email_hash).as_ref()],
+        bump
+    )]
+    pub escrow_token_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+
+    /// Recipient's inbox, if one has been opened (escrow path only).
+    #[account(
+        mut,
+        seeds = [b"inbox", email_hash.as_ref()],
+        bump = inbox.bump
+    )]
+    pub inbox: Option<Account<'info, Inbox>>,
+}
+
+/// Context for claiming a transfer with a verifier attestation.
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(expires_at: i64)]
+pub struct ClaimWithAttestation<'info> {
+    /// The transfer escrow state account.
+    #[account(
+        mut,
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// Program config holding the trusted verifier keys.
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    /// Marks the attestation as used (PDA).
+    /// Seeds: ["attestation", keccak(message)]
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + AttestationRecord::LEN,
+        seeds = [
+            b"attestation",
+            attestation_record_seed(&claim_attestation_message(
+                &transfer.email_hash,
+                &transfer.key(),
+                &destination.key(),
+                expires_at
+            ))
+            .as_ref()
+        ],
+        bump
+    )]
+    pub attestation_record: Account<'info, AttestationRecord>,
+
+    /// The wallet named in the attestation.
+    /// CHECK: Bound into the verified attestation message
+    pub destination: UncheckedAccount<'info>,
+
+    /// Destination wallet's token account (must match transfer's token mint).
+    #[account(
+        mut,
+        constraint = destination_token_account.owner == destination.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = destination_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
+
+    /// Escrow token account holding the escrowed tokens.
+    #[account(
+        mut,
+        constraint = escrow_token_account.key() == transfer.escrow_token_account @ ErrorCode::InvalidEscrowAccount,
+        seeds = [b"escrow", transfer.key().as_ref()],
+        bump = transfer.escrow_bump
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    /// Original sender (receives rent from closed escrow).
+    /// CHECK: Validated against transfer.sender
+    #[account(
+        mut,
+        constraint = sender.key() == transfer.sender @ ErrorCode::InvalidSender
+    )]
+    pub sender: AccountInfo<'info>,
+
+    /// Pays the transaction and the attestation record (recipient or relayer).
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// Instructions sysvar, used to read the Ed25519 attestation.
+    /// CHECK: Address constraint
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+
+    /// Recipient's inbox (required if the transfer is tracked by one).
+    #[account(
+        mut,
+        seeds = [b"inbox", transfer.email_hash.as_ref()],
+        bump = inbox.bump
+    )]
+    pub inbox: Option<Account<'info, Inbox>>,
+}
+
+/// Context for setting the zk-DKIM verifying key points.
+#[derive(Accounts)]
+pub struct SetDkimVerifyingKey<'info> {
+    /// Program config.
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::NotConfigAdmin
+    )]
+    pub config: Account<'info, Config>,
+
+    /// zk-DKIM verifying key (PDA).
+    /// Seeds: ["dkim_verifier"]
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + DkimVerifier::LEN,
+        seeds = [b"dkim_verifier"],
+        bump
+    )]
+    pub dkim_verifier: Account<'info, DkimVerifier>,
+
+    /// The config admin (must sign, pays for the verifying key account).
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for setting the zk-DKIM verifying key IC points.
+#[derive(Accounts)]
+pub struct SetDkimVerifyingKeyIc<'info> {
+    /// Program config.
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::NotConfigAdmin
+    )]
+    pub config: Account<'info, Config>,
+
+    /// zk-DKIM verifying key.
+    #[account(mut, seeds = [b"dkim_verifier"], bump = dkim_verifier.bump)]
+    pub dkim_verifier: Account<'info, DkimVerifier>,
+
+    /// The config admin (must sign).
+    pub admin: Signer<'info>,
+}
+
+/// Context for adding or removing a domain's DKIM key hashes.
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(domain_hash: [u8; 32])]
+pub struct UpdateDkimKeys<'info> {
+    /// Program config.
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::NotConfigAdmin
+    )]
+    pub config: Account<'info, Config>,
+
+    /// DKIM keys for the domain (PDA).
+    /// Seeds: ["dkim", domain_hash]
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + DkimDomain::LEN,
+        seeds = [b"dkim", domain_hash.as_ref()],
+        bump
+    )]
+    pub dkim_domain: Account<'info, DkimDomain>,
+
+    /// The config admin (must sign, pays for new domain accounts).
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context for claiming a transfer with a zk-DKIM proof.
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(proof: Groth16Proof, domain_hash: [u8; 32])]
+pub struct ClaimWithDkimProof<'info> {
+    /// The transfer escrow state account.
+    #[account(
+        mut,
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// zk-DKIM verifying key.
+    #[account(seeds = [b"dkim_verifier"], bump = dkim_verifier.bump)]
+    pub dkim_verifier: Account<'info, DkimVerifier>,
+
+    /// DKIM keys registered for the proof's domain.
+    #[account(seeds = [b"dkim", domain_hash.as_ref()], bump = dkim_domain.bump)]
+    pub dkim_domain: Account<'info, DkimDomain>,
+
+    /// The wallet bound into the proof's public inputs.
+    /// CHECK: Bound into the verified proof
+    pub destination: UncheckedAccount<'info>,
+
+    /// Destination wallet's token account (must match transfer's token mint).
+    #[account(
+        mut,
+        constraint = destination_token_account.owner == destination.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = destination_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
+
+    /// Escrow token account holding the escrowed tokens.
+    #[account(
+        mut,
+        constraint = escrow_token_account.key() == transfer.escrow_token_account @ ErrorCode::InvalidEscrowAccount,
+        seeds = [b"escrow", transfer.key().as_ref()],
+        bump = transfer.escrow_bump
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    /// Original sender (receives rent from closed escrow).
+    /// CHECK: Validated against transfer.sender
+    #[account(
+        mut,
+        constraint = sender.key() == transfer.sender @ ErrorCode::InvalidSender
+    )]
     pub sender: AccountInfo<'info>,
 
     pub token_program: Program<'info, Token>,
+
+    /// Recipient's inbox (required if the transfer is tracked by one).
+    #[account(
+        mut,
+        seeds = [b"inbox", transfer.email_hash.as_ref()],
+        bump = inbox.bump
+    )]
+    pub inbox: Option<Account<'info, Inbox>>,
 }
 
-/// Context for cancelling an active transfer (sender only).
+/// Context for setting the passkey on a transfer (sender only).
+#[event_cpi]
 #[derive(Accounts)]
-pub struct CancelTransfer<'info> {
+pub struct SetClaimPasskey<'info> {
     /// The transfer escrow state account.
     #[account(
         mut,
         seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
         bump = transfer.bump,
-        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
+        constraint = transfer.sender == sender.key() @ ErrorCode::InvalidSender,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState,
+        constraint = !transfer.irrevocable @ ErrorCode::IrrevocableTransfer
     )]
     pub transfer: Account<'info, TransferAccount>,
 
-    /// The original sender (must sign, only they can cancel).
-    #[account(
-        mut,
-        constraint = sender.key() == transfer.sender @ ErrorCode::Unauthorized
-    )]
+    /// The original sender (must sign).
     pub sender: Signer<'info>,
+}
 
-    /// Sender's token account to receive refund.
+/// Context for setting the passkey on an inbox (bound wallet only).
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetInboxPasskey<'info> {
+    /// The bound inbox.
     #[account(
         mut,
-        constraint = sender_token_account.owner == sender.key() @ ErrorCode::InvalidTokenAccount,
-        constraint = sender_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint
+        seeds = [b"inbox", inbox.email_hash.as_ref()],
+        bump = inbox.bump,
+        constraint = inbox.owner == owner.key() @ ErrorCode::NotInboxOwner
     )]
-    pub sender_token_account: Account<'info, TokenAccount>,
+    pub inbox: Account<'info, Inbox>,
 
-    /// Escrow token account to close.
+    /// The wallet bound to the inbox (must sign).
+    pub owner: Signer<'info>,
+}
+
+/// Context for reserving a transfer with a claim commitment.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CommitClaim<'info> {
+    /// The transfer escrow state account.
     #[account(
         mut,
-        constraint = escrow_token_account.key() == transfer.escrow_token_account @ ErrorCode::InvalidEscrowAccount,
-        seeds = [b"escrow", transfer.key().as_ref()],
-        bump = transfer.escrow_bump
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState,
+        constraint = transfer.commit_key == Some(committer.key()) @ ErrorCode::InvalidCommitter
     )]
-    pub escrow_token_account: Account<'info, TokenAccount>,
+    pub transfer: Account<'info, TransferAccount>,
 
-    pub token_program: Program<'info, Token>,
+    /// The transfer's commit key, derived from the claim code (must sign).
+    pub committer: Signer<'info>,
+
+    /// Recipient's inbox (required if the transfer is tracked by one), for
+    /// its passkey.
+    #[account(
+        seeds = [b"inbox", transfer.email_hash.as_ref()],
+        bump = inbox.bump
+    )]
+    pub inbox: Option<Account<'info, Inbox>>,
 }
 
-/// Context for reclaiming an expired transfer.
+/// Context for revealing a committed claim.
+#[event_cpi]
 #[derive(Accounts)]
-pub struct ReclaimExpired<'info> {
+pub struct RevealClaim<'info> {
     /// The transfer escrow state account.
     #[account(
         mut,
@@ -544,15 +3585,19 @@ pub struct ReclaimExpired<'info> {
     )]
     pub transfer: Account<'info, TransferAccount>,
 
-    /// Original sender's token account (receives the refund).
+    /// The wallet named in the commitment.
+    /// CHECK: Bound into the verified commitment
+    pub destination: UncheckedAccount<'info>,
+
+    /// Destination wallet's token account (must match transfer's token mint).
     #[account(
         mut,
-        constraint = sender_token_account.owner == transfer.sender @ ErrorCode::InvalidTokenAccount,
-        constraint = sender_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint
+        constraint = destination_token_account.owner == destination.key() @ ErrorCode::InvalidTokenAccount,
+        constraint = destination_token_account.mint == transfer.token_mint @ ErrorCode::InvalidTokenMint
     )]
-    pub sender_token_account: Account<'info, TokenAccount>,
+    pub destination_token_account: Account<'info, TokenAccount>,
 
-    /// Escrow token account to close.
+    /// Escrow token account holding the escrowed tokens.
     #[account(
         mut,
         constraint = escrow_token_account.key() == transfer.escrow_token_account @ ErrorCode::InvalidEscrowAccount,
@@ -561,86 +3606,528 @@ pub struct ReclaimExpired<'info> {
     )]
     pub escrow_token_account: Account<'info, TokenAccount>,
 
-    /// Original sender (receives rent from closed accounts).
+    /// Original sender (receives rent from closed escrow).
     /// CHECK: Validated against transfer.sender
     #[account(
         mut,
-        constraint = original_sender.key() == transfer.sender @ ErrorCode::InvalidSender
+        constraint = sender.key() == transfer.sender @ ErrorCode::InvalidSender
     )]
-    pub original_sender: AccountInfo<'info>,
+    pub sender: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    /// Recipient's inbox (required if the transfer is tracked by one).
+    #[account(
+        mut,
+        seeds = [b"inbox", transfer.email_hash.as_ref()],
+        bump = inbox.bump
+    )]
+    pub inbox: Option<Account<'info, Inbox>>,
+}
+
+/// Context for changing a transfer's cancel authority (sender only).
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetCancelAuthority<'info> {
+    /// The transfer escrow state account.
+    #[account(
+        mut,
+        seeds = [b"transfer", transfer.sender.as_ref(), transfer.email_hash.as_ref()],
+        bump = transfer.bump,
+        constraint = transfer.sender == sender.key() @ ErrorCode::Unauthorized,
+        constraint = transfer.status == TransferStatus::Active @ ErrorCode::InvalidTransferState
+    )]
+    pub transfer: Account<'info, TransferAccount>,
+
+    /// The original sender (must sign).
+    pub sender: Signer<'info>,
+}
+
+// ============================================================================
+// Account State
+// ============================================================================
+
+/// Transfer status enum - more gas efficient than multiple booleans
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TransferStatus {
+    /// Transfer is active and can be claimed
+    Active = 0,
+    /// Transfer has been successfully claimed
+    Claimed = 1,
+    /// Transfer was cancelled by sender
+    Cancelled = 2,
+    /// Transfer expired and was reclaimed
+    Expired = 3,
+}
+
+impl Default for TransferStatus {
+    fn default() -> Self {
+        TransferStatus::Active
+    }
+}
+
+/// How the escrow of a transfer is unlocked.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ClaimMode {
+    /// A single claim code releases funds via `claim_transfer`
+    #[default]
+    SingleCode = 0,
+    /// M-of-N codes are approved, then released via `claim_approved`
+    MultiCode = 1,
+}
+
+/// Sender-chosen settings applied when a transfer is created.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug, Default)]
+pub struct TransferOptions {
+    /// If true, `cancel_transfer` is disabled and only `reclaim_expired`
+    /// can return the funds
+    pub irrevocable: bool,
+    /// Owner of the token account refunds go to (default: sender)
+    pub refund_to: Option<Pubkey>,
+    /// If set, `reclaim_expired` pays this owner instead of `refund_to`
+    pub expiry_beneficiary: Option<Pubkey>,
+    /// Lamports deposited for whoever cranks `reclaim_expired`; returned to
+    /// the sender if the transfer is claimed or cancelled
+    pub crank_bounty_lamports: u64,
+    /// Share of the escrowed tokens paid to the cranker on expiry (max 500)
+    pub crank_bounty_bps: u16,
+    /// Wallet that may cancel besides the sender (e.g. a finance team)
+    pub cancel_authority: Option<Pubkey>,
+    /// Key derived from the claim code that signs `commit_claim`; without
+    /// it the transfer can't be claimed by commitment
+    pub commit_key: Option<Pubkey>,
+}
+
+/// State account for a pending token transfer.
+///
+/// This account stores all metadata about an escrow transfer,
+/// including the claim code hash for verification and the
+/// expiry timestamp.
+#[account]
+pub struct TransferAccount {
+    /// The sender who created and funded this transfer
+    pub sender: Pubkey,
+    /// SHA256 hash of (salt + recipient_email)
+    pub email_hash: [u8; 32],
+    /// SHA256 hash of the claim code
+    pub claim_code_hash: [u8; 32],
+    /// Amount of tokens held in escrow
+    pub amount: u64,
+    /// SPL token mint address
+    pub token_mint: Pubkey,
+    /// Address of the escrow token account
+    pub escrow_token_account: Pubkey,
+    /// Unix timestamp when transfer was created
+    pub created_at: i64,
+    /// Unix timestamp when transfer expires
+    pub expiry: i64,
+    /// Current status of the transfer
+    pub status: TransferStatus,
+    /// PDA bump seed for this transfer account
+    pub bump: u8,
+    /// PDA bump seed for the escrow token account
+    pub escrow_bump: u8,
+    /// Which instruction path can release the escrow
+    pub claim_mode: ClaimMode,
+    /// Unix timestamp when contributions close (0 for single-sender transfers)
+    pub contribution_deadline: i64,
+    /// Whether the recipient's inbox counts this transfer
+    pub in_inbox: bool,
+    /// Compressed secp256r1 key that replaces the claim code, if set
+    pub claim_passkey: Option<[u8; 33]>,
+    /// keccak(claim_code || destination || salt) of the latest reservation
+    pub claim_commitment: [u8; 32],
+    /// Unix timestamp when the latest reservation lapses (0 if none)
+    pub reservation_expiry: i64,
+    /// Number of reservations made so far
+    pub reservation_count: u8,
+    /// Whether the sender gave up `cancel_transfer` at creation
+    pub irrevocable: bool,
+    /// Owner of the token account that cancel and expiry refunds go to
+    pub refund_to: Pubkey,
+    /// Receives the funds on expiry instead of `refund_to`, if set
+    pub expiry_beneficiary: Option<Pubkey>,
+    /// Lamports deposited on this account for the reclaim cranker
+    pub crank_bounty_lamports: u64,
+    /// Share of the escrowed tokens (basis points) paid to the reclaim cranker
+    pub crank_bounty_bps: u16,
+    /// Wallet that may cancel besides the sender, if set
+    pub cancel_authority: Option<Pubkey>,
+    /// Key that must sign `commit_claim`, derived from the claim code
+    pub commit_key: Option<Pubkey>,
+}
+
+impl TransferAccount {
+    /// Account size in bytes:
+    /// - sender: 32
+    /// - email_hash: 32
+    /// - claim_code_hash: 32
+    /// - amount: 8
+    /// - token_mint: 32
+    /// - escrow_token_account: 32
+    /// - created_at: 8
+    /// - expiry: 8
+    /// - status: 1 (enum stored as u8)
+    /// - bump: 1
+    /// - escrow_bump: 1
+    /// - claim_mode: 1 (enum stored as u8)
+    /// - contribution_deadline: 8
+    /// - in_inbox: 1
+    /// - claim_passkey: 1 + 33
+    /// - claim_commitment: 32
+    /// - reservation_expiry: 8
+    /// - reservation_count: 1
+    /// - irrevocable: 1
+    /// - refund_to: 32
+    /// - expiry_beneficiary: 1 + 32
+    /// - crank_bounty_lamports: 8
+    /// - crank_bounty_bps: 2
+    /// - cancel_authority: 1 + 32
+    /// - commit_key: 1 + 32
+    /// Total: 414 bytes
+    pub const LEN: usize = (32 + 32 + 32 + 8 + 32 + 32 + 8 + 8 + 1 + 1 + 1 + 1 + 8 + 1)
+        + (34 + 32 + 8 + 1 + 1 + 32 + 33 + 8 + 2 + 33 + 33);
+
+    /// Whether this transfer is funded by multiple contributors.
+    pub fn is_group(&self) -> bool {
+        self.contribution_deadline != 0
+    }
+
+    /// Applies the creation options; `sender` must already be set.
+    pub fn apply_options(&mut self, options: &TransferOptions) -> Result<()> {
+        require!(
+            options.crank_bounty_bps <= MAX_CRANK_BOUNTY_BPS,
+            ErrorCode::InvalidCrankBounty
+        );
+        self.irrevocable = options.irrevocable;
+        self.refund_to = options.refund_to.unwrap_or(self.sender);
+        self.expiry_beneficiary = options.expiry_beneficiary;
+        self.crank_bounty_lamports = options.crank_bounty_lamports;
+        self.crank_bounty_bps = options.crank_bounty_bps;
+        self.cancel_authority = options.cancel_authority;
+        self.commit_key = options.commit_key;
+        Ok(())
+    }
+
+    /// Whether `signer` may cancel: the sender or the cancel authority.
+    pub fn can_cancel(&self, signer: &Pubkey) -> bool {
+        *signer == self.sender || self.cancel_authority == Some(*signer)
+    }
+
+    /// Owner that `reclaim_expired` pays out to.
+    pub fn expiry_recipient(&self) -> Pubkey {
+        self.expiry_beneficiary.unwrap_or(self.refund_to)
+    }
+
+    /// Whether a `commit_claim` reservation is live at `now`.
+    pub fn is_reserved(&self, now: i64) -> bool {
+        now < self.reservation_expiry
+    }
+}
+
+/// One wallet's running contribution to a group transfer.
+///
+/// Seeds: ["contribution", transfer_pda, contributor]
+#[account]
+pub struct Contribution {
+    /// The group transfer this contribution funds
+    pub transfer: Pubkey,
+    /// The contributing wallet (receives the refund on expiry)
+    pub contributor: Pubkey,
+    /// Total tokens contributed by this wallet
+    pub amount: u64,
+    /// PDA bump seed for this account
+    pub bump: u8,
+}
+
+impl Contribution {
+    /// Account size in bytes:
+    /// - transfer: 32
+    /// - contributor: 32
+    /// - amount: 8
+    /// - bump: 1
+    ///
+    /// Total: 73 bytes
+    pub const LEN: usize = 32 + 32 + 8 + 1;
+}
+
+/// Claim code keys and approvals for an M-of-N transfer.
+///
+/// Seeds: ["approvals", transfer_pda]
+#[account]
+pub struct ClaimApprovals {
+    /// The transfer these approvals unlock
+    pub transfer: Pubkey,
+    /// Number of codes that must approve the same wallet (M)
+    pub threshold: u8,
+    /// Key derived from each claim code (N, at most 8)
+    pub code_keys: Vec<Pubkey>,
+    /// Wallet each code currently approves, by code index
+    pub votes: Vec<Option<Pubkey>>,
+    /// PDA bump seed for this account
+    pub bump: u8,
+}
+
+impl ClaimApprovals {
+    /// Account size in bytes:
+    /// - transfer: 32
+    /// - threshold: 1
+    /// - code_keys: 4 + 32 * 8
+    /// - votes: 4 + 33 * 8
+    /// - bump: 1
+    ///
+    /// Total: 562 bytes
+    pub const LEN: usize = 32 + 1 + (4 + 32 * MAX_CLAIM_CODES) + (4 + 33 * MAX_CLAIM_CODES) + 1;
+
+    /// Number of codes approving `recipient`.
+    pub fn approval_count(&self, recipient: &Pubkey) -> u8 {
+        self.votes
+            .iter()
+            .filter(|vote| vote.as_ref() == Some(recipient))
+            .count() as u8
+    }
+
+    /// The wallet `threshold` codes approve, if any.
+    pub fn approved_recipient(&self) -> Option<Pubkey> {
+        self.votes
+            .iter()
+            .flatten()
+            .find(|wallet| self.approval_count(wallet) >= self.threshold)
+            .copied()
+    }
+}
+
+/// Program-wide configuration, including trusted attestation verifiers.
+///
+/// Seeds: ["config"]
+#[account]
+pub struct Config {
+    /// Wallet allowed to manage the verifier list
+    pub admin: Pubkey,
+    /// Ed25519 keys of the off-chain email verifiers (at most 4)
+    pub verifiers: Vec<Pubkey>,
+    /// PDA bump seed for this account
+    pub bump: u8,
+}
+
+impl Config {
+    /// Account size in bytes:
+    /// - admin: 32
+    /// - verifiers: 4 + 32 * 4
+    /// - bump: 1
+    ///
+    /// Total: 165 bytes
+    pub const LEN: usize = 32 + (4 + 32 * MAX_VERIFIERS) + 1;
+}
+
+/// Running total of pending tokens of one mint in an inbox.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MintTotal {
+    /// SPL token mint address
+    pub mint: Pubkey,
+    /// Sum of pending transfer amounts for this mint
+    pub amount: u64,
+}
+
+impl MintTotal {
+    /// Serialized size in bytes: mint (32) + amount (8)
+    pub const LEN: usize = 32 + 8;
+}
+
+/// Everything pending for one recipient email hash.
+///
+/// Seeds: ["inbox", email_hash]
+#[account]
+pub struct Inbox {
+    /// SHA256 hash of (salt + recipient_email)
+    pub email_hash: [u8; 32],
+    /// Wallet bound through a verifier attestation (default if unbound)
+    pub owner: Pubkey,
+    /// Number of active transfers tracked by this inbox
+    pub pending_count: u32,
+    /// Pending amount per mint (at most 8 mints)
+    pub totals: Vec<MintTotal>,
+    /// PDA bump seed for this account
+    pub bump: u8,
+    /// Compressed secp256r1 key that replaces claim codes, if set
+    pub passkey: Option<[u8; 33]>,
+}
+
+impl Inbox {
+    /// Account size in bytes:
+    /// - email_hash: 32
+    /// - owner: 32
+    /// - pending_count: 4
+    /// - totals: 4 + 40 * 8
+    /// - bump: 1
+    /// - passkey: 1 + 33
+    ///
+    /// Total: 427 bytes
+    pub const LEN: usize =
+        32 + 32 + 4 + (4 + MintTotal::LEN * MAX_INBOX_MINTS) + 1 + 1 + PASSKEY_LEN;
+
+    /// Whether a wallet has been bound to this inbox.
+    pub fn is_bound(&self) -> bool {
+        self.owner != Pubkey::default()
+    }
+
+    /// Adds a new pending transfer to the counters.
+    pub fn add_transfer(&mut self, mint: Pubkey, amount: u64) -> Result<()> {
+        match self.totals.iter_mut().find(|t| t.mint == mint) {
+            Some(total) => {
+                total.amount = total
+                    .amount
+                    .checked_add(amount)
+                    .ok_or(ErrorCode::Overflow)?;
+            }
+            None => {
+                require!(self.totals.len() < MAX_INBOX_MINTS, ErrorCode::InboxFull);
+                self.totals.push(MintTotal { mint, amount });
+            }
+        }
+        self.pending_count = self
+            .pending_count
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        Ok(())
+    }
+
+    /// Removes a settled transfer from the counters.
+    ///
+    /// # Errors
+    /// * `InboxMintNotTracked` - No pending amount of `mint`
+    /// * `Overflow` - More removed than was added
+    pub fn remove_transfer(&mut self, mint: Pubkey, amount: u64) -> Result<()> {
+        let position = self
+            .totals
+            .iter()
+            .position(|t| t.mint == mint)
+            .ok_or(ErrorCode::InboxMintNotTracked)?;
+        let total = &mut self.totals[position];
+        total.amount = total
+            .amount
+            .checked_sub(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        if total.amount == 0 {
+            self.totals.remove(position);
+        }
+        self.pending_count = self
+            .pending_count
+            .checked_sub(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        Ok(())
+    }
+}
+
+/// Wallet registered for an email hash, used for direct payments.
+///
+/// Seeds: ["registry", email_hash]
+#[account]
+pub struct RegistryEntry {
+    /// SHA256 hash of (salt + recipient_email)
+    pub email_hash: [u8; 32],
+    /// Wallet that receives direct payments to this email hash
+    pub wallet: Pubkey,
+    /// Verifier whose attestation created the binding
+    pub verifier: Pubkey,
+    /// Unix timestamp of the latest registration
+    pub registered_at: i64,
+    /// PDA bump seed for this account
+    pub bump: u8,
+}
+
+impl RegistryEntry {
+    /// Account size in bytes:
+    /// - email_hash: 32
+    /// - wallet: 32
+    /// - verifier: 32
+    /// - registered_at: 8
+    /// - bump: 1
+    ///
+    /// Total: 105 bytes
+    pub const LEN: usize = 32 + 32 + 32 + 8 + 1;
+}
 
-    pub token_program: Program<'info, Token>,
+/// Marks a verifier attestation as used, so it cannot be replayed.
+///
+/// Seeds: ["attestation", keccak(message)]
+#[account]
+pub struct AttestationRecord {
+    /// Expiry of the consumed attestation
+    pub expires_at: i64,
 }
 
-// ============================================================================
-// Account State
-// ============================================================================
+impl AttestationRecord {
+    /// Account size in bytes:
+    /// - expires_at: 8
+    ///
+    /// Total: 8 bytes
+    pub const LEN: usize = 8;
+}
 
-/// Transfer status enum - more gas efficient than multiple booleans
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
-pub enum TransferStatus {
-    /// Transfer is active and can be claimed
-    Active = 0,
-    /// Transfer has been successfully claimed
-    Claimed = 1,
-    /// Transfer was cancelled by sender
-    Cancelled = 2,
-    /// Transfer expired and was reclaimed
-    Expired = 3,
+/// zk-DKIM Groth16 verifying key, set by the config admin.
+///
+/// Seeds: ["dkim_verifier"]
+#[account]
+pub struct DkimVerifier {
+    /// Alpha point (G1, EIP-197 encoding)
+    pub alpha_g1: [u8; 64],
+    /// Beta point (G2)
+    pub beta_g2: [u8; 128],
+    /// Gamma point (G2)
+    pub gamma_g2: [u8; 128],
+    /// Delta point (G2)
+    pub delta_g2: [u8; 128],
+    /// IC points (G1), constant term first; empty until set
+    pub ic: Vec<[u8; 64]>,
+    /// PDA bump seed for this account
+    pub bump: u8,
 }
 
-impl Default for TransferStatus {
-    fn default() -> Self {
-        TransferStatus::Active
+impl DkimVerifier {
+    /// Account size in bytes:
+    /// - alpha_g1: 64
+    /// - beta_g2, gamma_g2, delta_g2: 3 * 128
+    /// - ic: 4 + 64 * (DKIM_PUBLIC_INPUTS + 1)
+    /// - bump: 1
+    ///
+    /// Total: 1029 bytes
+    pub const LEN: usize = 64 + 3 * 128 + 4 + 64 * (DKIM_PUBLIC_INPUTS + 1) + 1;
+
+    pub fn verifying_key(&self) -> groth16::VerifyingKey<'_> {
+        groth16::VerifyingKey {
+            alpha_g1: &self.alpha_g1,
+            beta_g2: &self.beta_g2,
+            gamma_g2: &self.gamma_g2,
+            delta_g2: &self.delta_g2,
+            ic: &self.ic,
+        }
     }
 }
 
-/// State account for a pending token transfer.
+/// DKIM key hashes accepted for an email domain.
 ///
-/// This account stores all metadata about an escrow transfer,
-/// including the claim code hash for verification and the
-/// expiry timestamp.
+/// Seeds: ["dkim", domain_hash]
 #[account]
-pub struct TransferAccount {
-    /// The sender who created and funded this transfer
-    pub sender: Pubkey,
-    /// SHA256 hash of (salt + recipient_email)
-    pub email_hash: [u8; 32],
-    /// SHA256 hash of the claim code
-    pub claim_code_hash: [u8; 32],
-    /// Amount of tokens held in escrow
-    pub amount: u64,
-    /// SPL token mint address
-    pub token_mint: Pubkey,
-    /// Address of the escrow token account
-    pub escrow_token_account: Pubkey,
-    /// Unix timestamp when transfer was created
-    pub created_at: i64,
-    /// Unix timestamp when transfer expires
-    pub expiry: i64,
-    /// Current status of the transfer
-    pub status: TransferStatus,
-    /// PDA bump seed for this transfer account
+pub struct DkimDomain {
+    /// Hash of the email domain
+    pub domain_hash: [u8; 32],
+    /// Hashes of the domain's active DKIM public keys
+    pub key_hashes: Vec<[u8; 32]>,
+    /// PDA bump seed for this account
     pub bump: u8,
-    /// PDA bump seed for the escrow token account
-    pub escrow_bump: u8,
 }
 
-impl TransferAccount {
+impl DkimDomain {
     /// Account size in bytes:
-    /// - sender: 32
-    /// - email_hash: 32
-    /// - claim_code_hash: 32
-    /// - amount: 8
-    /// - token_mint: 32
-    /// - escrow_token_account: 32
-    /// - created_at: 8
-    /// - expiry: 8
-    /// - status: 1 (enum stored as u8)
+    /// - domain_hash: 32
+    /// - key_hashes: 4 + 32 * MAX_DKIM_KEYS
     /// - bump: 1
-    /// - escrow_bump: 1
-    /// Total: 187 bytes
-    pub const LEN: usize = 32 + 32 + 32 + 8 + 32 + 32 + 8 + 8 + 1 + 1 + 1;
+    ///
+    /// Total: 165 bytes
+    pub const LEN: usize = 32 + 4 + 32 * MAX_DKIM_KEYS + 1;
 }
 
 // ============================================================================
@@ -650,49 +4137,367 @@ impl TransferAccount {
 /// Emitted when a new transfer escrow is created.
 #[event]
 pub struct TransferCreated {
+    /// Event schema version (`TRANSFER_EVENT_VERSION`)
+    pub version: u8,
     /// The transfer PDA address
     pub transfer: Pubkey,
     /// The sender who created this transfer
     pub sender: Pubkey,
+    /// SHA256(salt + email) of the recipient
+    pub email_hash: [u8; 32],
     /// The token mint being transferred
     pub token_mint: Pubkey,
     /// Amount of tokens escrowed
     pub amount: u64,
-    /// Unix timestamp when this transfer expires
+    /// Whether the sender can cancel before expiry
+    pub irrevocable: bool,
+    /// Unix timestamp when the transfer was created
+    pub created_at: i64,
+    /// Unix timestamp when the transfer expires
     pub expiry: i64,
+    /// Unix timestamp of this event
+    pub timestamp: i64,
 }
 
 /// Emitted when a transfer is successfully claimed.
 #[event]
 pub struct TransferClaimed {
+    /// Event schema version (`TRANSFER_EVENT_VERSION`)
+    pub version: u8,
     /// The transfer PDA address
     pub transfer: Pubkey,
+    /// The sender who created this transfer
+    pub sender: Pubkey,
+    /// SHA256(salt + email) of the recipient
+    pub email_hash: [u8; 32],
+    /// The token mint being transferred
+    pub token_mint: Pubkey,
     /// The recipient who claimed the transfer
     pub recipient: Pubkey,
     /// Amount of tokens received
     pub amount: u64,
+    /// Unix timestamp when the transfer was created
+    pub created_at: i64,
+    /// Unix timestamp when the transfer expires
+    pub expiry: i64,
+    /// Unix timestamp of this event
+    pub timestamp: i64,
 }
 
 /// Emitted when a transfer is cancelled by the sender.
 #[event]
 pub struct TransferCancelled {
+    /// Event schema version (`TRANSFER_EVENT_VERSION`)
+    pub version: u8,
     /// The transfer PDA address
     pub transfer: Pubkey,
-    /// The sender who cancelled
+    /// The sender who created this transfer
     pub sender: Pubkey,
+    /// SHA256(salt + email) of the recipient
+    pub email_hash: [u8; 32],
+    /// The token mint being transferred
+    pub token_mint: Pubkey,
+    /// The sender or cancel authority that signed the cancel
+    pub cancelled_by: Pubkey,
+    /// Owner of the token account that received the refund
+    pub refund_to: Pubkey,
+    /// Amount of tokens refunded
+    pub amount: u64,
+    /// Unix timestamp when the transfer was created
+    pub created_at: i64,
+    /// Unix timestamp when the transfer expires
+    pub expiry: i64,
+    /// Unix timestamp of this event
+    pub timestamp: i64,
+}
+
+impl TransferCreated {
+    fn new(key: Pubkey, transfer: &TransferAccount, amount: u64) -> Self {
+        Self {
+            version: TRANSFER_EVENT_VERSION,
+            transfer: key,
+            sender: transfer.sender,
+            email_hash: transfer.email_hash,
+            token_mint: transfer.token_mint,
+            amount,
+            irrevocable: transfer.irrevocable,
+            created_at: transfer.created_at,
+            expiry: transfer.expiry,
+            timestamp: transfer.created_at,
+        }
+    }
+}
+
+impl TransferClaimed {
+    fn new(
+        key: Pubkey,
+        transfer: &TransferAccount,
+        recipient: Pubkey,
+        amount: u64,
+        timestamp: i64,
+    ) -> Self {
+        Self {
+            version: TRANSFER_EVENT_VERSION,
+            transfer: key,
+            sender: transfer.sender,
+            email_hash: transfer.email_hash,
+            token_mint: transfer.token_mint,
+            recipient,
+            amount,
+            created_at: transfer.created_at,
+            expiry: transfer.expiry,
+            timestamp,
+        }
+    }
+}
+
+impl TransferCancelled {
+    fn new(
+        key: Pubkey,
+        transfer: &TransferAccount,
+        cancelled_by: Pubkey,
+        amount: u64,
+        timestamp: i64,
+    ) -> Self {
+        Self {
+            version: TRANSFER_EVENT_VERSION,
+            transfer: key,
+            sender: transfer.sender,
+            email_hash: transfer.email_hash,
+            token_mint: transfer.token_mint,
+            cancelled_by,
+            refund_to: transfer.refund_to,
+            amount,
+            created_at: transfer.created_at,
+            expiry: transfer.expiry,
+            timestamp,
+        }
+    }
+}
+
+/// Emitted when a claim code of an M-of-N transfer is approved.
+#[event]
+pub struct ClaimCodeApproved {
+    /// The transfer PDA address
+    pub transfer: Pubkey,
+    /// Key of the approving code
+    pub approver: Pubkey,
+    /// The wallet the code approves
+    pub recipient: Pubkey,
+    /// Index of the approving code
+    pub code_index: u8,
+    /// Number of codes approving `recipient`
+    pub approvals: u8,
+    /// Number of approvals required
+    pub threshold: u8,
+}
+
+/// Emitted when tokens are added to a group transfer.
+#[event]
+pub struct ContributionAdded {
+    /// The transfer PDA address
+    pub transfer: Pubkey,
+    /// The contributing wallet
+    pub contributor: Pubkey,
+    /// Amount contributed in this instruction
+    pub amount: u64,
+    /// Total escrowed across all contributors
+    pub total: u64,
+}
+
+/// Emitted when a contributor is refunded from an expired group transfer.
+#[event]
+pub struct ContributionRefunded {
+    /// The transfer PDA address
+    pub transfer: Pubkey,
+    /// The refunded contributor
+    pub contributor: Pubkey,
     /// Amount of tokens refunded
     pub amount: u64,
 }
 
+/// Emitted when the last contributor of an expired group transfer is
+/// refunded, which ends the transfer the way a reclaim ends others.
+#[event]
+pub struct GroupTransferRefunded {
+    /// Event schema version (`TRANSFER_EVENT_VERSION`)
+    pub version: u8,
+    /// The transfer PDA address
+    pub transfer: Pubkey,
+    /// The organizer of the transfer
+    pub sender: Pubkey,
+    /// SHA256(salt + email) of the recipient
+    pub email_hash: [u8; 32],
+    /// The token mint being transferred
+    pub token_mint: Pubkey,
+    /// The last contributor, who received the rest of the escrow
+    pub contributor: Pubkey,
+    /// Amount of tokens refunded to the last contributor
+    pub amount: u64,
+    /// Unix timestamp when the transfer was created
+    pub created_at: i64,
+    /// Unix timestamp when the transfer expires
+    pub expiry: i64,
+    /// Unix timestamp of this event
+    pub timestamp: i64,
+}
+
+/// Emitted when an attestation verifier is registered or removed.
+#[event]
+pub struct VerifierUpdated {
+    /// The verifier's Ed25519 public key
+    pub verifier: Pubkey,
+    /// Whether the key is now trusted
+    pub active: bool,
+}
+
+/// Emitted when a wallet is bound to an inbox.
+#[event]
+pub struct InboxBound {
+    /// The inbox PDA address
+    pub inbox: Pubkey,
+    /// The email hash the inbox belongs to
+    pub email_hash: [u8; 32],
+    /// The newly bound wallet
+    pub owner: Pubkey,
+    /// The verifier whose attestation was used
+    pub verifier: Pubkey,
+}
+
+/// Emitted after `sweep_inbox` claims a batch of transfers.
+#[event]
+pub struct InboxSwept {
+    /// The inbox PDA address
+    pub inbox: Pubkey,
+    /// The wallet that received the funds
+    pub owner: Pubkey,
+    /// Number of transfers claimed in this instruction
+    pub transfers: u32,
+}
+
 /// Emitted when an expired transfer is reclaimed.
 #[event]
 pub struct TransferReclaimed {
+    /// Event schema version (`TRANSFER_EVENT_VERSION`)
+    pub version: u8,
     /// The transfer PDA address
     pub transfer: Pubkey,
-    /// The original sender who received the refund
+    /// The original sender of the transfer
     pub sender: Pubkey,
+    /// SHA256(salt + email) of the recipient
+    pub email_hash: [u8; 32],
+    /// The token mint being transferred
+    pub token_mint: Pubkey,
+    /// Owner of the token account that received the funds
+    pub recipient: Pubkey,
     /// Amount of tokens refunded
     pub amount: u64,
+    /// Signer of the reclaim, who collected the bounty
+    pub cranker: Pubkey,
+    /// Lamport bounty paid to the cranker
+    pub bounty_lamports: u64,
+    /// Token bounty paid to the cranker
+    pub bounty_tokens: u64,
+    /// Unix timestamp when the transfer was created
+    pub created_at: i64,
+    /// Unix timestamp when the transfer expires
+    pub expiry: i64,
+    /// Unix timestamp of this event
+    pub timestamp: i64,
+}
+
+impl TransferReclaimed {
+    fn new(
+        key: Pubkey,
+        transfer: &TransferAccount,
+        amount: u64,
+        cranker: Pubkey,
+        bounty_lamports: u64,
+        bounty_tokens: u64,
+        timestamp: i64,
+    ) -> Self {
+        Self {
+            version: TRANSFER_EVENT_VERSION,
+            transfer: key,
+            sender: transfer.sender,
+            email_hash: transfer.email_hash,
+            token_mint: transfer.token_mint,
+            recipient: transfer.expiry_recipient(),
+            amount,
+            cranker,
+            bounty_lamports,
+            bounty_tokens,
+            created_at: transfer.created_at,
+            expiry: transfer.expiry,
+            timestamp,
+        }
+    }
+}
+
+/// Emitted when a wallet is registered for an email hash.
+#[event]
+pub struct WalletRegistered {
+    /// The registered email hash
+    pub email_hash: [u8; 32],
+    /// The wallet now receiving direct payments
+    pub wallet: Pubkey,
+    /// The verifier whose attestation was used
+    pub verifier: Pubkey,
+}
+
+/// Emitted when `send_to_email` pays a registered wallet directly.
+#[event]
+pub struct DirectPaymentSent {
+    /// The recipient's email hash
+    pub email_hash: [u8; 32],
+    /// The sender who paid
+    pub sender: Pubkey,
+    /// The registered wallet that received the tokens
+    pub recipient: Pubkey,
+    /// The token mint transferred
+    pub token_mint: Pubkey,
+    /// Amount of tokens transferred
+    pub amount: u64,
+}
+
+/// Emitted when a DKIM key hash is registered or revoked for a domain.
+#[event]
+pub struct DkimKeyUpdated {
+    /// Hash of the email domain
+    pub domain_hash: [u8; 32],
+    /// Hash of the DKIM public key
+    pub key_hash: [u8; 32],
+    /// Whether the key is now accepted for the domain
+    pub active: bool,
+}
+
+/// Emitted when a passkey is set or cleared on a transfer or inbox.
+#[event]
+pub struct ClaimPasskeyUpdated {
+    /// The transfer or inbox PDA address
+    pub account: Pubkey,
+    /// The new compressed secp256r1 key, or `None` if cleared
+    pub passkey: Option<[u8; 33]>,
+}
+
+/// Emitted when a transfer is reserved with `commit_claim`.
+#[event]
+pub struct ClaimCommitted {
+    /// The transfer PDA address
+    pub transfer: Pubkey,
+    /// The commit key that signed the commitment
+    pub committer: Pubkey,
+    /// Unix timestamp when the reservation lapses
+    pub expires_at: i64,
+}
+
+/// Emitted when the sender changes a transfer's cancel authority.
+#[event]
+pub struct CancelAuthorityUpdated {
+    /// The transfer PDA address
+    pub transfer: Pubkey,
+    /// Wallet that may now cancel besides the sender, if any
+    pub cancel_authority: Option<Pubkey>,
 }
 
 // ============================================================================
@@ -710,16 +4515,20 @@ pub enum ErrorCode {
     #[msg("Transfer has expired and can no longer be claimed")]
     TransferExpired,
 
+    /// Never raised: settled transfers fail with `InvalidTransferState`.
+    /// Kept so that later error numbers stay stable.
     #[msg("Transfer has already been claimed")]
     AlreadyClaimed,
 
+    /// Never raised: settled transfers fail with `InvalidTransferState`.
+    /// Kept so that later error numbers stay stable.
     #[msg("Transfer has already been refunded")]
     AlreadyRefunded,
 
     #[msg("Transfer has not expired yet")]
     NotExpired,
 
-    #[msg("Unauthorized: only the original sender can perform this action")]
+    #[msg("Unauthorized: only the sender, or for cancels its cancel authority, can perform this action")]
     Unauthorized,
 
     #[msg("Invalid amount: must be greater than zero")]
@@ -748,4 +4557,240 @@ pub enum ErrorCode {
 
     #[msg("Invalid transfer state for this operation")]
     InvalidTransferState,
+
+    #[msg("Transfer cannot be claimed with this instruction")]
+    InvalidClaimMode,
+
+    #[msg("Invalid claim codes: need 1 to 8 distinct code hashes")]
+    InvalidClaimCodeSet,
+
+    #[msg("Invalid threshold: must be between 1 and the number of codes")]
+    InvalidThreshold,
+
+    #[msg("Not enough claim codes have been approved")]
+    InsufficientApprovals,
+
+    #[msg("Recipient does not match the wallet the approvals name")]
+    ApprovalRecipientMismatch,
+
+    #[msg("Invalid contribution window: must be between 1 hour and the expiry")]
+    InvalidContributionDeadline,
+
+    #[msg("Transfer does not accept contributions")]
+    NotGroupTransfer,
+
+    #[msg("Contribution deadline has passed")]
+    ContributionsClosed,
+
+    #[msg("Group transfers are refunded per contributor with refund_contribution")]
+    GroupTransferRefund,
+
+    #[msg("Unauthorized: only the config admin can perform this action")]
+    NotConfigAdmin,
+
+    #[msg("Verifier list is full")]
+    VerifierListFull,
+
+    #[msg("Attestation is not signed by a registered verifier")]
+    UnknownVerifier,
+
+    #[msg("Invalid attestation: missing or malformed Ed25519 instruction")]
+    InvalidAttestation,
+
+    #[msg("Attestation has expired")]
+    AttestationExpired,
+
+    #[msg("Inbox already tracks the maximum number of mints")]
+    InboxFull,
+
+    #[msg("Transfer is tracked by an inbox that must be provided")]
+    InboxRequired,
+
+    #[msg("Unauthorized: only the wallet bound to the inbox can perform this action")]
+    NotInboxOwner,
+
+    #[msg("Invalid sweep accounts: need transfer, escrow, destination and sender")]
+    InvalidSweepAccounts,
+
+    #[msg("Email hash has a registered wallet: pass its token account")]
+    RecipientAccountRequired,
+
+    #[msg("Escrow accounts must be passed if and only if no wallet is registered")]
+    EscrowAccountsRequired,
+
+    #[msg("Invalid zk proof")]
+    InvalidProof,
+
+    #[msg("zk-DKIM verifying key is not fully set")]
+    DkimVerifierNotReady,
+
+    #[msg("Domain already has the maximum number of DKIM keys")]
+    DkimKeyListFull,
+
+    #[msg("DKIM key is not registered for this domain")]
+    UnknownDkimKey,
+
+    #[msg("Passkey must be a compressed secp256r1 public key")]
+    InvalidPasskey,
+
+    #[msg("Invalid passkey signature: missing or malformed secp256r1 assertion")]
+    InvalidPasskeySignature,
+
+    #[msg("Transfer is reserved by a pending claim commitment")]
+    ClaimReserved,
+
+    #[msg("Transfer has no live claim reservation")]
+    NoActiveReservation,
+
+    #[msg("Claim code, destination and salt do not match the commitment")]
+    InvalidCommitment,
+
+    #[msg("Transfer has used all of its claim reservations")]
+    ReservationLimitReached,
+
+    #[msg(
+        "Transfer is irrevocable: the sender can no longer cancel it or change how it is claimed"
+    )]
+    IrrevocableTransfer,
+
+    #[msg("Crank bounty exceeds the maximum of 500 basis points")]
+    InvalidCrankBounty,
+
+    #[msg("Commit must be signed by the transfer's commit key")]
+    InvalidCommitter,
+
+    #[msg("M-of-N transfers must pass their approvals account")]
+    ApprovalsRequired,
+
+    #[msg("Inbox does not track a pending amount of this mint")]
+    InboxMintNotTracked,
+
+    #[msg("Passkey client data exceeds maximum length of 1024 bytes")]
+    ClientDataTooLong,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program_pack::Pack;
+    use anchor_spl::token::spl_token::state::{Account as SplTokenAccount, AccountState};
+
+    fn token_account_data(owner: Pubkey, mint: Pubkey, state: AccountState) -> Vec<u8> {
+        let mut data = vec![0u8; SplTokenAccount::LEN];
+        SplTokenAccount {
+            mint,
+            owner,
+            state,
+            ..SplTokenAccount::default()
+        }
+        .pack_into_slice(&mut data);
+        data
+    }
+
+    fn route(
+        key: Pubkey,
+        account_owner: Pubkey,
+        data: &mut [u8],
+        owner: &Pubkey,
+        mint: &Pubkey,
+    ) -> Result<RefundRoute> {
+        let mut lamports = if data.is_empty() { 0 } else { 2_039_280 };
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            data,
+            &account_owner,
+            false,
+            0,
+        );
+        refund_route(&account, owner, mint)
+    }
+
+    #[test]
+    fn refund_to_open_token_account() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = token_account_data(owner, mint, AccountState::Initialized);
+        let result = route(Pubkey::new_unique(), token::ID, &mut data, &owner, &mint);
+        assert_eq!(result.unwrap(), RefundRoute::TokenAccount);
+    }
+
+    #[test]
+    fn closed_refund_account_creates_associated_account() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ata = get_associated_token_address(&owner, &mint);
+        let result = route(ata, System::id(), &mut [], &owner, &mint);
+        assert_eq!(result.unwrap(), RefundRoute::CreateAssociatedAccount);
+    }
+
+    #[test]
+    fn closed_non_associated_account_is_rejected() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let result = route(Pubkey::new_unique(), System::id(), &mut [], &owner, &mint);
+        assert_eq!(result.unwrap_err(), ErrorCode::InvalidTokenAccount.into());
+    }
+
+    #[test]
+    fn frozen_refund_account_hands_over_escrow() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = token_account_data(owner, mint, AccountState::Frozen);
+        let result = route(Pubkey::new_unique(), token::ID, &mut data, &owner, &mint);
+        assert_eq!(result.unwrap(), RefundRoute::HandOverEscrow);
+    }
+
+    #[test]
+    fn refund_account_of_other_owner_is_rejected() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = token_account_data(Pubkey::new_unique(), mint, AccountState::Initialized);
+        let result = route(Pubkey::new_unique(), token::ID, &mut data, &owner, &mint);
+        assert_eq!(result.unwrap_err(), ErrorCode::InvalidTokenAccount.into());
+    }
+
+    #[test]
+    fn refund_account_of_other_mint_is_rejected() {
+        let (owner, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = token_account_data(owner, Pubkey::new_unique(), AccountState::Frozen);
+        let result = route(Pubkey::new_unique(), token::ID, &mut data, &owner, &mint);
+        assert_eq!(result.unwrap_err(), ErrorCode::InvalidTokenMint.into());
+    }
+
+    #[test]
+    fn inbox_counts_transfers_per_mint() {
+        let (mint, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut inbox = Inbox {
+            email_hash: [0; 32],
+            owner: Pubkey::default(),
+            pending_count: 0,
+            totals: Vec::new(),
+            bump: 0,
+            passkey: None,
+        };
+        inbox.add_transfer(mint, 5).unwrap();
+        inbox.add_transfer(mint, 7).unwrap();
+        assert_eq!(inbox.pending_count, 2);
+        assert_eq!(inbox.totals, vec![MintTotal { mint, amount: 12 }]);
+
+        assert_eq!(
+            inbox.remove_transfer(other, 5).unwrap_err(),
+            ErrorCode::InboxMintNotTracked.into()
+        );
+        inbox.remove_transfer(mint, 5).unwrap();
+        inbox.remove_transfer(mint, 7).unwrap();
+        assert_eq!(inbox.pending_count, 0);
+        assert!(inbox.totals.is_empty());
+    }
+
+    #[test]
+    fn attestation_messages_are_domain_separated() {
+        let (email_hash, wallet) = ([7; 32], Pubkey::new_unique());
+        let bind = bind_attestation_message(&email_hash, &wallet, 100);
+        let register = register_attestation_message(&email_hash, &wallet, 100);
+        assert_ne!(bind, register);
+        assert!(register.starts_with(REGISTER_ATTESTATION_DOMAIN));
+        assert_eq!(
+            &register[REGISTER_ATTESTATION_DOMAIN.len()..],
+            &bind[BIND_ATTESTATION_DOMAIN.len()..]
+        );
+    }
 }
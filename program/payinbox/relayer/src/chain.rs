@@ -0,0 +1,120 @@
+//! Where the relayer reads transfers and lands claims.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use anyhow::Result;
+use payinbox::TransferAccount;
+use payinbox_client::accounts::fetch_transfer;
+use payinbox_client::rpc::{RpcClient, Simulation};
+use payinbox_client::ClientError;
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+/// Outcome of a dry run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Simulated {
+    /// Why the transaction would fail
+    pub error: Option<String>,
+    /// Lamports the relayer would lose, fee included
+    pub cost: i64,
+}
+
+/// A transaction that landed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Submitted {
+    pub signature: String,
+    /// Lamports the relayer spent on it, fee included
+    pub fee: u64,
+}
+
+/// A cluster the relayer works against. Transactions are paid for by the
+/// relayer and signed by it and the given `signers`.
+pub trait Chain {
+    fn relayer(&self) -> Pubkey;
+
+    /// The transfer at `address`, if there is one.
+    fn transfer(&self, address: &Pubkey) -> Result<Option<TransferAccount>>;
+
+    /// Base fee of `instructions` as one transaction.
+    fn fee(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<u64>;
+
+    /// Runs `instructions` as one transaction without landing it.
+    fn simulate(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<Simulated>;
+
+    /// Lands `instructions` as one transaction. Fails, without paying a
+    /// fee, if its simulation fails; fails if it failed or didn't confirm.
+    fn submit(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<Submitted>;
+}
+
+/// An RPC node, with the relayer's keypair.
+pub struct RpcChain {
+    rpc: RpcClient,
+    relayer: Keypair,
+}
+
+impl RpcChain {
+    pub fn new(rpc: RpcClient, relayer: Keypair) -> Self {
+        Self { rpc, relayer }
+    }
+
+    /// The cost is the relayer's balance now less its balance after the
+    /// simulation, so transactions of the relayer landing in between skew
+    /// it.
+    fn dry_run(&self, wire: &[u8]) -> Result<Simulated> {
+        let relayer = self.relayer.pubkey();
+        let before = self.rpc.get_balance(&relayer)?;
+        let simulation = self
+            .rpc
+            .simulate_transaction_with_balances(wire, &[relayer])?;
+        Ok(Simulated {
+            error: simulation.error_message(),
+            cost: cost(before, &simulation),
+        })
+    }
+}
+
+impl Chain for RpcChain {
+    fn relayer(&self) -> Pubkey {
+        self.relayer.pubkey()
+    }
+
+    fn transfer(&self, address: &Pubkey) -> Result<Option<TransferAccount>> {
+        match fetch_transfer(&self.rpc, address) {
+            Ok(transfer) => Ok(Some(transfer)),
+            Err(ClientError::AccountNotFound(_)) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn fee(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<u64> {
+        let message = self
+            .rpc
+            .sign_transaction(instructions, &self.relayer, signers)?
+            .message_data();
+        Ok(self.rpc.get_fee_for_message(&message)?)
+    }
+
+    fn simulate(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<Simulated> {
+        let transaction = self
+            .rpc
+            .sign_transaction(instructions, &self.relayer, signers)?;
+        self.dry_run(&bincode::serialize(&transaction)?)
+    }
+
+    fn submit(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<Submitted> {
+        let before = self.rpc.get_balance(&self.relayer.pubkey())?;
+        let sent = self
+            .rpc
+            .send_and_confirm(instructions, &self.relayer, signers)?;
+        Ok(Submitted {
+            signature: sent.signature,
+            fee: cost(before, &sent.simulation).max(0) as u64,
+        })
+    }
+}
+
+/// Lamports the relayer holds `before` less its balance after `simulation`.
+fn cost(before: u64, simulation: &Simulation) -> i64 {
+    let after = simulation.balances.first().copied().flatten().unwrap_or(0);
+    before as i64 - after as i64
+}
@@ -0,0 +1,152 @@
+//! SQLite record of the transaction fees the relayer paid.
+
+use std::net::IpAddr;
+use std::path::Path;
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{bail, Context, Result};
+use rusqlite::{named_params, Connection};
+
+/// Stored in `PRAGMA user_version`; bumped with every table change.
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sponsored (
+    signature   TEXT PRIMARY KEY,
+    transfer    TEXT NOT NULL,
+    destination TEXT NOT NULL,
+    client_ip   TEXT NOT NULL,
+    instruction TEXT NOT NULL,
+    fee         INTEGER NOT NULL,
+    relayed_at  INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS sponsored_by_transfer ON sponsored (transfer);
+";
+
+/// A transaction the relayer paid for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sponsored {
+    pub signature: String,
+    pub transfer: Pubkey,
+    pub destination: Pubkey,
+    /// Address the claim was requested from
+    pub client_ip: IpAddr,
+    /// `commit_claim` or `reveal_claim`
+    pub instruction: String,
+    /// Lamports the relayer spent on it, fee included
+    pub fee: u64,
+    /// Unix time it was submitted
+    pub relayed_at: i64,
+}
+
+/// Sums over the whole ledger.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub transactions: u64,
+    pub fees: u64,
+}
+
+/// The ledger database.
+pub struct Ledger {
+    conn: Connection,
+}
+
+impl Ledger {
+    /// Opens (creating if needed) the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Connection::open(path)
+            .map_err(anyhow::Error::from)
+            .and_then(Self::init)
+            .with_context(|| format!("opening {}", path.display()))
+    }
+
+    /// An empty database that lives as long as the `Ledger`.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        let tables: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'sponsored'",
+            [],
+            |row| row.get(0),
+        )?;
+        if tables > 0 && version != SCHEMA_VERSION {
+            bail!("ledger has schema version {version}, expected {SCHEMA_VERSION}");
+        }
+        conn.execute_batch(SCHEMA)
+            .context("creating the ledger tables")?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self { conn })
+    }
+
+    pub fn record(&self, sponsored: &Sponsored) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO sponsored
+                 (signature, transfer, destination, client_ip, instruction, fee, relayed_at)
+             VALUES (:signature, :transfer, :destination, :client_ip, :instruction, :fee,
+                     :relayed_at)",
+            named_params! {
+                ":signature": sponsored.signature,
+                ":transfer": sponsored.transfer.to_string(),
+                ":destination": sponsored.destination.to_string(),
+                ":client_ip": sponsored.client_ip.to_string(),
+                ":instruction": sponsored.instruction,
+                ":fee": i64::try_from(sponsored.fee).context("fee out of SQLite integer range")?,
+                ":relayed_at": sponsored.relayed_at,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Transactions sponsored for `transfer`, oldest first.
+    pub fn for_transfer(&self, transfer: &Pubkey) -> Result<Vec<Sponsored>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT signature, transfer, destination, client_ip, instruction, fee, relayed_at
+             FROM sponsored WHERE transfer = ?1 ORDER BY relayed_at, rowid",
+        )?;
+        let rows = stmt
+            .query_map([transfer.to_string()], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, i64>(5)?,
+                    row.get::<_, i64>(6)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter()
+            .map(
+                |(signature, transfer, destination, client_ip, instruction, fee, relayed_at)| {
+                    Ok(Sponsored {
+                        signature,
+                        transfer: transfer.parse().context("transfer address")?,
+                        destination: destination.parse().context("destination address")?,
+                        client_ip: client_ip.parse().context("client address")?,
+                        instruction,
+                        fee: fee as u64,
+                        relayed_at,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    pub fn totals(&self) -> Result<Totals> {
+        Ok(self.conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(fee), 0) FROM sponsored",
+            [],
+            |row| {
+                Ok(Totals {
+                    transactions: row.get::<_, i64>(0)? as u64,
+                    fees: row.get::<_, i64>(1)? as u64,
+                })
+            },
+        )?)
+    }
+}
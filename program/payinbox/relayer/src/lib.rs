@@ -0,0 +1,293 @@
+//! Gasless claim relayer for the payinbox program.
+//!
+//! A recipient without SOL sends the relayer their claim code and the
+//! wallet to pay out to. [`Relayer::relay`] claims through `commit_claim`
+//! and `reveal_claim`, paying both fees: the commitment binds the
+//! destination before the code is revealed on chain, and neither step
+//! needs the recipient's signature. The commit is co-signed by the commit
+//! key derived from the code, so only transfers created with that key can
+//! be relayed. The pair is simulated first, so a
+//! claim that would fail or cost more than the configured fee for both
+//! transactions never lands, and every transaction that does is written
+//! to a [`Ledger`].
+//!
+//! - [`chain`]: where transfers are read and claims land, an RPC node
+//!   outside of tests
+//! - [`limit`]: per-client and per-transfer rate limits
+//! - [`ledger`]: SQLite record of the sponsored fees
+//! - [`server`]: the HTTP API
+
+pub mod chain;
+pub mod ledger;
+pub mod limit;
+pub mod server;
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anchor_lang::prelude::Pubkey;
+use payinbox::TransferStatus;
+use payinbox_client::hash::commit_keypair;
+use payinbox_client::instructions::{CommitClaim, RevealClaim};
+use solana_signer::Signer;
+use thiserror::Error;
+
+use chain::{Chain, Submitted};
+use ledger::{Ledger, Sponsored};
+use limit::{Limit, RateLimiter};
+
+/// What the relayer sponsors, and how often.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Most lamports the relayer spends on one claim, fees of both the
+    /// commit and the reveal included
+    pub max_fee_lamports: u64,
+    /// Claim requests per client address
+    pub per_client: Limit,
+    /// Claim requests per transfer, whoever sends them
+    pub per_transfer: Limit,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            // The base fees of the commit, signed by the relayer and the
+            // commit key, and of the reveal, signed by the relayer alone
+            max_fee_lamports: 15_000,
+            per_client: Limit {
+                requests: 10,
+                window: Duration::from_secs(60),
+            },
+            per_transfer: Limit {
+                requests: 2,
+                window: Duration::from_secs(300),
+            },
+        }
+    }
+}
+
+/// A recipient's request to claim `transfer` into `destination`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClaimRequest {
+    pub transfer: Pubkey,
+    /// Wallet the claim pays out to
+    pub destination: Pubkey,
+    pub claim_code: String,
+    /// Token account to pay into instead of the destination's ATA. Either
+    /// must exist: the relayer doesn't pay for accounts.
+    pub destination_token_account: Option<Pubkey>,
+}
+
+/// A relayed claim.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Receipt {
+    pub commit_signature: String,
+    pub reveal_signature: String,
+    /// Lamports the two transactions cost the relayer
+    pub fee: u64,
+}
+
+/// Why a claim wasn't relayed.
+#[derive(Debug, Error)]
+pub enum RelayError {
+    /// Too many requests from the client or for the transfer
+    #[error("too many claim requests {scope}; retry in {}s", retry_after.as_secs().max(1))]
+    RateLimited {
+        scope: &'static str,
+        retry_after: Duration,
+    },
+    /// Another request for the transfer is being relayed
+    #[error("a claim of {0} is already in progress")]
+    InProgress(Pubkey),
+    /// The claim would fail, or cost the relayer too much
+    #[error("{0}")]
+    Rejected(String),
+    /// The cluster or the ledger failed
+    #[error(transparent)]
+    Failed(#[from] anyhow::Error),
+}
+
+/// Relays claims through a [`Chain`], one at a time per transfer.
+pub struct Relayer<C> {
+    chain: C,
+    config: Config,
+    ledger: Mutex<Ledger>,
+    per_client: Mutex<RateLimiter<IpAddr>>,
+    per_transfer: Mutex<RateLimiter<Pubkey>>,
+    in_progress: Mutex<HashSet<Pubkey>>,
+}
+
+impl<C: Chain> Relayer<C> {
+    pub fn new(chain: C, ledger: Ledger, config: Config) -> Self {
+        Self {
+            chain,
+            per_client: Mutex::new(RateLimiter::new(config.per_client)),
+            per_transfer: Mutex::new(RateLimiter::new(config.per_transfer)),
+            config,
+            ledger: Mutex::new(ledger),
+            in_progress: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn chain(&self) -> &C {
+        &self.chain
+    }
+
+    pub fn ledger(&self) -> MutexGuard<'_, Ledger> {
+        lock(&self.ledger)
+    }
+
+    /// Claims for `request`, sent from `client` at `now`.
+    ///
+    /// Requests count against the rate limits whether or not they are
+    /// relayed. If the reveal fails after the commitment landed, the
+    /// reservation lapses on its own and the claim can be retried.
+    pub fn relay(
+        &self,
+        client: IpAddr,
+        request: &ClaimRequest,
+        now: Instant,
+    ) -> Result<Receipt, RelayError> {
+        lock(&self.per_client)
+            .check(client, now)
+            .map_err(|retry_after| RelayError::RateLimited {
+                scope: "from this address",
+                retry_after,
+            })?;
+        lock(&self.per_transfer)
+            .check(request.transfer, now)
+            .map_err(|retry_after| RelayError::RateLimited {
+                scope: "for this transfer",
+                retry_after,
+            })?;
+        let _guard = InProgress::start(&self.in_progress, request.transfer)?;
+
+        let transfer = self
+            .chain
+            .transfer(&request.transfer)?
+            .ok_or_else(|| RelayError::Rejected(format!("no transfer at {}", request.transfer)))?;
+        if transfer.status != TransferStatus::Active {
+            return Err(RelayError::Rejected(format!(
+                "transfer is {:?}",
+                transfer.status
+            )));
+        }
+
+        // The commit key stands in for the code until the reveal
+        let commit_key = commit_keypair(&request.claim_code);
+        match transfer.commit_key {
+            None => {
+                return Err(RelayError::Rejected(
+                    "transfer has no commit key, so it can't be relayed".to_string(),
+                ))
+            }
+            Some(key) if key != commit_key.pubkey() => {
+                return Err(RelayError::Rejected("invalid claim code".to_string()))
+            }
+            Some(_) => {}
+        }
+
+        // Random, so the commitment can't be brute-forced for the code
+        let salt: [u8; 32] = rand::random();
+        let commitment =
+            payinbox::claim_commitment(request.claim_code.as_bytes(), &request.destination, &salt);
+        let commit = CommitClaim::new(&transfer, commit_key.pubkey(), commitment).instruction();
+        let mut reveal = RevealClaim::new(
+            &transfer,
+            request.destination,
+            request.claim_code.clone(),
+            salt,
+        );
+        if let Some(account) = request.destination_token_account {
+            reveal = reveal.destination_token_account(account);
+        }
+        let reveal = reveal.instruction();
+
+        // Both steps in one transaction: checks the code and destination
+        // without spending a reservation.
+        let simulated = self
+            .chain
+            .simulate(&[commit.clone(), reveal.clone()], &[&commit_key])?;
+        if let Some(error) = simulated.error {
+            return Err(RelayError::Rejected(format!("claim would fail: {error}")));
+        }
+        // Signed like the commit, the pair costs what the commit will; the
+        // reveal lands on its own, for its own fee.
+        let cost = simulated.cost + self.chain.fee(std::slice::from_ref(&reveal), &[])? as i64;
+        if cost > self.config.max_fee_lamports as i64 {
+            return Err(RelayError::Rejected(format!(
+                "claim would cost the relayer {cost} lamports, more than {}",
+                self.config.max_fee_lamports
+            )));
+        }
+
+        let committed = self.chain.submit(&[commit], &[&commit_key])?;
+        self.record(client, request, "commit_claim", &committed)?;
+        let revealed = self.chain.submit(&[reveal], &[]).map_err(|err| {
+            err.context(format!(
+                "committed in {}, but the reveal failed",
+                committed.signature
+            ))
+        })?;
+        self.record(client, request, "reveal_claim", &revealed)?;
+
+        Ok(Receipt {
+            fee: committed.fee + revealed.fee,
+            commit_signature: committed.signature,
+            reveal_signature: revealed.signature,
+        })
+    }
+
+    fn record(
+        &self,
+        client: IpAddr,
+        request: &ClaimRequest,
+        instruction: &str,
+        submitted: &Submitted,
+    ) -> anyhow::Result<()> {
+        let relayed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64);
+        self.ledger().record(&Sponsored {
+            signature: submitted.signature.clone(),
+            transfer: request.transfer,
+            destination: request.destination,
+            client_ip: client,
+            instruction: instruction.to_string(),
+            fee: submitted.fee,
+            relayed_at,
+        })
+    }
+}
+
+/// Marks a transfer as being relayed until dropped.
+struct InProgress<'a> {
+    transfers: &'a Mutex<HashSet<Pubkey>>,
+    transfer: Pubkey,
+}
+
+impl<'a> InProgress<'a> {
+    fn start(transfers: &'a Mutex<HashSet<Pubkey>>, transfer: Pubkey) -> Result<Self, RelayError> {
+        if !lock(transfers).insert(transfer) {
+            return Err(RelayError::InProgress(transfer));
+        }
+        Ok(Self {
+            transfers,
+            transfer,
+        })
+    }
+}
+
+impl Drop for InProgress<'_> {
+    fn drop(&mut self) {
+        lock(self.transfers).remove(&self.transfer);
+    }
+}
+
+/// The state behind these locks stays consistent if a holder panics, so
+/// poisoning is ignored.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,89 @@
+//! Sliding-window rate limits, kept in memory.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// At most `requests` in any `window`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limit {
+    pub requests: usize,
+    pub window: Duration,
+}
+
+/// Request times per key, for the last window.
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    limit: Limit,
+    hits: HashMap<K, VecDeque<Instant>>,
+    /// Key count at which idle keys are dropped
+    prune_at: usize,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(limit: Limit) -> Self {
+        Self {
+            limit,
+            hits: HashMap::new(),
+            prune_at: 1024,
+        }
+    }
+
+    /// Counts a request for `key` at `now`, unless the limit is reached;
+    /// then returns how long until the next request is allowed.
+    pub fn check(&mut self, key: K, now: Instant) -> Result<(), Duration> {
+        let window = self.limit.window;
+        if self.hits.len() >= self.prune_at {
+            self.hits.retain(|_, hits| {
+                hits.back()
+                    .is_some_and(|last| now.saturating_duration_since(*last) < window)
+            });
+            self.prune_at = (self.hits.len() * 2).max(1024);
+        }
+
+        let hits = self.hits.entry(key).or_default();
+        while hits
+            .front()
+            .is_some_and(|first| now.saturating_duration_since(*first) >= window)
+        {
+            hits.pop_front();
+        }
+        if hits.len() >= self.limit.requests {
+            let wait = match hits.front() {
+                Some(first) => window - now.saturating_duration_since(*first),
+                // A limit of zero requests
+                None => window,
+            };
+            return Err(wait);
+        }
+        hits.push_back(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_requests_again_as_the_window_slides() {
+        let window = Duration::from_secs(60);
+        let mut limiter = RateLimiter::new(Limit {
+            requests: 2,
+            window,
+        });
+        let start = Instant::now();
+        let later = start + Duration::from_secs(20);
+        assert_eq!(limiter.check("a", start), Ok(()));
+        assert_eq!(limiter.check("a", later), Ok(()));
+        assert_eq!(limiter.check("a", later), Err(Duration::from_secs(40)));
+        assert_eq!(limiter.check("b", later), Ok(()));
+
+        // The first request drops out of the window, the second doesn't.
+        assert_eq!(limiter.check("a", start + window), Ok(()));
+        assert_eq!(
+            limiter.check("a", start + window),
+            Err(Duration::from_secs(20))
+        );
+    }
+}
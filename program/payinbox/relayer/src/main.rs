@@ -0,0 +1,110 @@
+//! `payinbox-relayer`: claims payinbox transfers for recipients, paying
+//! the fees.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use payinbox_client::rpc::RpcClient;
+use payinbox_relayer::chain::{Chain, RpcChain};
+use payinbox_relayer::ledger::Ledger;
+use payinbox_relayer::limit::Limit;
+use payinbox_relayer::{server, Config, Relayer};
+use solana_keypair::read_keypair_file;
+use tiny_http::Server;
+
+#[derive(Parser)]
+#[command(
+    name = "payinbox-relayer",
+    version,
+    about = "Submit payinbox claims for recipients, paying their fees"
+)]
+struct Cli {
+    /// RPC endpoint
+    #[arg(
+        long,
+        env = "PAYINBOX_RPC_URL",
+        default_value = "https://api.devnet.solana.com"
+    )]
+    url: String,
+
+    /// Keypair file that signs and pays for the claims
+    #[arg(
+        long,
+        env = "PAYINBOX_KEYPAIR",
+        default_value = "~/.config/solana/id.json"
+    )]
+    keypair: String,
+
+    /// Address to serve the API at
+    #[arg(long, env = "PAYINBOX_RELAYER_ADDR", default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// SQLite ledger of the sponsored fees
+    #[arg(long, env = "PAYINBOX_RELAYER_LEDGER", default_value = "relayer.db")]
+    ledger: PathBuf,
+
+    /// Most lamports one claim may cost the relayer, fees of both the
+    /// commit and the reveal included
+    #[arg(long, default_value_t = 15_000)]
+    max_fee: u64,
+
+    /// Claim requests allowed per client address per `--client-window`
+    #[arg(long, default_value_t = 10)]
+    client_limit: usize,
+
+    /// Seconds over which `--client-limit` applies
+    #[arg(long, default_value_t = 60)]
+    client_window: u64,
+
+    /// Claim requests allowed per transfer per `--transfer-window`
+    #[arg(long, default_value_t = 2)]
+    transfer_limit: usize,
+
+    /// Seconds over which `--transfer-limit` applies
+    #[arg(long, default_value_t = 300)]
+    transfer_window: u64,
+
+    /// Requests handled at once
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u64).range(1..))]
+    workers: u64,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let chain = RpcChain::new(RpcClient::new(&cli.url), load_keypair(&cli.keypair)?);
+    let ledger = Ledger::open(&cli.ledger)?;
+    let relayer = Relayer::new(
+        chain,
+        ledger,
+        Config {
+            max_fee_lamports: cli.max_fee,
+            per_client: Limit {
+                requests: cli.client_limit,
+                window: Duration::from_secs(cli.client_window),
+            },
+            per_transfer: Limit {
+                requests: cli.transfer_limit,
+                window: Duration::from_secs(cli.transfer_window),
+            },
+        },
+    );
+    let server =
+        Server::http(&cli.listen).map_err(|e| anyhow!("serving at {}: {e}", cli.listen))?;
+    println!(
+        "relayer {} listening on {}",
+        relayer.chain().relayer(),
+        cli.listen
+    );
+    server::run(&server, &relayer, cli.workers as usize);
+    Ok(())
+}
+
+fn load_keypair(path: &str) -> Result<solana_keypair::Keypair> {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var("HOME").context("HOME is not set")?).join(rest),
+        None => PathBuf::from(path),
+    };
+    read_keypair_file(&path).map_err(|e| anyhow!("reading keypair {}: {e}", path.display()))
+}
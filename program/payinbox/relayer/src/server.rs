@@ -0,0 +1,143 @@
+//! The relayer's HTTP API.
+//!
+//! - `POST /claim` with `{"transfer", "destination", "claim_code"}` and an
+//!   optional `"destination_token_account"` (addresses in base58) relays a
+//!   claim. Answers `{"commit_signature", "reveal_signature", "fee"}`, or
+//!   `{"error"}` with 400 (bad request), 409 (claim in progress), 422
+//!   (claim rejected), 429 (rate limited, with `Retry-After`) or 502
+//!   (cluster failure).
+//! - `GET /fees` answers the ledger totals, `{"transactions", "fees"}`.
+//! - `GET /health` answers the relayer's address.
+
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr};
+use std::thread;
+use std::time::Instant;
+
+use anchor_lang::prelude::Pubkey;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::chain::Chain;
+use crate::{ClaimRequest, RelayError, Relayer};
+
+/// Largest request body read.
+const MAX_BODY: u64 = 4096;
+
+/// Body of `POST /claim`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClaimBody {
+    transfer: String,
+    destination: String,
+    claim_code: String,
+    destination_token_account: Option<String>,
+}
+
+/// Answers the requests to `server` with `workers` threads, until it is
+/// dropped.
+pub fn run<C: Chain + Sync>(server: &Server, relayer: &Relayer<C>, workers: usize) {
+    thread::scope(|scope| {
+        for _ in 0..workers.max(1) {
+            scope.spawn(|| {
+                for request in server.incoming_requests() {
+                    handle(relayer, request);
+                }
+            });
+        }
+    });
+}
+
+fn handle<C: Chain>(relayer: &Relayer<C>, mut request: Request) {
+    // Unix sockets have no peer address; they share one limit.
+    let client = request
+        .remote_addr()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |addr| addr.ip());
+    let (status, body, retry_after) = match (request.method(), request.url()) {
+        (Method::Post, "/claim") => match read_claim(&mut request) {
+            Ok(claim) => match relayer.relay(client, &claim, Instant::now()) {
+                Ok(receipt) => (
+                    200,
+                    json!({
+                        "commit_signature": receipt.commit_signature,
+                        "reveal_signature": receipt.reveal_signature,
+                        "fee": receipt.fee,
+                    }),
+                    None,
+                ),
+                Err(err) => {
+                    let (status, retry_after) = match &err {
+                        RelayError::RateLimited { retry_after, .. } => {
+                            (429, Some(retry_after.as_secs().max(1)))
+                        }
+                        RelayError::InProgress(_) => (409, None),
+                        RelayError::Rejected(_) => (422, None),
+                        RelayError::Failed(_) => (502, None),
+                    };
+                    (status, error(format!("{err:#}")), retry_after)
+                }
+            },
+            Err(message) => (400, error(message), None),
+        },
+        (Method::Get, "/fees") => match relayer.ledger().totals() {
+            Ok(totals) => (
+                200,
+                json!({ "transactions": totals.transactions, "fees": totals.fees }),
+                None,
+            ),
+            Err(err) => (500, error(format!("{err:#}")), None),
+        },
+        (Method::Get, "/health") => (
+            200,
+            json!({ "relayer": relayer.chain().relayer().to_string() }),
+            None,
+        ),
+        _ => (404, error("not found".to_string()), None),
+    };
+
+    let mut response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(
+            Header::from_bytes("Content-Type", "application/json").expect("static header is valid"),
+        );
+    if let Some(seconds) = retry_after {
+        response = response.with_header(
+            Header::from_bytes("Retry-After", seconds.to_string())
+                .expect("a number is a valid header value"),
+        );
+    }
+    // The client hanging up early is its problem.
+    let _ = request.respond(response);
+}
+
+fn read_claim(request: &mut Request) -> Result<ClaimRequest, String> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY)
+        .read_to_string(&mut body)
+        .map_err(|e| format!("reading the body: {e}"))?;
+    let body: ClaimBody =
+        serde_json::from_str(&body).map_err(|e| format!("invalid claim request: {e}"))?;
+    Ok(ClaimRequest {
+        transfer: address("transfer", &body.transfer)?,
+        destination: address("destination", &body.destination)?,
+        claim_code: body.claim_code,
+        destination_token_account: body
+            .destination_token_account
+            .as_deref()
+            .map(|account| address("destination_token_account", account))
+            .transpose()?,
+    })
+}
+
+fn address(field: &str, value: &str) -> Result<Pubkey, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {field} address: {value}"))
+}
+
+fn error(message: String) -> Value {
+    json!({ "error": message })
+}
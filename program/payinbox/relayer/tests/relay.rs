@@ -0,0 +1,423 @@
+//! Claims relayed into the in-process runtime.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_error::ProgramError;
+use anyhow::{bail, Result};
+use payinbox::{TransferAccount, TransferOptions, TransferStatus};
+use payinbox_client::hash::{claim_code_hash, commit_keypair};
+use payinbox_client::instructions::CreateTransfer;
+use payinbox_client::program_error;
+use payinbox_harness::Svm;
+use payinbox_relayer::chain::{Chain, Simulated, Submitted};
+use payinbox_relayer::ledger::{Ledger, Totals};
+use payinbox_relayer::limit::Limit;
+use payinbox_relayer::{server, ClaimRequest, Config, RelayError, Relayer};
+use serde_json::{json, Value};
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+use tiny_http::Server;
+
+const AMOUNT: u64 = 1_000_000;
+const CLAIM_CODE: &str = "correct-horse-battery-staple";
+/// Charged to the relayer per transaction, as a cluster would.
+const FEE: u64 = 5_000;
+
+/// The runtime as a cluster, with transactions paid for by `relayer`.
+struct Local {
+    svm: Mutex<Svm>,
+    relayer: Pubkey,
+    transactions: AtomicU64,
+}
+
+impl Local {
+    fn charge(&self, svm: &mut Svm) {
+        let mut account = svm.account(&self.relayer).unwrap();
+        account.lamports -= FEE;
+        svm.set_account(self.relayer, account);
+    }
+
+    fn dry_run(&self, svm: &Svm, instructions: &[Instruction]) -> Simulated {
+        let mut fork = svm.clone();
+        let before = fork.lamports(&self.relayer);
+        self.charge(&mut fork);
+        let error = fork.process_transaction(instructions).err().map(|e| {
+            match e {
+                ProgramError::Custom(code) => program_error(code).map(|error| error.name()),
+                _ => None,
+            }
+            .unwrap_or_else(|| e.to_string())
+        });
+        Simulated {
+            error,
+            cost: before as i64 - fork.lamports(&self.relayer) as i64,
+        }
+    }
+}
+
+impl Chain for Local {
+    fn relayer(&self) -> Pubkey {
+        self.relayer
+    }
+
+    fn transfer(&self, address: &Pubkey) -> Result<Option<TransferAccount>> {
+        Ok(self.svm.lock().unwrap().anchor_account(address))
+    }
+
+    fn fee(&self, _instructions: &[Instruction], _signers: &[&Keypair]) -> Result<u64> {
+        Ok(FEE)
+    }
+
+    fn simulate(&self, instructions: &[Instruction], _signers: &[&Keypair]) -> Result<Simulated> {
+        Ok(self.dry_run(&self.svm.lock().unwrap(), instructions))
+    }
+
+    fn submit(&self, instructions: &[Instruction], _signers: &[&Keypair]) -> Result<Submitted> {
+        let mut svm = self.svm.lock().unwrap();
+        if let Some(error) = self.dry_run(&svm, instructions).error {
+            bail!("simulation failed: {error}");
+        }
+        self.charge(&mut svm);
+        svm.process_transaction(instructions).unwrap();
+        let n = self.transactions.fetch_add(1, Ordering::Relaxed);
+        Ok(Submitted {
+            signature: format!("local-{n}"),
+            fee: FEE,
+        })
+    }
+}
+
+struct Setup {
+    relayer: Relayer<Local>,
+    transfer: Pubkey,
+    destination: Pubkey,
+    destination_ata: Pubkey,
+}
+
+impl Setup {
+    fn new(config: Config) -> Self {
+        Self::with_options(
+            config,
+            TransferOptions {
+                commit_key: Some(commit_keypair(CLAIM_CODE).pubkey()),
+                ..TransferOptions::default()
+            },
+        )
+    }
+
+    fn with_options(config: Config, options: TransferOptions) -> Self {
+        let mut svm = Svm::new();
+        let mint = svm.create_mint(&Pubkey::new_unique(), 6);
+        let sender = Pubkey::new_unique();
+        svm.airdrop(&sender, 1_000_000_000);
+        svm.create_token_account(&sender, &mint, AMOUNT);
+        let create = CreateTransfer::new(
+            sender,
+            mint,
+            [1; 32],
+            claim_code_hash(CLAIM_CODE),
+            AMOUNT,
+            72,
+        )
+        .options(options);
+        svm.process_instruction(create.instruction()).unwrap();
+        let destination = Pubkey::new_unique();
+        let destination_ata = svm.create_token_account(&destination, &mint, 0);
+        let relayer = Pubkey::new_unique();
+        svm.airdrop(&relayer, 1_000_000_000);
+
+        let chain = Local {
+            svm: Mutex::new(svm),
+            relayer,
+            transactions: AtomicU64::new(0),
+        };
+        Self {
+            relayer: Relayer::new(chain, Ledger::open_in_memory().unwrap(), config),
+            transfer: create.transfer_address(),
+            destination,
+            destination_ata,
+        }
+    }
+
+    fn request(&self, claim_code: &str) -> ClaimRequest {
+        ClaimRequest {
+            transfer: self.transfer,
+            destination: self.destination,
+            claim_code: claim_code.to_string(),
+            destination_token_account: None,
+        }
+    }
+
+    fn svm(&self) -> std::sync::MutexGuard<'_, Svm> {
+        self.relayer.chain().svm.lock().unwrap()
+    }
+
+    fn transfer_state(&self) -> TransferAccount {
+        self.svm().anchor_account(&self.transfer).unwrap()
+    }
+
+    fn relayer_lamports(&self) -> u64 {
+        self.svm().lamports(&self.relayer.chain().relayer)
+    }
+}
+
+fn client(n: u8) -> IpAddr {
+    IpAddr::V4(Ipv4Addr::new(203, 0, 113, n))
+}
+
+#[test]
+fn relays_a_claim_and_records_its_fees() {
+    let setup = Setup::new(Config::default());
+    let lamports = setup.relayer_lamports();
+
+    let receipt = setup
+        .relayer
+        .relay(client(1), &setup.request(CLAIM_CODE), Instant::now())
+        .unwrap();
+    assert_eq!(receipt.fee, 2 * FEE);
+    assert_eq!(setup.relayer_lamports(), lamports - 2 * FEE);
+    assert_eq!(setup.transfer_state().status, TransferStatus::Claimed);
+    assert_eq!(setup.svm().token_balance(&setup.destination_ata), AMOUNT);
+
+    let ledger = setup.relayer.ledger();
+    let sponsored = ledger.for_transfer(&setup.transfer).unwrap();
+    let entries: Vec<_> = sponsored
+        .iter()
+        .map(|entry| {
+            (
+                entry.signature.as_str(),
+                entry.instruction.as_str(),
+                entry.fee,
+            )
+        })
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (receipt.commit_signature.as_str(), "commit_claim", FEE),
+            (receipt.reveal_signature.as_str(), "reveal_claim", FEE),
+        ]
+    );
+    assert!(sponsored
+        .iter()
+        .all(|entry| entry.client_ip == client(1) && entry.destination == setup.destination));
+    assert_eq!(
+        ledger.totals().unwrap(),
+        Totals {
+            transactions: 2,
+            fees: 2 * FEE
+        }
+    );
+}
+
+#[test]
+fn rejects_claims_that_would_fail_without_paying() {
+    let setup = Setup::new(Config::default());
+    let lamports = setup.relayer_lamports();
+    let now = Instant::now();
+
+    let err = setup
+        .relayer
+        .relay(client(1), &setup.request("wrong"), now)
+        .unwrap_err();
+    assert!(
+        matches!(&err, RelayError::Rejected(reason) if reason == "invalid claim code"),
+        "{err}"
+    );
+
+    // The destination has no token account to pay into.
+    let mut request = setup.request(CLAIM_CODE);
+    request.destination = Pubkey::new_unique();
+    let err = setup.relayer.relay(client(2), &request, now).unwrap_err();
+    assert!(matches!(err, RelayError::Rejected(_)), "{err}");
+
+    let mut request = setup.request(CLAIM_CODE);
+    request.transfer = Pubkey::new_unique();
+    let err = setup.relayer.relay(client(3), &request, now).unwrap_err();
+    assert!(
+        matches!(&err, RelayError::Rejected(reason) if reason.starts_with("no transfer")),
+        "{err}"
+    );
+
+    // Nothing landed: no fee, no reservation used up.
+    assert_eq!(setup.relayer_lamports(), lamports);
+    let state = setup.transfer_state();
+    assert_eq!(state.status, TransferStatus::Active);
+    assert_eq!(state.reservation_count, 0);
+    assert_eq!(setup.relayer.ledger().totals().unwrap(), Totals::default());
+}
+
+#[test]
+fn rejects_transfers_without_a_commit_key() {
+    let setup = Setup::with_options(Config::default(), TransferOptions::default());
+    let err = setup
+        .relayer
+        .relay(client(1), &setup.request(CLAIM_CODE), Instant::now())
+        .unwrap_err();
+    assert!(
+        matches!(&err, RelayError::Rejected(reason) if reason.contains("no commit key")),
+        "{err}"
+    );
+    assert_eq!(setup.relayer.ledger().totals().unwrap(), Totals::default());
+}
+
+#[test]
+fn rejects_claims_costing_more_than_the_fee_cap() {
+    let setup = Setup::new(Config {
+        max_fee_lamports: FEE - 1,
+        ..Config::default()
+    });
+    let err = setup
+        .relayer
+        .relay(client(1), &setup.request(CLAIM_CODE), Instant::now())
+        .unwrap_err();
+    assert!(
+        matches!(&err, RelayError::Rejected(reason) if reason.contains("cost the relayer 10000")),
+        "{err}"
+    );
+    assert_eq!(setup.transfer_state().status, TransferStatus::Active);
+    assert_eq!(setup.relayer.ledger().totals().unwrap(), Totals::default());
+}
+
+#[test]
+fn fee_cap_covers_the_commit_and_the_reveal() {
+    // Enough for the simulated pair, one transaction, but not for the two
+    // the relayer lands
+    let setup = Setup::new(Config {
+        max_fee_lamports: FEE + FEE / 2,
+        ..Config::default()
+    });
+    let err = setup
+        .relayer
+        .relay(client(1), &setup.request(CLAIM_CODE), Instant::now())
+        .unwrap_err();
+    assert!(
+        matches!(&err, RelayError::Rejected(reason) if reason.contains("cost the relayer 10000")),
+        "{err}"
+    );
+    assert_eq!(setup.transfer_state().status, TransferStatus::Active);
+
+    let setup = Setup::new(Config {
+        max_fee_lamports: 2 * FEE,
+        ..Config::default()
+    });
+    let receipt = setup
+        .relayer
+        .relay(client(1), &setup.request(CLAIM_CODE), Instant::now())
+        .unwrap();
+    assert_eq!(receipt.fee, 2 * FEE);
+}
+
+#[test]
+fn rate_limits_clients_and_transfers() {
+    let window = Duration::from_secs(300);
+    let setup = Setup::new(Config {
+        per_client: Limit {
+            requests: 2,
+            window: Duration::from_secs(60),
+        },
+        per_transfer: Limit {
+            requests: 3,
+            window,
+        },
+        ..Config::default()
+    });
+    let start = Instant::now();
+    let relay = |ip, code, at| setup.relayer.relay(client(ip), &setup.request(code), at);
+
+    // Failed requests count too.
+    for _ in 0..2 {
+        assert!(matches!(
+            relay(1, "wrong", start),
+            Err(RelayError::Rejected(_))
+        ));
+    }
+    let err = relay(1, CLAIM_CODE, start).unwrap_err();
+    assert!(
+        matches!(err, RelayError::RateLimited { scope: "from this address", retry_after }
+            if retry_after == Duration::from_secs(60)),
+        "{err}"
+    );
+
+    assert!(matches!(
+        relay(2, "wrong", start),
+        Err(RelayError::Rejected(_))
+    ));
+    let err = relay(3, CLAIM_CODE, start).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            RelayError::RateLimited {
+                scope: "for this transfer",
+                ..
+            }
+        ),
+        "{err}"
+    );
+    assert_eq!(setup.transfer_state().status, TransferStatus::Active);
+
+    relay(3, CLAIM_CODE, start + window).unwrap();
+    assert_eq!(setup.transfer_state().status, TransferStatus::Claimed);
+}
+
+#[test]
+fn serves_claims_over_http() {
+    let setup = Setup::new(Config::default());
+    let (transfer, destination) = (setup.transfer, setup.destination);
+    let relayer = Arc::new(setup.relayer);
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+    let serving = Arc::clone(&relayer);
+    thread::spawn(move || server::run(&server, &serving, 2));
+
+    let post = |body: Value| match ureq::post(&format!("{url}/claim")).send_json(body) {
+        Ok(response) => (response.status(), response.into_json::<Value>().unwrap()),
+        Err(ureq::Error::Status(status, response)) => {
+            (status, response.into_json::<Value>().unwrap())
+        }
+        Err(err) => panic!("{err}"),
+    };
+
+    let (status, body) = post(json!({ "transfer": "nope" }));
+    assert_eq!(status, 400);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("invalid claim request"));
+
+    let (status, body) = post(json!({
+        "transfer": transfer.to_string(),
+        "destination": destination.to_string(),
+        "claim_code": "wrong",
+    }));
+    assert_eq!(status, 422);
+    assert_eq!(body["error"], "invalid claim code");
+
+    let (status, body) = post(json!({
+        "transfer": transfer.to_string(),
+        "destination": destination.to_string(),
+        "claim_code": CLAIM_CODE,
+    }));
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["fee"], 2 * FEE);
+    assert_eq!(body["reveal_signature"], "local-1");
+
+    let fees: Value = ureq::get(&format!("{url}/fees"))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+    assert_eq!(fees, json!({ "transactions": 2, "fees": 2 * FEE }));
+    let health: Value = ureq::get(&format!("{url}/health"))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+    assert_eq!(health["relayer"], relayer.chain().relayer().to_string());
+}
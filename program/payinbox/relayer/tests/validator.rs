@@ -0,0 +1,109 @@
+//! A relayed claim against `solana-test-validator`, through RPC.
+//!
+//! Needs `solana-test-validator` on the PATH and the program built with
+//! `anchor build`; run with `cargo test -p payinbox-relayer -- --ignored`.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_spl::token::spl_token;
+use payinbox::{TransferAccount, TransferOptions, TransferStatus};
+use payinbox_client::accounts::fetch_transfer;
+use payinbox_client::hash::{claim_code_hash, commit_keypair};
+use payinbox_client::instructions::CreateTransfer;
+use payinbox_client::pda;
+use payinbox_harness::{Account, Svm, TestValidator};
+use payinbox_relayer::chain::{Chain, RpcChain};
+use payinbox_relayer::ledger::Ledger;
+use payinbox_relayer::{ClaimRequest, Config, RelayError, Relayer};
+use solana_keypair::Keypair;
+use solana_signer::Signer;
+
+const AMOUNT: u64 = 1_000_000;
+const CLAIM_CODE: &str = "correct-horse-battery-staple";
+
+#[test]
+#[ignore = "needs solana-test-validator and target/deploy/payinbox.so"]
+fn relays_a_claim_through_rpc() {
+    // A transfer created just now, and a destination with a token account.
+    let mut svm = Svm::new();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    svm.set_unix_timestamp(now);
+    let mint = svm.create_mint(&Pubkey::new_unique(), 6);
+    let sender = Pubkey::new_unique();
+    svm.airdrop(&sender, 1_000_000_000);
+    svm.create_token_account(&sender, &mint, AMOUNT);
+    let create = CreateTransfer::new(
+        sender,
+        mint,
+        [1; 32],
+        claim_code_hash(CLAIM_CODE),
+        AMOUNT,
+        72,
+    )
+    .options(TransferOptions {
+        commit_key: Some(commit_keypair(CLAIM_CODE).pubkey()),
+        ..TransferOptions::default()
+    });
+    svm.process_instruction(create.instruction()).unwrap();
+    let transfer = create.transfer_address();
+    let destination = Pubkey::new_unique();
+    let destination_ata = svm.create_token_account(&destination, &mint, 0);
+
+    let relayer = Keypair::new();
+    let mut accounts: Vec<_> = [
+        transfer,
+        pda::escrow_address(&transfer),
+        mint,
+        sender,
+        destination_ata,
+    ]
+    .iter()
+    .map(|address| (*address, svm.account(address).unwrap()))
+    .collect();
+    accounts.push((
+        relayer.pubkey(),
+        Account {
+            lamports: 10_000_000_000,
+            ..Account::default()
+        },
+    ));
+
+    let validator = TestValidator::start(&accounts).expect("starting solana-test-validator");
+    let rpc = validator.rpc();
+    let relayer = Relayer::new(
+        RpcChain::new(validator.rpc(), relayer),
+        Ledger::open_in_memory().unwrap(),
+        Config::default(),
+    );
+    let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let mut request = ClaimRequest {
+        transfer,
+        destination,
+        claim_code: "wrong".to_string(),
+        destination_token_account: None,
+    };
+    let err = relayer.relay(client, &request, Instant::now()).unwrap_err();
+    assert!(matches!(err, RelayError::Rejected(_)), "{err}");
+
+    let payer = relayer.chain().relayer();
+    let lamports = rpc.get_balance(&payer).unwrap();
+    request.claim_code = CLAIM_CODE.to_string();
+    let receipt = relayer.relay(client, &request, Instant::now()).unwrap();
+    assert!(receipt.fee > 0);
+    assert_eq!(rpc.get_balance(&payer).unwrap(), lamports - receipt.fee);
+    assert_eq!(relayer.ledger().totals().unwrap().fees, receipt.fee);
+
+    let state: TransferAccount = fetch_transfer(&rpc, &transfer).unwrap();
+    assert_eq!(state.status, TransferStatus::Claimed);
+    let paid = rpc.get_account_data(&destination_ata).unwrap().unwrap();
+    assert_eq!(
+        spl_token::state::Account::unpack(&paid).unwrap().amount,
+        AMOUNT
+    );
+}